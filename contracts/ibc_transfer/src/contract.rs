@@ -1,12 +1,26 @@
+use crate::hooks::{record_call_result, wasm_hook_memo};
+use crate::ledger::{
+    attached_amount, execute_claim_refund, query_claimable, query_transfer,
+    query_transfers_by_sender, record_transfer, save_outgoing_transfer, settle_transfer,
+};
+use crate::multihop::{forward_memo, route_string, validate_hops};
 use crate::state::{
-    read_reply_payload, read_sudo_payload, save_reply_payload, save_sudo_payload, IBC_FEE,
-    IBC_SUDO_ID_RANGE_END, IBC_SUDO_ID_RANGE_START, TEST_COUNTER_ITEM,
+    read_reply_payload, read_sudo_payload, save_reply_payload, save_sudo_payload, OutgoingTransfer,
+    RemoteCall, TransferStatus, IBC_FEE, IBC_SUDO_ID_RANGE_END, IBC_SUDO_ID_RANGE_START,
+    RESUBMIT_FAILURE_REPLY_ID, TEST_COUNTER_ITEM,
+};
+use crate::traces::{origin_channel, query_holdings, resolve_denom};
+use contract_failures::{
+    execute_resubmit_failures, query_failures, query_resubmissions, resubmit_failure_reply,
+    ResubmitFilter,
 };
+pub use contract_failures::{FailureInfo, FailureKind};
 use cosmwasm_std::{
-    entry_point, Binary, CosmosMsg, Deps, DepsMut, Env, MessageInfo, Reply, Response, StdError,
-    StdResult, SubMsg, Uint128,
+    entry_point, to_json_binary, Addr, Binary, Coin, CosmosMsg, Deps, DepsMut, Env, MessageInfo,
+    Reply, Response, StdError, StdResult, SubMsg, Uint128,
 };
 use cw2::set_contract_version;
+use ibc_timeout::{PacketTimeout, TimeoutSpec};
 use neutron_sdk::interchain_txs::helpers::decode_message_response;
 use neutron_sdk::sudo::msg::{RequestPacket, TransferSudoMsg};
 use neutron_std::types::cosmos::base::v1beta1::Coin as StdCoin;
@@ -15,6 +29,11 @@ use neutron_std::types::ibc::core::client::v1::Height;
 use neutron_std::types::neutron::contractmanager::MsgResubmitFailure;
 use neutron_std::types::neutron::feerefunder::Fee;
 use neutron_std::types::neutron::transfer::{MsgTransfer, MsgTransferResponse};
+pub use ownership::OwnershipResponse;
+use ownership::{
+    accept_ownership, assert_owner, assert_owner_or_operator, initialize_owner, query_operators,
+    query_ownership, set_operators, transfer_ownership, OWNER,
+};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -26,21 +45,29 @@ use crate::{
 // Default timeout for IbcTransfer is 10000000 blocks
 const DEFAULT_TIMEOUT_HEIGHT: u64 = 10000000;
 
+// Default and maximum page sizes for the failures and transfers queries
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 100;
+
 const CONTRACT_NAME: &str = concat!("crates.io:neutron-contracts__", env!("CARGO_PKG_NAME"));
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
-pub struct InstantiateMsg {}
+pub struct InstantiateMsg {
+    /// defaults to the instantiator
+    pub owner: Option<String>,
+}
 
 #[entry_point]
 pub fn instantiate(
     deps: DepsMut,
     _env: Env,
-    _info: MessageInfo,
-    _msg: InstantiateMsg,
+    info: MessageInfo,
+    msg: InstantiateMsg,
 ) -> StdResult<Response> {
     deps.api.debug("WASMDEBUG: instantiate");
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    initialize_owner(deps, &info, msg.owner)?;
     Ok(Response::default())
 }
 
@@ -52,6 +79,8 @@ pub enum ExecuteMsg {
         to: String,
         denom: String,
         amount: Uint128,
+        timeout: Option<TimeoutSpec>,
+        /// deprecated in favour of `timeout`, the same as an `at_height` timeout
         timeout_height: Option<u64>,
     },
     // uses original MsgTransfer instead of neutron one (no fees argument)
@@ -60,14 +89,79 @@ pub enum ExecuteMsg {
         to: String,
         denom: String,
         amount: Uint128,
+        timeout: Option<TimeoutSpec>,
+        /// deprecated in favour of `timeout`, the same as an `at_height` timeout
         timeout_height: Option<u64>,
     },
+    /// Sends the base denom received over the trace path, e.g. `transfer/channel-0`, empty for
+    /// a native denom. A voucher is only sent back over the channel it has been received over
+    /// unless `allow_non_origin` is set.
+    SendWithPath {
+        channel: String,
+        to: String,
+        base_denom: String,
+        path: String,
+        amount: Uint128,
+        timeout: Option<TimeoutSpec>,
+        allow_non_origin: Option<bool>,
+    },
+    /// Sends the tokens over the first of the `(channel, receiver)` hops, the intermediate
+    /// chains forward them over the following ones with the packet forward middleware. A failed
+    /// transfer is reported with the whole route, the error acknowledgement doesn't identify
+    /// the failing hop.
+    SendMultiHop {
+        hops: Vec<(String, String)>,
+        denom: String,
+        amount: Uint128,
+        timeout: Option<TimeoutSpec>,
+        /// retries of every forward on the intermediate chains
+        retries: Option<u8>,
+    },
+    /// Sends the tokens to the contract of the destination chain and executes the msg, a JSON
+    /// object, on it with the ibc-hooks middleware. The result of the call is recorded from the
+    /// acknowledgement of the transfer. `ibc_callback` is the contract the middleware of this
+    /// chain notifies of the acknowledgement.
+    SendAndCall {
+        channel: String,
+        contract: String,
+        msg: Binary,
+        denom: String,
+        amount: Uint128,
+        timeout: Option<TimeoutSpec>,
+        ibc_callback: Option<String>,
+    },
     SetFees {
         fees: Option<Fees>,
     },
     ResubmitFailure {
         failure_id: u64,
     },
+    /// Sends the sender the tokens of its failed and timed out transfers of the denom
+    ClaimRefund {
+        denom: String,
+    },
+    /// Resubmits up to `max` contractmanager failures after `start_after` of acknowledgements
+    /// and timeouts on `channel_id` whose error contains `error_contains`. Every failure is
+    /// resubmitted only once, a resubmission failing again doesn't fail the others and its error
+    /// is recorded. A few pages of failures are checked at once, the last checked failure id is
+    /// returned in the `last_failure_id` attribute to continue from.
+    ResubmitFailures {
+        channel_id: Option<String>,
+        error_contains: Option<String>,
+        start_after: Option<u64>,
+        max: Option<u32>,
+    },
+    /// Starts a two-step ownership transfer, completed by `AcceptOwnership` from the new owner.
+    TransferOwnership {
+        new_owner: String,
+    },
+    AcceptOwnership {},
+    /// Replaces the addresses allowed to execute `action` besides the owner. The action is
+    /// the snake_case name of an execute message, e.g. `send`.
+    SetOperators {
+        action: String,
+        operators: Vec<String>,
+    },
     /// Used only in integration tests framework to simulate failures.
     /// After executing this message, contract will fail, all of this happening
     /// in sudo callback handler.
@@ -79,6 +173,83 @@ pub enum ExecuteMsg {
     IntegrationTestsUnsetSudoFailureMock {},
 }
 
+impl ExecuteMsg {
+    /// Name of the action the operators allow-list is kept for
+    pub fn action(&self) -> &'static str {
+        match self {
+            ExecuteMsg::Send { .. } => "send",
+            ExecuteMsg::SendNative { .. } => "send_native",
+            ExecuteMsg::SendWithPath { .. } => "send_with_path",
+            ExecuteMsg::SendMultiHop { .. } => "send_multi_hop",
+            ExecuteMsg::SendAndCall { .. } => "send_and_call",
+            ExecuteMsg::SetFees { .. } => "set_fees",
+            ExecuteMsg::ResubmitFailure { .. } => "resubmit_failure",
+            ExecuteMsg::ResubmitFailures { .. } => "resubmit_failures",
+            ExecuteMsg::ClaimRefund { .. } => "claim_refund",
+            ExecuteMsg::TransferOwnership { .. } => "transfer_ownership",
+            ExecuteMsg::AcceptOwnership {} => "accept_ownership",
+            ExecuteMsg::SetOperators { .. } => "set_operators",
+            ExecuteMsg::IntegrationTestsSetSudoFailureMock { .. } => {
+                "integration_tests_set_sudo_failure_mock"
+            }
+            ExecuteMsg::IntegrationTestsUnsetSudoFailureMock {} => {
+                "integration_tests_unset_sudo_failure_mock"
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    // this query returns the owner and the pending owner of the contract
+    Ownership {},
+    // this query returns the addresses allowed to execute an action besides the owner
+    Operators {
+        action: String,
+    },
+    // this query returns a page of the contract's contractmanager failures with their sudo
+    // payloads decoded
+    Failures {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // this query returns the outcomes of the failures resubmitted with ResubmitFailures
+    Resubmissions {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // this query returns the transfer sent with the sequence on the channel
+    Transfer {
+        channel_id: String,
+        sequence_id: u64,
+    },
+    // this query returns a page of the transfers sent by the address, ordered by
+    // (channel_id, sequence_id)
+    TransfersBySender {
+        sender: String,
+        start_after: Option<(String, u64)>,
+        limit: Option<u32>,
+    },
+    // this query returns the refunds the address can claim
+    Claimable {
+        address: String,
+    },
+    // this query returns the balances of the contract with the traces of the vouchers
+    Holdings {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Holding {
+    pub denom: String,
+    pub amount: Uint128,
+    pub base_denom: String,
+    /// trace path of a voucher, empty for a native denom
+    pub path: String,
+    /// channel the voucher has been received over
+    pub origin_channel: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct Fees {
     pub denom: String,
@@ -88,34 +259,115 @@ pub struct Fees {
 }
 
 #[entry_point]
-pub fn execute(deps: DepsMut, env: Env, _: MessageInfo, msg: ExecuteMsg) -> StdResult<Response> {
+pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> StdResult<Response> {
     deps.api
         .debug(format!("WASMDEBUG: execute: received msg: {:?}", msg).as_str());
+    authorize(deps.as_ref(), &info.sender, &msg)?;
     match msg {
         // NOTE: this is an example contract that shows how to make IBC transfers!
-        // Please add necessary authorization or other protection mechanisms
-        // if you intend to send funds over IBC
+        // Only the owner and the operators allowed to send can transfer funds
         ExecuteMsg::Send {
             channel,
             to,
             denom,
             amount,
+            timeout,
             timeout_height,
-        } => execute_send(deps, env, channel, to, denom, amount, timeout_height),
+        } => {
+            let timeout = legacy_timeout(timeout, timeout_height)?;
+            execute_send(deps, env, info, channel, to, denom, amount, timeout)
+        }
 
         ExecuteMsg::SendNative {
             channel,
             to,
             denom,
             amount,
+            timeout,
             timeout_height,
-        } => execute_send_native(deps, env, channel, to, denom, amount, timeout_height),
+        } => {
+            let timeout = legacy_timeout(timeout, timeout_height)?;
+            execute_send_native(deps, env, info, channel, to, denom, amount, timeout)
+        }
+
+        ExecuteMsg::SendWithPath {
+            channel,
+            to,
+            base_denom,
+            path,
+            amount,
+            timeout,
+            allow_non_origin,
+        } => execute_send_with_path(
+            deps,
+            env,
+            info,
+            channel,
+            to,
+            base_denom,
+            path,
+            amount,
+            timeout,
+            allow_non_origin.unwrap_or(false),
+        ),
+
+        ExecuteMsg::SendMultiHop {
+            hops,
+            denom,
+            amount,
+            timeout,
+            retries,
+        } => execute_send_multi_hop(deps, env, info, hops, denom, amount, timeout, retries),
+
+        ExecuteMsg::SendAndCall {
+            channel,
+            contract,
+            msg,
+            denom,
+            amount,
+            timeout,
+            ibc_callback,
+        } => execute_send_and_call(
+            deps,
+            env,
+            info,
+            channel,
+            contract,
+            msg,
+            denom,
+            amount,
+            timeout,
+            ibc_callback,
+        ),
 
         ExecuteMsg::SetFees { fees } => execute_set_fees(deps, fees),
 
         ExecuteMsg::ResubmitFailure { failure_id } => {
             execute_resubmit_failure(deps, env, failure_id)
         }
+        ExecuteMsg::ResubmitFailures {
+            channel_id,
+            error_contains,
+            start_after,
+            max,
+        } => execute_resubmit_failures::<TransferSudoMsg>(
+            deps,
+            env,
+            ResubmitFilter {
+                channel_id,
+                error_contains,
+            },
+            start_after,
+            max,
+            RESUBMIT_FAILURE_REPLY_ID,
+        ),
+        ExecuteMsg::ClaimRefund { denom } => execute_claim_refund(deps, info, denom),
+
+        ExecuteMsg::TransferOwnership { new_owner } => transfer_ownership(deps, info, new_owner),
+        ExecuteMsg::AcceptOwnership {} => accept_ownership(deps, info),
+        ExecuteMsg::SetOperators { action, operators } => {
+            set_operators(deps, info, action, operators)
+        }
 
         // Used only in integration tests framework to simulate failures.
         // After executing this message, contract fail, all of this happening
@@ -127,6 +379,69 @@ pub fn execute(deps: DepsMut, env: Env, _: MessageInfo, msg: ExecuteMsg) -> StdR
     }
 }
 
+fn authorize(deps: Deps, sender: &Addr, msg: &ExecuteMsg) -> StdResult<()> {
+    match msg {
+        // ownership messages are checked by their handlers, refunds are claimed by their senders
+        ExecuteMsg::TransferOwnership { .. }
+        | ExecuteMsg::ClaimRefund { .. }
+        | ExecuteMsg::AcceptOwnership {}
+        | ExecuteMsg::SetOperators { .. } => Ok(()),
+        ExecuteMsg::IntegrationTestsSetSudoFailureMock { .. }
+        | ExecuteMsg::IntegrationTestsUnsetSudoFailureMock {} => {
+            assert_integration_tests(deps, sender)
+        }
+        _ => assert_owner_or_operator(deps, sender, msg.action()),
+    }
+}
+
+/// Messages used only in integration tests framework are available to the owner if the contract
+/// has been built with the `integration-tests` feature.
+fn assert_integration_tests(deps: Deps, sender: &Addr) -> StdResult<()> {
+    if !cfg!(feature = "integration-tests") {
+        return Err(StdError::generic_err(
+            "integration tests messages are disabled",
+        ));
+    }
+    assert_owner(deps, sender)
+}
+
+#[entry_point]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Ownership {} => to_json_binary(&query_ownership(deps)?),
+        QueryMsg::Operators { action } => to_json_binary(&query_operators(deps, action)?),
+        QueryMsg::Failures { start_after, limit } => {
+            to_json_binary(&query_failures::<TransferSudoMsg>(
+                deps,
+                env,
+                start_after,
+                limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT),
+            )?)
+        }
+        QueryMsg::Resubmissions { start_after, limit } => to_json_binary(&query_resubmissions(
+            deps,
+            start_after,
+            limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT),
+        )?),
+        QueryMsg::Transfer {
+            channel_id,
+            sequence_id,
+        } => to_json_binary(&query_transfer(deps, channel_id, sequence_id)?),
+        QueryMsg::TransfersBySender {
+            sender,
+            start_after,
+            limit,
+        } => to_json_binary(&query_transfers_by_sender(
+            deps,
+            sender,
+            start_after,
+            limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT),
+        )?),
+        QueryMsg::Claimable { address } => to_json_binary(&query_claimable(deps, address)?),
+        QueryMsg::Holdings {} => to_json_binary(&query_holdings(deps, env)?),
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct Type1 {
@@ -161,12 +476,14 @@ fn msg_with_sudo_callback<C: Into<CosmosMsg<T>>, T>(
     deps: DepsMut,
     msg: C,
     payload: SudoPayload,
+    transfer: OutgoingTransfer,
 ) -> StdResult<SubMsg<T>> {
     let id = save_reply_payload(deps.storage, payload)?;
+    save_outgoing_transfer(deps.storage, id, &transfer)?;
     Ok(SubMsg::reply_on_success(msg, id))
 }
 
-fn prepare_sudo_payload(mut deps: DepsMut, _env: Env, msg: Reply) -> StdResult<Response> {
+fn prepare_sudo_payload(mut deps: DepsMut, env: Env, msg: Reply) -> StdResult<Response> {
     let payload = read_reply_payload(deps.storage, msg.id)?;
     let resp: MsgTransferResponse = decode_message_response(
         &msg.result
@@ -180,6 +497,7 @@ fn prepare_sudo_payload(mut deps: DepsMut, _env: Env, msg: Reply) -> StdResult<R
     .map_err(|e| StdError::generic_err(format!("failed to parse response: {:?}", e)))?;
     let seq_id = resp.sequence_id;
     let channel_id = resp.channel;
+    record_transfer(deps.storage, &env, msg.id, channel_id.clone(), seq_id)?;
     save_sudo_payload(deps.branch().storage, channel_id, seq_id, payload)?;
     Ok(Response::new())
 }
@@ -188,6 +506,7 @@ fn prepare_sudo_payload(mut deps: DepsMut, _env: Env, msg: Reply) -> StdResult<R
 pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> StdResult<Response> {
     match msg.id {
         IBC_SUDO_ID_RANGE_START..=IBC_SUDO_ID_RANGE_END => prepare_sudo_payload(deps, env, msg),
+        RESUBMIT_FAILURE_REPLY_ID => resubmit_failure_reply(deps, env, msg),
         _ => Err(StdError::generic_err(format!(
             "unsupported reply message id {}",
             msg.id
@@ -195,6 +514,81 @@ pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> StdResult<Response> {
     }
 }
 
+/// Timeout of a transfer over the channel, at the default height if not set
+fn packet_timeout(
+    deps: Deps,
+    env: &Env,
+    channel: &str,
+    timeout: Option<TimeoutSpec>,
+) -> StdResult<PacketTimeout> {
+    match timeout {
+        Some(timeout) => timeout.packet_timeout(&deps.querier, env, "transfer", channel),
+        None => Ok(PacketTimeout {
+            height: Some(Height {
+                revision_number: 2,
+                revision_height: DEFAULT_TIMEOUT_HEIGHT,
+            }),
+            timestamp: 0,
+        }),
+    }
+}
+
+/// Maps the `timeout_height` the sends took before `timeout` to an `at_height` timeout, so the
+/// messages of the older callers keep their timeouts
+fn legacy_timeout(
+    timeout: Option<TimeoutSpec>,
+    timeout_height: Option<u64>,
+) -> StdResult<Option<TimeoutSpec>> {
+    match (timeout, timeout_height) {
+        (Some(_), Some(_)) => Err(StdError::generic_err(
+            "timeout and timeout_height can't be both set",
+        )),
+        (None, Some(height)) => Ok(Some(TimeoutSpec::AtHeight { height })),
+        (timeout, None) => Ok(timeout),
+    }
+}
+
+/// Builds the transfer of the coin from the contract over the channel, `fee` pays the relayers
+/// of the neutron transfer module
+fn build_transfer(
+    env: &Env,
+    channel: String,
+    receiver: String,
+    coin: &Coin,
+    memo: String,
+    timeout: PacketTimeout,
+    fee: Option<Fee>,
+) -> MsgTransfer {
+    MsgTransfer {
+        source_port: "transfer".to_string(),
+        source_channel: channel,
+        sender: env.contract.address.to_string(),
+        receiver,
+        token: Some(StdCoin {
+            amount: coin.amount.to_string(),
+            denom: coin.denom.clone(),
+        }),
+        timeout_height: timeout.height,
+        timeout_timestamp: timeout.timestamp,
+        fee,
+        memo,
+    }
+}
+
+/// Turns the transfer into the original ibc-go one, which has no fees
+fn native_transfer(msg: MsgTransfer) -> NativeMsgTransfer {
+    NativeMsgTransfer {
+        source_port: msg.source_port,
+        source_channel: msg.source_channel,
+        sender: msg.sender,
+        receiver: msg.receiver,
+        token: msg.token,
+        timeout_height: msg.timeout_height,
+        timeout_timestamp: msg.timeout_timestamp,
+        memo: msg.memo,
+    }
+}
+
 fn get_fee_item(denom: String, amount: Uint128) -> Vec<StdCoin> {
     if amount == Uint128::new(0) {
         vec![]
@@ -219,58 +613,55 @@ fn execute_set_fees(deps: DepsMut, fees: Option<Fees>) -> StdResult<Response> {
     Ok(Response::default())
 }
 
+#[allow(clippy::too_many_arguments)]
 fn execute_send(
     mut deps: DepsMut,
     env: Env,
+    info: MessageInfo,
     channel: String,
     to: String,
     denom: String,
     amount: Uint128,
-    timeout_height: Option<u64>,
+    timeout: Option<TimeoutSpec>,
 ) -> StdResult<Response> {
+    let timeout = packet_timeout(deps.as_ref(), &env, &channel, timeout)?;
     let fee = IBC_FEE.load(deps.storage)?;
-    let coin1 = StdCoin {
-        amount: amount.to_string(),
-        denom: denom.clone(),
-    };
-    let msg1 = MsgTransfer {
-        source_port: "transfer".to_string(),
-        source_channel: channel.clone(),
-        sender: env.contract.address.to_string(),
-        receiver: to.clone(),
-        token: Some(coin1),
-        timeout_height: Some(Height {
-            revision_number: 2,
-            revision_height: timeout_height.unwrap_or(DEFAULT_TIMEOUT_HEIGHT),
-        }),
-        timeout_timestamp: 0,
-        fee: fee.clone(),
-        memo: "".to_string(),
-    };
-    let coin2 = StdCoin {
-        amount: (amount * Uint128::new(2)).to_string(),
-        denom: denom.clone(),
-    };
-    let msg2 = MsgTransfer {
-        source_port: "transfer".to_string(),
-        source_channel: channel,
-        sender: env.contract.address.to_string(),
-        receiver: to,
-        token: Some(coin2),
-        timeout_height: Some(Height {
-            revision_number: 2,
-            revision_height: timeout_height.unwrap_or(DEFAULT_TIMEOUT_HEIGHT),
-        }),
-        timeout_timestamp: 0,
+    let amount2 = amount.checked_mul(Uint128::new(2))?;
+    let msg1 = build_transfer(
+        &env,
+        channel.clone(),
+        to.clone(),
+        &Coin::new(amount, denom.clone()),
+        "".to_string(),
+        timeout.clone(),
+        fee.clone(),
+    );
+    let msg2 = build_transfer(
+        &env,
+        channel,
+        to.clone(),
+        &Coin::new(amount2, denom.clone()),
+        "".to_string(),
+        timeout,
         fee,
-        memo: "".to_string(),
-    };
+    );
+    // the attached funds are refunded for the first transfer, then for the second one
+    let attached = attached_amount(&info, &denom, amount.checked_mul(Uint128::new(3))?);
+    let refundable1 = attached.min(amount);
     let submsg1 = msg_with_sudo_callback(
         deps.branch(),
         msg1,
         SudoPayload::HandlerPayload1(Type1 {
             message: "message".to_string(),
         }),
+        OutgoingTransfer {
+            sender: info.sender.clone(),
+            receiver: to.clone(),
+            amount: Coin::new(amount, denom.clone()),
+            refundable: refundable1,
+            route: vec![],
+            call: None,
+        },
     )?;
     let submsg2 = msg_with_sudo_callback(
         deps.branch(),
@@ -278,6 +669,14 @@ fn execute_send(
         SudoPayload::HandlerPayload2(Type2 {
             data: "data".to_string(),
         }),
+        OutgoingTransfer {
+            sender: info.sender,
+            receiver: to,
+            amount: Coin::new(amount2, denom),
+            refundable: attached - refundable1,
+            route: vec![],
+            call: None,
+        },
     )?;
     deps.as_ref()
         .api
@@ -288,38 +687,41 @@ fn execute_send(
     Ok(Response::default().add_submessages(vec![submsg1, submsg2]))
 }
 
+#[allow(clippy::too_many_arguments)]
 fn execute_send_native(
     mut deps: DepsMut,
     env: Env,
+    info: MessageInfo,
     channel: String,
     to: String,
     denom: String,
     amount: Uint128,
-    timeout_height: Option<u64>,
+    timeout: Option<TimeoutSpec>,
 ) -> StdResult<Response> {
-    let coin = StdCoin {
-        amount: amount.to_string(),
-        denom: denom.clone(),
-    };
-    let msg = NativeMsgTransfer {
-        source_port: "transfer".to_string(),
-        source_channel: channel.clone(),
-        sender: env.contract.address.to_string(),
-        receiver: to.clone(),
-        token: Some(coin),
-        timeout_height: Some(Height {
-            revision_number: 2,
-            revision_height: timeout_height.unwrap_or(DEFAULT_TIMEOUT_HEIGHT),
-        }),
-        timeout_timestamp: 0,
-        memo: "".to_string(),
-    };
+    let timeout = packet_timeout(deps.as_ref(), &env, &channel, timeout)?;
+    let msg = native_transfer(build_transfer(
+        &env,
+        channel,
+        to.clone(),
+        &Coin::new(amount, denom.clone()),
+        "".to_string(),
+        timeout,
+        None,
+    ));
     let submsg = msg_with_sudo_callback(
         deps.branch(),
         msg,
         SudoPayload::HandlerPayload1(Type1 {
             message: "message".to_string(),
         }),
+        OutgoingTransfer {
+            refundable: attached_amount(&info, &denom, amount),
+            sender: info.sender,
+            receiver: to,
+            amount: Coin::new(amount, denom),
+            route: vec![],
+            call: None,
+        },
     )?;
     deps.as_ref()
         .api
@@ -328,6 +730,177 @@ fn execute_send_native(
     Ok(Response::default().add_submessage(submsg))
 }
 
+#[allow(clippy::too_many_arguments)]
+fn execute_send_with_path(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    channel: String,
+    to: String,
+    base_denom: String,
+    path: String,
+    amount: Uint128,
+    timeout: Option<TimeoutSpec>,
+    allow_non_origin: bool,
+) -> StdResult<Response> {
+    let denom = resolve_denom(deps.as_ref(), &path, &base_denom)?;
+    match origin_channel(&path) {
+        Some(origin) if origin != channel && !allow_non_origin => {
+            return Err(StdError::generic_err(format!(
+                "{} can only be sent back over its origin channel {}",
+                denom, origin
+            )));
+        }
+        _ => {}
+    }
+    let timeout = packet_timeout(deps.as_ref(), &env, &channel, timeout)?;
+    let fee = IBC_FEE.load(deps.storage)?;
+    let msg = build_transfer(
+        &env,
+        channel,
+        to.clone(),
+        &Coin::new(amount, denom.clone()),
+        "".to_string(),
+        timeout,
+        fee,
+    );
+    let submsg = msg_with_sudo_callback(
+        deps.branch(),
+        msg,
+        SudoPayload::HandlerPayload1(Type1 {
+            message: "message".to_string(),
+        }),
+        OutgoingTransfer {
+            refundable: attached_amount(&info, &denom, amount),
+            sender: info.sender,
+            receiver: to,
+            amount: Coin::new(amount, denom),
+            route: vec![],
+            call: None,
+        },
+    )?;
+    deps.api.debug(
+        format!(
+            "WASMDEBUG: execute_send_with_path: sent submsg: {:?}",
+            submsg
+        )
+        .as_str(),
+    );
+
+    Ok(Response::default().add_submessage(submsg))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn execute_send_multi_hop(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    hops: Vec<(String, String)>,
+    denom: String,
+    amount: Uint128,
+    timeout: Option<TimeoutSpec>,
+    retries: Option<u8>,
+) -> StdResult<Response> {
+    let route = validate_hops(&hops)?;
+    let timeout = packet_timeout(deps.as_ref(), &env, &route[0].channel, timeout)?;
+    let fee = IBC_FEE.load(deps.storage)?;
+    let msg = build_transfer(
+        &env,
+        route[0].channel.clone(),
+        route[0].receiver.clone(),
+        &Coin::new(amount, denom.clone()),
+        forward_memo(&route, retries)?,
+        timeout,
+        fee,
+    );
+    let submsg = msg_with_sudo_callback(
+        deps.branch(),
+        msg,
+        SudoPayload::HandlerPayload1(Type1 {
+            message: "message".to_string(),
+        }),
+        OutgoingTransfer {
+            refundable: attached_amount(&info, &denom, amount),
+            sender: info.sender,
+            receiver: route[route.len() - 1].receiver.clone(),
+            amount: Coin::new(amount, denom),
+            route: route.clone(),
+            call: None,
+        },
+    )?;
+    deps.api.debug(
+        format!(
+            "WASMDEBUG: execute_send_multi_hop: sent submsg: {:?}",
+            submsg
+        )
+        .as_str(),
+    );
+
+    Ok(Response::default()
+        .add_attribute("route", route_string(&route))
+        .add_submessage(submsg))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn execute_send_and_call(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    channel: String,
+    contract: String,
+    msg: Binary,
+    denom: String,
+    amount: Uint128,
+    timeout: Option<TimeoutSpec>,
+    ibc_callback: Option<String>,
+) -> StdResult<Response> {
+    let ibc_callback = ibc_callback
+        .map(|addr| deps.api.addr_validate(&addr))
+        .transpose()?;
+    let memo = wasm_hook_memo(&contract, &msg, ibc_callback.as_ref().map(Addr::as_str))?;
+    let timeout = packet_timeout(deps.as_ref(), &env, &channel, timeout)?;
+    let fee = IBC_FEE.load(deps.storage)?;
+    // the middleware only executes the hook if the tokens are sent to the contract
+    let transfer = build_transfer(
+        &env,
+        channel,
+        contract.clone(),
+        &Coin::new(amount, denom.clone()),
+        memo,
+        timeout,
+        fee,
+    );
+    let submsg = msg_with_sudo_callback(
+        deps.branch(),
+        transfer,
+        SudoPayload::HandlerPayload1(Type1 {
+            message: "message".to_string(),
+        }),
+        OutgoingTransfer {
+            refundable: attached_amount(&info, &denom, amount),
+            sender: info.sender,
+            receiver: contract.clone(),
+            amount: Coin::new(amount, denom),
+            route: vec![],
+            call: Some(RemoteCall {
+                contract,
+                msg,
+                ack: None,
+                result: None,
+            }),
+        },
+    )?;
+    deps.api.debug(
+        format!(
+            "WASMDEBUG: execute_send_and_call: sent submsg: {:?}",
+            submsg
+        )
+        .as_str(),
+    );
+
+    Ok(Response::default().add_submessage(submsg))
+}
+
 fn execute_resubmit_failure(_: DepsMut, env: Env, failure_id: u64) -> StdResult<Response> {
     let msg: CosmosMsg = MsgResubmitFailure {
         sender: env.contract.address.to_string(),
@@ -395,7 +968,24 @@ fn sudo_error(deps: DepsMut, req: RequestPacket, data: String) -> StdResult<Resp
         )
         .as_str(),
     );
-    Ok(Response::new())
+    let settled = settle_transfer(
+        deps.storage,
+        &req,
+        TransferStatus::Failed {
+            details: data.clone(),
+        },
+    )?;
+    match settled {
+        // the error may have happened on any hop, the intermediate chains pass its
+        // acknowledgement back unchanged. It's redacted to the ABCI code of the error, which
+        // doesn't identify the hop, so the whole route is reported
+        Some(transfer) if !transfer.route.is_empty() => Ok(Response::new()
+            .add_attribute("action", "multi_hop_transfer_failed")
+            .add_attribute("route", route_string(&transfer.route))
+            .add_attribute("sequence_id", transfer.sequence_id.to_string())
+            .add_attribute("details", data)),
+        _ => Ok(Response::new()),
+    }
 }
 
 fn sudo_timeout(deps: DepsMut, req: RequestPacket) -> StdResult<Response> {
@@ -406,6 +996,7 @@ fn sudo_timeout(deps: DepsMut, req: RequestPacket) -> StdResult<Response> {
         )
         .as_str(),
     );
+    settle_transfer(deps.storage, &req, TransferStatus::TimedOut)?;
     Ok(Response::new())
 }
 
@@ -417,6 +1008,9 @@ fn sudo_response(deps: DepsMut, req: RequestPacket, data: Binary) -> StdResult<R
         )
         .as_str(),
     );
+    if let Some(transfer) = settle_transfer(deps.storage, &req, TransferStatus::Acknowledged)? {
+        record_call_result(deps.storage, transfer, &data)?;
+    }
     let seq_id = req
         .sequence
         .ok_or_else(|| StdError::generic_err("sequence not found"))?;
@@ -432,10 +1026,17 @@ fn sudo_response(deps: DepsMut, req: RequestPacket, data: Binary) -> StdResult<R
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
-pub struct MigrateMsg {}
+pub struct MigrateMsg {
+    /// sets the owner of a contract instantiated before ownership has been introduced
+    pub owner: Option<String>,
+}
 
 #[entry_point]
-pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> StdResult<Response> {
+pub fn migrate(deps: DepsMut, _env: Env, msg: MigrateMsg) -> StdResult<Response> {
     deps.api.debug("WASMDEBUG: migrate");
+    if let Some(owner) = msg.owner {
+        let owner = deps.api.addr_validate(&owner)?;
+        OWNER.save(deps.storage, &owner)?;
+    }
     Ok(Response::default())
 }
@@ -0,0 +1,91 @@
+use crate::state::{Transfer, TRANSFERS};
+use cosmwasm_std::{from_json, Binary, StdError, StdResult, Storage};
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+// Maximum memo length accepted by the transfer module
+const MAX_MEMO_LEN: usize = 32768;
+const BECH32_CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// Result of a successful acknowledgement of the ibc-hooks middleware
+#[derive(Deserialize)]
+struct HookAck {
+    contract_result: Option<Binary>,
+}
+
+fn to_json_string(value: &str) -> StdResult<String> {
+    serde_json_wasm::to_string(value)
+        .map_err(|e| StdError::generic_err(format!("failed to build memo: {}", e)))
+}
+
+/// Checks the address is a bech32 one, its prefix is the remote chain's
+fn validate_remote_contract(contract: &str) -> StdResult<()> {
+    let valid = contract == contract.to_lowercase()
+        && contract.rsplit_once('1').is_some_and(|(hrp, data)| {
+            !hrp.is_empty() && data.len() >= 6 && data.chars().all(|c| BECH32_CHARSET.contains(c))
+        });
+    if !valid {
+        return Err(StdError::generic_err(format!(
+            "invalid contract address {}",
+            contract
+        )));
+    }
+    Ok(())
+}
+
+/// Builds the memo of the ibc-hooks middleware executing the msg, a JSON object, on the contract
+/// of the destination chain. `ibc_callback` is the contract the middleware of the source chain
+/// notifies of the acknowledgement.
+pub fn wasm_hook_memo(
+    contract: &str,
+    msg: &Binary,
+    ibc_callback: Option<&str>,
+) -> StdResult<String> {
+    validate_remote_contract(contract)?;
+    from_json::<BTreeMap<String, IgnoredAny>>(msg)
+        .map_err(|e| StdError::generic_err(format!("msg is not a JSON object: {}", e)))?;
+    let msg = std::str::from_utf8(msg.as_slice())
+        .map_err(|e| StdError::generic_err(format!("msg is not a JSON object: {}", e)))?;
+    // the msg is embedded as is, the middleware passes it to the contract
+    let mut memo = format!(
+        r#"{{"wasm":{{"contract":{},"msg":{}}}"#,
+        to_json_string(contract)?,
+        msg.trim()
+    );
+    if let Some(ibc_callback) = ibc_callback {
+        memo.push_str(&format!(
+            r#","ibc_callback":{}"#,
+            to_json_string(ibc_callback)?
+        ));
+    }
+    memo.push('}');
+    if memo.len() > MAX_MEMO_LEN {
+        return Err(StdError::generic_err(format!(
+            "memo is {} bytes long, the maximum is {}",
+            memo.len(),
+            MAX_MEMO_LEN
+        )));
+    }
+    Ok(memo)
+}
+
+/// Records the result of the remote call of the acknowledged transfer
+pub fn record_call_result(
+    store: &mut dyn Storage,
+    mut transfer: Transfer,
+    data: &Binary,
+) -> StdResult<()> {
+    let Some(call) = transfer.call.as_mut() else {
+        return Ok(());
+    };
+    call.result = from_json::<HookAck>(data)
+        .ok()
+        .and_then(|ack| ack.contract_result);
+    call.ack = Some(data.clone());
+    TRANSFERS.save(
+        store,
+        (transfer.channel_id.clone(), transfer.sequence_id),
+        &transfer,
+    )
+}
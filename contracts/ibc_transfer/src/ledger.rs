@@ -0,0 +1,147 @@
+use crate::state::{
+    OutgoingTransfer, Transfer, TransferStatus, CLAIMABLE, OUTGOING_TRANSFERS, SENDER_TRANSFERS,
+    TRANSFERS,
+};
+use cosmwasm_std::{
+    BankMsg, Coin, Deps, DepsMut, Env, MessageInfo, Order, Response, StdError, StdResult, Storage,
+    Uint128,
+};
+use cw_storage_plus::Bound;
+use neutron_sdk::sudo::msg::RequestPacket;
+
+/// Keeps the transfer until its sequence is known in the reply of the transfer message
+pub fn save_outgoing_transfer(
+    store: &mut dyn Storage,
+    reply_id: u64,
+    transfer: &OutgoingTransfer,
+) -> StdResult<()> {
+    OUTGOING_TRANSFERS.save(store, reply_id, transfer)
+}
+
+/// Records the transfer sent in the ledger under the sequence assigned by the transfer module
+pub fn record_transfer(
+    store: &mut dyn Storage,
+    env: &Env,
+    reply_id: u64,
+    channel_id: String,
+    seq_id: u64,
+) -> StdResult<()> {
+    let outgoing = OUTGOING_TRANSFERS.load(store, reply_id)?;
+    OUTGOING_TRANSFERS.remove(store, reply_id);
+    SENDER_TRANSFERS.save(store, (&outgoing.sender, (channel_id.clone(), seq_id)), &())?;
+    TRANSFERS.save(
+        store,
+        (channel_id.clone(), seq_id),
+        &Transfer {
+            sender: outgoing.sender,
+            receiver: outgoing.receiver,
+            amount: outgoing.amount,
+            refundable: outgoing.refundable,
+            channel_id,
+            sequence_id: seq_id,
+            sent_at_height: env.block.height,
+            route: outgoing.route,
+            call: outgoing.call,
+            status: TransferStatus::Pending,
+        },
+    )
+}
+
+/// Sets the final status of the transfer the packet has been sent for. The tokens of a failed
+/// or timed out transfer are refunded to the contract, the part the sender has attached becomes
+/// claimable by the sender and the rest stays with the contract.
+/// Returns the transfer if it has been settled now.
+pub fn settle_transfer(
+    store: &mut dyn Storage,
+    req: &RequestPacket,
+    status: TransferStatus,
+) -> StdResult<Option<Transfer>> {
+    let (Some(channel_id), Some(seq_id)) = (req.source_channel.clone(), req.sequence) else {
+        return Err(StdError::generic_err("channel_id or sequence not found"));
+    };
+    // transfers sent before the ledger has been introduced aren't tracked
+    let Some(mut transfer) = TRANSFERS.may_load(store, (channel_id.clone(), seq_id))? else {
+        return Ok(None);
+    };
+    if transfer.status != TransferStatus::Pending {
+        return Ok(None);
+    }
+    if status != TransferStatus::Acknowledged && !transfer.refundable.is_zero() {
+        CLAIMABLE.update(
+            store,
+            (&transfer.sender, transfer.amount.denom.as_str()),
+            |claimable| -> StdResult<_> { Ok(claimable.unwrap_or_default() + transfer.refundable) },
+        )?;
+    }
+    transfer.status = status;
+    TRANSFERS.save(store, (channel_id, seq_id), &transfer)?;
+    Ok(Some(transfer))
+}
+
+/// Returns the amount of the denom the sender has attached to the message, up to `amount`
+pub fn attached_amount(info: &MessageInfo, denom: &str, amount: Uint128) -> Uint128 {
+    info.funds
+        .iter()
+        .filter(|coin| coin.denom == denom)
+        .map(|coin| coin.amount)
+        .sum::<Uint128>()
+        .min(amount)
+}
+
+/// Sends the sender the whole refunded amount of the denom
+pub fn execute_claim_refund(
+    deps: DepsMut,
+    info: MessageInfo,
+    denom: String,
+) -> StdResult<Response> {
+    let amount = CLAIMABLE
+        .may_load(deps.storage, (&info.sender, &denom))?
+        .unwrap_or_default();
+    if amount.is_zero() {
+        return Err(StdError::generic_err(format!(
+            "nothing to claim in {}",
+            denom
+        )));
+    }
+    CLAIMABLE.remove(deps.storage, (&info.sender, &denom));
+    Ok(Response::default()
+        .add_attribute("action", "claim_refund")
+        .add_attribute("amount", Coin::new(amount, denom.clone()).to_string())
+        .add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: vec![Coin::new(amount, denom)],
+        }))
+}
+
+pub fn query_transfer(deps: Deps, channel_id: String, sequence_id: u64) -> StdResult<Transfer> {
+    TRANSFERS.load(deps.storage, (channel_id, sequence_id))
+}
+
+pub fn query_transfers_by_sender(
+    deps: Deps,
+    sender: String,
+    start_after: Option<(String, u64)>,
+    limit: u32,
+) -> StdResult<Vec<Transfer>> {
+    let sender = deps.api.addr_validate(&sender)?;
+    SENDER_TRANSFERS
+        .prefix(&sender)
+        .keys(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit as usize)
+        .map(|key| TRANSFERS.load(deps.storage, key?))
+        .collect()
+}
+
+pub fn query_claimable(deps: Deps, address: String) -> StdResult<Vec<Coin>> {
+    let address = deps.api.addr_validate(&address)?;
+    CLAIMABLE
+        .prefix(&address)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(denom, amount): (String, Uint128)| Coin::new(amount, denom)))
+        .collect()
+}
@@ -17,4 +17,12 @@
 pub mod contract;
 pub mod state;
 
+mod hooks;
 mod integration_tests_mock_handlers;
+mod ledger;
+mod multihop;
+mod traces;
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod testing;
@@ -0,0 +1,85 @@
+use crate::state::Hop;
+use cosmwasm_std::{StdError, StdResult};
+use serde::Serialize;
+
+const TRANSFER_PORT: &str = "transfer";
+// Number of hops the packet forward middleware is expected to handle in a single transfer
+const MAX_HOPS: usize = 4;
+
+/// Memo of the packet forward middleware, the intermediate chain forwards the received tokens
+/// as described in `forward`
+#[derive(Serialize)]
+struct ForwardMemo {
+    forward: Forward,
+}
+
+#[derive(Serialize)]
+struct Forward {
+    receiver: String,
+    port: String,
+    channel: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retries: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next: Option<Box<ForwardMemo>>,
+}
+
+/// Checks the route of a multi-hop transfer, every hop needs a channel and a receiver
+pub fn validate_hops(hops: &[(String, String)]) -> StdResult<Vec<Hop>> {
+    if hops.len() < 2 || hops.len() > MAX_HOPS {
+        return Err(StdError::generic_err(format!(
+            "a multi-hop transfer takes from 2 to {} hops, got {}",
+            MAX_HOPS,
+            hops.len()
+        )));
+    }
+    hops.iter()
+        .map(|(channel, receiver)| {
+            if !channel.starts_with("channel-") {
+                return Err(StdError::generic_err(format!(
+                    "invalid channel {}",
+                    channel
+                )));
+            }
+            if receiver.trim().is_empty() {
+                return Err(StdError::generic_err(format!(
+                    "empty receiver of the hop over {}",
+                    channel
+                )));
+            }
+            Ok(Hop {
+                channel: channel.clone(),
+                receiver: receiver.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Builds the memo forwarding the tokens over the hops following the first one, which is the
+/// transfer itself
+pub fn forward_memo(hops: &[Hop], retries: Option<u8>) -> StdResult<String> {
+    let memo = hops[1..].iter().rev().fold(None, |next, hop| {
+        Some(Box::new(ForwardMemo {
+            forward: Forward {
+                receiver: hop.receiver.clone(),
+                port: TRANSFER_PORT.to_string(),
+                channel: hop.channel.clone(),
+                retries,
+                next,
+            },
+        }))
+    });
+    match memo {
+        Some(memo) => serde_json_wasm::to_string(&memo)
+            .map_err(|e| StdError::generic_err(format!("failed to build memo: {}", e))),
+        None => Ok("".to_string()),
+    }
+}
+
+/// Formats the route for the events, e.g. `channel-0>channel-5`
+pub fn route_string(hops: &[Hop]) -> String {
+    hops.iter()
+        .map(|hop| hop.channel.as_str())
+        .collect::<Vec<_>>()
+        .join(">")
+}
@@ -1,4 +1,5 @@
-use cosmwasm_std::{from_json, to_json_vec, Binary, StdResult, Storage};
+pub use contract_failures::Resubmission;
+use cosmwasm_std::{from_json, to_json_vec, Addr, Binary, Coin, StdResult, Storage, Uint128};
 use cw_storage_plus::{Item, Map};
 use neutron_std::types::neutron::feerefunder::Fee;
 use schemars::JsonSchema;
@@ -9,6 +10,8 @@ use crate::contract::SudoPayload;
 pub const IBC_SUDO_ID_RANGE_START: u64 = 1_000_000_000;
 pub const IBC_SUDO_ID_RANGE_SIZE: u64 = 1_000;
 pub const IBC_SUDO_ID_RANGE_END: u64 = IBC_SUDO_ID_RANGE_START + IBC_SUDO_ID_RANGE_SIZE;
+// out of the sudo payload ids range
+pub const RESUBMIT_FAILURE_REPLY_ID: u64 = 1;
 
 pub const IBC_FEE: Item<Option<Fee>> = Item::new("ibc_fee");
 
@@ -67,6 +70,80 @@ pub fn read_sudo_payload(
     from_json(Binary::new(data))
 }
 
+/// Transfers waiting for the transfer module to assign them a sequence, by reply id
+pub const OUTGOING_TRANSFERS: Map<u64, OutgoingTransfer> = Map::new("outgoing_transfers");
+/// Ledger of the sent transfers, by (channel_id, seq_id)
+pub const TRANSFERS: Map<(String, u64), Transfer> = Map::new("transfers");
+/// Index of the ledger by the address which executed the send
+pub const SENDER_TRANSFERS: Map<(&Addr, (String, u64)), ()> = Map::new("sender_transfers");
+/// Refunds of failed and timed out transfers the sender can claim, by (sender, denom)
+pub const CLAIMABLE: Map<(&Addr, &str), Uint128> = Map::new("claimable");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct OutgoingTransfer {
+    pub sender: Addr,
+    pub receiver: String,
+    pub amount: Coin,
+    pub refundable: Uint128,
+    pub route: Vec<Hop>,
+    pub call: Option<RemoteCall>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Transfer {
+    pub sender: Addr,
+    pub receiver: String,
+    pub amount: Coin,
+    /// part of the amount the sender has attached to the message, it's refunded to the sender
+    /// if the transfer fails while the rest stays with the contract
+    #[serde(default)]
+    pub refundable: Uint128,
+    pub channel_id: String,
+    pub sequence_id: u64,
+    pub sent_at_height: u64,
+    /// hops of a multi-hop transfer, the first one being the transfer itself, empty otherwise
+    #[serde(default)]
+    pub route: Vec<Hop>,
+    /// contract called with the ibc-hooks middleware on the destination chain
+    #[serde(default)]
+    pub call: Option<RemoteCall>,
+    pub status: TransferStatus,
+}
+
+/// Outcome of the call follows the transfer's status: an error acknowledgement carries the
+/// error of the call
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct RemoteCall {
+    pub contract: String,
+    pub msg: Binary,
+    /// result of the successful acknowledgement
+    pub ack: Option<Binary>,
+    /// data the contract has responded with, decoded from the acknowledgement
+    pub result: Option<Binary>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Hop {
+    pub channel: String,
+    pub receiver: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferStatus {
+    Pending,
+    /// a multi-hop transfer is acknowledged once the last hop has been, the intermediate chains
+    /// hold the acknowledgement back until then
+    Acknowledged,
+    /// refunded, with the details of the error acknowledgement. The error of a later hop of a
+    /// multi-hop transfer is passed back in the acknowledgement of the first one.
+    Failed {
+        details: String,
+    },
+    /// refunded
+    TimedOut,
+}
+
 /// Used only in integration tests framework to simulate failures.
 pub const INTEGRATION_TESTS_SUDO_FAILURE_MOCK: Item<IntegrationTestsSudoFailureMock> =
     Item::new("integration_tests_sudo_mock");
@@ -0,0 +1,689 @@
+// Copyright 2022 Neutron
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::contract::{
+    execute, instantiate, query, reply, sudo, ExecuteMsg, FailureInfo, FailureKind, Fees, Holding,
+    InstantiateMsg, QueryMsg,
+};
+use crate::state::{IntegrationTestsSudoFailureMock, Resubmission, Transfer, TransferStatus};
+use cosmwasm_std::testing::message_info;
+use cosmwasm_std::{from_json, Addr, Binary, Coin, Uint128};
+use ibc_timeout::TimeoutSpec;
+use neutron_sdk::sudo::msg::RequestPacketTimeoutHeight;
+use neutron_std::types::ibc::core::client::v1::Height;
+use neutron_test_support::{ContractWrapper, NeutronApp, RELAYER_ADDR};
+
+const RECEIVER: &str = "cosmos10h9stc5v6ntgeygf5xf945njqq5h32r53uquvw";
+
+fn setup() -> (NeutronApp, Addr) {
+    let mut app = NeutronApp::new(
+        ContractWrapper::new(execute, reply, sudo),
+        &[Coin::new(100_000u128, "stake")],
+    );
+    let owner = app.deps.api.addr_make("owner");
+    instantiate(
+        app.deps.as_mut(),
+        app.env.clone(),
+        message_info(&owner, &[]),
+        InstantiateMsg { owner: None },
+    )
+    .unwrap();
+    app.execute(
+        message_info(&owner, &[]),
+        &ExecuteMsg::SetFees {
+            fees: Some(Fees {
+                denom: "stake".to_string(),
+                recv_fee: Uint128::zero(),
+                ack_fee: Uint128::new(2000),
+                timeout_fee: Uint128::new(2000),
+            }),
+        },
+    )
+    .unwrap();
+    (app, owner)
+}
+
+fn send(amount: u128) -> ExecuteMsg {
+    ExecuteMsg::Send {
+        channel: "channel-0".to_string(),
+        to: RECEIVER.to_string(),
+        denom: "stake".to_string(),
+        amount: Uint128::new(amount),
+        timeout: None,
+        timeout_height: None,
+    }
+}
+
+fn balance(app: &NeutronApp, addr: &str) -> u128 {
+    app.deps.querier.balance_of(addr, "stake").u128()
+}
+
+#[test]
+fn test_send_acknowledged() {
+    let (mut app, owner) = setup();
+    let contract = app.contract_address();
+
+    // a send makes two transfers, of the amount and twice the amount, each locking the fees
+    app.execute(message_info(&owner, &[]), &send(5000)).unwrap();
+    assert_eq!(app.transfer.in_flight().len(), 2);
+    assert_eq!(balance(&app, contract.as_str()), 100_000 - 15_000 - 8000);
+
+    app.transfer_ack("channel-0", 1).unwrap();
+    app.transfer_ack("channel-0", 2).unwrap();
+    assert!(app.transfer.in_flight().is_empty());
+    // the relayer gets the ack fees and the timeout fees are refunded
+    assert_eq!(balance(&app, RELAYER_ADDR), 4000);
+    assert_eq!(balance(&app, contract.as_str()), 100_000 - 15_000 - 4000);
+}
+
+#[test]
+fn test_timed_out_transfer_refunded() {
+    let (mut app, owner) = setup();
+    let contract = app.contract_address();
+
+    app.execute(
+        message_info(&owner, &[]),
+        &ExecuteMsg::SendNative {
+            channel: "channel-0".to_string(),
+            to: RECEIVER.to_string(),
+            denom: "stake".to_string(),
+            amount: Uint128::new(1000),
+            timeout: None,
+            timeout_height: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(balance(&app, contract.as_str()), 99_000);
+    app.transfer_timeout("channel-0", 1).unwrap();
+    assert_eq!(balance(&app, contract.as_str()), 100_000);
+}
+
+#[test]
+fn test_sudo_failures() {
+    let (mut app, owner) = setup();
+    let contract = app.contract_address();
+
+    app.execute(
+        message_info(&owner, &[]),
+        &ExecuteMsg::IntegrationTestsSetSudoFailureMock {
+            state: IntegrationTestsSudoFailureMock::Enabled,
+        },
+    )
+    .unwrap();
+    app.execute(message_info(&owner, &[]), &send(5000)).unwrap();
+    app.transfer_ack("channel-0", 1).unwrap_err();
+    app.transfer_error("channel-0", 2, "receiver is blocked")
+        .unwrap_err();
+
+    // the failed callbacks are recorded, the error one with the refund already done
+    let failures = app.deps.querier.failures(contract.as_str());
+    assert_eq!(
+        failures.iter().map(|f| f.id).collect::<Vec<_>>(),
+        vec![0, 1]
+    );
+    assert_eq!(balance(&app, contract.as_str()), 100_000 - 5000 - 4000);
+
+    app.execute(
+        message_info(&owner, &[]),
+        &ExecuteMsg::IntegrationTestsUnsetSudoFailureMock {},
+    )
+    .unwrap();
+    app.execute(
+        message_info(&owner, &[]),
+        &ExecuteMsg::ResubmitFailure { failure_id: 1 },
+    )
+    .unwrap();
+    let failures = app.deps.querier.failures(contract.as_str());
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].id, 0);
+}
+
+#[test]
+fn test_resubmit_failures() {
+    let (mut app, owner) = setup();
+    app.execute(
+        message_info(&owner, &[]),
+        &ExecuteMsg::IntegrationTestsSetSudoFailureMock {
+            state: IntegrationTestsSudoFailureMock::Enabled,
+        },
+    )
+    .unwrap();
+    app.execute(message_info(&owner, &[]), &send(5000)).unwrap();
+    app.transfer_ack("channel-0", 1).unwrap_err();
+    app.transfer_timeout("channel-0", 2).unwrap_err();
+
+    let failures = |app: &NeutronApp| {
+        let res: Vec<FailureInfo> = from_json(
+            query(
+                app.deps.as_ref(),
+                app.env.clone(),
+                QueryMsg::Failures {
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        res
+    };
+    let listed = failures(&app);
+    assert_eq!(
+        listed
+            .iter()
+            .map(|f| (f.kind.clone(), f.channel_id.clone(), f.sequence_id))
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                FailureKind::Response,
+                Some("channel-0".to_string()),
+                Some(1)
+            ),
+            (FailureKind::Timeout, Some("channel-0".to_string()), Some(2)),
+        ]
+    );
+
+    app.execute(
+        message_info(&owner, &[]),
+        &ExecuteMsg::IntegrationTestsUnsetSudoFailureMock {},
+    )
+    .unwrap();
+    app.execute(
+        message_info(&owner, &[]),
+        &ExecuteMsg::ResubmitFailures {
+            channel_id: Some("channel-0".to_string()),
+            error_contains: None,
+            start_after: None,
+
+            max: Some(1),
+        },
+    )
+    .unwrap();
+    let listed = failures(&app);
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].id, 1);
+    let resubmissions: Vec<Resubmission> = from_json(
+        query(
+            app.deps.as_ref(),
+            app.env.clone(),
+            QueryMsg::Resubmissions {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        resubmissions,
+        vec![Resubmission {
+            failure_id: 0,
+            height: app.env.block.height,
+            error: None,
+        }]
+    );
+}
+
+#[test]
+fn test_transfer_ledger() {
+    let (mut app, owner) = setup();
+    let operator = app.deps.api.addr_make("operator");
+    app.execute(
+        message_info(&owner, &[]),
+        &ExecuteMsg::SetOperators {
+            action: "send".to_string(),
+            operators: vec![operator.to_string()],
+        },
+    )
+    .unwrap();
+    // the operator funds a part of the first transfer, the contract funds the rest
+    app.deps
+        .querier
+        .credit(operator.as_str(), &[Coin::new(3000u128, "stake")]);
+    app.execute(
+        message_info(&operator, &[Coin::new(3000u128, "stake")]),
+        &send(5000),
+    )
+    .unwrap();
+    app.transfer_error("channel-0", 1, "receiver is blocked")
+        .unwrap();
+    app.transfer_ack("channel-0", 2).unwrap();
+
+    let transfers: Vec<Transfer> = from_json(
+        query(
+            app.deps.as_ref(),
+            app.env.clone(),
+            QueryMsg::TransfersBySender {
+                sender: operator.to_string(),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        transfers
+            .iter()
+            .map(|t| (
+                t.sequence_id,
+                t.amount.clone(),
+                t.refundable,
+                t.status.clone()
+            ))
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                1,
+                Coin::new(5000u128, "stake"),
+                Uint128::new(3000),
+                TransferStatus::Failed {
+                    details: "receiver is blocked".to_string()
+                }
+            ),
+            (
+                2,
+                Coin::new(10_000u128, "stake"),
+                Uint128::zero(),
+                TransferStatus::Acknowledged
+            ),
+        ]
+    );
+    let transfer: Transfer = from_json(
+        query(
+            app.deps.as_ref(),
+            app.env.clone(),
+            QueryMsg::Transfer {
+                channel_id: "channel-0".to_string(),
+                sequence_id: 2,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(transfer, transfers[1]);
+
+    // only the attached part of the failed transfer is refunded to the operator, not to the owner
+    let claimable = |app: &NeutronApp, addr: &Addr| {
+        let res: Vec<Coin> = from_json(
+            query(
+                app.deps.as_ref(),
+                app.env.clone(),
+                QueryMsg::Claimable {
+                    address: addr.to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        res
+    };
+    assert_eq!(
+        claimable(&app, &operator),
+        vec![Coin::new(3000u128, "stake")]
+    );
+    assert!(claimable(&app, &owner).is_empty());
+    app.execute(
+        message_info(&owner, &[]),
+        &ExecuteMsg::ClaimRefund {
+            denom: "stake".to_string(),
+        },
+    )
+    .unwrap_err();
+    app.execute(
+        message_info(&operator, &[]),
+        &ExecuteMsg::ClaimRefund {
+            denom: "stake".to_string(),
+        },
+    )
+    .unwrap();
+    assert_eq!(balance(&app, operator.as_str()), 3000);
+    assert!(claimable(&app, &operator).is_empty());
+}
+
+#[test]
+fn test_send_with_path() {
+    let (mut app, owner) = setup();
+    let contract = app.contract_address();
+    let voucher = app
+        .deps
+        .querier
+        .add_denom_trace("transfer/channel-1", "uatom");
+    app.deps
+        .querier
+        .credit(contract.as_str(), &[Coin::new(10_000u128, voucher.clone())]);
+    let send_with_path = |channel: &str, path: &str, allow_non_origin| ExecuteMsg::SendWithPath {
+        channel: channel.to_string(),
+        to: RECEIVER.to_string(),
+        base_denom: "uatom".to_string(),
+        path: path.to_string(),
+        amount: Uint128::new(1000),
+        timeout: None,
+        allow_non_origin,
+    };
+
+    // the trace has to be known and the voucher goes back over its origin channel
+    app.execute(
+        message_info(&owner, &[]),
+        &send_with_path("channel-1", "transfer/channel-2", None),
+    )
+    .unwrap_err();
+    app.execute(
+        message_info(&owner, &[]),
+        &send_with_path("channel-0", "transfer/channel-1", None),
+    )
+    .unwrap_err();
+    app.execute(
+        message_info(&owner, &[]),
+        &send_with_path("channel-1", "transfer/channel-1", None),
+    )
+    .unwrap();
+    app.execute(
+        message_info(&owner, &[]),
+        &send_with_path("channel-0", "transfer/channel-1", Some(true)),
+    )
+    .unwrap();
+    assert_eq!(
+        app.transfer
+            .in_flight()
+            .iter()
+            .map(|t| (t.packet.source_channel.clone().unwrap(), t.token.clone()))
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                "channel-1".to_string(),
+                Coin::new(1000u128, voucher.clone())
+            ),
+            (
+                "channel-0".to_string(),
+                Coin::new(1000u128, voucher.clone())
+            ),
+        ]
+    );
+
+    let holdings: Vec<Holding> =
+        from_json(query(app.deps.as_ref(), app.env.clone(), QueryMsg::Holdings {}).unwrap())
+            .unwrap();
+    let voucher_holding = holdings.iter().find(|h| h.denom == voucher).unwrap();
+    assert_eq!(
+        voucher_holding,
+        &Holding {
+            denom: voucher,
+            amount: Uint128::new(8000),
+            base_denom: "uatom".to_string(),
+            path: "transfer/channel-1".to_string(),
+            origin_channel: Some("channel-1".to_string()),
+        }
+    );
+    let native_holding = holdings.iter().find(|h| h.denom == "stake").unwrap();
+    assert_eq!(native_holding.path, "");
+    assert_eq!(native_holding.origin_channel, None);
+}
+
+#[test]
+fn test_send_multi_hop() {
+    let (mut app, owner) = setup();
+    let hops = |hops: &[(&str, &str)]| {
+        hops.iter()
+            .map(|(channel, receiver)| (channel.to_string(), receiver.to_string()))
+            .collect::<Vec<_>>()
+    };
+    let send_multi_hop = |hops| ExecuteMsg::SendMultiHop {
+        hops,
+        denom: "stake".to_string(),
+        amount: Uint128::new(1000),
+        timeout: None,
+        retries: None,
+    };
+
+    app.execute(
+        message_info(&owner, &[]),
+        &send_multi_hop(hops(&[("channel-0", "pfm")])),
+    )
+    .unwrap_err();
+    app.execute(
+        message_info(&owner, &[]),
+        &send_multi_hop(hops(&[
+            ("channel-0", "pfm"),
+            ("transfer/channel-5", RECEIVER),
+        ])),
+    )
+    .unwrap_err();
+    app.execute(
+        message_info(&owner, &[]),
+        &send_multi_hop(hops(&[
+            ("channel-0", "pfm"),
+            ("channel-5", "pfm"),
+            ("channel-7", RECEIVER),
+        ])),
+    )
+    .unwrap();
+    let in_flight = &app.transfer.in_flight()[0];
+    assert_eq!(in_flight.receiver, "pfm");
+    assert_eq!(
+        in_flight.memo,
+        format!(
+            r#"{{"forward":{{"receiver":"pfm","port":"transfer","channel":"channel-5","next":{{"forward":{{"receiver":"{}","port":"transfer","channel":"channel-7"}}}}}}}}"#,
+            RECEIVER
+        )
+    );
+
+    // a later hop failing is reported in the error acknowledgement of the first one
+    app.transfer_error(
+        "channel-0",
+        1,
+        "ABCI code: 5: error handling packet: see events for details",
+    )
+    .unwrap();
+    let transfer: Transfer = from_json(
+        query(
+            app.deps.as_ref(),
+            app.env.clone(),
+            QueryMsg::Transfer {
+                channel_id: "channel-0".to_string(),
+                sequence_id: 1,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(transfer.receiver, RECEIVER);
+    assert_eq!(
+        transfer
+            .route
+            .iter()
+            .map(|hop| hop.channel.as_str())
+            .collect::<Vec<_>>(),
+        vec!["channel-0", "channel-5", "channel-7"]
+    );
+    assert_eq!(
+        transfer.status,
+        TransferStatus::Failed {
+            details: "ABCI code: 5: error handling packet: see events for details".to_string()
+        }
+    );
+}
+
+#[test]
+fn test_send_and_call() {
+    let (mut app, owner) = setup();
+    let remote_contract = "osmo14hj2tavq8fpesdwxxcu44rty3hh90vhujrvcmstl4zr3txmfvw9sq2r9g9";
+    let send_and_call = |contract: &str, msg: &str, ibc_callback: &str| ExecuteMsg::SendAndCall {
+        channel: "channel-0".to_string(),
+        contract: contract.to_string(),
+        msg: Binary::from(msg.as_bytes()),
+        denom: "stake".to_string(),
+        amount: Uint128::new(1000),
+        timeout: None,
+        ibc_callback: Some(ibc_callback.to_string()),
+    };
+
+    for (contract, msg, ibc_callback) in [
+        (remote_contract, "not json", owner.as_str()),
+        (remote_contract, "[1, 2]", owner.as_str()),
+        (remote_contract, r#"{"swap":{}} {}"#, owner.as_str()),
+        ("osmo1invalid", r#"{"swap":{}}"#, owner.as_str()),
+        (
+            remote_contract.to_uppercase().as_str(),
+            r#"{"swap":{}}"#,
+            owner.as_str(),
+        ),
+        (remote_contract, r#"{"swap":{}}"#, "not an address"),
+    ] {
+        app.execute(
+            message_info(&owner, &[]),
+            &send_and_call(contract, msg, ibc_callback),
+        )
+        .unwrap_err();
+    }
+    app.execute(
+        message_info(&owner, &[]),
+        &send_and_call(
+            remote_contract,
+            r#"{"swap":{"min_out":"10"}}"#,
+            owner.as_str(),
+        ),
+    )
+    .unwrap();
+    let in_flight = &app.transfer.in_flight()[0];
+    assert_eq!(in_flight.receiver, remote_contract);
+    assert_eq!(
+        in_flight.memo,
+        format!(
+            r#"{{"wasm":{{"contract":"{}","msg":{{"swap":{{"min_out":"10"}}}}}},"ibc_callback":"{}"}}"#,
+            remote_contract, owner
+        )
+    );
+
+    // the result of the call is recorded from the acknowledgement of ibc-hooks
+    app.transfer_ack_with(
+        "channel-0",
+        1,
+        Binary::from(
+            format!(
+                r#"{{"contract_result":"{}","ibc_ack":"eyJyZXN1bHQiOiJBUT09In0="}}"#,
+                Binary::from(b"swapped").to_base64()
+            )
+            .as_bytes(),
+        ),
+    )
+    .unwrap();
+    let transfer: Transfer = from_json(
+        query(
+            app.deps.as_ref(),
+            app.env.clone(),
+            QueryMsg::Transfer {
+                channel_id: "channel-0".to_string(),
+                sequence_id: 1,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(transfer.status, TransferStatus::Acknowledged);
+    let call = transfer.call.unwrap();
+    assert_eq!(call.contract, remote_contract);
+    assert_eq!(call.result, Some(Binary::from(b"swapped")));
+}
+
+#[test]
+fn test_send_timeouts() {
+    let (mut app, owner) = setup();
+    app.deps.querier.set_channel_client(
+        "transfer",
+        "channel-0",
+        "07-tendermint-0",
+        Height {
+            revision_number: 4,
+            revision_height: 1000,
+        },
+    );
+    let send_native = |timeout| ExecuteMsg::SendNative {
+        channel: "channel-0".to_string(),
+        to: RECEIVER.to_string(),
+        denom: "stake".to_string(),
+        amount: Uint128::new(1000),
+        timeout,
+        timeout_height: None,
+    };
+
+    // timeouts are validated before the transfer is sent
+    app.execute(
+        message_info(&owner, &[]),
+        &send_native(Some(TimeoutSpec::AtHeight { height: 900 })),
+    )
+    .unwrap_err();
+    app.execute(
+        message_info(&owner, &[]),
+        &send_native(Some(TimeoutSpec::AtTime {
+            time: app.env.block.time,
+        })),
+    )
+    .unwrap_err();
+    assert!(app.transfer.in_flight().is_empty());
+
+    app.execute(
+        message_info(&owner, &[]),
+        &send_native(Some(TimeoutSpec::AfterBlocks { blocks: 100 })),
+    )
+    .unwrap();
+    app.execute(
+        message_info(&owner, &[]),
+        &send_native(Some(TimeoutSpec::AfterSeconds { seconds: 600 })),
+    )
+    .unwrap();
+    let packets: Vec<_> = app
+        .transfer
+        .in_flight()
+        .iter()
+        .map(|t| (t.packet.timeout_height.clone(), t.packet.timeout_timestamp))
+        .collect();
+    assert_eq!(
+        packets,
+        vec![
+            (
+                Some(RequestPacketTimeoutHeight {
+                    revision_number: Some(4),
+                    revision_height: Some(1100),
+                }),
+                Some(0)
+            ),
+            (None, Some(app.env.block.time.plus_seconds(600).nanos())),
+        ]
+    );
+
+    // the timeout_height of the older callers is an at_height timeout
+    let legacy = |timeout| ExecuteMsg::SendNative {
+        channel: "channel-0".to_string(),
+        to: RECEIVER.to_string(),
+        denom: "stake".to_string(),
+        amount: Uint128::new(1000),
+        timeout,
+        timeout_height: Some(1200),
+    };
+    app.execute(
+        message_info(&owner, &[]),
+        &legacy(Some(TimeoutSpec::AfterBlocks { blocks: 100 })),
+    )
+    .unwrap_err();
+    app.execute(message_info(&owner, &[]), &legacy(None))
+        .unwrap();
+    assert_eq!(
+        app.transfer.in_flight()[2].packet.timeout_height,
+        Some(RequestPacketTimeoutHeight {
+            revision_number: Some(4),
+            revision_height: Some(1200),
+        })
+    );
+}
@@ -0,0 +1,83 @@
+use crate::contract::Holding;
+use cosmwasm_std::{Deps, Env, StdError, StdResult};
+use neutron_std::types::ibc::applications::transfer::v1::{DenomTrace, TransferQuerier};
+use sha2::{Digest, Sha256};
+
+const IBC_DENOM_PREFIX: &str = "ibc/";
+const TRANSFER_PORT: &str = "transfer";
+
+fn sha256_upper_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect()
+}
+
+/// Denom of the base denom received over the trace path, the base denom itself if the path
+/// is empty
+pub fn ibc_denom(path: &str, base_denom: &str) -> String {
+    if path.is_empty() {
+        return base_denom.to_string();
+    }
+    format!(
+        "{}{}",
+        IBC_DENOM_PREFIX,
+        sha256_upper_hex(format!("{}/{}", path, base_denom).as_bytes())
+    )
+}
+
+/// Channel the voucher has been received over, the first hop of its trace path
+pub fn origin_channel(path: &str) -> Option<&str> {
+    let mut hops = path.split('/');
+    match (hops.next(), hops.next()) {
+        (Some(TRANSFER_PORT), Some(channel)) => Some(channel),
+        _ => None,
+    }
+}
+
+fn denom_trace(deps: Deps, hash: &str) -> StdResult<DenomTrace> {
+    TransferQuerier::new(&deps.querier)
+        .denom_trace(hash.to_string())?
+        .denom_trace
+        .ok_or_else(|| StdError::generic_err(format!("denom trace {} not found", hash)))
+}
+
+/// Resolves the denom of the base denom and the trace path, a voucher's trace has to be known
+/// to the transfer module
+pub fn resolve_denom(deps: Deps, path: &str, base_denom: &str) -> StdResult<String> {
+    let denom = ibc_denom(path, base_denom);
+    if let Some(hash) = denom.strip_prefix(IBC_DENOM_PREFIX) {
+        let trace = denom_trace(deps, hash)?;
+        if trace.path != path || trace.base_denom != base_denom {
+            return Err(StdError::generic_err(format!(
+                "denom {} is traced to {}/{}",
+                denom, trace.path, trace.base_denom
+            )));
+        }
+    }
+    Ok(denom)
+}
+
+/// Balances of the contract with the traces of the vouchers decoded
+pub fn query_holdings(deps: Deps, env: Env) -> StdResult<Vec<Holding>> {
+    deps.querier
+        .query_all_balances(env.contract.address)?
+        .into_iter()
+        .map(|coin| {
+            let (path, base_denom) = match coin.denom.strip_prefix(IBC_DENOM_PREFIX) {
+                Some(hash) => {
+                    let trace = denom_trace(deps, hash)?;
+                    (trace.path, trace.base_denom)
+                }
+                None => (String::new(), coin.denom.clone()),
+            };
+            Ok(Holding {
+                origin_channel: origin_channel(&path).map(str::to_string),
+                denom: coin.denom,
+                amount: coin.amount,
+                base_denom,
+                path,
+            })
+        })
+        .collect()
+}
@@ -12,24 +12,36 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::error::{ContractError, ContractResult};
 use crate::integration_tests_mock_handlers::{set_query_mock, unset_query_mock};
 use crate::msg::{
-    ExecuteMsg, GetRecipientTxsResponse, GetTransfersAmountResponse, InstantiateMsg,
-    KvCallbackStatsResponse, MigrateMsg, QueryMsg,
+    ContractStorageKey, DecodedValue, ExecuteMsg, GetDelegateTxsResponse, GetIbcTransfersResponse,
+    GetRecipientTxsResponse, GetTransfersAmountResponse, InstantiateMsg, KvCallbackStatsResponse,
+    ListQueriesResponse, MigrateMsg, QueryKind, QueryMsg, QuerySpec, RawKvValue,
+    RawQueryResultResponse, TransferCursor,
 };
 use crate::state::{
-    IntegrationTestsQueryMock, Transfer, INTEGRATION_TESTS_QUERY_MOCK, KV_CALLBACK_STATS,
-    RECIPIENT_TXS, TRANSFERS,
+    DelegateTx, IbcTransfer, IntegrationTestsQueryMock, KvDecoder, KvResult, KvSnapshot,
+    LabeledQuery, MaxResultAge, Transfer, TransferSource, TxFilterHandler, DELEGATE_TXS,
+    FLAGGED_TRANSFERS, IBC_TRANSFERS, INTEGRATION_TESTS_QUERY_MOCK, KV_CALLBACK_STATS,
+    KV_SNAPSHOTS, LABELED_QUERIES, MAX_RESULT_AGES, PENDING_DEPOSITS, QUERY_LABELS,
+    RAW_KV_DECODERS, STALE_QUERY_BLOCKS, TRACKED_QUERIES, TRANSFERS, TRANSFER_LIMITS,
+    TX_QUERY_HANDLERS, TX_QUERY_MATCHES,
 };
-use cosmos_sdk_proto::cosmos::bank::v1beta1::MsgSend;
+use cosmos_sdk_proto::cosmos::bank::v1beta1::{MsgMultiSend, MsgSend};
+use cosmos_sdk_proto::cosmos::staking::v1beta1::MsgDelegate;
 use cosmos_sdk_proto::cosmos::tx::v1beta1::{TxBody, TxRaw};
+use cosmos_sdk_proto::Any;
 use cosmwasm_std::{
-    entry_point, to_json_binary, Addr, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdError,
-    StdResult,
+    entry_point, from_json, to_json_binary, to_json_string, Addr, Binary, CosmosMsg, Deps, DepsMut,
+    Env, MessageInfo, Order, Reply, Response, StdError, StdResult, Storage, SubMsg, Uint128,
+    Uint256,
 };
 use cw2::set_contract_version;
+use cw_storage_plus::Bound;
+use ibc_timeout::connection_client_latest_height;
 use neutron_sdk::interchain_queries::get_registered_query;
-use neutron_sdk::interchain_queries::helpers::register_interchain_query;
+use neutron_sdk::interchain_queries::helpers::{decode_and_convert, register_interchain_query};
 use neutron_sdk::interchain_queries::helpers::{
     remove_interchain_query as helpers_remove_interchain_query,
     update_interchain_query as helpers_update_interchain_query,
@@ -37,7 +49,10 @@ use neutron_sdk::interchain_queries::helpers::{
 use neutron_sdk::interchain_queries::types::{
     QueryPayload, TransactionFilterItem, TransactionFilterOp, TransactionFilterValue,
 };
-use neutron_sdk::interchain_queries::v045::types::HEIGHT_FIELD;
+use neutron_sdk::interchain_queries::v045::types::{
+    BALANCES_PREFIX, BANK_STORE_KEY, DELEGATION_KEY, GOV_STORE_KEY, HEIGHT_FIELD,
+    PROPOSALS_KEY_PREFIX, STAKING_STORE_KEY, VALIDATORS_KEY,
+};
 use neutron_sdk::interchain_queries::v047::queries::{
     query_balance, query_bank_total, query_delegations, query_distribution_fee_pool,
     query_government_proposal_votes, query_government_proposals, query_staking_validators,
@@ -55,15 +70,57 @@ use neutron_sdk::interchain_queries::v047::register_queries::{
     new_register_validators_signing_infos_query_msg,
 };
 use neutron_sdk::interchain_queries::v047::types::{COSMOS_SDK_TRANSFER_MSG_URL, RECIPIENT_FIELD};
+use neutron_sdk::interchain_txs::helpers::decode_message_response;
 use neutron_sdk::sudo::msg::Height;
 use neutron_sdk::sudo::msg::SudoMsg;
 use neutron_sdk::{NeutronError, NeutronResult};
-use neutron_std::types::neutron::interchainqueries::KvKey;
+use neutron_std::types::cosmos::auth::v1beta1::BaseAccount;
+use neutron_std::types::cosmos::bank::v1beta1::Metadata;
+use neutron_std::types::cosmos::base::v1beta1::Coin as ProtoCoin;
+use neutron_std::types::ibc::applications::transfer::v1::MsgTransfer;
+use neutron_std::types::ibc::core::channel::v1::{MsgRecvPacket, Packet};
+use neutron_std::types::neutron::interchainqueries::{
+    InterchainqueriesQuerier, KvKey, MsgRegisterInterchainQueryResponse,
+};
 use prost::Message as ProstMessage;
+use recipient_transfers::{
+    migrate_recipient_txs, query_pending_deposits, query_recipient_totals, query_recipient_txs,
+    queue_pending, save_transfers, sha256_upper_hex, tx_hash,
+};
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use tracked_queries::{
+    find_stale_queries, query_deposits_summary, touch_tracked_query, track_query,
+};
 
-/// defines the incoming transfers limit to make a case of failed callback possible.
-const MAX_ALLOWED_TRANSFER: u64 = 20000;
+/// defines how many messages of a tx are processed at once to prevent 'out of gas' conditions,
+/// the rest of the deposit messages are processed later with ProcessPending. The IBC transfers
+/// and delegations handlers don't queue messages, they reject the bigger txs.
 const MAX_ALLOWED_MESSAGES: usize = 20;
+/// defines how many typed results are kept per KV query.
+const MAX_KV_HISTORY: usize = 20;
+
+const IBC_TRANSFER_MSG_URL: &str = "/ibc.applications.transfer.v1.MsgTransfer";
+const MULTI_SEND_MSG_URL: &str = "/cosmos.bank.v1beta1.MsgMultiSend";
+const RECV_PACKET_MSG_URL: &str = "/ibc.core.channel.v1.MsgRecvPacket";
+const TRANSFER_PORT: &str = "transfer";
+const DELEGATE_MSG_URL: &str = "/cosmos.staking.v1beta1.MsgDelegate";
+
+const REGISTER_QUERY_REPLY_ID: u64 = 1;
+
+const COIN_TYPE_URL: &str = "/cosmos.base.v1beta1.Coin";
+const METADATA_TYPE_URL: &str = "/cosmos.bank.v1beta1.Metadata";
+const BASE_ACCOUNT_TYPE_URL: &str = "/cosmos.auth.v1beta1.BaseAccount";
+const WASM_STORE_KEY: &str = "wasm";
+/// prefix of the contract storage keys in the wasm module store.
+const WASM_CONTRACT_STORE_PREFIX: u8 = 0x03;
+/// protobuf types the values of raw KV queries can be decoded from.
+const PROTOBUF_DECODER_TYPES: [&str; 3] = [COIN_TYPE_URL, METADATA_TYPE_URL, BASE_ACCOUNT_TYPE_URL];
+
+/// default and maximum page sizes of the recipient transfers query.
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 100;
 
 const CONTRACT_NAME: &str = concat!("crates.io:neutron-contracts__", env!("CARGO_PKG_NAME"));
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -84,7 +141,7 @@ pub fn instantiate(
 pub fn execute(
     deps: DepsMut,
     env: Env,
-    _: MessageInfo,
+    info: MessageInfo,
     msg: ExecuteMsg,
 ) -> NeutronResult<Response> {
     match msg {
@@ -195,6 +252,72 @@ pub fn execute(
             update_period,
             min_height,
         ),
+        ExecuteMsg::RegisterIbcTransfersQuery {
+            connection_id,
+            update_period,
+            sender,
+            min_height,
+        } => register_tx_query(
+            env.contract.address,
+            connection_id,
+            ibc_transfers_filter(&sender),
+            update_period,
+            min_height,
+            TxFilterHandler::IbcTransfers { sender },
+        ),
+        ExecuteMsg::RegisterDelegateTxsQuery {
+            connection_id,
+            update_period,
+            delegator,
+            min_height,
+        } => register_tx_query(
+            env.contract.address,
+            connection_id,
+            delegate_txs_filter(&delegator),
+            update_period,
+            min_height,
+            TxFilterHandler::Delegations { delegator },
+        ),
+        ExecuteMsg::RegisterTxQuery {
+            connection_id,
+            update_period,
+            transactions_filter,
+        } => register_tx_query(
+            env.contract.address,
+            connection_id,
+            transactions_filter,
+            update_period,
+            None,
+            TxFilterHandler::Generic {},
+        ),
+        ExecuteMsg::RegisterQueries {
+            connection_id,
+            specs,
+        } => register_queries(deps, env.contract.address, connection_id, specs),
+        ExecuteMsg::RegisterRawKvQuery {
+            connection_id,
+            keys,
+            update_period,
+            decoder,
+        } => register_raw_kv_query(
+            env.contract.address,
+            connection_id,
+            keys,
+            update_period,
+            decoder,
+        ),
+        ExecuteMsg::RegisterWasmContractStateQuery {
+            connection_id,
+            update_period,
+            contract_address,
+            storage_key,
+        } => register_wasm_contract_state_query(
+            env.contract.address,
+            connection_id,
+            update_period,
+            contract_address,
+            storage_key,
+        ),
         ExecuteMsg::UpdateInterchainQuery {
             query_id,
             new_keys,
@@ -208,8 +331,23 @@ pub fn execute(
             new_recipient,
         ),
         ExecuteMsg::RemoveInterchainQuery { query_id } => {
-            remove_interchain_query(env.contract.address, query_id)
+            remove_interchain_query(deps, env.contract.address, query_id)
+        }
+        ExecuteMsg::SetStaleQueryBlocks { blocks } => {
+            set_stale_query_blocks(deps, env, info, blocks)
+        }
+        ExecuteMsg::RemoveStaleQueries { start_after, limit } => {
+            remove_stale_queries(deps, env, start_after, limit)
         }
+        ExecuteMsg::SetMaxResultAge {
+            query_id,
+            remote_blocks,
+            local_blocks,
+        } => set_max_result_age(deps, env, info, query_id, remote_blocks, local_blocks),
+        ExecuteMsg::SetTransferLimit { denom, max_amount } => {
+            set_transfer_limit(deps, env, info, denom, max_amount)
+        }
+        ExecuteMsg::ProcessPending { limit } => process_pending(deps, limit),
         ExecuteMsg::IntegrationTestsSetQueryMock {} => set_query_mock(deps),
         ExecuteMsg::IntegrationTestsUnsetQueryMock {} => unset_query_mock(deps),
         ExecuteMsg::IntegrationTestsRegisterQueryEmptyId { connection_id } => {
@@ -234,7 +372,10 @@ pub fn register_balances_query(
     let msg =
         new_register_balances_query_msg(contract, connection_id, addr, denoms, update_period)?;
 
-    Ok(Response::new().add_message(msg))
+    Ok(Response::new().add_submessage(register_query_submsg(
+        msg,
+        &RegisterQueryPayload::default(),
+    )?))
 }
 
 pub fn register_bank_total_supply_query(
@@ -246,7 +387,10 @@ pub fn register_bank_total_supply_query(
     let msg =
         new_register_bank_total_supply_query_msg(contract, connection_id, denoms, update_period)?;
 
-    Ok(Response::new().add_message(msg))
+    Ok(Response::new().add_submessage(register_query_submsg(
+        msg,
+        &RegisterQueryPayload::default(),
+    )?))
 }
 
 pub fn register_distribution_fee_pool_query(
@@ -256,7 +400,10 @@ pub fn register_distribution_fee_pool_query(
 ) -> NeutronResult<Response> {
     let msg = new_register_distribution_fee_pool_query_msg(contract, connection_id, update_period)?;
 
-    Ok(Response::new().add_message(msg))
+    Ok(Response::new().add_submessage(register_query_submsg(
+        msg,
+        &RegisterQueryPayload::default(),
+    )?))
 }
 
 pub fn register_gov_proposal_query(
@@ -272,7 +419,10 @@ pub fn register_gov_proposal_query(
         update_period,
     )?;
 
-    Ok(Response::new().add_message(msg))
+    Ok(Response::new().add_submessage(register_query_submsg(
+        msg,
+        &RegisterQueryPayload::default(),
+    )?))
 }
 
 pub fn register_gov_proposal_votes_query(
@@ -294,7 +444,10 @@ pub fn register_gov_proposal_votes_query(
         update_period,
     )?;
 
-    Ok(Response::new().add_message(msg))
+    Ok(Response::new().add_submessage(register_query_submsg(
+        msg,
+        &RegisterQueryPayload::default(),
+    )?))
 }
 
 pub fn register_staking_validators_query(
@@ -310,7 +463,10 @@ pub fn register_staking_validators_query(
         update_period,
     )?;
 
-    Ok(Response::new().add_message(msg))
+    Ok(Response::new().add_submessage(register_query_submsg(
+        msg,
+        &RegisterQueryPayload::default(),
+    )?))
 }
 
 pub fn register_delegations_query(
@@ -328,7 +484,10 @@ pub fn register_delegations_query(
         update_period,
     )?;
 
-    Ok(Response::new().add_message(msg))
+    Ok(Response::new().add_submessage(register_query_submsg(
+        msg,
+        &RegisterQueryPayload::default(),
+    )?))
 }
 
 pub fn register_unbonding_delegations_query(
@@ -346,7 +505,10 @@ pub fn register_unbonding_delegations_query(
         update_period,
     )?;
 
-    Ok(Response::new().add_message(msg))
+    Ok(Response::new().add_submessage(register_query_submsg(
+        msg,
+        &RegisterQueryPayload::default(),
+    )?))
 }
 
 pub fn register_validators_signing_infos_query(
@@ -362,7 +524,10 @@ pub fn register_validators_signing_infos_query(
         update_period,
     )?;
 
-    Ok(Response::new().add_message(msg))
+    Ok(Response::new().add_submessage(register_query_submsg(
+        msg,
+        &RegisterQueryPayload::default(),
+    )?))
 }
 
 pub fn register_transfers_query(
@@ -372,15 +537,48 @@ pub fn register_transfers_query(
     update_period: u64,
     min_height: Option<u64>,
 ) -> NeutronResult<Response> {
-    let mut query_data: Vec<TransactionFilterItem> = recipients
-        .into_iter()
-        .map(|r| TransactionFilterItem {
-            field: RECIPIENT_FIELD.to_string(),
-            op: TransactionFilterOp::Eq,
-            value: TransactionFilterValue::String(r),
-        })
-        .collect();
+    register_tx_query(
+        contract,
+        connection_id,
+        transfers_filter(&recipients),
+        update_period,
+        min_height,
+        TxFilterHandler::BankDeposits { recipients },
+    )
+}
 
+/// Registers a TX query whose results are processed by the given handler. The query id is known
+/// only in the reply, so the handler is passed to it in the submessage payload.
+pub fn register_tx_query(
+    contract: Addr,
+    connection_id: String,
+    query_data: Vec<TransactionFilterItem>,
+    update_period: u64,
+    min_height: Option<u64>,
+    handler: TxFilterHandler,
+) -> NeutronResult<Response> {
+    let msg = new_register_tx_query_msg(
+        contract,
+        connection_id,
+        query_data,
+        update_period,
+        min_height,
+    )?;
+    let payload = RegisterQueryPayload {
+        handler: Some(handler),
+        ..Default::default()
+    };
+
+    Ok(Response::new().add_submessage(register_query_submsg(msg, &payload)?))
+}
+
+fn new_register_tx_query_msg(
+    contract: Addr,
+    connection_id: String,
+    mut query_data: Vec<TransactionFilterItem>,
+    update_period: u64,
+    min_height: Option<u64>,
+) -> NeutronResult<CosmosMsg> {
     if let Some(min_height) = min_height {
         query_data.push(TransactionFilterItem {
             field: HEIGHT_FIELD.to_string(),
@@ -389,14 +587,349 @@ pub fn register_transfers_query(
         })
     }
 
-    let msg = register_interchain_query(
+    register_interchain_query(
         contract,
         QueryPayload::TX(query_data),
         connection_id,
         update_period,
-    )?;
+    )
+}
 
-    Ok(Response::new().add_message(msg))
+fn transfers_filter(recipients: &[String]) -> Vec<TransactionFilterItem> {
+    recipients
+        .iter()
+        .map(|r| TransactionFilterItem {
+            field: RECIPIENT_FIELD.to_string(),
+            op: TransactionFilterOp::Eq,
+            value: TransactionFilterValue::String(r.clone()),
+        })
+        .collect()
+}
+
+fn ibc_transfers_filter(sender: &str) -> Vec<TransactionFilterItem> {
+    vec![TransactionFilterItem {
+        field: "ibc_transfer.sender".to_string(),
+        op: TransactionFilterOp::Eq,
+        value: TransactionFilterValue::String(sender.to_string()),
+    }]
+}
+
+fn delegate_txs_filter(delegator: &str) -> Vec<TransactionFilterItem> {
+    vec![
+        TransactionFilterItem {
+            field: "message.sender".to_string(),
+            op: TransactionFilterOp::Eq,
+            value: TransactionFilterValue::String(delegator.to_string()),
+        },
+        TransactionFilterItem {
+            field: "message.action".to_string(),
+            op: TransactionFilterOp::Eq,
+            value: TransactionFilterValue::String(DELEGATE_MSG_URL.to_string()),
+        },
+    ]
+}
+
+/// Passed from the query registration to its reply, where the query id is known.
+#[derive(Serialize, Deserialize, Default)]
+struct RegisterQueryPayload {
+    /// handler of the results of the TX query
+    handler: Option<TxFilterHandler>,
+    /// connection and spec of the query registered with RegisterQueries
+    labeled: Option<(String, QuerySpec)>,
+    /// decoder of the values of the raw KV query
+    decoder: Option<KvDecoder>,
+}
+
+/// Registers a KV query for arbitrary keys, its result values are read with RawQueryResult
+/// decoded by the decoder.
+pub fn register_raw_kv_query(
+    contract: Addr,
+    connection_id: String,
+    keys: Vec<KvKey>,
+    update_period: u64,
+    decoder: KvDecoder,
+) -> NeutronResult<Response> {
+    let msg =
+        new_register_raw_kv_query_msg(contract, connection_id, keys, update_period, &decoder)?;
+    let payload = RegisterQueryPayload {
+        decoder: Some(decoder),
+        ..Default::default()
+    };
+
+    Ok(Response::new().add_submessage(register_query_submsg(msg, &payload)?))
+}
+
+/// Registers a raw KV query with the JSON decoder for the storage of the remote contract.
+pub fn register_wasm_contract_state_query(
+    contract: Addr,
+    connection_id: String,
+    update_period: u64,
+    contract_address: String,
+    storage_key: ContractStorageKey,
+) -> NeutronResult<Response> {
+    let key = wasm_contract_state_key(&contract_address, &storage_key)?;
+    register_raw_kv_query(
+        contract,
+        connection_id,
+        vec![key],
+        update_period,
+        KvDecoder::Json {},
+    )
+}
+
+/// Returns the key of the contract storage in the wasm module store:
+/// `0x03 | contract address bytes | storage key`.
+pub fn wasm_contract_state_key(
+    contract_address: &str,
+    storage_key: &ContractStorageKey,
+) -> NeutronResult<KvKey> {
+    let mut key = vec![WASM_CONTRACT_STORE_PREFIX];
+    key.extend(decode_and_convert(contract_address)?);
+    key.extend(storage_key.to_storage_key());
+    Ok(KvKey {
+        path: WASM_STORE_KEY.to_string(),
+        key,
+    })
+}
+
+fn new_register_raw_kv_query_msg(
+    contract: Addr,
+    connection_id: String,
+    keys: Vec<KvKey>,
+    update_period: u64,
+    decoder: &KvDecoder,
+) -> NeutronResult<CosmosMsg> {
+    if let KvDecoder::Protobuf { type_url } = decoder {
+        if !PROTOBUF_DECODER_TYPES.contains(&type_url.as_str()) {
+            return Err(NeutronError::Std(StdError::generic_err(format!(
+                "unsupported protobuf type: {}",
+                type_url
+            ))));
+        }
+    }
+
+    register_interchain_query(
+        contract,
+        QueryPayload::KV(keys),
+        connection_id,
+        update_period,
+    )
+}
+
+/// Wraps the query registration message into a submessage, its reply starts tracking the query
+/// deposit and applies the payload.
+fn register_query_submsg(
+    msg: impl Into<CosmosMsg>,
+    payload: &RegisterQueryPayload,
+) -> StdResult<SubMsg> {
+    Ok(SubMsg::reply_on_success(msg, REGISTER_QUERY_REPLY_ID)
+        .with_payload(to_json_binary(payload)?))
+}
+
+/// Registers the queries of the specs, their ids are saved under the spec labels in the reply.
+/// The queries registered under the same labels before are removed.
+pub fn register_queries(
+    deps: DepsMut,
+    contract: Addr,
+    connection_id: String,
+    specs: Vec<QuerySpec>,
+) -> NeutronResult<Response> {
+    let mut labels: Vec<&str> = specs.iter().map(|spec| spec.label.as_str()).collect();
+    labels.sort_unstable();
+    if let Some(label) = labels.windows(2).find(|w| w[0] == w[1]).map(|w| w[0]) {
+        return Err(NeutronError::Std(StdError::generic_err(format!(
+            "duplicate query label: {}",
+            label
+        ))));
+    }
+
+    let mut response = Response::new().add_attribute("action", "register_queries");
+    for spec in specs {
+        if let Some(old) = LABELED_QUERIES.may_load(deps.storage, &spec.label)? {
+            response = response.add_message(remove_query_msg(
+                deps.storage,
+                contract.clone(),
+                old.query_id,
+            )?);
+        }
+
+        let (msg, mut payload) = new_register_query_kind_msg(
+            contract.clone(),
+            connection_id.clone(),
+            spec.update_period,
+            spec.query.clone(),
+        )?;
+        payload.labeled = Some((connection_id.clone(), spec));
+        response = response.add_submessage(register_query_submsg(msg, &payload)?);
+    }
+    Ok(response)
+}
+
+/// Builds the message registering the query, with the payload its reply needs.
+fn new_register_query_kind_msg(
+    contract: Addr,
+    connection_id: String,
+    update_period: u64,
+    query: QueryKind,
+) -> NeutronResult<(CosmosMsg, RegisterQueryPayload)> {
+    let (filter, min_height, handler) = match query {
+        QueryKind::Balances { addr, denoms } => {
+            let msg = new_register_balances_query_msg(
+                contract,
+                connection_id,
+                addr,
+                denoms,
+                update_period,
+            )?;
+            return Ok((msg, RegisterQueryPayload::default()));
+        }
+        QueryKind::BankTotalSupply { denoms } => {
+            let msg = new_register_bank_total_supply_query_msg(
+                contract,
+                connection_id,
+                denoms,
+                update_period,
+            )?;
+            return Ok((msg, RegisterQueryPayload::default()));
+        }
+        QueryKind::DistributionFeePool {} => {
+            let msg = new_register_distribution_fee_pool_query_msg(
+                contract,
+                connection_id,
+                update_period,
+            )?;
+            return Ok((msg, RegisterQueryPayload::default()));
+        }
+        QueryKind::StakingValidators { validators } => {
+            let msg = new_register_staking_validators_query_msg(
+                contract,
+                connection_id,
+                validators,
+                update_period,
+            )?;
+            return Ok((msg, RegisterQueryPayload::default()));
+        }
+        QueryKind::GovernmentProposals { proposals_ids } => {
+            let msg = new_register_gov_proposals_query_msg(
+                contract,
+                connection_id,
+                proposals_ids,
+                update_period,
+            )?;
+            return Ok((msg, RegisterQueryPayload::default()));
+        }
+        QueryKind::GovernmentProposalVotes {
+            proposals_ids,
+            voters,
+        } => {
+            let msg = new_register_gov_proposals_voters_votes_query_msg(
+                contract,
+                connection_id,
+                proposals_ids,
+                voters,
+                update_period,
+            )?;
+            return Ok((msg, RegisterQueryPayload::default()));
+        }
+        QueryKind::DelegatorDelegations {
+            delegator,
+            validators,
+        } => {
+            let msg = new_register_delegator_delegations_query_msg(
+                contract,
+                connection_id,
+                delegator,
+                validators,
+                update_period,
+            )?;
+            return Ok((msg, RegisterQueryPayload::default()));
+        }
+        QueryKind::DelegatorUnbondingDelegations {
+            delegator,
+            validators,
+        } => {
+            let msg = new_register_delegator_unbonding_delegations_query_msg(
+                contract,
+                connection_id,
+                delegator,
+                validators,
+                update_period,
+            )?;
+            return Ok((msg, RegisterQueryPayload::default()));
+        }
+        QueryKind::ValidatorsSigningInfo { validators } => {
+            let msg = new_register_validators_signing_infos_query_msg(
+                contract,
+                connection_id,
+                validators,
+                update_period,
+            )?;
+            return Ok((msg, RegisterQueryPayload::default()));
+        }
+        QueryKind::Transfers {
+            recipients,
+            min_height,
+        } => (
+            transfers_filter(&recipients),
+            min_height,
+            TxFilterHandler::BankDeposits { recipients },
+        ),
+        QueryKind::IbcTransfers { sender, min_height } => (
+            ibc_transfers_filter(&sender),
+            min_height,
+            TxFilterHandler::IbcTransfers { sender },
+        ),
+        QueryKind::DelegateTxs {
+            delegator,
+            min_height,
+        } => (
+            delegate_txs_filter(&delegator),
+            min_height,
+            TxFilterHandler::Delegations { delegator },
+        ),
+        QueryKind::Tx {
+            transactions_filter,
+        } => (transactions_filter, None, TxFilterHandler::Generic {}),
+        QueryKind::WasmContractState {
+            contract_address,
+            storage_key,
+        } => {
+            let key = wasm_contract_state_key(&contract_address, &storage_key)?;
+            let msg = register_interchain_query(
+                contract,
+                QueryPayload::KV(vec![key]),
+                connection_id,
+                update_period,
+            )?;
+            let payload = RegisterQueryPayload {
+                decoder: Some(KvDecoder::Json {}),
+                ..Default::default()
+            };
+            return Ok((msg, payload));
+        }
+        QueryKind::RawKv { keys, decoder } => {
+            let msg = new_register_raw_kv_query_msg(
+                contract,
+                connection_id,
+                keys,
+                update_period,
+                &decoder,
+            )?;
+            let payload = RegisterQueryPayload {
+                decoder: Some(decoder),
+                ..Default::default()
+            };
+            return Ok((msg, payload));
+        }
+    };
+
+    let msg =
+        new_register_tx_query_msg(contract, connection_id, filter, update_period, min_height)?;
+    let payload = RegisterQueryPayload {
+        handler: Some(handler),
+        ..Default::default()
+    };
+    Ok((msg, payload))
 }
 
 pub fn register_query_empty_id(
@@ -475,15 +1008,172 @@ pub fn update_interchain_query(
     Ok(Response::new().add_message(update_msg))
 }
 
-pub fn remove_interchain_query(contract: Addr, query_id: u64) -> NeutronResult<Response> {
-    let remove_msg = helpers_remove_interchain_query(contract, query_id)?;
+pub fn remove_interchain_query(
+    deps: DepsMut,
+    contract: Addr,
+    query_id: u64,
+) -> NeutronResult<Response> {
+    let remove_msg = remove_query_msg(deps.storage, contract, query_id)?;
     Ok(Response::new().add_message(remove_msg))
 }
 
+/// Builds the message removing the query and stops tracking it, its label is freed. The deposit
+/// of the query is refunded to the contract by the removal.
+fn remove_query_msg(
+    storage: &mut dyn Storage,
+    contract: Addr,
+    query_id: u64,
+) -> NeutronResult<CosmosMsg> {
+    TRACKED_QUERIES.remove(storage, query_id);
+    TX_QUERY_HANDLERS.remove(storage, query_id);
+    RAW_KV_DECODERS.remove(storage, query_id);
+    if let Some(label) = QUERY_LABELS.may_load(storage, query_id)? {
+        LABELED_QUERIES.remove(storage, &label);
+        QUERY_LABELS.remove(storage, query_id);
+    }
+    helpers_remove_interchain_query(contract, query_id)
+}
+
+pub fn set_stale_query_blocks(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    blocks: Option<u64>,
+) -> NeutronResult<Response> {
+    if !is_admin(deps.as_ref(), &env, &info.sender)? {
+        return Err(NeutronError::Std(StdError::generic_err(
+            "only the contract admin can set stale query blocks",
+        )));
+    }
+
+    match blocks {
+        Some(blocks) => STALE_QUERY_BLOCKS.save(deps.storage, &blocks)?,
+        None => STALE_QUERY_BLOCKS.remove(deps.storage),
+    }
+    Ok(Response::new().add_attribute("action", "set_stale_query_blocks"))
+}
+
+/// Checks up to `limit` tracked queries after `start_after`, the oldest query ids first, and
+/// removes the ones which haven't received results for the stale query blocks.
+pub fn remove_stale_queries(
+    deps: DepsMut,
+    env: Env,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> NeutronResult<Response> {
+    let (stale, last_query_id) = find_stale_queries(
+        deps.as_ref(),
+        env.block.height,
+        start_after,
+        limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize,
+    )?;
+
+    let mut response = Response::new().add_attribute("action", "remove_stale_queries");
+    if let Some(query_id) = last_query_id {
+        response = response.add_attribute("last_query_id", query_id.to_string());
+    }
+    for query_id in stale {
+        response = response
+            .add_message(remove_query_msg(
+                deps.storage,
+                env.contract.address.clone(),
+                query_id,
+            )?)
+            .add_attribute("removed_query_id", query_id.to_string());
+    }
+    Ok(response)
+}
+
+fn is_admin(deps: Deps, env: &Env, sender: &Addr) -> StdResult<bool> {
+    let contract_info = deps
+        .querier
+        .query_wasm_contract_info(env.contract.address.clone())?;
+    Ok(contract_info.admin.as_ref() == Some(sender))
+}
+
+pub fn set_max_result_age(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    query_id: u64,
+    remote_blocks: Option<u64>,
+    local_blocks: Option<u64>,
+) -> NeutronResult<Response> {
+    if !is_admin(deps.as_ref(), &env, &info.sender)? {
+        return Err(NeutronError::Std(StdError::generic_err(
+            "only the contract admin can set max result ages",
+        )));
+    }
+
+    if remote_blocks.is_none() && local_blocks.is_none() {
+        MAX_RESULT_AGES.remove(deps.storage, query_id);
+    } else {
+        MAX_RESULT_AGES.save(
+            deps.storage,
+            query_id,
+            &MaxResultAge {
+                remote_blocks,
+                local_blocks,
+            },
+        )?;
+    }
+    Ok(Response::new().add_attribute("action", "set_max_result_age"))
+}
+
+pub fn set_transfer_limit(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    denom: String,
+    max_amount: Option<Uint256>,
+) -> NeutronResult<Response> {
+    if !is_admin(deps.as_ref(), &env, &info.sender)? {
+        return Err(NeutronError::Std(StdError::generic_err(
+            "only the contract admin can set transfer limits",
+        )));
+    }
+
+    match max_amount {
+        Some(max_amount) => TRANSFER_LIMITS.save(deps.storage, &denom, &max_amount)?,
+        None => TRANSFER_LIMITS.remove(deps.storage, &denom),
+    }
+    Ok(Response::new()
+        .add_attribute("action", "set_transfer_limit")
+        .add_attribute("denom", denom))
+}
+
+/// Credits deposits from up to `limit` pending messages, the oldest transactions first.
+pub fn process_pending(deps: DepsMut, limit: Option<u32>) -> NeutronResult<Response> {
+    let limit = limit.map_or(MAX_ALLOWED_MESSAGES, |l| {
+        (l as usize).min(MAX_ALLOWED_MESSAGES)
+    });
+    let (processed, stored) = recipient_transfers::process_pending(
+        deps.storage,
+        limit,
+        |messages, recipient, height, tx_hash, first_index| -> NeutronResult<Vec<Transfer>> {
+            let messages = messages
+                .iter()
+                .map(|msg| Any::decode(msg.as_slice()))
+                .collect::<Result<Vec<Any>, _>>()?;
+            recipient_deposits_from_messages(&messages, recipient, height, tx_hash, first_index)
+        },
+        over_limit,
+    )?;
+    add_transfers_number(deps.storage, stored)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "process_pending")
+        .add_attribute("processed_messages", processed.to_string())
+        .add_attribute("stored_transfers", stored.to_string()))
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> NeutronResult<Binary> {
-    match msg {
-        //TODO: check if query.result.height is too old (for all interchain queries)
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> ContractResult<Binary> {
+    if let Some(query_id) = msg.result_query_id() {
+        check_result_age(deps, &env, query_id)?;
+    }
+
+    let res = match msg {
         QueryMsg::Balance { query_id } => Ok(to_json_binary(&query_balance(deps, env, query_id)?)?),
         QueryMsg::BankTotalSupply { query_id } => {
             Ok(to_json_binary(&query_bank_total(deps, env, query_id)?)?)
@@ -512,17 +1202,308 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> NeutronResult<Binary> {
         QueryMsg::GetRegisteredQuery { query_id } => {
             Ok(to_json_binary(&get_registered_query(deps, query_id)?)?)
         }
-        QueryMsg::GetRecipientTxs { recipient } => query_recipient_txs(deps, recipient),
+        QueryMsg::GetRecipientTxs {
+            recipient,
+            start_after,
+            limit,
+            filter,
+        } => Ok(to_json_binary(&query_recipient_txs(
+            deps.storage,
+            &recipient,
+            start_after,
+            limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize,
+            filter.unwrap_or_default(),
+        )?)?),
+        QueryMsg::GetRecipientTotals { recipient } => Ok(to_json_binary(&query_recipient_totals(
+            deps.storage,
+            &recipient,
+        )?)?),
+        QueryMsg::GetFlaggedTxs {
+            recipient,
+            start_after,
+            limit,
+        } => query_flagged_txs(deps, recipient, start_after, limit),
+        QueryMsg::TransferLimit { denom } => Ok(to_json_binary(
+            &TRANSFER_LIMITS.may_load(deps.storage, &denom)?,
+        )?),
+        QueryMsg::GetPendingDeposits { limit } => Ok(to_json_binary(&query_pending_deposits(
+            deps.storage,
+            limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize,
+        )?)?),
+        QueryMsg::TrackedQuery { query_id } => Ok(to_json_binary(
+            &TRACKED_QUERIES.may_load(deps.storage, query_id)?,
+        )?),
+        QueryMsg::DepositsSummary {} => Ok(to_json_binary(&query_deposits_summary(deps.storage)?)?),
+        QueryMsg::RawQueryResult { query_id } => query_raw_result(deps, query_id),
+        QueryMsg::WasmContractState { query_id } => query_wasm_contract_state(deps, query_id),
+        QueryMsg::GetQueryByLabel { label } => Ok(to_json_binary(
+            &LABELED_QUERIES.load(deps.storage, &label)?,
+        )?),
+        QueryMsg::ListQueries { start_after, limit } => {
+            let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+            let queries = LABELED_QUERIES
+                .range(
+                    deps.storage,
+                    start_after.as_deref().map(Bound::exclusive),
+                    None,
+                    Order::Ascending,
+                )
+                .take(limit)
+                .map(|item| item.map(|(_, query)| query))
+                .collect::<StdResult<Vec<LabeledQuery>>>()?;
+            Ok(to_json_binary(&ListQueriesResponse { queries })?)
+        }
+        QueryMsg::GetIbcTransfers {
+            sender,
+            start_after,
+            limit,
+        } => query_ibc_transfers(deps, sender, start_after, limit),
+        QueryMsg::GetDelegateTxs {
+            delegator,
+            start_after,
+            limit,
+        } => query_delegate_txs(deps, delegator, start_after, limit),
+        QueryMsg::GetTxQueryMatches { query_id } => Ok(to_json_binary(
+            &TX_QUERY_MATCHES
+                .may_load(deps.storage, query_id)?
+                .unwrap_or_default(),
+        )?),
+        QueryMsg::TxQueryHandler { query_id } => Ok(to_json_binary(
+            &TX_QUERY_HANDLERS.may_load(deps.storage, query_id)?,
+        )?),
         QueryMsg::GetTransfersNumber {} => query_transfers_number(deps),
         QueryMsg::KvCallbackStats { query_id } => query_kv_callback_stats(deps, query_id),
+        QueryMsg::BalanceAt { query_id, height } => query_balance_at(deps, query_id, height),
+        QueryMsg::History { query_id, limit } => query_history(deps, query_id, limit),
+        QueryMsg::MaxResultAge { query_id } => Ok(to_json_binary(
+            &MAX_RESULT_AGES.may_load(deps.storage, query_id)?,
+        )?),
+    };
+    Ok(res?)
+}
+
+/// Fails if the query has a maximum result age configured and its last result is older
+fn check_result_age(deps: Deps, env: &Env, query_id: u64) -> ContractResult<()> {
+    let max_age = match MAX_RESULT_AGES.may_load(deps.storage, query_id)? {
+        Some(max_age) => max_age,
+        None => return Ok(()),
+    };
+    let registered_query = get_registered_query(deps, query_id)?;
+    let remote_height = registered_query
+        .last_submitted_result_remote_height
+        .map_or(0, |h| h.revision_height);
+    // results may be submitted without the KV callback, so the submission height counts too
+    let local_height = KV_CALLBACK_STATS
+        .may_load(deps.storage, query_id)?
+        .unwrap_or_default()
+        .max(registered_query.last_submitted_result_local_height);
+
+    let remote_stale = match max_age.remote_blocks {
+        Some(max) => {
+            // the latest height of the remote chain known to the light client of the connection
+            let latest_height =
+                connection_client_latest_height(&deps.querier, &registered_query.connection_id)?
+                    .revision_height;
+            latest_height.saturating_sub(remote_height) > max
+        }
+        None => false,
+    };
+    let local_stale = max_age
+        .local_blocks
+        .is_some_and(|max| env.block.height.saturating_sub(local_height) > max);
+    if remote_stale || local_stale {
+        return Err(ContractError::StaleResult {
+            query_id,
+            remote_height,
+            local_height,
+        });
     }
+    Ok(())
+}
+
+/// Returns the stored balances of the query taken at the given remote height or the closest
+/// earlier one
+fn query_balance_at(deps: Deps, query_id: u64, height: u64) -> NeutronResult<Binary> {
+    let snapshot = KV_SNAPSHOTS
+        .prefix(query_id)
+        .range(
+            deps.storage,
+            None,
+            Some(Bound::inclusive(height)),
+            Order::Descending,
+        )
+        .next()
+        .transpose()?;
+    match snapshot {
+        Some((
+            _,
+            KvSnapshot {
+                result: KvResult::Balance(balance),
+                ..
+            },
+        )) => Ok(to_json_binary(&balance)?),
+        Some(_) => Err(NeutronError::Std(StdError::generic_err(format!(
+            "query {} is not a balances query",
+            query_id
+        )))),
+        None => Err(NeutronError::Std(StdError::not_found(format!(
+            "balances of query {} at height {}",
+            query_id, height
+        )))),
+    }
+}
+
+fn query_history(deps: Deps, query_id: u64, limit: Option<u32>) -> NeutronResult<Binary> {
+    let limit = limit.map_or(MAX_KV_HISTORY, |l| l as usize);
+    let snapshots = KV_SNAPSHOTS
+        .prefix(query_id)
+        .range(deps.storage, None, None, Order::Descending)
+        .take(limit)
+        .map(|item| item.map(|(_, snapshot)| snapshot))
+        .collect::<StdResult<Vec<KvSnapshot>>>()?;
+    Ok(to_json_binary(&snapshots)?)
+}
+
+fn query_flagged_txs(
+    deps: Deps,
+    recipient: String,
+    start_after: Option<TransferCursor>,
+    limit: Option<u32>,
+) -> NeutronResult<Binary> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let min = start_after
+        .as_ref()
+        .map(|c| Bound::exclusive((c.height, (c.tx_hash.as_str(), c.index, c.coin_index))));
+    let transfers = FLAGGED_TRANSFERS
+        .sub_prefix(&recipient)
+        .range(deps.storage, min, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, t)| t))
+        .collect::<StdResult<Vec<Transfer>>>()?;
+    Ok(to_json_binary(&GetRecipientTxsResponse { transfers })?)
 }
 
-fn query_recipient_txs(deps: Deps, recipient: String) -> NeutronResult<Binary> {
-    let txs = RECIPIENT_TXS
-        .load(deps.storage, &recipient)
-        .unwrap_or_default();
-    Ok(to_json_binary(&GetRecipientTxsResponse { transfers: txs })?)
+fn query_ibc_transfers(
+    deps: Deps,
+    sender: String,
+    start_after: Option<TransferCursor>,
+    limit: Option<u32>,
+) -> NeutronResult<Binary> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let min = start_after
+        .as_ref()
+        .map(|c| Bound::exclusive((c.height, (c.tx_hash.as_str(), c.index))));
+    let transfers = IBC_TRANSFERS
+        .sub_prefix(&sender)
+        .range(deps.storage, min, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, t)| t))
+        .collect::<StdResult<Vec<IbcTransfer>>>()?;
+    Ok(to_json_binary(&GetIbcTransfersResponse { transfers })?)
+}
+
+fn query_delegate_txs(
+    deps: Deps,
+    delegator: String,
+    start_after: Option<TransferCursor>,
+    limit: Option<u32>,
+) -> NeutronResult<Binary> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let min = start_after
+        .as_ref()
+        .map(|c| Bound::exclusive((c.height, (c.tx_hash.as_str(), c.index))));
+    let delegations = DELEGATE_TXS
+        .sub_prefix(&delegator)
+        .range(deps.storage, min, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, d)| d))
+        .collect::<StdResult<Vec<DelegateTx>>>()?;
+    Ok(to_json_binary(&GetDelegateTxsResponse { delegations })?)
+}
+
+/// Returns the values of the query result decoded by the decoder chosen at the query registration,
+/// the values of the queries registered otherwise are returned raw.
+fn query_raw_result(deps: Deps, query_id: u64) -> NeutronResult<Binary> {
+    let decoder = RAW_KV_DECODERS
+        .may_load(deps.storage, query_id)?
+        .unwrap_or(KvDecoder::Raw {});
+    let result = InterchainqueriesQuerier::new(&deps.querier)
+        .query_result(query_id)?
+        .result
+        .ok_or_else(|| StdError::generic_err("no result in registered query"))?;
+
+    let values = result
+        .kv_results
+        .into_iter()
+        .map(|kv| {
+            let value = if kv.value.is_empty() {
+                None
+            } else {
+                Some(decode_kv_value(&decoder, kv.value)?)
+            };
+            Ok(RawKvValue {
+                storage_prefix: kv.storage_prefix,
+                key: Binary::from(kv.key),
+                value,
+            })
+        })
+        .collect::<NeutronResult<Vec<RawKvValue>>>()?;
+    Ok(to_json_binary(&RawQueryResultResponse {
+        height: result.height,
+        values,
+    })?)
+}
+
+fn query_wasm_contract_state(deps: Deps, query_id: u64) -> NeutronResult<Binary> {
+    let result = InterchainqueriesQuerier::new(&deps.querier)
+        .query_result(query_id)?
+        .result
+        .ok_or_else(|| StdError::generic_err("no result in registered query"))?;
+
+    match result.kv_results.into_iter().next() {
+        Some(kv) if !kv.value.is_empty() => Ok(Binary::from(kv.value)),
+        _ => Ok(to_json_binary(&None::<()>)?),
+    }
+}
+
+fn decode_kv_value(decoder: &KvDecoder, value: Vec<u8>) -> NeutronResult<DecodedValue> {
+    match decoder {
+        KvDecoder::Raw {} => Ok(DecodedValue::Raw(Binary::from(value))),
+        KvDecoder::Uint128BigEndian {} => {
+            if value.len() > 16 {
+                return Err(NeutronError::Std(StdError::generic_err(format!(
+                    "value of {} bytes doesn't fit Uint128",
+                    value.len()
+                ))));
+            }
+            let mut bytes = [0u8; 16];
+            bytes[16 - value.len()..].copy_from_slice(&value);
+            Ok(DecodedValue::Uint128(Uint128::new(u128::from_be_bytes(
+                bytes,
+            ))))
+        }
+        KvDecoder::Json {} => {
+            from_json::<IgnoredAny>(&value)
+                .map_err(|e| StdError::generic_err(format!("value isn't JSON: {}", e)))?;
+            let json = String::from_utf8(value)
+                .map_err(|e| StdError::generic_err(format!("value isn't JSON: {}", e)))?;
+            Ok(DecodedValue::Json(json))
+        }
+        KvDecoder::Protobuf { type_url } => {
+            let json = match type_url.as_str() {
+                COIN_TYPE_URL => to_json_string(&ProtoCoin::decode(value.as_slice())?)?,
+                METADATA_TYPE_URL => to_json_string(&Metadata::decode(value.as_slice())?)?,
+                BASE_ACCOUNT_TYPE_URL => to_json_string(&BaseAccount::decode(value.as_slice())?)?,
+                _ => {
+                    return Err(NeutronError::Std(StdError::generic_err(format!(
+                        "unsupported protobuf type: {}",
+                        type_url
+                    ))))
+                }
+            };
+            Ok(DecodedValue::Json(json))
+        }
+    }
 }
 
 /// Returns the number of transfers made on remote chain and queried with ICQ
@@ -545,7 +1526,52 @@ pub fn query_kv_callback_stats(deps: Deps, query_id: u64) -> NeutronResult<Binar
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> StdResult<Response> {
     deps.api.debug("WASMDEBUG: migrate");
-    Ok(Response::default())
+    // the transfers stored as a list per recipient are already counted in TRANSFERS
+    let migrated = migrate_recipient_txs(deps.storage)?;
+    Ok(Response::default().add_attribute("migrated_transfers", migrated.to_string()))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(mut deps: DepsMut, env: Env, msg: Reply) -> StdResult<Response> {
+    match msg.id {
+        REGISTER_QUERY_REPLY_ID => {
+            let resp: MsgRegisterInterchainQueryResponse = decode_message_response(
+                &msg.result
+                    .into_result()
+                    .map_err(StdError::generic_err)?
+                    .msg_responses[0]
+                    .clone()
+                    .value
+                    .to_vec(),
+            )
+            .map_err(|e| StdError::generic_err(format!("failed to parse response: {:?}", e)))?;
+            let payload: RegisterQueryPayload = from_json(&msg.payload)?;
+            track_query(deps.branch(), resp.id, env.block.height)?;
+            if let Some(handler) = payload.handler {
+                TX_QUERY_HANDLERS.save(deps.storage, resp.id, &handler)?;
+            }
+            if let Some(decoder) = payload.decoder {
+                RAW_KV_DECODERS.save(deps.storage, resp.id, &decoder)?;
+            }
+            if let Some((connection_id, spec)) = payload.labeled {
+                QUERY_LABELS.save(deps.storage, resp.id, &spec.label)?;
+                LABELED_QUERIES.save(
+                    deps.storage,
+                    &spec.label.clone(),
+                    &LabeledQuery {
+                        query_id: resp.id,
+                        connection_id,
+                        spec,
+                    },
+                )?;
+            }
+            Ok(Response::new().add_attribute("query_id", resp.id.to_string()))
+        }
+        _ => Err(StdError::generic_err(format!(
+            "unsupported reply message id {}",
+            msg.id
+        ))),
+    }
 }
 
 #[entry_point]
@@ -565,9 +1591,9 @@ pub fn sudo(deps: DepsMut, env: Env, msg: SudoMsg) -> NeutronResult<Response> {
 /// deposits received as a result on the registered query in the contract's state.
 pub fn sudo_tx_query_result(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     query_id: u64,
-    _height: Height,
+    height: Height,
     data: Binary,
 ) -> NeutronResult<Response> {
     if let Some(IntegrationTestsQueryMock::Enabled {}) =
@@ -576,116 +1602,367 @@ pub fn sudo_tx_query_result(
         // simulate error on tx query submit for integration tests
         return Err(NeutronError::IntegrationTestsMock {});
     }
+    touch_tracked_query(deps.storage, query_id, env.block.height)?;
     // Decode the transaction data
+    let tx_hash = tx_hash(&data);
     let tx: TxRaw = TxRaw::decode(data.as_slice())?;
     let body: TxBody = TxBody::decode(tx.body_bytes.as_slice())?;
 
     // Get the registered query by ID and retrieve the raw query string
     let registered_query = get_registered_query(deps.as_ref(), query_id)?;
-    let transactions_filter = registered_query.transactions_filter;
-
-    #[allow(clippy::match_single_binding)]
-    // Depending of the query type, check the transaction data to see whether is satisfies
-    // the original query. If you don't write specific checks for a transaction query type,
-    // all submitted results will be treated as valid.
-    //
-    // TODO: come up with solution to determine transactions filter type
-    match registered_query.query_type {
-        _ => {
-            // For transfer queries, query data looks like `[{"field:"transfer.recipient", "op":"eq", "value":"some_address"}]`
-            let query_data: Vec<TransactionFilterItem> =
-                serde_json_wasm::from_str(transactions_filter.as_str()).map_err(|e| {
-                    StdError::generic_err(format!("failed to parse transactions_filter: {:?}", e))
-                })?;
-
-            let recipient = query_data
-                .iter()
-                .find(|x| x.field == RECIPIENT_FIELD && x.op == TransactionFilterOp::Eq)
-                .map(|x| match &x.value {
-                    TransactionFilterValue::String(v) => v.as_str(),
-                    _ => "",
-                })
-                .unwrap_or("");
-
-            let deposits = recipient_deposits_from_tx_body(body, recipient)?;
-            // If we didn't find a Send message with the correct recipient, return an error, and
-            // this query result will be rejected by Neutron: no data will be saved to state.
-            if deposits.is_empty() {
-                return Err(NeutronError::Std(StdError::generic_err(
-                    "failed to find a matching transaction message",
-                )));
-            }
-
-            let mut stored_transfers: u64 = TRANSFERS.load(deps.storage).unwrap_or_default();
-            stored_transfers += deposits.len() as u64;
-            TRANSFERS.save(deps.storage, &stored_transfers)?;
 
-            check_deposits_size(&deposits)?;
-            let mut stored_deposits: Vec<Transfer> = RECIPIENT_TXS
-                .load(deps.storage, recipient)
-                .unwrap_or_default();
-            stored_deposits.extend(deposits);
-            RECIPIENT_TXS.save(deps.storage, recipient, &stored_deposits)?;
+    // Depending of the handler chosen at the query registration, check the transaction data to
+    // see whether is satisfies the original query and store it.
+    let handler = match TX_QUERY_HANDLERS.may_load(deps.storage, query_id)? {
+        Some(handler) => handler,
+        // queries registered before the handlers registry was introduced are transfer queries
+        None => TxFilterHandler::BankDeposits {
+            recipients: filter_recipients(&registered_query.transactions_filter)?,
+        },
+    };
+    match handler {
+        TxFilterHandler::BankDeposits { recipients } => {
+            store_bank_deposits(deps, body, &recipients, height.revision_height, &tx_hash)
+        }
+        TxFilterHandler::IbcTransfers { sender } => {
+            store_ibc_transfers(deps, body, &sender, height.revision_height, &tx_hash)
+        }
+        TxFilterHandler::Delegations { delegator } => {
+            store_delegate_txs(deps, body, &delegator, height.revision_height, &tx_hash)
+        }
+        TxFilterHandler::Generic {} => {
+            TX_QUERY_MATCHES.update(deps.storage, query_id, |n| -> StdResult<u64> {
+                Ok(n.unwrap_or_default() + 1)
+            })?;
             Ok(Response::new())
         }
     }
 }
 
-/// parses tx body and retrieves transactions to the given recipient.
-fn recipient_deposits_from_tx_body(
-    tx_body: TxBody,
-    recipient: &str,
-) -> NeutronResult<Vec<Transfer>> {
-    let mut deposits: Vec<Transfer> = vec![];
-    // Only handle up to MAX_ALLOWED_MESSAGES messages, everything else
-    // will be ignored to prevent 'out of gas' conditions.
-    // Note: in real contracts you will have to somehow save ignored
-    // data in order to handle it later.
-    for msg in tx_body.messages.iter().take(MAX_ALLOWED_MESSAGES) {
-        // Skip all messages in this transaction that are not Send messages.
-        if msg.type_url != *COSMOS_SDK_TRANSFER_MSG_URL.to_string() {
+/// returns the recipients of a transfer query, its filter looks like
+/// `[{"field:"transfer.recipient", "op":"eq", "value":"some_address"}]`
+fn filter_recipients(transactions_filter: &str) -> StdResult<Vec<String>> {
+    let query_data: Vec<TransactionFilterItem> = serde_json_wasm::from_str(transactions_filter)
+        .map_err(|e| {
+            StdError::generic_err(format!("failed to parse transactions_filter: {:?}", e))
+        })?;
+
+    Ok(query_data
+        .into_iter()
+        .filter(|x| x.field == RECIPIENT_FIELD && x.op == TransactionFilterOp::Eq)
+        .filter_map(|x| match x.value {
+            TransactionFilterValue::String(v) => Some(v),
+            _ => None,
+        })
+        .collect())
+}
+
+fn store_bank_deposits(
+    deps: DepsMut,
+    body: TxBody,
+    recipients: &[String],
+    height: u64,
+    tx_hash: &str,
+) -> NeutronResult<Response> {
+    // Only handle up to MAX_ALLOWED_MESSAGES messages now, the rest of them are saved
+    // raw and processed later with ProcessPending to prevent 'out of gas' conditions.
+    let (messages, overflow) = body
+        .messages
+        .split_at(body.messages.len().min(MAX_ALLOWED_MESSAGES));
+    let mut found = false;
+    let mut stored = 0u64;
+    for recipient in recipients {
+        let deposits = recipient_deposits_from_messages(messages, recipient, height, tx_hash, 0)?;
+        if deposits.is_empty() {
             continue;
         }
+        found = true;
 
-        // Parse a Send message and check that it has the required recipient.
-        let transfer_msg: MsgSend = MsgSend::decode(msg.value.as_slice())?;
-        if transfer_msg.to_address == recipient {
-            for coin in transfer_msg.amount {
-                deposits.push(Transfer {
-                    sender: transfer_msg.from_address.clone(),
-                    amount: coin.amount.clone(),
-                    denom: coin.denom,
-                    recipient: recipient.to_string(),
-                });
+        stored += save_transfers(deps.storage, deposits, over_limit)?;
+    }
+    // A resubmitted tx must not be counted twice
+    if (found && stored == 0) || PENDING_DEPOSITS.has(deps.storage, (height, tx_hash)) {
+        return Err(NeutronError::Std(StdError::generic_err(
+            "transaction has already been processed",
+        )));
+    }
+    // If we didn't find a Send message with the correct recipient, return an error, and
+    // this query result will be rejected by Neutron: no data will be saved to state.
+    if !found && overflow.is_empty() {
+        return Err(NeutronError::Std(StdError::generic_err(
+            "failed to find a matching transaction message",
+        )));
+    }
+
+    let mut response = Response::new();
+    if !overflow.is_empty() {
+        queue_pending(
+            deps.storage,
+            height,
+            tx_hash,
+            recipients,
+            messages.len() as u32,
+            overflow
+                .iter()
+                .map(|msg| Binary::from(msg.encode_to_vec()))
+                .collect(),
+        )?;
+        response = response.add_attribute("pending_messages", overflow.len().to_string());
+    }
+    add_transfers_number(deps.storage, stored)?;
+    Ok(response)
+}
+
+fn add_transfers_number(storage: &mut dyn Storage, stored: u64) -> StdResult<()> {
+    let mut stored_transfers: u64 = TRANSFERS.load(storage).unwrap_or_default();
+    stored_transfers += stored;
+    TRANSFERS.save(storage, &stored_transfers)
+}
+
+/// Transfers over the limit of their denom are saved as flagged and aren't added to the totals.
+fn over_limit(storage: &dyn Storage, transfer: &Transfer) -> StdResult<bool> {
+    let limit = TRANSFER_LIMITS.may_load(storage, &transfer.denom)?;
+    Ok(limit.is_some_and(|limit| transfer.amount > limit))
+}
+
+fn store_ibc_transfers(
+    deps: DepsMut,
+    body: TxBody,
+    sender: &str,
+    height: u64,
+    tx_hash: &str,
+) -> NeutronResult<Response> {
+    check_messages_number(&body)?;
+    let mut found = false;
+    let mut stored = 0u64;
+    for (index, msg) in body.messages.iter().enumerate() {
+        if msg.type_url != IBC_TRANSFER_MSG_URL {
+            continue;
+        }
+        let transfer_msg = MsgTransfer::decode(msg.value.as_slice())?;
+        if transfer_msg.sender != sender {
+            continue;
+        }
+        if let Some(token) = transfer_msg.token {
+            found = true;
+            let key = (sender, height, (tx_hash, index as u32));
+            if IBC_TRANSFERS.has(deps.storage, key) {
+                continue;
             }
+            IBC_TRANSFERS.save(
+                deps.storage,
+                key,
+                &IbcTransfer {
+                    sender: transfer_msg.sender,
+                    receiver: transfer_msg.receiver,
+                    source_channel: transfer_msg.source_channel,
+                    denom: token.denom,
+                    amount: token.amount,
+                    height,
+                    tx_hash: tx_hash.to_string(),
+                    index: index as u32,
+                },
+            )?;
+            stored += 1;
         }
     }
-    Ok(deposits)
+    check_stored(found, stored)?;
+    Ok(Response::new())
 }
 
-// checks whether there are deposits that are greater then MAX_ALLOWED_TRANSFER.
-fn check_deposits_size(deposits: &Vec<Transfer>) -> StdResult<()> {
-    for deposit in deposits {
-        match deposit.amount.parse::<u64>() {
-            Ok(amount) => {
-                if amount > MAX_ALLOWED_TRANSFER {
-                    return Err(StdError::generic_err(format!(
-                        "maximum allowed transfer is {}",
-                        MAX_ALLOWED_TRANSFER
-                    )));
-                };
-            }
-            Err(error) => {
-                return Err(StdError::generic_err(format!(
-                    "failed to cast transfer amount to u64: {}",
-                    error
-                )));
+fn store_delegate_txs(
+    deps: DepsMut,
+    body: TxBody,
+    delegator: &str,
+    height: u64,
+    tx_hash: &str,
+) -> NeutronResult<Response> {
+    check_messages_number(&body)?;
+    let mut found = false;
+    let mut stored = 0u64;
+    for (index, msg) in body.messages.iter().enumerate() {
+        if msg.type_url != DELEGATE_MSG_URL {
+            continue;
+        }
+        let delegate_msg = MsgDelegate::decode(msg.value.as_slice())?;
+        if delegate_msg.delegator_address != delegator {
+            continue;
+        }
+        if let Some(amount) = delegate_msg.amount {
+            found = true;
+            let key = (delegator, height, (tx_hash, index as u32));
+            if DELEGATE_TXS.has(deps.storage, key) {
+                continue;
             }
-        };
+            DELEGATE_TXS.save(
+                deps.storage,
+                key,
+                &DelegateTx {
+                    delegator: delegate_msg.delegator_address,
+                    validator: delegate_msg.validator_address,
+                    denom: amount.denom,
+                    amount: amount.amount,
+                    height,
+                    tx_hash: tx_hash.to_string(),
+                    index: index as u32,
+                },
+            )?;
+            stored += 1;
+        }
+    }
+    check_stored(found, stored)?;
+    Ok(Response::new())
+}
+
+/// Rejects a tx with more messages than can be processed in the callback, the result isn't
+/// stored by Neutron and the error is reported to the relayer.
+fn check_messages_number(body: &TxBody) -> NeutronResult<()> {
+    if body.messages.len() > MAX_ALLOWED_MESSAGES {
+        return Err(NeutronError::Std(StdError::generic_err(format!(
+            "transaction has {} messages, at most {} can be processed",
+            body.messages.len(),
+            MAX_ALLOWED_MESSAGES
+        ))));
     }
     Ok(())
 }
 
+/// Rejects the tx result if no message matched or if all of them were stored before
+fn check_stored(found: bool, stored: u64) -> NeutronResult<()> {
+    if !found {
+        return Err(NeutronError::Std(StdError::generic_err(
+            "failed to find a matching transaction message",
+        )));
+    }
+    // A resubmitted tx must not be stored twice
+    if stored == 0 {
+        return Err(NeutronError::Std(StdError::generic_err(
+            "transaction has already been processed",
+        )));
+    }
+    Ok(())
+}
+
+/// ICS-20 packet data, the fields the deposits are made of.
+#[derive(Deserialize)]
+struct FungibleTokenPacketData {
+    denom: String,
+    amount: String,
+    sender: String,
+    receiver: String,
+}
+
+/// parses tx messages and retrieves transactions to the given recipient, `first_index` is the
+/// index of the first of the messages in the tx.
+fn recipient_deposits_from_messages(
+    messages: &[Any],
+    recipient: &str,
+    height: u64,
+    tx_hash: &str,
+    first_index: u32,
+) -> NeutronResult<Vec<Transfer>> {
+    let mut deposits: Vec<Transfer> = vec![];
+    for (i, msg) in messages.iter().enumerate() {
+        let index = first_index + i as u32;
+        let mut coin_index = 0u32;
+        let mut push = |sender: &str, denom: String, amount: &str, source: TransferSource| {
+            deposits.push(Transfer {
+                sender: sender.to_string(),
+                amount: Uint256::from_str(amount)?,
+                denom,
+                recipient: recipient.to_string(),
+                height,
+                tx_hash: tx_hash.to_string(),
+                index,
+                coin_index,
+                source,
+            });
+            coin_index += 1;
+            StdResult::Ok(())
+        };
+        // Parse the messages that can transfer coins and check that they have the required
+        // recipient, skip all other messages in this transaction.
+        if msg.type_url == *COSMOS_SDK_TRANSFER_MSG_URL.to_string() {
+            let transfer_msg: MsgSend = MsgSend::decode(msg.value.as_slice())?;
+            if transfer_msg.to_address == recipient {
+                for coin in transfer_msg.amount {
+                    push(
+                        &transfer_msg.from_address,
+                        coin.denom,
+                        &coin.amount,
+                        TransferSource::Send,
+                    )?;
+                }
+            }
+        } else if msg.type_url == MULTI_SEND_MSG_URL {
+            let multi_send_msg = MsgMultiSend::decode(msg.value.as_slice())?;
+            let sender = multi_send_msg
+                .inputs
+                .first()
+                .map(|input| input.address.clone())
+                .unwrap_or_default();
+            for output in multi_send_msg.outputs {
+                if output.address != recipient {
+                    continue;
+                }
+                for coin in output.coins {
+                    push(&sender, coin.denom, &coin.amount, TransferSource::MultiSend)?;
+                }
+            }
+        } else if msg.type_url == RECV_PACKET_MSG_URL {
+            let Some(packet) = MsgRecvPacket::decode(msg.value.as_slice())?.packet else {
+                continue;
+            };
+            if packet.destination_port != TRANSFER_PORT {
+                continue;
+            }
+            // Packets of other applications bound to the transfer port aren't deposits
+            let Ok(data) = from_json::<FungibleTokenPacketData>(&packet.data) else {
+                continue;
+            };
+            // A packet can be relayed again in a later tx, it's recognized by its destination
+            // channel and sequence when saved. A receive acknowledged with an error isn't a
+            // deposit, but it can't be told apart from the TxBody since the acknowledgement is
+            // only in the tx result events.
+            if data.receiver == recipient {
+                push(
+                    &data.sender,
+                    received_denom(&packet, &data.denom),
+                    &data.amount,
+                    TransferSource::IbcRecv {
+                        destination_channel: packet.destination_channel.clone(),
+                        sequence: packet.sequence,
+                    },
+                )?;
+            }
+        }
+    }
+    Ok(deposits)
+}
+
+/// returns the denom the tokens of the ICS-20 packet have on the receiving chain.
+fn received_denom(packet: &Packet, denom: &str) -> String {
+    // The tokens return to the chain they came from, the source hop is removed from the trace
+    let source_prefix = format!("{}/{}/", packet.source_port, packet.source_channel);
+    let trace = match denom.strip_prefix(&source_prefix) {
+        Some(unprefixed) => unprefixed.to_string(),
+        None => format!(
+            "{}/{}/{}",
+            packet.destination_port, packet.destination_channel, denom
+        ),
+    };
+    // A trace starting with a port and a channel is a voucher, otherwise it's a native denom
+    let is_voucher = trace
+        .split('/')
+        .nth(1)
+        .is_some_and(|channel| channel.starts_with("channel-"));
+    if is_voucher {
+        format!("ibc/{}", sha256_upper_hex(trace.as_bytes()))
+    } else {
+        trace
+    }
+}
+
 /// sudo_kv_query_result is the contract's callback for KV query results. Note that only the query
 /// id is provided, so you need to read the query result from the state.
 pub fn sudo_kv_query_result(deps: DepsMut, env: Env, query_id: u64) -> NeutronResult<Response> {
@@ -709,9 +1986,76 @@ pub fn sudo_kv_query_result(deps: DepsMut, env: Env, query_id: u64) -> NeutronRe
 
     // store last KV callback update time
     KV_CALLBACK_STATS.save(deps.storage, query_id, &env.block.height)?;
+    touch_tracked_query(deps.storage, query_id, env.block.height)?;
+
+    let registered_query = get_registered_query(deps.as_ref(), query_id)?;
+    let remote_height = registered_query
+        .last_submitted_result_remote_height
+        .map_or(0, |h| h.revision_height);
+
+    let result = match query_typed_kv_result(
+        deps.as_ref(),
+        env.clone(),
+        query_id,
+        &registered_query.keys,
+    )? {
+        Some(result) => result,
+        // results of the other query types aren't stored
+        None => return Ok(Response::default()),
+    };
+    KV_SNAPSHOTS.save(
+        deps.storage,
+        (query_id, remote_height),
+        &KvSnapshot {
+            remote_height,
+            local_height: env.block.height,
+            result,
+        },
+    )?;
 
-    // TODO: provide an actual example. Currently to many things are going to change
-    // after @pro0n00gler's PRs to implement this.
+    // keep only the last MAX_KV_HISTORY results of the query
+    let heights = KV_SNAPSHOTS
+        .prefix(query_id)
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<u64>>>()?;
+    for height in heights
+        .iter()
+        .take(heights.len().saturating_sub(MAX_KV_HISTORY))
+    {
+        KV_SNAPSHOTS.remove(deps.storage, (query_id, *height));
+    }
 
     Ok(Response::default())
 }
+
+/// Reads the result of the KV query decoded according to the type of the registered keys.
+/// Returns `None` for the query types the contract doesn't store.
+fn query_typed_kv_result(
+    deps: Deps,
+    env: Env,
+    query_id: u64,
+    keys: &[KvKey],
+) -> NeutronResult<Option<KvResult>> {
+    let has_key = |path: &str, prefix: u8| {
+        keys.iter()
+            .any(|k| k.path == path && k.key.first() == Some(&prefix))
+    };
+
+    let result = if has_key(BANK_STORE_KEY, BALANCES_PREFIX) {
+        KvResult::Balance(query_balance(deps, env, query_id)?)
+    } else if has_key(STAKING_STORE_KEY, DELEGATION_KEY) {
+        // delegations query also contains validators keys, so it's checked first
+        KvResult::Delegations(query_delegations(deps, env, query_id)?)
+    } else if !keys.is_empty()
+        && keys
+            .iter()
+            .all(|k| k.path == STAKING_STORE_KEY && k.key.first() == Some(&VALIDATORS_KEY))
+    {
+        KvResult::StakingValidators(query_staking_validators(deps, env, query_id)?)
+    } else if has_key(GOV_STORE_KEY, PROPOSALS_KEY_PREFIX) {
+        KvResult::GovernmentProposals(query_government_proposals(deps, env, query_id)?)
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(result))
+}
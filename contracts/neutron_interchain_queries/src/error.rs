@@ -0,0 +1,21 @@
+use cosmwasm_std::StdError;
+use neutron_sdk::NeutronError;
+use thiserror::Error;
+
+pub type ContractResult<T> = Result<T, ContractError>;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ContractError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("{0}")]
+    Neutron(#[from] NeutronError),
+
+    #[error("result of query {query_id} is stale: taken at remote height {remote_height}, last updated at local height {local_height}")]
+    StaleResult {
+        query_id: u64,
+        remote_height: u64,
+        local_height: u64,
+    },
+}
@@ -1,8 +1,17 @@
-use crate::state::Transfer;
+use crate::state::{DelegateTx, IbcTransfer, KvDecoder, LabeledQuery};
+use cosmwasm_std::{Binary, Uint128, Uint256};
+use neutron_sdk::interchain_queries::types::TransactionFilterItem;
 use neutron_std::types::neutron::interchainqueries::KvKey;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+pub use crate::state::{ContractStorageKey, QueryKind, QuerySpec};
+pub use recipient_transfers::{
+    GetPendingDepositsResponse, GetRecipientTotalsResponse, GetRecipientTxsResponse, PendingTx,
+    TransferCursor, TransferFilter, TransferTotal,
+};
+pub use tracked_queries::DepositsSummaryResponse;
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct InstantiateMsg {}
 
@@ -46,6 +55,26 @@ pub enum ExecuteMsg {
         recipients: Vec<String>,
         min_height: Option<u64>,
     },
+    /// Registers a TX query for IBC transfers sent by the sender
+    RegisterIbcTransfersQuery {
+        connection_id: String,
+        update_period: u64,
+        sender: String,
+        min_height: Option<u64>,
+    },
+    /// Registers a TX query for delegations made by the delegator
+    RegisterDelegateTxsQuery {
+        connection_id: String,
+        update_period: u64,
+        delegator: String,
+        min_height: Option<u64>,
+    },
+    /// Registers a TX query with an arbitrary filter, the contract only counts matched transactions
+    RegisterTxQuery {
+        connection_id: String,
+        update_period: u64,
+        transactions_filter: Vec<TransactionFilterItem>,
+    },
     RegisterDelegatorDelegationsQuery {
         delegator: String,
         validators: Vec<String>,
@@ -63,6 +92,28 @@ pub enum ExecuteMsg {
         connection_id: String,
         update_period: u64,
     },
+    /// Registers the queries and saves their ids under the labels of the specs. A spec with
+    /// a label already in use replaces the query registered under it, so the same specs can be
+    /// registered again, e.g. on a new connection.
+    RegisterQueries {
+        connection_id: String,
+        specs: Vec<QuerySpec>,
+    },
+    /// Registers a KV query for arbitrary keys, its values are read with RawQueryResult
+    RegisterRawKvQuery {
+        connection_id: String,
+        keys: Vec<KvKey>,
+        update_period: u64,
+        decoder: KvDecoder,
+    },
+    /// Registers a KV query for the storage of a CosmWasm contract on the remote chain,
+    /// its JSON value is read with WasmContractState
+    RegisterWasmContractStateQuery {
+        connection_id: String,
+        update_period: u64,
+        contract_address: String,
+        storage_key: ContractStorageKey,
+    },
     UpdateInterchainQuery {
         query_id: u64,
         new_keys: Vec<KvKey>,
@@ -72,6 +123,38 @@ pub enum ExecuteMsg {
     RemoveInterchainQuery {
         query_id: u64,
     },
+    /// Sets the number of blocks without results after which a query can be removed with
+    /// RemoveStaleQueries. Omitting it disables the removal. Only the contract admin can set it.
+    SetStaleQueryBlocks {
+        blocks: Option<u64>,
+    },
+    /// Checks up to `limit` tracked queries after `start_after` and removes the ones that haven't
+    /// received results for the stale query blocks, reclaiming their deposits. The last checked
+    /// query id is returned in the `last_query_id` attribute. Anyone can call it.
+    RemoveStaleQueries {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Makes reading the query result fail once it's older than the given number of blocks.
+    /// The remote age is counted from the latest height of the remote chain known to the light
+    /// client of the query connection. Omitting both limits removes the check. Only the
+    /// contract admin can set it.
+    SetMaxResultAge {
+        query_id: u64,
+        remote_blocks: Option<u64>,
+        local_blocks: Option<u64>,
+    },
+    /// Sets the maximum amount of a transfer in the denom, transfers over it are stored as flagged.
+    /// Omitting the amount removes the limit. Only the contract admin can set limits.
+    SetTransferLimit {
+        denom: String,
+        max_amount: Option<Uint256>,
+    },
+    /// Credits deposits from up to `limit` (at most 20) messages of the TX query results that
+    /// had more messages than could be processed in the callback. Anyone can call it.
+    ProcessPending {
+        limit: Option<u32>,
+    },
     /// Used only in integration tests framework to simulate failures. It tries to register query where keys is an empty array.
     IntegrationTestsRegisterQueryEmptyKeys {
         connection_id: String,
@@ -97,25 +180,180 @@ pub enum ExecuteMsg {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
-    Balance { query_id: u64 },
-    BankTotalSupply { query_id: u64 },
-    DistributionFeePool { query_id: u64 },
-    StakingValidators { query_id: u64 },
-    ValidatorsSigningInfos { query_id: u64 },
-    GovernmentProposals { query_id: u64 },
-    GovernmentProposalVotes { query_id: u64 },
-    GetDelegations { query_id: u64 },
-    GetUnbondingDelegations { query_id: u64 },
-    GetRegisteredQuery { query_id: u64 },
-    GetRecipientTxs { recipient: String },
-    KvCallbackStats { query_id: u64 },
+    Balance {
+        query_id: u64,
+    },
+    BankTotalSupply {
+        query_id: u64,
+    },
+    DistributionFeePool {
+        query_id: u64,
+    },
+    StakingValidators {
+        query_id: u64,
+    },
+    ValidatorsSigningInfos {
+        query_id: u64,
+    },
+    GovernmentProposals {
+        query_id: u64,
+    },
+    GovernmentProposalVotes {
+        query_id: u64,
+    },
+    GetDelegations {
+        query_id: u64,
+    },
+    GetUnbondingDelegations {
+        query_id: u64,
+    },
+    GetRegisteredQuery {
+        query_id: u64,
+    },
+    /// Returns the transfers to the recipient ordered by remote height
+    GetRecipientTxs {
+        recipient: String,
+        start_after: Option<TransferCursor>,
+        limit: Option<u32>,
+        filter: Option<TransferFilter>,
+    },
+    /// Returns the sums of the transfers to the recipient per denom
+    GetRecipientTotals {
+        recipient: String,
+    },
+    /// Returns the transfers to the recipient that exceeded the limit of their denom
+    GetFlaggedTxs {
+        recipient: String,
+        start_after: Option<TransferCursor>,
+        limit: Option<u32>,
+    },
+    TransferLimit {
+        denom: String,
+    },
+    /// Returns the transactions with messages waiting for ProcessPending, the oldest first
+    GetPendingDeposits {
+        limit: Option<u32>,
+    },
+    /// Returns the IBC transfers of the sender ordered by remote height
+    GetIbcTransfers {
+        sender: String,
+        start_after: Option<TransferCursor>,
+        limit: Option<u32>,
+    },
+    /// Returns the delegations of the delegator ordered by remote height
+    GetDelegateTxs {
+        delegator: String,
+        start_after: Option<TransferCursor>,
+        limit: Option<u32>,
+    },
+    GetTxQueryMatches {
+        query_id: u64,
+    },
+    TxQueryHandler {
+        query_id: u64,
+    },
+    KvCallbackStats {
+        query_id: u64,
+    },
     GetTransfersNumber {},
+    /// Returns the latest stored balances of the query taken at or before the remote height
+    BalanceAt {
+        query_id: u64,
+        height: u64,
+    },
+    /// Returns the stored typed results of the KV query, the newest first
+    History {
+        query_id: u64,
+        limit: Option<u32>,
+    },
+    MaxResultAge {
+        query_id: u64,
+    },
+    /// Returns the deposit and the result heights of the query registered by the contract
+    TrackedQuery {
+        query_id: u64,
+    },
+    /// Returns the values of the KV query result decoded by the decoder of the query
+    RawQueryResult {
+        query_id: u64,
+    },
+    /// Returns the JSON value of the remote contract storage as it's stored, `null` if it's absent
+    WasmContractState {
+        query_id: u64,
+    },
+    /// Returns the sum of the deposits locked by the queries registered by the contract
+    DepositsSummary {},
+    /// Returns the query registered under the label with RegisterQueries
+    GetQueryByLabel {
+        label: String,
+    },
+    /// Returns the queries registered with RegisterQueries ordered by label
+    ListQueries {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+}
+
+impl QueryMsg {
+    /// Id of the interchain query whose latest result is read by the query
+    pub fn result_query_id(&self) -> Option<u64> {
+        match self {
+            QueryMsg::Balance { query_id }
+            | QueryMsg::BankTotalSupply { query_id }
+            | QueryMsg::DistributionFeePool { query_id }
+            | QueryMsg::StakingValidators { query_id }
+            | QueryMsg::ValidatorsSigningInfos { query_id }
+            | QueryMsg::GovernmentProposals { query_id }
+            | QueryMsg::GovernmentProposalVotes { query_id }
+            | QueryMsg::GetDelegations { query_id }
+            | QueryMsg::GetUnbondingDelegations { query_id } => Some(*query_id),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct RawQueryResultResponse {
+    /// remote height of the result
+    pub height: u64,
+    pub values: Vec<RawKvValue>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct RawKvValue {
+    pub storage_prefix: String,
+    pub key: Binary,
+    /// decoded value, absent if the key has no value on the remote chain
+    pub value: Option<DecodedValue>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DecodedValue {
+    Raw(Binary),
+    Uint128(Uint128),
+    /// JSON text of the value
+    Json(String),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ListQueriesResponse {
+    pub queries: Vec<LabeledQuery>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct GetIbcTransfersResponse {
+    pub transfers: Vec<IbcTransfer>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
-pub struct GetRecipientTxsResponse {
-    pub transfers: Vec<Transfer>,
+pub struct GetDelegateTxsResponse {
+    pub delegations: Vec<DelegateTx>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
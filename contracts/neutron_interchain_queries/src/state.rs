@@ -1,22 +1,205 @@
+use cosmwasm_std::{Binary, Uint256};
 use cw_storage_plus::{Item, Map};
+use neutron_sdk::interchain_queries::types::TransactionFilterItem;
+use neutron_sdk::interchain_queries::v047::queries::{
+    BalanceResponse, DelegatorDelegationsResponse, ProposalResponse, ValidatorResponse,
+};
+use neutron_std::types::neutron::interchainqueries::KvKey;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-pub type Recipient = str;
+pub use recipient_transfers::{
+    PendingDeposits, Recipient, Transfer, TransferSource, FLAGGED_TRANSFERS, PENDING_DEPOSITS,
+    RECIPIENT_TOTALS, RECIPIENT_TRANSFERS,
+};
+pub use tracked_queries::{TrackedQuery, STALE_QUERY_BLOCKS, TRACKED_QUERIES};
 
-/// contains all transfers mapped by a recipient address observed by the contract.
-pub const RECIPIENT_TXS: Map<&Recipient, Vec<Transfer>> = Map::new("recipient_txs");
+/// contains maximum allowed transfer amounts mapped by denom, set by the contract admin.
+pub const TRANSFER_LIMITS: Map<&str, Uint256> = Map::new("transfer_limits");
 /// contains number of transfers to addresses observed by the contract.
 pub const TRANSFERS: Item<u64> = Item::new("transfers");
 
+/// Handler of the TX query results chosen at the query registration.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
-pub struct Transfer {
-    pub recipient: String,
+#[serde(rename_all = "snake_case")]
+pub enum TxFilterHandler {
+    /// stores bank MsgSend deposits to the recipients
+    BankDeposits { recipients: Vec<String> },
+    /// stores IBC MsgTransfer sent by the sender
+    IbcTransfers { sender: String },
+    /// stores MsgDelegate sent by the delegator
+    Delegations { delegator: String },
+    /// counts the transactions matching the filter, their messages aren't checked
+    Generic {},
+}
+
+/// contains TX query result handlers mapped by query id.
+pub const TX_QUERY_HANDLERS: Map<u64, TxFilterHandler> = Map::new("tx_query_handlers");
+
+/// contains decoders of the raw KV query values mapped by query id.
+pub const RAW_KV_DECODERS: Map<u64, KvDecoder> = Map::new("raw_kv_decoders");
+
+/// Decoding of the raw KV query values chosen at the query registration.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum KvDecoder {
+    /// the value bytes as they are
+    Raw {},
+    /// protobuf message of the type converted to JSON, only a few common types are supported
+    Protobuf { type_url: String },
+    /// big-endian unsigned integer of up to 16 bytes
+    Uint128BigEndian {},
+    /// JSON stored as is, e.g. by CosmWasm contracts
+    Json {},
+}
+
+/// contains queries registered with RegisterQueries mapped by their labels.
+pub const LABELED_QUERIES: Map<&str, LabeledQuery> = Map::new("labeled_queries");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct LabeledQuery {
+    pub query_id: u64,
+    pub connection_id: String,
+    pub spec: QuerySpec,
+}
+
+/// contains labels of the queries registered with RegisterQueries mapped by query id.
+pub const QUERY_LABELS: Map<u64, String> = Map::new("query_labels");
+
+/// Query to register with RegisterQueries
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct QuerySpec {
+    /// name the query id is saved under
+    pub label: String,
+    pub update_period: u64,
+    pub query: QueryKind,
+}
+
+/// Kinds of the queries the Register*Query messages register, with the same parameters
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryKind {
+    Balances {
+        addr: String,
+        denoms: Vec<String>,
+    },
+    BankTotalSupply {
+        denoms: Vec<String>,
+    },
+    DistributionFeePool {},
+    StakingValidators {
+        validators: Vec<String>,
+    },
+    GovernmentProposals {
+        proposals_ids: Vec<u64>,
+    },
+    GovernmentProposalVotes {
+        proposals_ids: Vec<u64>,
+        voters: Vec<String>,
+    },
+    DelegatorDelegations {
+        delegator: String,
+        validators: Vec<String>,
+    },
+    DelegatorUnbondingDelegations {
+        delegator: String,
+        validators: Vec<String>,
+    },
+    ValidatorsSigningInfo {
+        validators: Vec<String>,
+    },
+    Transfers {
+        recipients: Vec<String>,
+        min_height: Option<u64>,
+    },
+    IbcTransfers {
+        sender: String,
+        min_height: Option<u64>,
+    },
+    DelegateTxs {
+        delegator: String,
+        min_height: Option<u64>,
+    },
+    Tx {
+        transactions_filter: Vec<TransactionFilterItem>,
+    },
+    RawKv {
+        keys: Vec<KvKey>,
+        decoder: KvDecoder,
+    },
+    WasmContractState {
+        contract_address: String,
+        storage_key: ContractStorageKey,
+    },
+}
+
+/// Key of a cw-storage-plus value in the storage of a contract
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ContractStorageKey {
+    /// `Item` stored under the namespace
+    Item { namespace: String },
+    /// entry of a `Map` stored under the namespace, the key is the raw bytes of the map key,
+    /// e.g. the UTF-8 bytes of a `&str` key or the big-endian bytes of a `u64` key
+    Map { namespace: String, key: Binary },
+}
+
+impl ContractStorageKey {
+    /// Returns the key the way cw-storage-plus stores the value
+    pub fn to_storage_key(&self) -> Vec<u8> {
+        match self {
+            ContractStorageKey::Item { namespace } => namespace.as_bytes().to_vec(),
+            // a map key is prefixed with the length-prefixed namespace
+            ContractStorageKey::Map { namespace, key } => {
+                let mut storage_key = Vec::with_capacity(2 + namespace.len() + key.len());
+                storage_key.extend_from_slice(&(namespace.len() as u16).to_be_bytes());
+                storage_key.extend_from_slice(namespace.as_bytes());
+                storage_key.extend_from_slice(key.as_slice());
+                storage_key
+            }
+        }
+    }
+}
+
+/// contains IBC transfers observed by the contract mapped by
+/// (sender, remote height, (tx hash, msg index)).
+pub const IBC_TRANSFERS: Map<(&str, u64, (&str, u32)), IbcTransfer> = Map::new("ibc_transfers");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct IbcTransfer {
     pub sender: String,
+    pub receiver: String,
+    pub source_channel: String,
+    pub denom: String,
+    pub amount: String,
+    /// remote height of the tx
+    pub height: u64,
+    pub tx_hash: String,
+    /// index of the MsgTransfer among the tx messages
+    pub index: u32,
+}
+
+/// contains delegations observed by the contract mapped by
+/// (delegator, remote height, (tx hash, msg index)).
+pub const DELEGATE_TXS: Map<(&str, u64, (&str, u32)), DelegateTx> = Map::new("delegate_txs");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct DelegateTx {
+    pub delegator: String,
+    pub validator: String,
     pub denom: String,
     pub amount: String,
+    /// remote height of the tx
+    pub height: u64,
+    pub tx_hash: String,
+    /// index of the MsgDelegate among the tx messages
+    pub index: u32,
 }
 
+/// contains number of transactions matched by generic TX queries mapped by query id.
+pub const TX_QUERY_MATCHES: Map<u64, u64> = Map::new("tx_query_matches");
+
 pub const INTEGRATION_TESTS_QUERY_MOCK: Item<IntegrationTestsQueryMock> =
     Item::new("integration_tests_query_mock");
 
@@ -27,3 +210,40 @@ pub enum IntegrationTestsQueryMock {
 }
 
 pub const KV_CALLBACK_STATS: Map<u64, u64> = Map::new("kv_callback_stats");
+
+/// Maximum age of a query result after which reading it fails.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct MaxResultAge {
+    /// how many blocks the remote chain may have produced since the result height
+    pub remote_blocks: Option<u64>,
+    /// how many blocks may have passed on Neutron since the last result update
+    pub local_blocks: Option<u64>,
+}
+
+/// contains configured maximum result ages mapped by query id.
+pub const MAX_RESULT_AGES: Map<u64, MaxResultAge> = Map::new("max_result_ages");
+
+/// Typed result of a KV query decoded according to the registered query keys.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum KvResult {
+    Balance(BalanceResponse),
+    Delegations(DelegatorDelegationsResponse),
+    StakingValidators(ValidatorResponse),
+    GovernmentProposals(ProposalResponse),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct KvSnapshot {
+    /// height of the remote chain the result has been taken at
+    pub remote_height: u64,
+    /// height of the KV callback execution
+    pub local_height: u64,
+    pub result: KvResult,
+}
+
+/// contains typed KV query results mapped by (query_id, remote height), the oldest ones are
+/// pruned once a query has more than `MAX_KV_HISTORY` of them.
+pub const KV_SNAPSHOTS: Map<(u64, u64), KvSnapshot> = Map::new("kv_snapshots");
@@ -18,7 +18,7 @@ use std::marker::PhantomData;
 use cosmwasm_std::testing::{MockApi, MockQuerier, MockStorage};
 use cosmwasm_std::{
     from_json, Binary, Coin, ContractResult, CustomQuery, GrpcQuery, OwnedDeps, Querier,
-    QuerierResult, QueryRequest, SystemError, SystemResult, Uint128,
+    QuerierResult, QueryRequest, SystemError, SystemResult, Uint128, WasmQuery,
 };
 use neutron_std::types::neutron::interchainqueries::{
     QueryRegisteredQueryRequest, QueryRegisteredQueryResultRequest,
@@ -101,6 +101,13 @@ impl WasmMockQuerier {
     pub fn add_registered_queries(&mut self, query_id: u64, response: Binary) {
         self.registered_queries.insert(query_id, response);
     }
+
+    pub fn update_wasm<WH>(&mut self, handler: WH)
+    where
+        WH: Fn(&WasmQuery) -> QuerierResult + 'static,
+    {
+        self.base.update_wasm(handler);
+    }
 }
 
 #[derive(Clone, Default)]
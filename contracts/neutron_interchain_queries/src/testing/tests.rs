@@ -13,11 +13,24 @@
 use std::str::FromStr;
 
 use super::mock_querier::mock_dependencies as dependencies;
-use crate::contract::{execute, query, sudo_tx_query_result};
-use crate::msg::{ExecuteMsg, QueryMsg};
-use crate::state::{Transfer, RECIPIENT_TXS};
+use crate::contract::{
+    execute, query, reply, sudo, sudo_kv_query_result, sudo_tx_query_result,
+    wasm_contract_state_key,
+};
+use crate::error::ContractError;
+use crate::msg::{
+    ContractStorageKey, DecodedValue, DepositsSummaryResponse, ExecuteMsg, GetIbcTransfersResponse,
+    GetPendingDepositsResponse, GetRecipientTotalsResponse, GetRecipientTxsResponse,
+    GetTransfersAmountResponse, ListQueriesResponse, PendingTx, QueryKind, QueryMsg, QuerySpec,
+    RawKvValue, RawQueryResultResponse, TransferCursor, TransferFilter, TransferTotal,
+};
+use crate::state::{
+    IbcTransfer, KvDecoder, KvResult, KvSnapshot, LabeledQuery, TrackedQuery, Transfer,
+    TransferSource, TxFilterHandler,
+};
 use crate::testing::mock_querier::WasmMockQuerier;
 use base64::prelude::*;
+use cosmos_sdk_proto::cosmos::bank::v1beta1::{Input, MsgMultiSend, MsgSend, Output};
 use cosmos_sdk_proto::cosmos::base::v1beta1::{Coin as CosmosCoin, DecCoin as CosmosDecCoin};
 use cosmos_sdk_proto::cosmos::distribution::v1beta1::FeePool as CosmosFeePool;
 use cosmos_sdk_proto::cosmos::gov::v1beta1::{
@@ -26,10 +39,13 @@ use cosmos_sdk_proto::cosmos::gov::v1beta1::{
 };
 use cosmos_sdk_proto::cosmos::slashing::v1beta1::ValidatorSigningInfo as CosmosValidatorSigningInfo;
 use cosmos_sdk_proto::cosmos::staking::v1beta1::Validator as CosmosValidator;
+use cosmos_sdk_proto::cosmos::tx::v1beta1::{TxBody, TxRaw};
 use cosmos_sdk_proto::Any;
 use cosmwasm_std::testing::{message_info, mock_env, MockApi, MockStorage};
 use cosmwasm_std::{
-    from_json, Addr, Binary, Coin, Decimal, Env, MessageInfo, OwnedDeps, StdError, Uint128,
+    from_json, Addr, Binary, Coin, ContractResult, Decimal, Env, MessageInfo, MsgResponse,
+    OwnedDeps, Querier, Reply, StdError, SubMsgResponse, SubMsgResult, SystemResult, Uint128,
+    Uint256,
 };
 use neutron_sdk::interchain_queries::helpers::{
     decode_and_convert, decode_hex, kv_key_from_string,
@@ -54,10 +70,15 @@ use neutron_sdk::interchain_queries::v047::types::{
 use neutron_sdk::sudo::msg::Height as QueriesHeight;
 use neutron_sdk::NeutronError;
 use neutron_std::types::cosmos::base::v1beta1::Coin as StdCoin;
+use neutron_std::types::ibc::applications::transfer::v1::MsgTransfer;
+use neutron_std::types::ibc::core::channel::v1::{MsgRecvPacket, Packet};
 use neutron_std::types::ibc::core::client::v1::Height;
 use neutron_std::types::neutron::interchainqueries::{
-    KvKey, QueryRegisteredQueryResponse, QueryRegisteredQueryResultResponse, QueryResult,
-    RegisteredQuery, StorageValue,
+    KvKey, MsgRegisterInterchainQueryResponse, QueryRegisteredQueryResponse,
+    QueryRegisteredQueryResultResponse, QueryResult, RegisteredQuery, StorageValue,
+};
+use neutron_test_support::{
+    proto, ContractWrapper, Delivery, MockIcq, NeutronApp, NeutronMockQuerier,
 };
 use prost::Message as ProstMessage;
 use schemars::_serde_json::to_string;
@@ -292,8 +313,8 @@ fn build_interchain_query_balances_response(addr: Addr, balances: Vec<Coin>) ->
 }
 
 // registers an interchain query
-fn register_query(
-    deps: &mut OwnedDeps<MockStorage, MockApi, WasmMockQuerier>,
+fn register_query<Q: Querier>(
+    deps: &mut OwnedDeps<MockStorage, MockApi, Q>,
     env: Env,
     info: MessageInfo,
     msg: ExecuteMsg,
@@ -1028,6 +1049,30 @@ fn test_query_delegator_delegations() {
     )
 }
 
+/// hash of the tx to watched_addr used in the transfers query tests
+const TX_HASH: &str = "8B0F05EED9D5BEC8F50020BEC878D48739DE0B75D7961F8198C8EA409C953353";
+
+fn recipient_txs(
+    deps: &OwnedDeps<MockStorage, MockApi, WasmMockQuerier>,
+    recipient: &str,
+) -> Vec<Transfer> {
+    let resp: GetRecipientTxsResponse = from_json(
+        query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetRecipientTxs {
+                recipient: recipient.to_string(),
+                start_after: None,
+                limit: None,
+                filter: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    resp.transfers
+}
+
 #[test]
 fn test_sudo_tx_query_result_callback() {
     let mut deps = dependencies(&[]);
@@ -1072,14 +1117,19 @@ fn test_sudo_tx_query_result_callback() {
     sudo_tx_query_result(deps.as_mut(), env.clone(), query_id, height.clone(), data).unwrap();
 
     // ensure the callback has worked and contract's state has changed
-    let txs = RECIPIENT_TXS.load(&deps.storage, &watched_addr).unwrap();
+    let txs = recipient_txs(&deps, &watched_addr);
     assert_eq!(
         txs,
         Vec::from([Transfer {
             recipient: watched_addr.clone(),
             sender: "neutron10h9stc5v6ntgeygf5xf945njqq5h32r54rf7kf".to_string(),
             denom: "stake".to_string(),
-            amount: "10000".to_string(),
+            amount: Uint256::from(10000u128),
+            height: 1,
+            tx_hash: TX_HASH.to_string(),
+            index: 0,
+            coin_index: 0,
+            source: TransferSource::Send,
         }])
     );
 
@@ -1095,41 +1145,53 @@ fn test_sudo_tx_query_result_callback() {
             "failed to find a matching transaction message",
         ))
     );
-    let txs = RECIPIENT_TXS.load(&deps.storage, &watched_addr).unwrap();
+    let txs = recipient_txs(&deps, &watched_addr);
     assert_eq!(
         txs,
         Vec::from([Transfer {
             recipient: watched_addr.clone(),
             sender: "neutron10h9stc5v6ntgeygf5xf945njqq5h32r54rf7kf".to_string(),
             denom: "stake".to_string(),
-            amount: "10000".to_string(),
+            amount: Uint256::from(10000u128),
+            height: 1,
+            tx_hash: TX_HASH.to_string(),
+            index: 0,
+            coin_index: 0,
+            source: TransferSource::Send,
         }])
     );
 
-    // simulate neutron's SudoTxQueryResult call with the following payload:
+    // simulate neutron's SudoTxQueryResult call with the same payload once again:
     // a sending from neutron10h9stc5v6ntgeygf5xf945njqq5h32r54rf7kf to watched_addr of 10000 stake
     let data: Binary = Binary::from(BASE64_STANDARD.decode("CpMBCpABChwvY29zbW9zLmJhbmsudjFiZXRhMS5Nc2dTZW5kEnAKLm5ldXRyb24xMGg5c3RjNXY2bnRnZXlnZjV4Zjk0NW5qcXE1aDMycjU0cmY3a2YSLm5ldXRyb24xZmo2eXFya3B3NmZtcDdmN2poajU3ZHVqZnB3YWw0bTI1ZGFmengaDgoFc3Rha2USBTEwMDAwEmcKUApGCh8vY29zbW9zLmNyeXB0by5zZWNwMjU2azEuUHViS2V5EiMKIQJPYibh+Zef13ZkulPqI27rV5xswZ0H/vh1Tnymp1RHPhIECgIIARgAEhMKDQoFc3Rha2USBDEwMDAQwJoMGkAIiXNJXmA57KhyaWpKcLLr3602A5+hlvv/b4PgcDDm9y0qikC+biNZXin1dEMpHOvX9DwOWJ9utv6EKljiSyfT").unwrap());
-    sudo_tx_query_result(deps.as_mut(), env, query_id, height, data).unwrap();
+    let res = sudo_tx_query_result(deps.as_mut(), env, query_id, height, data);
 
-    // ensure the callback has worked and contract's state has changed again
-    let txs = RECIPIENT_TXS.load(&deps.storage, &watched_addr).unwrap();
+    // ensure the resubmitted tx has been rejected and hasn't been counted twice
+    assert_eq!(
+        res.unwrap_err(),
+        NeutronError::Std(StdError::generic_err(
+            "transaction has already been processed",
+        ))
+    );
+    let txs = recipient_txs(&deps, &watched_addr);
     assert_eq!(
         txs,
-        Vec::from([
-            Transfer {
-                recipient: watched_addr.clone(),
-                sender: "neutron10h9stc5v6ntgeygf5xf945njqq5h32r54rf7kf".to_string(),
-                denom: "stake".to_string(),
-                amount: "10000".to_string(),
-            },
-            Transfer {
-                recipient: watched_addr,
-                sender: "neutron10h9stc5v6ntgeygf5xf945njqq5h32r54rf7kf".to_string(),
-                denom: "stake".to_string(),
-                amount: "10000".to_string(),
-            }
-        ])
+        Vec::from([Transfer {
+            recipient: watched_addr.clone(),
+            sender: "neutron10h9stc5v6ntgeygf5xf945njqq5h32r54rf7kf".to_string(),
+            denom: "stake".to_string(),
+            amount: Uint256::from(10000u128),
+            height: 1,
+            tx_hash: TX_HASH.to_string(),
+            index: 0,
+            coin_index: 0,
+            source: TransferSource::Send,
+        }])
     );
+    let resp: GetTransfersAmountResponse =
+        from_json(query(deps.as_ref(), mock_env(), QueryMsg::GetTransfersNumber {}).unwrap())
+            .unwrap();
+    assert_eq!(resp.transfers_number, 1);
 }
 
 #[test]
@@ -1176,14 +1238,19 @@ fn test_sudo_tx_query_result_min_height_callback() {
     sudo_tx_query_result(deps.as_mut(), env.clone(), query_id, height.clone(), data).unwrap();
 
     // ensure the callback has worked and contract's state has changed
-    let txs = RECIPIENT_TXS.load(&deps.storage, &watched_addr).unwrap();
+    let txs = recipient_txs(&deps, &watched_addr);
     assert_eq!(
         txs,
         Vec::from([Transfer {
             recipient: watched_addr.clone(),
             sender: "neutron10h9stc5v6ntgeygf5xf945njqq5h32r54rf7kf".to_string(),
             denom: "stake".to_string(),
-            amount: "10000".to_string(),
+            amount: Uint256::from(10000u128),
+            height: 1,
+            tx_hash: TX_HASH.to_string(),
+            index: 0,
+            coin_index: 0,
+            source: TransferSource::Send,
         }])
     );
 
@@ -1199,39 +1266,1852 @@ fn test_sudo_tx_query_result_min_height_callback() {
             "failed to find a matching transaction message",
         ))
     );
-    let txs = RECIPIENT_TXS.load(&deps.storage, &watched_addr).unwrap();
+    let txs = recipient_txs(&deps, &watched_addr);
     assert_eq!(
         txs,
         Vec::from([Transfer {
             recipient: watched_addr.clone(),
             sender: "neutron10h9stc5v6ntgeygf5xf945njqq5h32r54rf7kf".to_string(),
             denom: "stake".to_string(),
-            amount: "10000".to_string(),
+            amount: Uint256::from(10000u128),
+            height: 1,
+            tx_hash: TX_HASH.to_string(),
+            index: 0,
+            coin_index: 0,
+            source: TransferSource::Send,
         }])
     );
 
-    // simulate neutron's SudoTxQueryResult call with the following payload:
+    // simulate neutron's SudoTxQueryResult call with the same payload once again:
     // a sending from neutron10h9stc5v6ntgeygf5xf945njqq5h32r54rf7kf to watched_addr of 10000 stake
     let data: Binary = Binary::from(BASE64_STANDARD.decode("CpMBCpABChwvY29zbW9zLmJhbmsudjFiZXRhMS5Nc2dTZW5kEnAKLm5ldXRyb24xMGg5c3RjNXY2bnRnZXlnZjV4Zjk0NW5qcXE1aDMycjU0cmY3a2YSLm5ldXRyb24xZmo2eXFya3B3NmZtcDdmN2poajU3ZHVqZnB3YWw0bTI1ZGFmengaDgoFc3Rha2USBTEwMDAwEmcKUApGCh8vY29zbW9zLmNyeXB0by5zZWNwMjU2azEuUHViS2V5EiMKIQJPYibh+Zef13ZkulPqI27rV5xswZ0H/vh1Tnymp1RHPhIECgIIARgAEhMKDQoFc3Rha2USBDEwMDAQwJoMGkAIiXNJXmA57KhyaWpKcLLr3602A5+hlvv/b4PgcDDm9y0qikC+biNZXin1dEMpHOvX9DwOWJ9utv6EKljiSyfT").unwrap());
-    sudo_tx_query_result(deps.as_mut(), env, query_id, height, data).unwrap();
+    let res = sudo_tx_query_result(deps.as_mut(), env, query_id, height, data);
 
-    // ensure the callback has worked and contract's state has changed again
-    let txs = RECIPIENT_TXS.load(&deps.storage, &watched_addr).unwrap();
+    // ensure the resubmitted tx has been rejected and hasn't been counted twice
+    assert_eq!(
+        res.unwrap_err(),
+        NeutronError::Std(StdError::generic_err(
+            "transaction has already been processed",
+        ))
+    );
+    let txs = recipient_txs(&deps, &watched_addr);
     assert_eq!(
         txs,
-        Vec::from([
-            Transfer {
-                recipient: watched_addr.clone(),
-                sender: "neutron10h9stc5v6ntgeygf5xf945njqq5h32r54rf7kf".to_string(),
-                denom: "stake".to_string(),
-                amount: "10000".to_string(),
+        Vec::from([Transfer {
+            recipient: watched_addr.clone(),
+            sender: "neutron10h9stc5v6ntgeygf5xf945njqq5h32r54rf7kf".to_string(),
+            denom: "stake".to_string(),
+            amount: Uint256::from(10000u128),
+            height: 1,
+            tx_hash: TX_HASH.to_string(),
+            index: 0,
+            coin_index: 0,
+            source: TransferSource::Send,
+        }])
+    );
+    let resp: GetTransfersAmountResponse =
+        from_json(query(deps.as_ref(), mock_env(), QueryMsg::GetTransfersNumber {}).unwrap())
+            .unwrap();
+    assert_eq!(resp.transfers_number, 1);
+}
+
+// sets the remote height of the last submitted result of the registered query
+fn with_remote_height(registered_query: Binary, height: u64) -> Binary {
+    let mut resp = QueryRegisteredQueryResponse::decode(registered_query.as_slice()).unwrap();
+    if let Some(query) = resp.registered_query.as_mut() {
+        query.last_submitted_result_remote_height = Some(Height {
+            revision_number: 0,
+            revision_height: height,
+        });
+    }
+    Binary::from(resp.encode_to_vec())
+}
+
+#[test]
+fn test_sudo_kv_query_result_history() {
+    let mut deps = dependencies(&[]);
+    let addr = "osmo1yz54ncxj9csp7un3xled03q6thrrhy9cztkfzs";
+
+    let msg = ExecuteMsg::RegisterBalancesQuery {
+        connection_id: "connection".to_string(),
+        update_period: 10,
+        addr: addr.to_string(),
+        denoms: vec!["uosmo".to_string()],
+    };
+    let keys = register_query(
+        &mut deps,
+        mock_env(),
+        message_info(&Addr::unchecked(""), &[]),
+        msg,
+    );
+    let registered_query =
+        build_registered_query_response(1, QueryParam::Keys(keys), QueryType::KV, 987);
+
+    // two results at remote heights 100 and 200
+    for (remote_height, amount) in [(100u64, 100u128), (200, 200)] {
+        deps.querier.add_registered_queries(
+            1,
+            with_remote_height(registered_query.clone(), remote_height),
+        );
+        deps.querier.add_query_response(
+            1,
+            build_interchain_query_balances_response(
+                Addr::unchecked(addr),
+                vec![Coin::new(amount, "uosmo")],
+            ),
+        );
+        let mut env = mock_env();
+        env.block.height = remote_height + 1;
+        sudo_kv_query_result(deps.as_mut(), env, 1).unwrap();
+    }
+
+    let balance_at = |deps: &OwnedDeps<MockStorage, MockApi, WasmMockQuerier>, height| {
+        query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::BalanceAt {
+                query_id: 1,
+                height,
             },
-            Transfer {
-                recipient: watched_addr,
-                sender: "neutron10h9stc5v6ntgeygf5xf945njqq5h32r54rf7kf".to_string(),
-                denom: "stake".to_string(),
-                amount: "10000".to_string(),
+        )
+        .map(|res| from_json::<BalanceResponse>(res).unwrap().balances.coins)
+    };
+    assert!(balance_at(&deps, 99).is_err());
+    assert_eq!(
+        balance_at(&deps, 150).unwrap(),
+        vec![Coin::new(100u128, "uosmo")]
+    );
+    assert_eq!(
+        balance_at(&deps, 200).unwrap(),
+        vec![Coin::new(200u128, "uosmo")]
+    );
+
+    let history: Vec<KvSnapshot> = from_json(
+        query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::History {
+                query_id: 1,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        history
+            .iter()
+            .map(|s| (s.remote_height, s.local_height))
+            .collect::<Vec<_>>(),
+        vec![(200, 201), (100, 101)]
+    );
+    assert_eq!(
+        history[0].result,
+        KvResult::Balance(BalanceResponse {
+            last_submitted_local_height: 987,
+            balances: Balances {
+                coins: vec![Coin::new(200u128, "uosmo")]
+            },
+        })
+    );
+}
+
+#[test]
+fn test_max_result_age() {
+    let mut deps = neutron_test_support::mock_dependencies(&[]);
+    deps.querier.update_wasm(|_| {
+        SystemResult::Ok(ContractResult::Ok(Binary::from(
+            br#"{"code_id":1,"creator":"creator","admin":"admin","pinned":false,"ibc_port":null}"#
+                .as_slice(),
+        )))
+    });
+    let addr = "osmo1yz54ncxj9csp7un3xled03q6thrrhy9cztkfzs";
+
+    let msg = ExecuteMsg::RegisterBalancesQuery {
+        connection_id: "connection".to_string(),
+        update_period: 10,
+        addr: addr.to_string(),
+        denoms: vec!["uosmo".to_string()],
+    };
+    let keys = register_query(
+        &mut deps,
+        mock_env(),
+        message_info(&Addr::unchecked(""), &[]),
+        msg,
+    );
+    let registered_query = with_remote_height(
+        build_registered_query_response(1, QueryParam::Keys(keys), QueryType::KV, 987),
+        100,
+    );
+    let mut registered_query = QueryRegisteredQueryResponse::decode(registered_query.as_slice())
+        .unwrap()
+        .registered_query
+        .unwrap();
+    registered_query.connection_id = "connection".to_string();
+    deps.querier.add_registered_query(registered_query);
+    let query_result = build_interchain_query_balances_response(
+        Addr::unchecked(addr),
+        vec![Coin::new(100u128, "uosmo")],
+    );
+    deps.querier.set_query_result(
+        1,
+        QueryRegisteredQueryResultResponse::decode(query_result.as_slice())
+            .unwrap()
+            .result
+            .unwrap(),
+    );
+    // the light client of the connection knows the remote chain at 200
+    deps.querier.set_connection_client(
+        "connection",
+        "07-tendermint-0",
+        Height {
+            revision_number: 0,
+            revision_height: 200,
+        },
+    );
+
+    let set_max_age = |deps: &mut OwnedDeps<MockStorage, MockApi, NeutronMockQuerier>,
+                       sender: &str,
+                       remote_blocks,
+                       local_blocks| {
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&Addr::unchecked(sender), &[]),
+            ExecuteMsg::SetMaxResultAge {
+                query_id: 1,
+                remote_blocks,
+                local_blocks,
+            },
+        )
+    };
+    let query_balance = |deps: &OwnedDeps<MockStorage, MockApi, NeutronMockQuerier>| {
+        query(deps.as_ref(), mock_env(), QueryMsg::Balance { query_id: 1 })
+    };
+
+    // only the admin can make the reads fail
+    set_max_age(&mut deps, "someone", None, Some(100)).unwrap_err();
+    query_balance(&deps).unwrap();
+
+    // the result has been submitted at 987, mock_env() is at 12345
+    set_max_age(&mut deps, "admin", None, Some(20000)).unwrap();
+    query_balance(&deps).unwrap();
+    set_max_age(&mut deps, "admin", None, Some(100)).unwrap();
+    assert_eq!(
+        query_balance(&deps).unwrap_err(),
+        ContractError::StaleResult {
+            query_id: 1,
+            remote_height: 100,
+            local_height: 987,
+        }
+    );
+    // a KV callback counts as an update
+    sudo_kv_query_result(deps.as_mut(), mock_env(), 1).unwrap();
+    query_balance(&deps).unwrap();
+
+    // the result is 100 blocks behind the remote chain
+    set_max_age(&mut deps, "admin", Some(150), None).unwrap();
+    query_balance(&deps).unwrap();
+    set_max_age(&mut deps, "admin", Some(50), None).unwrap();
+    assert_eq!(
+        query_balance(&deps).unwrap_err(),
+        ContractError::StaleResult {
+            query_id: 1,
+            remote_height: 100,
+            local_height: 12345,
+        }
+    );
+
+    // removing the limits disables the check
+    set_max_age(&mut deps, "admin", None, None).unwrap();
+    query_balance(&deps).unwrap();
+}
+
+#[allow(deprecated)]
+fn build_register_query_reply(query_id: u64, payload: Binary) -> Reply {
+    Reply {
+        id: 1,
+        payload,
+        gas_used: 0,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: None,
+            msg_responses: vec![MsgResponse {
+                type_url: "/neutron.interchainqueries.MsgRegisterInterchainQueryResponse"
+                    .to_string(),
+                value: Binary::from(
+                    MsgRegisterInterchainQueryResponse { id: query_id }.encode_to_vec(),
+                ),
+            }],
+        }),
+    }
+}
+
+fn build_ibc_transfer_tx(transfers: Vec<(&str, &str, u128)>) -> Binary {
+    let body = TxBody {
+        messages: transfers
+            .into_iter()
+            .map(|(sender, receiver, amount)| Any {
+                type_url: "/ibc.applications.transfer.v1.MsgTransfer".to_string(),
+                value: MsgTransfer {
+                    source_port: "transfer".to_string(),
+                    source_channel: "channel-0".to_string(),
+                    token: Some(StdCoin {
+                        denom: "uatom".to_string(),
+                        amount: amount.to_string(),
+                    }),
+                    sender: sender.to_string(),
+                    receiver: receiver.to_string(),
+                    timeout_height: None,
+                    timeout_timestamp: 0,
+                    memo: "".to_string(),
+                }
+                .encode_to_vec(),
+            })
+            .collect(),
+        ..Default::default()
+    };
+    let tx = TxRaw {
+        body_bytes: body.encode_to_vec(),
+        ..Default::default()
+    };
+    Binary::from(tx.encode_to_vec())
+}
+
+#[test]
+fn test_sudo_tx_query_result_ibc_transfers() {
+    let mut deps = dependencies(&[]);
+    let env = mock_env();
+    let sender = "cosmos1yz54ncxj9csp7un3xled03q6thrrhy9cztkfzs";
+    let height = QueriesHeight {
+        revision_number: 0,
+        revision_height: 1,
+    };
+
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&Addr::unchecked(""), &[]),
+        ExecuteMsg::RegisterIbcTransfersQuery {
+            connection_id: "connection".to_string(),
+            update_period: 1,
+            sender: sender.to_string(),
+            min_height: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(res.messages[0].id, 1);
+    deps.querier.add_registered_queries(
+        1,
+        build_registered_query_response(
+            1,
+            QueryParam::TransactionsFilter("[]".to_string()),
+            QueryType::TX,
+            0,
+        ),
+    );
+    reply(
+        deps.as_mut(),
+        env.clone(),
+        build_register_query_reply(1, res.messages[0].payload.clone()),
+    )
+    .unwrap();
+    let handler: Option<TxFilterHandler> = from_json(
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::TxQueryHandler { query_id: 1 },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        handler,
+        Some(TxFilterHandler::IbcTransfers {
+            sender: sender.to_string()
+        })
+    );
+
+    // only the transfers of the watched sender are stored
+    let tx = build_ibc_transfer_tx(vec![
+        ("cosmos1other", "osmo1receiver", 200),
+        (sender, "osmo1receiver", 100),
+    ]);
+    sudo_tx_query_result(deps.as_mut(), env.clone(), 1, height.clone(), tx.clone()).unwrap();
+    // a resubmitted tx is rejected
+    assert_eq!(
+        sudo_tx_query_result(deps.as_mut(), env.clone(), 1, height.clone(), tx).unwrap_err(),
+        NeutronError::Std(StdError::generic_err(
+            "transaction has already been processed",
+        ))
+    );
+    // the transfers aren't queued, a tx with too many messages is rejected
+    assert_eq!(
+        sudo_tx_query_result(
+            deps.as_mut(),
+            env.clone(),
+            1,
+            height.clone(),
+            build_ibc_transfer_tx(vec![(sender, "osmo1receiver", 1); 21]),
+        )
+        .unwrap_err(),
+        NeutronError::Std(StdError::generic_err(
+            "transaction has 21 messages, at most 20 can be processed",
+        ))
+    );
+    // a tx without transfers of the sender is rejected
+    assert_eq!(
+        sudo_tx_query_result(
+            deps.as_mut(),
+            env.clone(),
+            1,
+            height,
+            build_ibc_transfer_tx(vec![("cosmos1other", "osmo1receiver", 300)]),
+        )
+        .unwrap_err(),
+        NeutronError::Std(StdError::generic_err(
+            "failed to find a matching transaction message",
+        ))
+    );
+
+    let resp: GetIbcTransfersResponse = from_json(
+        query(
+            deps.as_ref(),
+            env,
+            QueryMsg::GetIbcTransfers {
+                sender: sender.to_string(),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        resp.transfers,
+        vec![IbcTransfer {
+            sender: sender.to_string(),
+            receiver: "osmo1receiver".to_string(),
+            source_channel: "channel-0".to_string(),
+            denom: "uatom".to_string(),
+            amount: "100".to_string(),
+            height: 1,
+            tx_hash: resp.transfers[0].tx_hash.clone(),
+            // the index of the message in the tx
+            index: 1,
+        }]
+    );
+}
+
+fn build_send_tx(from: &str, to: &str, coins: Vec<(&str, u128)>) -> Binary {
+    let body = TxBody {
+        messages: vec![Any {
+            type_url: "/cosmos.bank.v1beta1.MsgSend".to_string(),
+            value: MsgSend {
+                from_address: from.to_string(),
+                to_address: to.to_string(),
+                amount: coins
+                    .into_iter()
+                    .map(|(denom, amount)| CosmosCoin {
+                        denom: denom.to_string(),
+                        amount: amount.to_string(),
+                    })
+                    .collect(),
             }
-        ])
+            .encode_to_vec(),
+        }],
+        ..Default::default()
+    };
+    let tx = TxRaw {
+        body_bytes: body.encode_to_vec(),
+        ..Default::default()
+    };
+    Binary::from(tx.encode_to_vec())
+}
+
+#[test]
+fn test_recipient_txs_pagination() {
+    let mut deps = dependencies(&[]);
+    let recipient = "neutron1fj6yqrkpw6fmp7f7jhj57dujfpwal4m25dafzx";
+    let registered_query = build_registered_query_response(
+        1,
+        QueryParam::TransactionsFilter(
+            to_string(&vec![&TransactionFilterItem {
+                field: RECIPIENT_FIELD.to_string(),
+                op: TransactionFilterOp::Eq,
+                value: TransactionFilterValue::String(recipient.to_string()),
+            }])
+            .unwrap(),
+        ),
+        QueryType::TX,
+        0,
+    );
+    deps.querier.add_registered_queries(1, registered_query);
+
+    let txs = [
+        (
+            1u64,
+            "neutron1alice",
+            vec![("stake", 100u128), ("uatom", 5)],
+        ),
+        (2, "neutron1bob", vec![("stake", 200)]),
+        (3, "neutron1alice", vec![("stake", 300)]),
+    ];
+    for (height, sender, coins) in txs {
+        sudo_tx_query_result(
+            deps.as_mut(),
+            mock_env(),
+            1,
+            QueriesHeight {
+                revision_number: 0,
+                revision_height: height,
+            },
+            build_send_tx(sender, recipient, coins),
+        )
+        .unwrap();
+    }
+
+    let get_txs = |start_after: Option<TransferCursor>, limit, filter| {
+        let resp: GetRecipientTxsResponse = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::GetRecipientTxs {
+                    recipient: recipient.to_string(),
+                    start_after,
+                    limit,
+                    filter,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        resp.transfers
+            .into_iter()
+            .map(|t| (t.height, t.coin_index, t.sender, t.denom, t.amount))
+            .collect::<Vec<_>>()
+    };
+    let transfer = |height: u64, coin_index: u32, sender: &str, denom: &str, amount: u128| {
+        (
+            height,
+            coin_index,
+            sender.to_string(),
+            denom.to_string(),
+            Uint256::from(amount),
+        )
+    };
+
+    // a MsgSend of two coins makes a transfer per coin
+    let page = get_txs(None, Some(2), None);
+    assert_eq!(
+        page,
+        vec![
+            transfer(1, 0, "neutron1alice", "stake", 100),
+            transfer(1, 1, "neutron1alice", "uatom", 5),
+        ]
+    );
+    let all = recipient_txs(&deps, recipient);
+    let last = &all[1];
+    let page = get_txs(
+        Some(TransferCursor {
+            height: last.height,
+            tx_hash: last.tx_hash.clone(),
+            index: last.index,
+            coin_index: last.coin_index,
+        }),
+        Some(2),
+        None,
+    );
+    assert_eq!(
+        page,
+        vec![
+            transfer(2, 0, "neutron1bob", "stake", 200),
+            transfer(3, 0, "neutron1alice", "stake", 300),
+        ]
+    );
+
+    let page = get_txs(
+        None,
+        None,
+        Some(TransferFilter {
+            sender: Some("neutron1alice".to_string()),
+            denom: Some("stake".to_string()),
+            ..Default::default()
+        }),
+    );
+    assert_eq!(
+        page,
+        vec![
+            transfer(1, 0, "neutron1alice", "stake", 100),
+            transfer(3, 0, "neutron1alice", "stake", 300),
+        ]
+    );
+    let page = get_txs(
+        None,
+        None,
+        Some(TransferFilter {
+            min_height: Some(2),
+            max_height: Some(2),
+            ..Default::default()
+        }),
+    );
+    assert_eq!(page, vec![transfer(2, 0, "neutron1bob", "stake", 200)]);
+
+    let resp: GetRecipientTotalsResponse = from_json(
+        query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetRecipientTotals {
+                recipient: recipient.to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        resp.totals,
+        vec![
+            TransferTotal {
+                denom: "stake".to_string(),
+                amount: Uint256::from(600u128),
+            },
+            TransferTotal {
+                denom: "uatom".to_string(),
+                amount: Uint256::from(5u128),
+            },
+        ]
     );
 }
+
+#[test]
+fn test_transfer_limits() {
+    let mut deps = dependencies(&[]);
+    deps.querier.update_wasm(|_| {
+        SystemResult::Ok(ContractResult::Ok(Binary::from(
+            br#"{"code_id":1,"creator":"creator","admin":"admin","pinned":false,"ibc_port":null}"#
+                .as_slice(),
+        )))
+    });
+    let recipient = "neutron1fj6yqrkpw6fmp7f7jhj57dujfpwal4m25dafzx";
+    let registered_query = build_registered_query_response(
+        1,
+        QueryParam::TransactionsFilter(
+            to_string(&vec![&TransactionFilterItem {
+                field: RECIPIENT_FIELD.to_string(),
+                op: TransactionFilterOp::Eq,
+                value: TransactionFilterValue::String(recipient.to_string()),
+            }])
+            .unwrap(),
+        ),
+        QueryType::TX,
+        0,
+    );
+    deps.querier.add_registered_queries(1, registered_query);
+
+    let set_limit = ExecuteMsg::SetTransferLimit {
+        denom: "stake".to_string(),
+        max_amount: Some(Uint256::from(1000u128)),
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        message_info(&Addr::unchecked("stranger"), &[]),
+        set_limit.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        NeutronError::Std(StdError::generic_err(
+            "only the contract admin can set transfer limits"
+        ))
+    );
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        message_info(&Addr::unchecked("admin"), &[]),
+        set_limit,
+    )
+    .unwrap();
+    let limit: Option<Uint256> = from_json(
+        query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::TransferLimit {
+                denom: "stake".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(limit, Some(Uint256::from(1000u128)));
+
+    // amounts over u64 are fine for denoms without a limit
+    let txs = [
+        (1u64, vec![("stake", 500u128)]),
+        (2, vec![("stake", 5000)]),
+        (3, vec![("uatom", u128::MAX)]),
+    ];
+    for (height, coins) in txs {
+        sudo_tx_query_result(
+            deps.as_mut(),
+            mock_env(),
+            1,
+            QueriesHeight {
+                revision_number: 0,
+                revision_height: height,
+            },
+            build_send_tx("neutron1alice", recipient, coins),
+        )
+        .unwrap();
+    }
+
+    let flagged: GetRecipientTxsResponse = from_json(
+        query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetFlaggedTxs {
+                recipient: recipient.to_string(),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(flagged.transfers.len(), 1);
+    assert_eq!(flagged.transfers[0].height, 2);
+    assert_eq!(flagged.transfers[0].amount, Uint256::from(5000u128));
+
+    let resp: GetRecipientTotalsResponse = from_json(
+        query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetRecipientTotals {
+                recipient: recipient.to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        resp.totals,
+        vec![
+            TransferTotal {
+                denom: "stake".to_string(),
+                amount: Uint256::from(500u128),
+            },
+            TransferTotal {
+                denom: "uatom".to_string(),
+                amount: Uint256::from(u128::MAX),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_process_pending_deposits() {
+    let mut deps = dependencies(&[]);
+    let recipient = "neutron1fj6yqrkpw6fmp7f7jhj57dujfpwal4m25dafzx";
+    let registered_query = build_registered_query_response(
+        1,
+        QueryParam::TransactionsFilter(
+            to_string(&vec![&TransactionFilterItem {
+                field: RECIPIENT_FIELD.to_string(),
+                op: TransactionFilterOp::Eq,
+                value: TransactionFilterValue::String(recipient.to_string()),
+            }])
+            .unwrap(),
+        ),
+        QueryType::TX,
+        0,
+    );
+    deps.querier.add_registered_queries(1, registered_query);
+
+    // 25 MsgSend of 1, 2, ..., 25 stake, 5 of them are left for ProcessPending
+    let body = TxBody {
+        messages: (1..=25u128)
+            .map(|amount| Any {
+                type_url: "/cosmos.bank.v1beta1.MsgSend".to_string(),
+                value: MsgSend {
+                    from_address: "neutron1alice".to_string(),
+                    to_address: recipient.to_string(),
+                    amount: vec![CosmosCoin {
+                        denom: "stake".to_string(),
+                        amount: amount.to_string(),
+                    }],
+                }
+                .encode_to_vec(),
+            })
+            .collect(),
+        ..Default::default()
+    };
+    let tx = Binary::from(
+        TxRaw {
+            body_bytes: body.encode_to_vec(),
+            ..Default::default()
+        }
+        .encode_to_vec(),
+    );
+    let height = QueriesHeight {
+        revision_number: 0,
+        revision_height: 1,
+    };
+    sudo_tx_query_result(deps.as_mut(), mock_env(), 1, height.clone(), tx.clone()).unwrap();
+
+    let transfers_number = |deps: &OwnedDeps<MockStorage, MockApi, WasmMockQuerier>| {
+        let resp: GetTransfersAmountResponse =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::GetTransfersNumber {}).unwrap())
+                .unwrap();
+        resp.transfers_number
+    };
+    let pending_deposits = |deps: &OwnedDeps<MockStorage, MockApi, WasmMockQuerier>| {
+        let resp: GetPendingDepositsResponse = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::GetPendingDeposits { limit: None },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        resp.txs
+    };
+    assert_eq!(transfers_number(&deps), 20);
+    let pending = pending_deposits(&deps);
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].height, 1);
+    assert_eq!(pending[0].messages, 5);
+
+    // the pending tx can't be submitted again
+    let err = sudo_tx_query_result(deps.as_mut(), mock_env(), 1, height, tx).unwrap_err();
+    assert_eq!(
+        err,
+        NeutronError::Std(StdError::generic_err(
+            "transaction has already been processed"
+        ))
+    );
+
+    let process = |deps: &mut OwnedDeps<MockStorage, MockApi, WasmMockQuerier>, limit| {
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&Addr::unchecked("anyone"), &[]),
+            ExecuteMsg::ProcessPending { limit },
+        )
+        .unwrap()
+    };
+    process(&mut deps, Some(3));
+    assert_eq!(transfers_number(&deps), 23);
+    assert_eq!(
+        pending_deposits(&deps),
+        vec![PendingTx {
+            height: 1,
+            tx_hash: pending[0].tx_hash.clone(),
+            messages: 2,
+        }]
+    );
+    process(&mut deps, None);
+    assert_eq!(transfers_number(&deps), 25);
+    assert!(pending_deposits(&deps).is_empty());
+
+    let resp: GetRecipientTxsResponse = from_json(
+        query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetRecipientTxs {
+                recipient: recipient.to_string(),
+                start_after: None,
+                limit: Some(100),
+                filter: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        resp.transfers
+            .iter()
+            .map(|t| (t.index, t.amount))
+            .collect::<Vec<_>>(),
+        (0..25u32)
+            .map(|index| (index, Uint256::from(index as u128 + 1)))
+            .collect::<Vec<_>>()
+    );
+
+    // nothing is left to process
+    let res = process(&mut deps, None);
+    assert!(res
+        .attributes
+        .iter()
+        .any(|attr| attr.key == "processed_messages" && attr.value == "0"));
+}
+
+fn build_recv_packet(
+    source_channel: &str,
+    sequence: u64,
+    denom: &str,
+    amount: &str,
+    receiver: &str,
+) -> Any {
+    Any {
+        type_url: "/ibc.core.channel.v1.MsgRecvPacket".to_string(),
+        value: MsgRecvPacket {
+            packet: Some(Packet {
+                sequence,
+                source_port: "transfer".to_string(),
+                source_channel: source_channel.to_string(),
+                destination_port: "transfer".to_string(),
+                destination_channel: "channel-0".to_string(),
+                data: format!(
+                    r#"{{"denom":"{}","amount":"{}","sender":"osmo1sender","receiver":"{}"}}"#,
+                    denom, amount, receiver
+                )
+                .into_bytes(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+        .encode_to_vec(),
+    }
+}
+
+#[test]
+fn test_multi_send_and_recv_packet_deposits() {
+    let mut deps = dependencies(&[]);
+    let recipient = "neutron1fj6yqrkpw6fmp7f7jhj57dujfpwal4m25dafzx";
+    let registered_query = build_registered_query_response(
+        1,
+        QueryParam::TransactionsFilter(
+            to_string(&vec![&TransactionFilterItem {
+                field: RECIPIENT_FIELD.to_string(),
+                op: TransactionFilterOp::Eq,
+                value: TransactionFilterValue::String(recipient.to_string()),
+            }])
+            .unwrap(),
+        ),
+        QueryType::TX,
+        0,
+    );
+    deps.querier.add_registered_queries(1, registered_query);
+
+    let coin = |denom: &str, amount: &str| CosmosCoin {
+        denom: denom.to_string(),
+        amount: amount.to_string(),
+    };
+    let body = TxBody {
+        messages: vec![
+            Any {
+                type_url: "/cosmos.bank.v1beta1.MsgMultiSend".to_string(),
+                value: MsgMultiSend {
+                    inputs: vec![Input {
+                        address: "neutron1alice".to_string(),
+                        coins: vec![coin("stake", "15"), coin("uatom", "3")],
+                    }],
+                    outputs: vec![
+                        Output {
+                            address: recipient.to_string(),
+                            coins: vec![coin("stake", "10"), coin("uatom", "3")],
+                        },
+                        Output {
+                            address: "neutron1bob".to_string(),
+                            coins: vec![coin("stake", "5")],
+                        },
+                    ],
+                }
+                .encode_to_vec(),
+            },
+            // uosmo sent from the counterparty chain gets an IBC denom
+            build_recv_packet("channel-7", 1, "uosmo", "42", recipient),
+            // stake sent back to its chain gets its native denom
+            build_recv_packet("channel-7", 2, "transfer/channel-7/stake", "7", recipient),
+            build_recv_packet("channel-7", 3, "uosmo", "1", "neutron1bob"),
+        ],
+        ..Default::default()
+    };
+    let tx = TxRaw {
+        body_bytes: body.encode_to_vec(),
+        ..Default::default()
+    };
+    sudo_tx_query_result(
+        deps.as_mut(),
+        mock_env(),
+        1,
+        QueriesHeight {
+            revision_number: 0,
+            revision_height: 1,
+        },
+        Binary::from(tx.encode_to_vec()),
+    )
+    .unwrap();
+
+    assert_eq!(
+        recipient_txs(&deps, recipient)
+            .into_iter()
+            .map(|t| (t.index, t.coin_index, t.sender, t.denom, t.amount, t.source))
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                0,
+                0,
+                "neutron1alice".to_string(),
+                "stake".to_string(),
+                Uint256::from(10u128),
+                TransferSource::MultiSend
+            ),
+            (
+                0,
+                1,
+                "neutron1alice".to_string(),
+                "uatom".to_string(),
+                Uint256::from(3u128),
+                TransferSource::MultiSend
+            ),
+            (
+                1,
+                0,
+                "osmo1sender".to_string(),
+                "ibc/ED07A3391A112B175915CD8FAF43A2DA8E4790EDE12566649D0C2F97716B8518".to_string(),
+                Uint256::from(42u128),
+                TransferSource::IbcRecv {
+                    destination_channel: "channel-0".to_string(),
+                    sequence: 1
+                }
+            ),
+            (
+                2,
+                0,
+                "osmo1sender".to_string(),
+                "stake".to_string(),
+                Uint256::from(7u128),
+                TransferSource::IbcRecv {
+                    destination_channel: "channel-0".to_string(),
+                    sequence: 2
+                }
+            ),
+        ]
+    );
+
+    // a packet relayed again in a later tx isn't credited twice
+    let tx = TxRaw {
+        body_bytes: TxBody {
+            messages: vec![build_recv_packet("channel-7", 1, "uosmo", "42", recipient)],
+            ..Default::default()
+        }
+        .encode_to_vec(),
+        ..Default::default()
+    };
+    let err = sudo_tx_query_result(
+        deps.as_mut(),
+        mock_env(),
+        1,
+        QueriesHeight {
+            revision_number: 0,
+            revision_height: 2,
+        },
+        Binary::from(tx.encode_to_vec()),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        NeutronError::Std(StdError::generic_err(
+            "transaction has already been processed"
+        ))
+    );
+    assert_eq!(recipient_txs(&deps, recipient).len(), 4);
+}
+
+#[test]
+fn test_register_queries() {
+    let mut deps = dependencies(&[]);
+    let env = mock_env();
+    let specs = vec![
+        QuerySpec {
+            label: "hub_balance".to_string(),
+            update_period: 10,
+            query: QueryKind::Balances {
+                addr: "cosmos1yz54ncxj9csp7un3xled03q6thrrhy9cztkfzs".to_string(),
+                denoms: vec!["uatom".to_string()],
+            },
+        },
+        QuerySpec {
+            label: "deposits".to_string(),
+            update_period: 5,
+            query: QueryKind::Transfers {
+                recipients: vec!["cosmos1yz54ncxj9csp7un3xled03q6thrrhy9cztkfzs".to_string()],
+                min_height: None,
+            },
+        },
+    ];
+    let register = |deps: &mut OwnedDeps<MockStorage, MockApi, WasmMockQuerier>,
+                    connection_id: &str,
+                    specs: Vec<QuerySpec>| {
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&Addr::unchecked(""), &[]),
+            ExecuteMsg::RegisterQueries {
+                connection_id: connection_id.to_string(),
+                specs,
+            },
+        )
+    };
+
+    let err = register(
+        &mut deps,
+        "connection-0",
+        vec![specs[0].clone(), specs[0].clone()],
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        NeutronError::Std(StdError::generic_err("duplicate query label: hub_balance"))
+    );
+
+    for query_id in 1..=3 {
+        deps.querier.add_registered_queries(
+            query_id,
+            build_registered_query_response(query_id, QueryParam::Keys(vec![]), QueryType::KV, 0),
+        );
+    }
+    let res = register(&mut deps, "connection-0", specs.clone()).unwrap();
+    assert_eq!(res.messages.len(), 2);
+    for (query_id, msg) in (1..).zip(res.messages) {
+        assert_eq!(msg.id, 1);
+        reply(
+            deps.as_mut(),
+            env.clone(),
+            build_register_query_reply(query_id, msg.payload),
+        )
+        .unwrap();
+    }
+
+    let deposits: LabeledQuery = from_json(
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::GetQueryByLabel {
+                label: "deposits".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        deposits,
+        LabeledQuery {
+            query_id: 2,
+            connection_id: "connection-0".to_string(),
+            spec: specs[1].clone(),
+        }
+    );
+    let handler: Option<TxFilterHandler> = from_json(
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::TxQueryHandler { query_id: 2 },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        handler,
+        Some(TxFilterHandler::BankDeposits {
+            recipients: vec!["cosmos1yz54ncxj9csp7un3xled03q6thrrhy9cztkfzs".to_string()]
+        })
+    );
+
+    // registering the label again on a new connection removes the old query
+    let res = register(&mut deps, "connection-1", vec![specs[0].clone()]).unwrap();
+    assert_eq!(res.messages.len(), 2);
+    reply(
+        deps.as_mut(),
+        env.clone(),
+        build_register_query_reply(3, res.messages[1].payload.clone()),
+    )
+    .unwrap();
+
+    let list = |start_after: Option<String>| {
+        let resp: ListQueriesResponse = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::ListQueries {
+                    start_after,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        resp.queries
+            .into_iter()
+            .map(|q| (q.spec.label, q.query_id, q.connection_id))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        list(None),
+        vec![
+            ("deposits".to_string(), 2, "connection-0".to_string()),
+            ("hub_balance".to_string(), 3, "connection-1".to_string()),
+        ]
+    );
+    assert_eq!(
+        list(Some("deposits".to_string())),
+        vec![("hub_balance".to_string(), 3, "connection-1".to_string())]
+    );
+
+    // removing a query frees its label, registering it again doesn't remove the query twice
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        message_info(&Addr::unchecked(""), &[]),
+        ExecuteMsg::RemoveInterchainQuery { query_id: 2 },
+    )
+    .unwrap();
+    query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::GetQueryByLabel {
+            label: "deposits".to_string(),
+        },
+    )
+    .unwrap_err();
+    let res = register(&mut deps, "connection-0", vec![specs[1].clone()]).unwrap();
+    assert_eq!(res.messages.len(), 1);
+}
+
+#[test]
+fn test_query_deposits_lifecycle() {
+    let mut deps = dependencies(&[]);
+    deps.querier.update_wasm(|_| {
+        SystemResult::Ok(ContractResult::Ok(Binary::from(
+            br#"{"code_id":1,"creator":"creator","admin":"admin","pinned":false,"ibc_port":null}"#
+                .as_slice(),
+        )))
+    });
+    let recipient = "neutron1fj6yqrkpw6fmp7f7jhj57dujfpwal4m25dafzx";
+    let mut env = mock_env();
+    let registered_height = env.block.height;
+
+    for query_id in 1..=2 {
+        deps.querier.add_registered_queries(
+            query_id,
+            build_registered_query_response(
+                query_id,
+                QueryParam::TransactionsFilter(
+                    to_string(&vec![&TransactionFilterItem {
+                        field: RECIPIENT_FIELD.to_string(),
+                        op: TransactionFilterOp::Eq,
+                        value: TransactionFilterValue::String(recipient.to_string()),
+                    }])
+                    .unwrap(),
+                ),
+                QueryType::TX,
+                0,
+            ),
+        );
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&Addr::unchecked(""), &[]),
+            ExecuteMsg::RegisterTransfersQuery {
+                connection_id: "connection".to_string(),
+                update_period: 1,
+                recipients: vec![recipient.to_string()],
+                min_height: None,
+            },
+        )
+        .unwrap();
+        reply(
+            deps.as_mut(),
+            env.clone(),
+            build_register_query_reply(query_id, res.messages[0].payload.clone()),
+        )
+        .unwrap();
+    }
+    let deposits_summary = |deps: &OwnedDeps<MockStorage, MockApi, WasmMockQuerier>| {
+        let resp: DepositsSummaryResponse =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::DepositsSummary {}).unwrap())
+                .unwrap();
+        resp
+    };
+    assert_eq!(
+        deposits_summary(&deps),
+        DepositsSummaryResponse {
+            queries: 2,
+            locked: vec![Coin::new(200u128, "stake")],
+        }
+    );
+
+    let set_stale_blocks = ExecuteMsg::SetStaleQueryBlocks { blocks: Some(10) };
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&Addr::unchecked("stranger"), &[]),
+        set_stale_blocks.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        NeutronError::Std(StdError::generic_err(
+            "only the contract admin can set stale query blocks"
+        ))
+    );
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&Addr::unchecked("admin"), &[]),
+        set_stale_blocks,
+    )
+    .unwrap();
+
+    // the second query receives a result, so only the first one gets stale
+    env.block.height = registered_height + 10;
+    sudo_tx_query_result(
+        deps.as_mut(),
+        env.clone(),
+        2,
+        QueriesHeight {
+            revision_number: 0,
+            revision_height: 1,
+        },
+        build_send_tx("neutron1alice", recipient, vec![("stake", 100)]),
+    )
+    .unwrap();
+    env.block.height = registered_height + 15;
+    let mut remove_stale = |start_after, limit| {
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&Addr::unchecked("anyone"), &[]),
+            ExecuteMsg::RemoveStaleQueries { start_after, limit },
+        )
+        .unwrap()
+    };
+    let res = remove_stale(Some(1), None);
+    assert!(res.messages.is_empty());
+    assert!(res
+        .attributes
+        .iter()
+        .any(|attr| attr.key == "last_query_id" && attr.value == "2"));
+    // only the first query is checked
+    let res = remove_stale(None, Some(1));
+    assert_eq!(res.messages.len(), 1);
+    assert!(res
+        .attributes
+        .iter()
+        .any(|attr| attr.key == "removed_query_id" && attr.value == "1"));
+    assert!(res
+        .attributes
+        .iter()
+        .any(|attr| attr.key == "last_query_id" && attr.value == "1"));
+    let tracked_query = |deps: &OwnedDeps<MockStorage, MockApi, WasmMockQuerier>, query_id| {
+        let resp: Option<TrackedQuery> = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::TrackedQuery { query_id },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        resp
+    };
+    assert_eq!(tracked_query(&deps, 1), None);
+    assert_eq!(
+        tracked_query(&deps, 2),
+        Some(TrackedQuery {
+            deposit: vec![Coin::new(100u128, "stake")],
+            registered_height,
+            last_result_height: registered_height + 10,
+        })
+    );
+
+    // the deposit of a removed query isn't locked anymore
+    execute(
+        deps.as_mut(),
+        env,
+        message_info(&Addr::unchecked(""), &[]),
+        ExecuteMsg::RemoveInterchainQuery { query_id: 2 },
+    )
+    .unwrap();
+    assert_eq!(
+        deposits_summary(&deps),
+        DepositsSummaryResponse {
+            queries: 0,
+            locked: vec![],
+        }
+    );
+}
+
+#[test]
+fn test_raw_kv_query() {
+    let mut deps = dependencies(&[]);
+    let env = mock_env();
+    let keys = vec![
+        KvKey {
+            path: "bank".to_string(),
+            key: b"balance".to_vec(),
+        },
+        KvKey {
+            path: "bank".to_string(),
+            key: b"missing".to_vec(),
+        },
+    ];
+    let register = |deps: &mut OwnedDeps<MockStorage, MockApi, WasmMockQuerier>,
+                    decoder: KvDecoder| {
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&Addr::unchecked(""), &[]),
+            ExecuteMsg::RegisterRawKvQuery {
+                connection_id: "connection".to_string(),
+                keys: keys.clone(),
+                update_period: 1,
+                decoder,
+            },
+        )
+    };
+
+    let err = register(
+        &mut deps,
+        KvDecoder::Protobuf {
+            type_url: "/cosmos.staking.v1beta1.Validator".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        NeutronError::Std(StdError::generic_err(
+            "unsupported protobuf type: /cosmos.staking.v1beta1.Validator"
+        ))
+    );
+
+    let values = [
+        StdCoin {
+            denom: "uatom".to_string(),
+            amount: "42".to_string(),
+        }
+        .encode_to_vec(),
+        1000u128.to_be_bytes()[8..].to_vec(),
+        br#"{"count": 3}"#.to_vec(),
+        b"{count: 3".to_vec(),
+    ];
+    let decoders = [
+        KvDecoder::Protobuf {
+            type_url: "/cosmos.base.v1beta1.Coin".to_string(),
+        },
+        KvDecoder::Uint128BigEndian {},
+        KvDecoder::Json {},
+        KvDecoder::Json {},
+    ];
+    for (query_id, (value, decoder)) in (1..).zip(values.into_iter().zip(decoders)) {
+        deps.querier.add_registered_queries(
+            query_id,
+            build_registered_query_response(
+                query_id,
+                QueryParam::Keys(keys.clone()),
+                QueryType::KV,
+                0,
+            ),
+        );
+        deps.querier.add_query_response(
+            query_id,
+            Binary::from(
+                QueryRegisteredQueryResultResponse {
+                    result: Some(QueryResult {
+                        kv_results: vec![
+                            StorageValue {
+                                storage_prefix: "bank".to_string(),
+                                key: b"balance".to_vec(),
+                                value,
+                                proof: None,
+                            },
+                            StorageValue {
+                                storage_prefix: "bank".to_string(),
+                                key: b"missing".to_vec(),
+                                value: vec![],
+                                proof: None,
+                            },
+                        ],
+                        block: None,
+                        height: 100,
+                        revision: 2,
+                        allow_kv_callbacks: false,
+                    }),
+                }
+                .encode_to_vec(),
+            ),
+        );
+        let res = register(&mut deps, decoder).unwrap();
+        reply(
+            deps.as_mut(),
+            env.clone(),
+            build_register_query_reply(query_id, res.messages[0].payload.clone()),
+        )
+        .unwrap();
+    }
+
+    let raw_result = |query_id| {
+        let resp: RawQueryResultResponse = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::RawQueryResult { query_id },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        resp
+    };
+    assert_eq!(
+        raw_result(1),
+        RawQueryResultResponse {
+            height: 100,
+            values: vec![
+                RawKvValue {
+                    storage_prefix: "bank".to_string(),
+                    key: Binary::from(b"balance".as_slice()),
+                    value: Some(DecodedValue::Json(
+                        r#"{"denom":"uatom","amount":"42"}"#.to_string()
+                    )),
+                },
+                RawKvValue {
+                    storage_prefix: "bank".to_string(),
+                    key: Binary::from(b"missing".as_slice()),
+                    value: None,
+                },
+            ],
+        }
+    );
+    assert_eq!(
+        raw_result(2).values[0].value,
+        Some(DecodedValue::Uint128(Uint128::new(1000)))
+    );
+    assert_eq!(
+        raw_result(3).values[0].value,
+        Some(DecodedValue::Json(r#"{"count": 3}"#.to_string()))
+    );
+    // the value is parsed, not only checked to be UTF-8
+    let err = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::RawQueryResult { query_id: 4 },
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("value isn't JSON"));
+}
+
+#[test]
+fn test_wasm_contract_state_query() {
+    let mut deps = dependencies(&[]);
+    let contract_address = "osmo1yz54ncxj9csp7un3xled03q6thrrhy9cztkfzs";
+    let address_bytes = decode_and_convert(contract_address).unwrap();
+
+    let item_key = wasm_contract_state_key(
+        contract_address,
+        &ContractStorageKey::Item {
+            namespace: "config".to_string(),
+        },
+    )
+    .unwrap();
+    assert_eq!(item_key.path, "wasm");
+    assert_eq!(
+        item_key.key,
+        [vec![0x03], address_bytes.clone(), b"config".to_vec()].concat()
+    );
+    let map_key = wasm_contract_state_key(
+        contract_address,
+        &ContractStorageKey::Map {
+            namespace: "pools".to_string(),
+            key: Binary::from(1u64.to_be_bytes()),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        map_key.key,
+        [
+            vec![0x03],
+            address_bytes,
+            vec![0x00, 0x05],
+            b"pools".to_vec(),
+            1u64.to_be_bytes().to_vec(),
+        ]
+        .concat()
+    );
+
+    deps.querier.add_registered_queries(
+        1,
+        build_registered_query_response(
+            1,
+            QueryParam::Keys(vec![map_key.clone()]),
+            QueryType::KV,
+            0,
+        ),
+    );
+    let pool = br#"{"reserves":["100","200"]}"#.to_vec();
+    let query_result = |value: Vec<u8>| {
+        Binary::from(
+            QueryRegisteredQueryResultResponse {
+                result: Some(QueryResult {
+                    kv_results: vec![StorageValue {
+                        storage_prefix: "wasm".to_string(),
+                        key: map_key.key.clone(),
+                        value,
+                        proof: None,
+                    }],
+                    block: None,
+                    height: 100,
+                    revision: 2,
+                    allow_kv_callbacks: false,
+                }),
+            }
+            .encode_to_vec(),
+        )
+    };
+    deps.querier
+        .add_query_response(1, query_result(pool.clone()));
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        message_info(&Addr::unchecked(""), &[]),
+        ExecuteMsg::RegisterWasmContractStateQuery {
+            connection_id: "connection".to_string(),
+            update_period: 1,
+            contract_address: contract_address.to_string(),
+            storage_key: ContractStorageKey::Map {
+                namespace: "pools".to_string(),
+                key: Binary::from(1u64.to_be_bytes()),
+            },
+        },
+    )
+    .unwrap();
+    reply(
+        deps.as_mut(),
+        mock_env(),
+        build_register_query_reply(1, res.messages[0].payload.clone()),
+    )
+    .unwrap();
+
+    // the value is returned as the remote contract stores it
+    let state = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::WasmContractState { query_id: 1 },
+    )
+    .unwrap();
+    assert_eq!(state, Binary::from(pool));
+
+    deps.querier.add_query_response(1, query_result(vec![]));
+    let state = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::WasmContractState { query_id: 1 },
+    )
+    .unwrap();
+    assert_eq!(state, Binary::from(b"null".as_slice()));
+}
+
+fn deliver(
+    deps: &mut OwnedDeps<MockStorage, MockApi, NeutronMockQuerier>,
+    env: &Env,
+    deliveries: Vec<Delivery>,
+) {
+    for delivery in deliveries {
+        match delivery {
+            Delivery::Reply(msg) => {
+                reply(deps.as_mut(), env.clone(), msg).unwrap();
+            }
+            Delivery::Sudo(msg) => {
+                sudo(deps.as_mut(), env.clone(), msg).unwrap();
+            }
+        }
+    }
+}
+
+#[test]
+fn test_queries_through_mock_icq_module() {
+    let mut deps = neutron_test_support::mock_dependencies(&[]);
+    let env = mock_env();
+    let mut icq = MockIcq::new();
+    let watched_addr = "cosmos1yz54ncxj9csp7un3xled03q6thrrhy9cztkfzs";
+    let sender = "cosmos10h9stc5v6ntgeygf5xf945njqq5h32r5r2mmx6";
+
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&Addr::unchecked(""), &[]),
+        ExecuteMsg::RegisterBalancesQuery {
+            connection_id: "connection-0".to_string(),
+            update_period: 10,
+            addr: watched_addr.to_string(),
+            denoms: vec!["uatom".to_string()],
+        },
+    )
+    .unwrap();
+    let deliveries = icq.handle_response(&mut deps.querier, &env, &res);
+    deliver(&mut deps, &env, deliveries);
+
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&Addr::unchecked(""), &[]),
+        ExecuteMsg::RegisterTransfersQuery {
+            connection_id: "connection-0".to_string(),
+            update_period: 10,
+            recipients: vec![watched_addr.to_string()],
+            min_height: None,
+        },
+    )
+    .unwrap();
+    let deliveries = icq.handle_response(&mut deps.querier, &env, &res);
+    deliver(&mut deps, &env, deliveries);
+
+    // both queries are registered with the module deposit and tracked by the contract
+    let summary: DepositsSummaryResponse =
+        from_json(query(deps.as_ref(), env.clone(), QueryMsg::DepositsSummary {}).unwrap())
+            .unwrap();
+    assert_eq!(summary.queries, 2);
+    assert_eq!(summary.locked, vec![Coin::new(2_000_000u128, "untrn")]);
+
+    // the relayer submits the KV result of the balance query
+    let callback = icq.submit_kv_result(
+        &mut deps.querier,
+        &env,
+        1,
+        vec![proto::balance_value(watched_addr, "uatom", 1000)],
+        100,
+    );
+    deliver(&mut deps, &env, vec![Delivery::Sudo(callback)]);
+    let resp: BalanceResponse = from_json(
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::Balance { query_id: 1 },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        resp,
+        BalanceResponse {
+            last_submitted_local_height: env.block.height,
+            balances: Balances {
+                coins: vec![Coin::new(1000u128, "uatom")]
+            },
+        }
+    );
+
+    // and a transaction matching the transfers query
+    let callback = icq.submit_tx_result(
+        &mut deps.querier,
+        2,
+        101,
+        proto::send_tx(sender, watched_addr, &[("uatom", 500)]),
+    );
+    deliver(&mut deps, &env, vec![Delivery::Sudo(callback)]);
+    let resp: GetRecipientTxsResponse = from_json(
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::GetRecipientTxs {
+                recipient: watched_addr.to_string(),
+                start_after: None,
+                limit: None,
+                filter: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(resp.transfers.len(), 1);
+    assert_eq!(resp.transfers[0].sender, sender);
+    assert_eq!(resp.transfers[0].amount, Uint256::from(500u128));
+    assert_eq!(resp.transfers[0].height, 101);
+
+    // removing the query removes it from the module as well
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&Addr::unchecked(""), &[]),
+        ExecuteMsg::RemoveInterchainQuery { query_id: 1 },
+    )
+    .unwrap();
+    assert!(icq
+        .handle_response(&mut deps.querier, &env, &res)
+        .is_empty());
+    assert!(deps.querier.registered_query(1).is_none());
+    query(deps.as_ref(), env, QueryMsg::Balance { query_id: 1 }).unwrap_err();
+}
+
+#[test]
+fn test_query_results_through_neutron_app() {
+    let mut app = NeutronApp::new(ContractWrapper::new(execute, reply, sudo), &[]);
+    let watched_addr = "cosmos1yz54ncxj9csp7un3xled03q6thrrhy9cztkfzs";
+    let sender = "cosmos10h9stc5v6ntgeygf5xf945njqq5h32r5r2mmx6";
+
+    app.execute(
+        message_info(&Addr::unchecked(""), &[]),
+        &ExecuteMsg::RegisterBalancesQuery {
+            connection_id: "connection-0".to_string(),
+            update_period: 5,
+            addr: watched_addr.to_string(),
+            denoms: vec!["stake".to_string()],
+        },
+    )
+    .unwrap();
+    app.execute(
+        message_info(&Addr::unchecked(""), &[]),
+        &ExecuteMsg::RegisterTransfersQuery {
+            connection_id: "connection-0".to_string(),
+            update_period: 5,
+            recipients: vec![watched_addr.to_string()],
+            min_height: Some(1),
+        },
+    )
+    .unwrap();
+
+    // a few blocks later the relayer submits both results
+    app.next_block();
+    app.submit_kv_result(
+        1,
+        vec![proto::balance_value(watched_addr, "stake", 1000)],
+        100,
+    )
+    .unwrap();
+    app.submit_tx_result(
+        2,
+        101,
+        proto::send_tx(sender, watched_addr, &[("stake", 500)]),
+    )
+    .unwrap();
+
+    let resp: BalanceResponse = from_json(
+        query(
+            app.deps.as_ref(),
+            app.env.clone(),
+            QueryMsg::Balance { query_id: 1 },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(resp.last_submitted_local_height, app.env.block.height);
+    assert_eq!(resp.balances.coins, vec![Coin::new(1000u128, "stake")]);
+
+    let resp: GetRecipientTxsResponse = from_json(
+        query(
+            app.deps.as_ref(),
+            app.env.clone(),
+            QueryMsg::GetRecipientTxs {
+                recipient: watched_addr.to_string(),
+                start_after: None,
+                limit: None,
+                filter: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(resp.transfers.len(), 1);
+    assert_eq!(resp.transfers[0].amount, Uint256::from(500u128));
+}
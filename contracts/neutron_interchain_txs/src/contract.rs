@@ -12,36 +12,56 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use cosmos_sdk_proto::cosmos::bank::v1beta1::MsgSendResponse;
 use cosmos_sdk_proto::cosmos::base::v1beta1::Coin;
+use cosmos_sdk_proto::cosmos::distribution::v1beta1::{
+    MsgSetWithdrawAddress, MsgSetWithdrawAddressResponse, MsgWithdrawDelegatorReward,
+    MsgWithdrawDelegatorRewardResponse,
+};
+use cosmos_sdk_proto::cosmos::gov::v1beta1::{MsgVote, MsgVoteResponse};
 use cosmos_sdk_proto::cosmos::staking::v1beta1::{
-    MsgDelegate, MsgDelegateResponse, MsgUndelegate, MsgUndelegateResponse,
+    MsgBeginRedelegate, MsgBeginRedelegateResponse, MsgDelegate, MsgDelegateResponse,
+    MsgUndelegate, MsgUndelegateResponse,
 };
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_json_binary, Binary, CosmosMsg, CustomQuery, Deps, DepsMut, Env, MessageInfo, Reply,
-    ReplyOn, Response, StdError, StdResult, SubMsg, Uint128,
+    to_json_binary, Addr, AnyMsg, Binary, Coin as CwCoin, CosmosMsg, CustomQuery, Deps, DepsMut,
+    Env, MessageInfo, Reply, ReplyOn, Response, StdError, StdResult, SubMsg, Timestamp, Uint128,
 };
 use cw2::set_contract_version;
+use cw_storage_plus::Bound;
+use ibc_timeout::TimeoutSpec;
 use neutron_std::types::cosmos::base::v1beta1::Coin as StdCoin;
 use prost::Message;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 
 use crate::integration_tests_mock_handlers::{
     set_sudo_failure_mock, set_sudo_submsg_failure_in_reply_mock, set_sudo_submsg_failure_mock,
     unset_sudo_failure_mock,
 };
 use crate::msg::{
-    AcknowledgementResultsResponse, ExecuteMsg, Fees, InstantiateMsg, MigrateMsg, QueryMsg,
+    AcknowledgementResultsResponse, ExecuteMsg, Fees, InstantiateMsg, LedgerResponse, MigrateMsg,
+    QueryMsg,
 };
 use crate::storage::{
-    add_error_to_queue, read_errors_from_queue, read_reply_payload, read_sudo_payload,
-    save_reply_payload, save_sudo_payload, AcknowledgementResult, DoubleDelegateInfo,
-    IntegrationTestsSudoFailureMock, IntegrationTestsSudoSubmsgFailureMock, SudoPayload,
-    ACKNOWLEDGEMENT_RESULTS, IBC_FEE, ICA_CHANNELS, INTEGRATION_TESTS_SUDO_FAILURE_MOCK,
-    INTEGRATION_TESTS_SUDO_SUBMSG_FAILURE_MOCK, INTERCHAIN_ACCOUNTS, REGISTER_FEE,
-    REGISTER_ICA_REPLY_ID, SUDO_FAILING_SUBMSG_REPLY_ID, SUDO_PAYLOAD_REPLY_ID, TEST_COUNTER_ITEM,
+    add_error_to_queue, add_ledger_entry, add_retry_attempt, finish_ledger_entry,
+    read_errors_from_queue, read_reply_payload, read_sudo_payload, remove_ledger_entry,
+    save_reply_payload, save_sudo_payload, set_ledger_entry_packet, AcknowledgementResult,
+    DoubleDelegateInfo, IcaFee, IcaOperation, IcaOperationResult, IcaRegistration,
+    IntegrationTestsSudoFailureMock, IntegrationTestsSudoSubmsgFailureMock, LedgerEntry,
+    LedgerRetention, LedgerStatus, MsgResult, RetryHistory, RetryPolicy, SudoPayload,
+    ACKNOWLEDGEMENT_RESULTS, IBC_FEE, ICA_CHANNELS, ICA_OPERATION_RESULTS, ICA_REGISTRATIONS,
+    INTEGRATION_TESTS_SUDO_FAILURE_MOCK, INTEGRATION_TESTS_SUDO_SUBMSG_FAILURE_MOCK,
+    INTERCHAIN_ACCOUNTS, LEDGER, LEDGER_BY_ICA, LEDGER_BY_STATUS, LEDGER_COUNT, LEDGER_RETENTION,
+    REGISTER_FEE, REGISTER_ICA_REPLY_ID, RESUBMIT_FAILURE_REPLY_ID, RETRIES, RETRY_POLICY,
+    RETRY_QUEUE, SUDO_FAILING_SUBMSG_REPLY_ID, SUDO_PAYLOAD_REPLY_ID, TEST_COUNTER_ITEM,
+};
+use contract_failures::{
+    execute_resubmit_failures, query_failures, query_resubmissions, resubmit_failure_reply,
+    ResubmitFilter,
 };
 use neutron_sdk::interchain_txs::helpers::{
     decode_message_response, get_port_id, register_interchain_account, submit_tx,
@@ -50,16 +70,27 @@ use neutron_sdk::interchain_txs::v047::helpers::decode_acknowledgement_response;
 use neutron_sdk::sudo::msg::{RequestPacket, SudoMsg};
 use neutron_sdk::NeutronResult;
 use neutron_std::shim::Any;
+use neutron_std::types::ibc::applications::transfer::v1::MsgTransferResponse;
 use neutron_std::types::ibc::core::channel::v1::Order;
 use neutron_std::types::neutron::contractmanager::MsgResubmitFailure;
 use neutron_std::types::neutron::feerefunder::Fee;
 use neutron_std::types::neutron::interchaintxs::v1::{
     InterchaintxsQuerier, MsgRegisterInterchainAccountResponse, MsgSubmitTxResponse,
 };
+use ownership::{
+    accept_ownership, assert_owner, assert_owner_or_operator, initialize_owner, query_operators,
+    query_ownership, set_operators, transfer_ownership, OWNER,
+};
 
 // Default timeout for SubmitTX is two weeks
 const DEFAULT_TIMEOUT_SECONDS: u64 = 60 * 60 * 24 * 7 * 2;
 
+// Default and maximum page sizes for the ledger queries
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 100;
+// Maximum number of ledger entries inspected by a single ledger query or prune
+const MAX_LEDGER_SCAN: usize = 1000;
+
 const CONTRACT_NAME: &str = concat!("crates.io:neutron-contracts__", env!("CARGO_PKG_NAME"));
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -81,19 +112,33 @@ struct ExecuteDelegateInfo {
     pub validator: String,
     pub amount: Uint128,
     pub denom: String,
-    pub timeout: Option<u64>,
+    pub timeout: Option<TimeoutSpec>,
+    pub info: Option<DoubleDelegateInfo>,
+}
+
+struct SubmitTxInfo {
+    pub interchain_account_id: String,
+    pub msgs: Vec<Any>,
+    pub memo: String,
+    pub timeout: Option<TimeoutSpec>,
+    /// whether a decoded result for every message is stored on acknowledgement
+    pub typed_results: bool,
+    pub operation: Option<IcaOperation>,
     pub info: Option<DoubleDelegateInfo>,
+    /// ledger id of the first submission if the tx is a retry of a failed one
+    pub retry_of: Option<u64>,
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
-    deps: DepsMut,
+    mut deps: DepsMut,
     _env: Env,
-    _info: MessageInfo,
-    _msg: InstantiateMsg,
+    info: MessageInfo,
+    msg: InstantiateMsg,
 ) -> NeutronResult<Response> {
     deps.api.debug("WASMDEBUG: instantiate");
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    initialize_owner(deps.branch(), &info, msg.owner)?;
     REGISTER_FEE.save(
         deps.storage,
         &vec![StdCoin {
@@ -105,9 +150,10 @@ pub fn instantiate(
 }
 
 #[entry_point]
-pub fn execute(deps: DepsMut, env: Env, _: MessageInfo, msg: ExecuteMsg) -> StdResult<Response> {
+pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> StdResult<Response> {
     deps.api
         .debug(format!("WASMDEBUG: execute: received msg: {:?}", msg).as_str());
+    authorize(deps.as_ref(), &env, &info.sender, &msg)?;
     match msg {
         ExecuteMsg::Register {
             connection_id,
@@ -123,12 +169,13 @@ pub fn execute(deps: DepsMut, env: Env, _: MessageInfo, msg: ExecuteMsg) -> StdR
         } => execute_delegate(
             deps,
             env,
+            info.sender,
             ExecuteDelegateInfo {
                 interchain_account_id,
                 validator,
                 amount,
                 denom,
-                timeout,
+                timeout: timeout.map(Into::into),
                 info: None,
             },
         ),
@@ -141,12 +188,13 @@ pub fn execute(deps: DepsMut, env: Env, _: MessageInfo, msg: ExecuteMsg) -> StdR
         } => execute_delegate_double_ack(
             deps,
             env,
+            info.sender,
             ExecuteDelegateInfo {
                 interchain_account_id: interchain_account_id.clone(),
                 validator: validator.clone(),
                 amount,
                 denom: denom.clone(),
-                timeout,
+                timeout: timeout.map(Into::into),
                 info: Some(DoubleDelegateInfo {
                     interchain_account_id,
                     validator,
@@ -164,17 +212,121 @@ pub fn execute(deps: DepsMut, env: Env, _: MessageInfo, msg: ExecuteMsg) -> StdR
         } => execute_undelegate(
             deps,
             env,
+            info.sender,
+            ExecuteDelegateInfo {
+                interchain_account_id,
+                validator,
+                amount,
+                denom,
+                timeout: timeout.map(Into::into),
+                info: None,
+            },
+        ),
+        ExecuteMsg::SubmitTx {
             interchain_account_id,
-            validator,
+            msgs,
+            memo,
+            timeout,
+        } => execute_submit_tx(
+            deps,
+            env,
+            info.sender,
+            interchain_account_id,
+            msgs,
+            memo,
+            timeout.map(Into::into),
+        ),
+        ExecuteMsg::BeginRedelegate {
+            interchain_account_id,
+            validator_src,
+            validator_dst,
             amount,
             denom,
             timeout,
+        } => execute_operation(
+            deps,
+            env,
+            info.sender,
+            interchain_account_id,
+            IcaOperation::BeginRedelegate {
+                validator_src,
+                validator_dst,
+                amount: CwCoin { denom, amount },
+            },
+            timeout.map(Into::into),
+        ),
+        ExecuteMsg::WithdrawDelegatorReward {
+            interchain_account_id,
+            validator,
+            timeout,
+        } => execute_operation(
+            deps,
+            env,
+            info.sender,
+            interchain_account_id,
+            IcaOperation::WithdrawDelegatorReward { validator },
+            timeout.map(Into::into),
+        ),
+        ExecuteMsg::SetWithdrawAddress {
+            interchain_account_id,
+            withdraw_address,
+            timeout,
+        } => execute_operation(
+            deps,
+            env,
+            info.sender,
+            interchain_account_id,
+            IcaOperation::SetWithdrawAddress { withdraw_address },
+            timeout.map(Into::into),
+        ),
+        ExecuteMsg::Vote {
+            interchain_account_id,
+            proposal_id,
+            option,
+            timeout,
+        } => execute_operation(
+            deps,
+            env,
+            info.sender,
+            interchain_account_id,
+            IcaOperation::Vote {
+                proposal_id,
+                option,
+            },
+            timeout.map(Into::into),
         ),
         ExecuteMsg::SetFees { fees } => execute_set_fees(deps, fees),
         ExecuteMsg::CleanAckResults {} => execute_clean_ack_results(deps),
+        ExecuteMsg::SetLedgerRetention { retention } => {
+            execute_set_ledger_retention(deps, retention)
+        }
+        ExecuteMsg::PruneLedger { limit } => execute_prune_ledger(deps, env, limit),
+        ExecuteMsg::SetRetryPolicy { policy } => execute_set_retry_policy(deps, policy),
+        ExecuteMsg::ProcessRetry {} => execute_process_retry(deps, env),
         ExecuteMsg::ResubmitFailure { failure_id } => {
             execute_resubmit_failure(deps, env, failure_id)
         }
+        ExecuteMsg::ResubmitFailures {
+            channel_id,
+            error_contains,
+            start_after,
+            max,
+        } => execute_resubmit_failures::<SudoMsg>(
+            deps,
+            env,
+            ResubmitFilter {
+                channel_id,
+                error_contains,
+            },
+            start_after,
+            max,
+            RESUBMIT_FAILURE_REPLY_ID,
+        ),
+        ExecuteMsg::TransferOwnership { new_owner } => transfer_ownership(deps, info, new_owner),
+        ExecuteMsg::AcceptOwnership {} => accept_ownership(deps, info),
+        ExecuteMsg::SetOperators { action, operators } => {
+            set_operators(deps, info, action, operators)
+        }
 
         // The section below is used only in integration tests framework to simulate failures.
         ExecuteMsg::IntegrationTestsSetSudoFailureMock { state } => {
@@ -191,6 +343,41 @@ pub fn execute(deps: DepsMut, env: Env, _: MessageInfo, msg: ExecuteMsg) -> StdR
     }
 }
 
+fn authorize(deps: Deps, env: &Env, sender: &Addr, msg: &ExecuteMsg) -> StdResult<()> {
+    match msg {
+        // ownership messages are checked by their handlers, anyone can process due retries
+        ExecuteMsg::TransferOwnership { .. }
+        | ExecuteMsg::AcceptOwnership {}
+        | ExecuteMsg::SetOperators { .. }
+        | ExecuteMsg::ProcessRetry {} => Ok(()),
+        ExecuteMsg::IntegrationTestsSetSudoFailureMock { .. }
+        | ExecuteMsg::IntegrationTestsSetSudoSubmsgFailureMock {}
+        | ExecuteMsg::IntegrationTestsSetSudoSubmsgReplyFailureMock {}
+        | ExecuteMsg::IntegrationTestsUnsetSudoFailureMock {} => {
+            assert_integration_tests(deps, sender)
+        }
+        // sent by the contract to itself from sudo while the submsg failure mock is set
+        ExecuteMsg::IntegrationTestsSudoSubmsg {}
+            if cfg!(feature = "integration-tests") && *sender == env.contract.address =>
+        {
+            Ok(())
+        }
+        ExecuteMsg::IntegrationTestsSudoSubmsg {} => assert_integration_tests(deps, sender),
+        _ => assert_owner_or_operator(deps, sender, msg.action()),
+    }
+}
+
+/// Messages used only in integration tests framework are available to the owner if the contract
+/// has been built with the `integration-tests` feature.
+fn assert_integration_tests(deps: Deps, sender: &Addr) -> StdResult<()> {
+    if !cfg!(feature = "integration-tests") {
+        return Err(StdError::generic_err(
+            "integration tests messages are disabled",
+        ));
+    }
+    assert_owner(deps, sender)
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> NeutronResult<Binary> {
     match msg {
@@ -206,7 +393,49 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> NeutronResult<Binary> {
             sequence_id,
         } => query_acknowledgement_result(deps, env, interchain_account_id, sequence_id),
         QueryMsg::AcknowledgementResults {} => query_acknowledgement_results(deps),
+        QueryMsg::OperationResult {
+            interchain_account_id,
+            sequence_id,
+        } => query_operation_result(deps, env, interchain_account_id, sequence_id),
+        QueryMsg::LedgerEntry { id } => query_ledger_entry(deps, id),
+        QueryMsg::Ledger {
+            interchain_account_id,
+            status,
+            from_time,
+            to_time,
+            start_after,
+            limit,
+        } => query_ledger(
+            deps,
+            LedgerFilter {
+                interchain_account_id,
+                status,
+                from_time,
+                to_time,
+            },
+            start_after,
+            limit,
+        ),
+        QueryMsg::RetryPolicy {} => query_retry_policy(deps),
+        QueryMsg::RetryHistory { ledger_id } => query_retry_history(deps, ledger_id),
+        QueryMsg::Ownership {} => Ok(to_json_binary(&query_ownership(deps)?)?),
+        QueryMsg::Operators { action } => Ok(to_json_binary(&query_operators(deps, action)?)?),
         QueryMsg::ErrorsQueue {} => query_errors_queue(deps),
+        QueryMsg::Failures { start_after, limit } => {
+            Ok(to_json_binary(&query_failures::<SudoMsg>(
+                deps,
+                env,
+                start_after,
+                limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT),
+            )?)?)
+        }
+        QueryMsg::Resubmissions { start_after, limit } => {
+            Ok(to_json_binary(&query_resubmissions(
+                deps,
+                start_after,
+                limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT),
+            )?)?)
+        }
     }
 }
 
@@ -264,6 +493,118 @@ pub fn query_acknowledgement_results(deps: Deps) -> NeutronResult<Binary> {
     Ok(to_json_binary(&results)?)
 }
 
+pub fn query_operation_result(
+    deps: Deps,
+    env: Env,
+    interchain_account_id: String,
+    sequence_id: u64,
+) -> NeutronResult<Binary> {
+    let port_id = get_port_id(env.contract.address.as_str(), &interchain_account_id);
+    let res: Option<IcaOperationResult> =
+        ICA_OPERATION_RESULTS.may_load(deps.storage, (port_id, sequence_id))?;
+    Ok(to_json_binary(&res)?)
+}
+
+pub fn query_ledger_entry(deps: Deps, id: u64) -> NeutronResult<Binary> {
+    let res: Option<LedgerEntry> = LEDGER.may_load(deps.storage, id)?;
+    Ok(to_json_binary(&res)?)
+}
+
+pub fn query_retry_policy(deps: Deps) -> NeutronResult<Binary> {
+    let res: RetryPolicy = RETRY_POLICY.may_load(deps.storage)?.unwrap_or_default();
+    Ok(to_json_binary(&res)?)
+}
+
+pub fn query_retry_history(deps: Deps, ledger_id: u64) -> NeutronResult<Binary> {
+    let entry = LEDGER.load(deps.storage, ledger_id)?;
+    let res: Option<RetryHistory> =
+        RETRIES.may_load(deps.storage, entry.retry_of.unwrap_or(entry.id))?;
+    Ok(to_json_binary(&res)?)
+}
+
+struct LedgerFilter {
+    interchain_account_id: Option<String>,
+    status: Option<LedgerStatus>,
+    from_time: Option<Timestamp>,
+    to_time: Option<Timestamp>,
+}
+
+impl LedgerFilter {
+    fn matches(&self, entry: &LedgerEntry) -> bool {
+        self.interchain_account_id
+            .as_ref()
+            .map_or(true, |id| *id == entry.interchain_account_id)
+            && self.status.as_ref().map_or(true, |s| *s == entry.status)
+            && self
+                .from_time
+                .map_or(true, |from| entry.submitted_time >= from)
+            && self.to_time.map_or(true, |to| entry.submitted_time <= to)
+    }
+}
+
+fn query_ledger(
+    deps: Deps,
+    filter: LedgerFilter,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> NeutronResult<Binary> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    // the ICA id or the status narrows the ids down through an index, the rest of the filter is
+    // checked on the entries
+    let ids: Box<dyn Iterator<Item = StdResult<u64>> + '_> =
+        match (&filter.interchain_account_id, &filter.status) {
+            (Some(interchain_account_id), _) => {
+                Box::new(LEDGER_BY_ICA.prefix(interchain_account_id.as_str()).keys(
+                    deps.storage,
+                    start,
+                    None,
+                    cosmwasm_std::Order::Ascending,
+                ))
+            }
+            (None, Some(status)) => Box::new(LEDGER_BY_STATUS.prefix(status.key()).keys(
+                deps.storage,
+                start,
+                None,
+                cosmwasm_std::Order::Ascending,
+            )),
+            (None, None) => {
+                Box::new(LEDGER.keys(deps.storage, start, None, cosmwasm_std::Order::Ascending))
+            }
+        };
+
+    let mut entries: Vec<LedgerEntry> = vec![];
+    let mut last_scanned = None;
+    let mut scanned = 0;
+    for id in ids.take(MAX_LEDGER_SCAN) {
+        let entry = LEDGER.load(deps.storage, id?)?;
+        // ids are assigned in the order of submission, no later entry is in the time range
+        if filter.to_time.is_some_and(|to| entry.submitted_time > to) {
+            return Ok(to_json_binary(&LedgerResponse {
+                entries,
+                next_start_after: None,
+            })?);
+        }
+        scanned += 1;
+        last_scanned = Some(entry.id);
+        if filter.matches(&entry) {
+            entries.push(entry);
+            if entries.len() == limit {
+                break;
+            }
+        }
+    }
+    let next_start_after = if entries.len() == limit || scanned == MAX_LEDGER_SCAN {
+        last_scanned
+    } else {
+        None
+    };
+    Ok(to_json_binary(&LedgerResponse {
+        entries,
+        next_start_after,
+    })?)
+}
+
 pub fn query_errors_queue(deps: Deps) -> NeutronResult<Binary> {
     let res = read_errors_from_queue(deps.storage)?;
     Ok(to_json_binary(&res)?)
@@ -305,87 +646,88 @@ fn execute_register_ica(
     ordering: Option<Order>,
 ) -> StdResult<Response> {
     let register_fee = REGISTER_FEE.load(deps.storage)?;
+    let key = get_port_id(env.contract.address.as_str(), &interchain_account_id);
+    INTERCHAIN_ACCOUNTS.save(deps.storage, key.clone(), &None)?;
+    ICA_REGISTRATIONS.save(
+        deps.storage,
+        key,
+        &IcaRegistration {
+            interchain_account_id: interchain_account_id.clone(),
+            connection_id: connection_id.clone(),
+            ordering,
+        },
+    )?;
     let register = register_interchain_account(
         env.contract.address.clone(),
         connection_id,
-        interchain_account_id.clone(),
+        interchain_account_id,
         register_fee,
         ordering,
     );
-    let key = get_port_id(env.contract.address.as_str(), &interchain_account_id);
-    INTERCHAIN_ACCOUNTS.save(deps.storage, key, &None)?;
     Ok(Response::new().add_submessage(SubMsg::reply_on_success(register, REGISTER_ICA_REPLY_ID)))
 }
 
-fn execute_delegate(deps: DepsMut, env: Env, info: ExecuteDelegateInfo) -> StdResult<Response> {
-    do_delegate(deps, env, info)
+fn execute_delegate(
+    deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    info: ExecuteDelegateInfo,
+) -> StdResult<Response> {
+    do_delegate(deps, env, sender, info)
 }
 
 fn execute_undelegate(
-    mut deps: DepsMut,
+    deps: DepsMut,
     env: Env,
-    interchain_account_id: String,
-    validator: String,
-    amount: Uint128,
-    denom: String,
-    timeout: Option<u64>,
+    sender: Addr,
+    info: ExecuteDelegateInfo,
 ) -> StdResult<Response> {
-    let fee = IBC_FEE.load(deps.storage)?;
-    let (delegator, connection_id) = get_ica(deps.as_ref(), &env, &interchain_account_id)?;
-    let delegate_msg = MsgUndelegate {
+    let (delegator, _) = get_ica(deps.as_ref(), &env, &info.interchain_account_id)?;
+    let undelegate_msg = MsgUndelegate {
         delegator_address: delegator,
-        validator_address: validator,
+        validator_address: info.validator,
         amount: Some(Coin {
-            denom,
-            amount: amount.to_string(),
+            denom: info.denom,
+            amount: info.amount.to_string(),
         }),
     };
-    let mut buf = Vec::with_capacity(delegate_msg.encoded_len());
-    buf.reserve(delegate_msg.encoded_len());
-
-    if let Err(e) = delegate_msg.encode(&mut buf) {
-        return Err(StdError::generic_err(format!("Encode error: {}", e)));
-    }
-
-    let any_msg = Any {
-        type_url: "/cosmos.staking.v1beta1.MsgUndelegate".to_string(),
-        value: buf,
-    };
 
-    let cosmos_msg = submit_tx(
-        env.contract.address.clone(),
-        connection_id,
-        interchain_account_id.clone(),
-        vec![any_msg],
-        "".to_string(),
-        timeout.unwrap_or(DEFAULT_TIMEOUT_SECONDS),
-        fee,
-    );
-
-    let submsg = msg_with_sudo_callback(
-        deps.branch(),
-        cosmos_msg,
-        SudoPayload {
-            port_id: get_port_id(env.contract.address.as_str(), &interchain_account_id),
-            message: "message".to_string(),
+    do_submit_tx(
+        deps,
+        env,
+        sender,
+        SubmitTxInfo {
+            interchain_account_id: info.interchain_account_id,
+            msgs: vec![to_any(
+                "/cosmos.staking.v1beta1.MsgUndelegate",
+                &undelegate_msg,
+            )],
+            memo: "".to_string(),
+            timeout: info.timeout,
+            typed_results: false,
+            operation: None,
             info: None,
+            retry_of: None,
         },
-    )?;
-
-    Ok(Response::default().add_submessages(vec![submsg]))
+    )
 }
 
 fn execute_delegate_double_ack(
     deps: DepsMut,
     env: Env,
+    sender: Addr,
     info: ExecuteDelegateInfo,
 ) -> StdResult<Response> {
-    do_delegate(deps, env, info)
+    do_delegate(deps, env, sender, info)
 }
 
-fn do_delegate(mut deps: DepsMut, env: Env, info: ExecuteDelegateInfo) -> StdResult<Response> {
-    let fee = IBC_FEE.load(deps.storage)?;
-    let (delegator, connection_id) = get_ica(deps.as_ref(), &env, &info.interchain_account_id)?;
+fn do_delegate(
+    deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    info: ExecuteDelegateInfo,
+) -> StdResult<Response> {
+    let (delegator, _) = get_ica(deps.as_ref(), &env, &info.interchain_account_id)?;
     let delegate_msg = MsgDelegate {
         delegator_address: delegator,
         validator_address: info.validator,
@@ -394,25 +736,197 @@ fn do_delegate(mut deps: DepsMut, env: Env, info: ExecuteDelegateInfo) -> StdRes
             amount: info.amount.to_string(),
         }),
     };
-    let mut buf = Vec::with_capacity(delegate_msg.encoded_len());
-    buf.reserve(delegate_msg.encoded_len());
 
-    if let Err(e) = delegate_msg.encode(&mut buf) {
-        return Err(StdError::generic_err(format!("Encode error: {}", e)));
+    do_submit_tx(
+        deps,
+        env,
+        sender,
+        SubmitTxInfo {
+            interchain_account_id: info.interchain_account_id,
+            msgs: vec![to_any("/cosmos.staking.v1beta1.MsgDelegate", &delegate_msg)],
+            memo: "".to_string(),
+            timeout: info.timeout,
+            typed_results: false,
+            operation: None,
+            info: info.info,
+            retry_of: None,
+        },
+    )
+}
+
+fn execute_submit_tx(
+    deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    interchain_account_id: String,
+    msgs: Vec<AnyMsg>,
+    memo: Option<String>,
+    timeout: Option<TimeoutSpec>,
+) -> StdResult<Response> {
+    if msgs.is_empty() {
+        return Err(StdError::generic_err("no messages to submit"));
+    }
+
+    let any_msgs: Vec<Any> = msgs
+        .into_iter()
+        .map(|msg| Any {
+            type_url: msg.type_url,
+            value: msg.value.to_vec(),
+        })
+        .collect();
+
+    do_submit_tx(
+        deps,
+        env,
+        sender,
+        SubmitTxInfo {
+            interchain_account_id,
+            msgs: any_msgs,
+            memo: memo.unwrap_or_default(),
+            timeout,
+            typed_results: true,
+            operation: None,
+            info: None,
+            retry_of: None,
+        },
+    )
+}
+
+/// Sends one of the dedicated staking or governance operations to the host chain.
+fn execute_operation(
+    deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    interchain_account_id: String,
+    operation: IcaOperation,
+    timeout: Option<TimeoutSpec>,
+) -> StdResult<Response> {
+    let (ica_address, _) = get_ica(deps.as_ref(), &env, &interchain_account_id)?;
+    let any_msg = match operation.clone() {
+        IcaOperation::BeginRedelegate {
+            validator_src,
+            validator_dst,
+            amount,
+        } => to_any(
+            "/cosmos.staking.v1beta1.MsgBeginRedelegate",
+            &MsgBeginRedelegate {
+                delegator_address: ica_address,
+                validator_src_address: validator_src,
+                validator_dst_address: validator_dst,
+                amount: Some(Coin {
+                    denom: amount.denom,
+                    amount: amount.amount.to_string(),
+                }),
+            },
+        ),
+        IcaOperation::WithdrawDelegatorReward { validator } => to_any(
+            "/cosmos.distribution.v1beta1.MsgWithdrawDelegatorReward",
+            &MsgWithdrawDelegatorReward {
+                delegator_address: ica_address,
+                validator_address: validator,
+            },
+        ),
+        IcaOperation::SetWithdrawAddress { withdraw_address } => to_any(
+            "/cosmos.distribution.v1beta1.MsgSetWithdrawAddress",
+            &MsgSetWithdrawAddress {
+                delegator_address: ica_address,
+                withdraw_address,
+            },
+        ),
+        IcaOperation::Vote {
+            proposal_id,
+            option,
+        } => to_any(
+            "/cosmos.gov.v1beta1.MsgVote",
+            &MsgVote {
+                proposal_id,
+                voter: ica_address,
+                option: option as i32,
+            },
+        ),
+    };
+
+    do_submit_tx(
+        deps,
+        env,
+        sender,
+        SubmitTxInfo {
+            interchain_account_id,
+            msgs: vec![any_msg],
+            memo: "".to_string(),
+            timeout,
+            typed_results: true,
+            operation: Some(operation),
+            info: None,
+            retry_of: None,
+        },
+    )
+}
+
+fn to_any<M: Message>(type_url: &str, msg: &M) -> Any {
+    Any {
+        type_url: type_url.to_string(),
+        value: msg.encode_to_vec(),
     }
+}
+
+/// Sends the messages as a single interchain tx and records it in the operations ledger.
+fn do_submit_tx(
+    mut deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    tx: SubmitTxInfo,
+) -> StdResult<Response> {
+    let fee = IBC_FEE.load(deps.storage)?;
+    let (_, connection_id) = get_ica(deps.as_ref(), &env, &tx.interchain_account_id)?;
 
-    let any_msg = Any {
-        type_url: "/cosmos.staking.v1beta1.MsgDelegate".to_string(),
-        value: buf,
+    let type_urls: Vec<String> = tx.msgs.iter().map(|msg| msg.type_url.clone()).collect();
+    // the interchaintxs module only takes a timeout in seconds after the current block
+    let timeout = match tx.timeout {
+        Some(timeout) => timeout.relative_seconds(&env)?,
+        None => DEFAULT_TIMEOUT_SECONDS,
     };
 
+    let ledger_id = add_ledger_entry(
+        deps.storage,
+        LedgerEntry {
+            id: 0,
+            sender,
+            interchain_account_id: tx.interchain_account_id.clone(),
+            msgs: tx
+                .msgs
+                .iter()
+                .map(|msg| AnyMsg {
+                    type_url: msg.type_url.clone(),
+                    value: Binary::from(msg.value.clone()),
+                })
+                .collect(),
+            memo: tx.memo.clone(),
+            operation: tx.operation.clone(),
+            fee: fee.as_ref().map(to_ica_fee).transpose()?,
+            timeout,
+            retry_of: tx.retry_of,
+            submitted_height: env.block.height,
+            submitted_time: env.block.time,
+            channel_id: None,
+            sequence_id: None,
+            status: LedgerStatus::Pending,
+            outcome: None,
+            finished_height: None,
+            finished_time: None,
+        },
+    )?;
+    if let Some(original_id) = tx.retry_of {
+        add_retry_attempt(deps.storage, original_id, ledger_id)?;
+    }
+
     let cosmos_msg = submit_tx(
         env.contract.address.clone(),
         connection_id,
-        info.interchain_account_id.clone(),
-        vec![any_msg],
-        "".to_string(),
-        info.timeout.unwrap_or(DEFAULT_TIMEOUT_SECONDS),
+        tx.interchain_account_id.clone(),
+        tx.msgs,
+        tx.memo,
+        timeout,
         fee,
     );
 
@@ -422,15 +936,173 @@ fn do_delegate(mut deps: DepsMut, env: Env, info: ExecuteDelegateInfo) -> StdRes
         deps.branch(),
         cosmos_msg,
         SudoPayload {
-            port_id: get_port_id(env.contract.address.as_str(), &info.interchain_account_id),
+            port_id: get_port_id(env.contract.address.as_str(), &tx.interchain_account_id),
             message: "message".to_string(),
-            info: info.info,
+            info: tx.info,
+            msgs: if tx.typed_results {
+                Some(type_urls)
+            } else {
+                None
+            },
+            operation: tx.operation,
+            ledger_id: Some(ledger_id),
         },
     )?;
 
     Ok(Response::default().add_submessages(vec![submsg]))
 }
 
+fn to_ica_fee(fee: &Fee) -> StdResult<IcaFee> {
+    let convert = |coins: &Vec<StdCoin>| -> StdResult<Vec<CwCoin>> {
+        coins
+            .iter()
+            .map(|coin| {
+                Ok(CwCoin {
+                    denom: coin.denom.clone(),
+                    amount: Uint128::from_str(&coin.amount)?,
+                })
+            })
+            .collect()
+    };
+    Ok(IcaFee {
+        recv_fee: convert(&fee.recv_fee)?,
+        ack_fee: convert(&fee.ack_fee)?,
+        timeout_fee: convert(&fee.timeout_fee)?,
+    })
+}
+
+fn execute_set_ledger_retention(deps: DepsMut, retention: LedgerRetention) -> StdResult<Response> {
+    LEDGER_RETENTION.save(deps.storage, &retention)?;
+    Ok(Response::default())
+}
+
+/// Removes finished ledger entries which fall out of the retention policy, together with
+/// acknowledgement results stored for them. Pending entries are never removed.
+fn execute_prune_ledger(deps: DepsMut, env: Env, limit: Option<u32>) -> StdResult<Response> {
+    let retention = LEDGER_RETENTION.may_load(deps.storage)?.unwrap_or_default();
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
+    let total = LEDGER_COUNT.may_load(deps.storage)?.unwrap_or_default();
+    let mut over_limit = retention
+        .max_entries
+        .map_or(0, |max_entries| total.saturating_sub(max_entries));
+
+    // the oldest entries are inspected first, a call gives up after MAX_LEDGER_SCAN of them
+    let mut to_remove: Vec<LedgerEntry> = vec![];
+    for entry in LEDGER
+        .range(deps.storage, None, None, cosmwasm_std::Order::Ascending)
+        .take(MAX_LEDGER_SCAN)
+    {
+        let (_, entry) = entry?;
+        if to_remove.len() >= limit {
+            break;
+        }
+        let finished_time = match entry.finished_time {
+            Some(finished_time) => finished_time,
+            None => continue,
+        };
+        // the last submission of a tx is needed until its scheduled retry is processed
+        let retry_scheduled = RETRIES
+            .may_load(deps.storage, entry.retry_of.unwrap_or(entry.id))?
+            .map_or(false, |history| history.next_attempt_height.is_some());
+        if retry_scheduled {
+            continue;
+        }
+        let expired = retention.max_age.map_or(false, |max_age| {
+            finished_time.plus_seconds(max_age) < env.block.time
+        });
+        if expired || over_limit > 0 {
+            over_limit = over_limit.saturating_sub(1);
+            to_remove.push(entry);
+        }
+    }
+
+    for entry in to_remove.iter() {
+        remove_ledger_entry(deps.storage, entry)?;
+        if entry.retry_of.is_none() {
+            RETRIES.remove(deps.storage, entry.id);
+        }
+        if let Some(sequence_id) = entry.sequence_id {
+            let port_id = get_port_id(env.contract.address.as_str(), &entry.interchain_account_id);
+            ACKNOWLEDGEMENT_RESULTS.remove(deps.storage, (port_id.clone(), sequence_id));
+            ICA_OPERATION_RESULTS.remove(deps.storage, (port_id, sequence_id));
+        }
+    }
+
+    Ok(Response::default().add_attribute("pruned", to_remove.len().to_string()))
+}
+
+fn execute_set_retry_policy(deps: DepsMut, policy: RetryPolicy) -> StdResult<Response> {
+    RETRY_POLICY.save(deps.storage, &policy)?;
+    Ok(Response::default())
+}
+
+/// Resubmits the earliest tx whose retry is due. Only one tx is resubmitted per call, since the
+/// reply payload of a submitted tx is kept in a single slot until the reply is processed.
+fn execute_process_retry(deps: DepsMut, env: Env) -> StdResult<Response> {
+    let (scheduled_height, original_id) = RETRY_QUEUE
+        .keys(
+            deps.storage,
+            None,
+            Some(Bound::inclusive((env.block.height, u64::MAX))),
+            cosmwasm_std::Order::Ascending,
+        )
+        .next()
+        .transpose()?
+        .ok_or_else(|| StdError::generic_err("no retries are due"))?;
+    RETRY_QUEUE.remove(deps.storage, (scheduled_height, original_id));
+
+    let mut history = RETRIES.load(deps.storage, original_id)?;
+    let last_id = history.attempts.last().copied().unwrap_or(original_id);
+    let last = LEDGER.load(deps.storage, last_id)?;
+
+    // the ICA channel is still being reopened after a timeout, postpone the retry
+    if get_ica(deps.as_ref(), &env, &last.interchain_account_id).is_err() {
+        let policy = RETRY_POLICY.may_load(deps.storage)?.unwrap_or_default();
+        let next_attempt_height = env.block.height + policy.backoff_blocks.max(1);
+        RETRY_QUEUE.save(deps.storage, (next_attempt_height, original_id), &())?;
+        history.next_attempt_height = Some(next_attempt_height);
+        RETRIES.save(deps.storage, original_id, &history)?;
+        return Ok(Response::default()
+            .add_attribute("action", "retry_postponed")
+            .add_attribute("ledger_id", original_id.to_string()));
+    }
+
+    let (channel_id, sequence_id) = last
+        .channel_id
+        .zip(last.sequence_id)
+        .ok_or_else(|| StdError::generic_err("failed tx has no packet identifiers"))?;
+    let payload = read_sudo_payload(deps.storage, channel_id, sequence_id)?;
+
+    let res = do_submit_tx(
+        deps,
+        env,
+        last.sender,
+        SubmitTxInfo {
+            interchain_account_id: last.interchain_account_id,
+            msgs: last
+                .msgs
+                .into_iter()
+                .map(|msg| Any {
+                    type_url: msg.type_url,
+                    value: msg.value.to_vec(),
+                })
+                .collect(),
+            memo: last.memo,
+            timeout: Some(TimeoutSpec::AfterSeconds {
+                seconds: last.timeout,
+            }),
+            typed_results: payload.msgs.is_some(),
+            operation: payload.operation,
+            info: payload.info,
+            retry_of: Some(original_id),
+        },
+    )?;
+    Ok(res
+        .add_attribute("action", "retry")
+        .add_attribute("ledger_id", original_id.to_string()))
+}
+
 fn execute_clean_ack_results(deps: DepsMut) -> StdResult<Response> {
     let keys: Vec<StdResult<(String, u64)>> = ACKNOWLEDGEMENT_RESULTS
         .keys(deps.storage, None, None, cosmwasm_std::Order::Descending)
@@ -485,7 +1157,9 @@ pub fn sudo(mut deps: DepsMut, env: Env, msg: SudoMsg) -> StdResult<Response> {
         SudoMsg::Response { request, data } => {
             sudo_response(deps.branch(), env.clone(), request, data)?
         }
-        SudoMsg::Error { request, details } => sudo_error(deps.branch(), request, details)?,
+        SudoMsg::Error { request, details } => {
+            sudo_error(deps.branch(), env.clone(), request, details)?
+        }
         SudoMsg::Timeout { request } => sudo_timeout(deps.branch(), env.clone(), request)?,
         SudoMsg::OpenAck {
             port_id,
@@ -556,8 +1230,12 @@ pub fn sudo(mut deps: DepsMut, env: Env, msg: SudoMsg) -> StdResult<Response> {
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> StdResult<Response> {
+pub fn migrate(deps: DepsMut, _env: Env, msg: MigrateMsg) -> StdResult<Response> {
     deps.api.debug("WASMDEBUG: migrate");
+    if let Some(owner) = msg.owner {
+        let owner = deps.api.addr_validate(&owner)?;
+        OWNER.save(deps.storage, &owner)?;
+    }
     Ok(Response::default())
 }
 
@@ -627,39 +1305,30 @@ fn sudo_response(
     let parsed_data = decode_acknowledgement_response(data)?;
 
     let mut item_types = vec![];
+    let mut msg_results = vec![];
     for item in parsed_data {
-        let item_type = item.type_url.as_str();
-        item_types.push(item_type.to_string());
-        match item_type {
-            "/cosmos.staking.v1beta1.MsgUndelegateResponse" => {
-                let out: MsgUndelegateResponse = decode_message_response(&item.value)?;
-
-                let completion_time = out.completion_time.or_else(|| {
-                    let error_msg = "WASMDEBUG: sudo_response: Recoverable error. Failed to get completion time";
-                    deps.api
-                        .debug(error_msg);
-                    add_error_to_queue(deps.storage, error_msg.to_string());
-                    Some(prost_types::Timestamp::default())
-                });
-                deps.api
-                    .debug(format!("Undelegation completion time: {:?}", completion_time).as_str());
-            }
-            "/cosmos.staking.v1beta1.MsgDelegateResponse" => {
-                let _out: MsgDelegateResponse = decode_message_response(&item.value)?;
-            }
-            _ => {
-                deps.api.debug(
-                    format!(
-                        "This type of acknowledgement is not implemented: {:?}",
-                        payload
-                    )
-                    .as_str(),
-                );
-            }
-        }
+        item_types.push(item.type_url.clone());
+        msg_results.push(decode_msg_result(deps.branch(), item.type_url, item.value)?);
     }
 
     if let Some(payload) = payload {
+        // generic txs get a decoded result per message, the rest keep the list of response types
+        if let Some(operation) = payload.operation.clone() {
+            ICA_OPERATION_RESULTS.save(
+                deps.storage,
+                (payload.port_id.clone(), seq_id),
+                &IcaOperationResult {
+                    operation,
+                    results: msg_results.clone(),
+                },
+            )?;
+        }
+
+        let ack_result = if payload.msgs.is_some() {
+            AcknowledgementResult::SuccessResults(msg_results)
+        } else {
+            AcknowledgementResult::Success(item_types)
+        };
         // update but also check that we don't update same seq_id twice
         ACKNOWLEDGEMENT_RESULTS.update(
             deps.storage,
@@ -667,10 +1336,11 @@ fn sudo_response(
             |maybe_ack| -> StdResult<AcknowledgementResult> {
                 match maybe_ack {
                     Some(_ack) => Err(StdError::generic_err("trying to update same seq_id")),
-                    None => Ok(AcknowledgementResult::Success(item_types)),
+                    None => Ok(ack_result.clone()),
                 }
             },
         )?;
+        finish_ledger_entry(deps.storage, &env.block, payload.ledger_id, ack_result)?;
 
         deps.api
             .debug(format!("WASMDEBUG: payload received: {:?}", payload).as_str());
@@ -679,7 +1349,8 @@ fn sudo_response(
             let res = {
                 do_delegate(
                     deps.branch(),
-                    env,
+                    env.clone(),
+                    env.contract.address,
                     ExecuteDelegateInfo {
                         interchain_account_id: info.interchain_account_id,
                         validator: info.validator,
@@ -708,7 +1379,91 @@ fn sudo_response(
     Ok(Response::default())
 }
 
-fn sudo_timeout(deps: DepsMut, _env: Env, request: RequestPacket) -> StdResult<Response> {
+/// Decodes a single message response from the acknowledgement into a typed result.
+/// Unknown response types are stored as is.
+fn decode_msg_result(deps: DepsMut, type_url: String, data: Vec<u8>) -> StdResult<MsgResult> {
+    let result = match type_url.as_str() {
+        "/cosmos.staking.v1beta1.MsgUndelegateResponse" => {
+            let out: MsgUndelegateResponse = decode_message_response(&data)?;
+
+            let completion_time = out.completion_time.or_else(|| {
+                let error_msg =
+                    "WASMDEBUG: sudo_response: Recoverable error. Failed to get completion time";
+                deps.api.debug(error_msg);
+                add_error_to_queue(deps.storage, error_msg.to_string());
+                Some(prost_types::Timestamp::default())
+            });
+            deps.api
+                .debug(format!("Undelegation completion time: {:?}", completion_time).as_str());
+            MsgResult::Undelegate {
+                completion_time: completion_time.map(to_timestamp),
+            }
+        }
+        "/cosmos.staking.v1beta1.MsgDelegateResponse" => {
+            let _out: MsgDelegateResponse = decode_message_response(&data)?;
+            MsgResult::Delegate {}
+        }
+        "/cosmos.bank.v1beta1.MsgSendResponse" => {
+            let _out: MsgSendResponse = decode_message_response(&data)?;
+            MsgResult::BankSend {}
+        }
+        "/cosmos.staking.v1beta1.MsgBeginRedelegateResponse" => {
+            let out: MsgBeginRedelegateResponse = decode_message_response(&data)?;
+            MsgResult::BeginRedelegate {
+                completion_time: out.completion_time.map(to_timestamp),
+            }
+        }
+        "/cosmos.distribution.v1beta1.MsgWithdrawDelegatorRewardResponse" => {
+            let out: MsgWithdrawDelegatorRewardResponse = decode_message_response(&data)?;
+            let amount = out
+                .amount
+                .into_iter()
+                .map(|coin| -> StdResult<CwCoin> {
+                    Ok(CwCoin {
+                        amount: Uint128::from_str(&coin.amount)?,
+                        denom: coin.denom,
+                    })
+                })
+                .collect::<StdResult<Vec<CwCoin>>>()?;
+            MsgResult::WithdrawDelegatorReward { amount }
+        }
+        "/cosmos.distribution.v1beta1.MsgSetWithdrawAddressResponse" => {
+            let _out: MsgSetWithdrawAddressResponse = decode_message_response(&data)?;
+            MsgResult::SetWithdrawAddress {}
+        }
+        "/cosmos.gov.v1beta1.MsgVoteResponse" | "/cosmos.gov.v1.MsgVoteResponse" => {
+            // both versions of the response are empty messages
+            let _out: MsgVoteResponse = decode_message_response(&data)?;
+            MsgResult::Vote {}
+        }
+        "/ibc.applications.transfer.v1.MsgTransferResponse" => {
+            let out: MsgTransferResponse = decode_message_response(&data)?;
+            MsgResult::IbcTransfer {
+                sequence: out.sequence,
+            }
+        }
+        _ => {
+            deps.api.debug(
+                format!(
+                    "This type of acknowledgement is not implemented: {:?}",
+                    type_url
+                )
+                .as_str(),
+            );
+            MsgResult::Unknown {
+                type_url,
+                data: Binary::new(data),
+            }
+        }
+    };
+    Ok(result)
+}
+
+fn to_timestamp(t: prost_types::Timestamp) -> Timestamp {
+    Timestamp::from_seconds(t.seconds as u64).plus_nanos(t.nanos as u64)
+}
+
+fn sudo_timeout(mut deps: DepsMut, env: Env, request: RequestPacket) -> StdResult<Response> {
     deps.api
         .debug(format!("WASMDEBUG: sudo timeout request: {:?}", request).as_str());
 
@@ -723,17 +1478,25 @@ fn sudo_timeout(deps: DepsMut, _env: Env, request: RequestPacket) -> StdResult<R
     // update but also check that we don't update same seq_id twice
     let payload = read_sudo_payload(deps.storage, channel_id, seq_id).ok();
     if let Some(payload) = payload {
+        let ack_result = AcknowledgementResult::Timeout(payload.message);
         // update but also check that we don't update same seq_id twice
         ACKNOWLEDGEMENT_RESULTS.update(
             deps.storage,
-            (payload.port_id, seq_id),
+            (payload.port_id.clone(), seq_id),
             |maybe_ack| -> StdResult<AcknowledgementResult> {
                 match maybe_ack {
                     Some(_ack) => Err(StdError::generic_err("trying to update same seq_id")),
-                    None => Ok(AcknowledgementResult::Timeout(payload.message)),
+                    None => Ok(ack_result.clone()),
                 }
             },
         )?;
+        finish_ledger_entry(deps.storage, &env.block, payload.ledger_id, ack_result)?;
+
+        let policy = RETRY_POLICY.may_load(deps.storage)?.unwrap_or_default();
+        if policy.retry_on_timeout {
+            schedule_retry(deps.branch(), &env, &policy, payload.ledger_id)?;
+            return reopen_ica_channel(deps, env, payload.port_id);
+        }
     } else {
         let error_msg = "WASMDEBUG: Error: Unable to read sudo payload";
         deps.api.debug(error_msg);
@@ -743,7 +1506,12 @@ fn sudo_timeout(deps: DepsMut, _env: Env, request: RequestPacket) -> StdResult<R
     Ok(Response::default())
 }
 
-fn sudo_error(deps: DepsMut, request: RequestPacket, details: String) -> StdResult<Response> {
+fn sudo_error(
+    mut deps: DepsMut,
+    env: Env,
+    request: RequestPacket,
+    details: String,
+) -> StdResult<Response> {
     deps.api
         .debug(format!("WASMDEBUG: sudo error: {}", details).as_str());
     deps.api
@@ -759,6 +1527,12 @@ fn sudo_error(deps: DepsMut, request: RequestPacket, details: String) -> StdResu
     let payload = read_sudo_payload(deps.storage, channel_id, seq_id).ok();
 
     if let Some(payload) = payload {
+        let policy = RETRY_POLICY.may_load(deps.storage)?.unwrap_or_default();
+        let retryable = policy
+            .retryable_errors
+            .iter()
+            .any(|error| details.contains(error.as_str()));
+        let ack_result = AcknowledgementResult::Error((payload.message, details));
         // update but also check that we don't update same seq_id twice
         ACKNOWLEDGEMENT_RESULTS.update(
             deps.storage,
@@ -766,10 +1540,14 @@ fn sudo_error(deps: DepsMut, request: RequestPacket, details: String) -> StdResu
             |maybe_ack| -> StdResult<AcknowledgementResult> {
                 match maybe_ack {
                     Some(_ack) => Err(StdError::generic_err("trying to update same seq_id")),
-                    None => Ok(AcknowledgementResult::Error((payload.message, details))),
+                    None => Ok(ack_result.clone()),
                 }
             },
         )?;
+        finish_ledger_entry(deps.storage, &env.block, payload.ledger_id, ack_result)?;
+        if retryable {
+            schedule_retry(deps.branch(), &env, &policy, payload.ledger_id)?;
+        }
     } else {
         let error_msg = "WASMDEBUG: Error: Unable to read sudo payload";
         deps.api.debug(error_msg);
@@ -779,6 +1557,65 @@ fn sudo_error(deps: DepsMut, request: RequestPacket, details: String) -> StdResu
     Ok(Response::default())
 }
 
+/// Schedules a resubmission of the failed tx unless it has run out of attempts
+fn schedule_retry(
+    deps: DepsMut,
+    env: &Env,
+    policy: &RetryPolicy,
+    ledger_id: Option<u64>,
+) -> StdResult<()> {
+    let entry = match ledger_id {
+        Some(ledger_id) => LEDGER.load(deps.storage, ledger_id)?,
+        None => return Ok(()),
+    };
+    let original_id = entry.retry_of.unwrap_or(entry.id);
+    let mut history = RETRIES
+        .may_load(deps.storage, original_id)?
+        .unwrap_or(RetryHistory {
+            original_id,
+            attempts: vec![],
+            next_attempt_height: None,
+        });
+    if history.attempts.len() >= policy.max_attempts as usize {
+        deps.api.debug(
+            format!(
+                "WASMDEBUG: retry attempts are exhausted for ledger id {}",
+                original_id
+            )
+            .as_str(),
+        );
+        return Ok(());
+    }
+
+    let next_attempt_height = env.block.height + policy.backoff_blocks;
+    RETRY_QUEUE.save(deps.storage, (next_attempt_height, original_id), &())?;
+    history.next_attempt_height = Some(next_attempt_height);
+    RETRIES.save(deps.storage, original_id, &history)
+}
+
+/// A timeout closes an ordered channel, so the ICA is registered again to reopen it.
+fn reopen_ica_channel(deps: DepsMut, env: Env, port_id: String) -> StdResult<Response> {
+    let registration = match ICA_REGISTRATIONS.may_load(deps.storage, port_id.clone())? {
+        Some(registration) => registration,
+        None => return Ok(Response::default()),
+    };
+    // the account is already being registered again after another timed out packet
+    let is_open = INTERCHAIN_ACCOUNTS
+        .may_load(deps.storage, port_id)?
+        .flatten()
+        .is_some();
+    if registration.ordering == Some(Order::Unordered) || !is_open {
+        return Ok(Response::default());
+    }
+    execute_register_ica(
+        deps,
+        env,
+        registration.connection_id,
+        registration.interchain_account_id,
+        registration.ordering,
+    )
+}
+
 fn prepare_sudo_payload(mut deps: DepsMut, _env: Env, msg: Reply) -> StdResult<Response> {
     let payload = read_reply_payload(deps.storage)?;
     let resp: MsgSubmitTxResponse = decode_message_response(
@@ -795,6 +1632,7 @@ fn prepare_sudo_payload(mut deps: DepsMut, _env: Env, msg: Reply) -> StdResult<R
         .debug(format!("WASMDEBUG: reply msg: {:?}", resp).as_str());
     let seq_id = resp.sequence_id;
     let channel_id = resp.channel;
+    set_ledger_entry_packet(deps.storage, payload.ledger_id, channel_id.clone(), seq_id)?;
     save_sudo_payload(deps.branch().storage, channel_id, seq_id, payload)?;
     Ok(Response::new())
 }
@@ -849,6 +1687,7 @@ pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> StdResult<Response> {
             Ok(Response::default())
         }
         REGISTER_ICA_REPLY_ID => prepare_register_ica(deps, msg),
+        RESUBMIT_FAILURE_REPLY_ID => resubmit_failure_reply(deps, env, msg),
         _ => Err(StdError::generic_err(format!(
             "unsupported reply message id {}",
             msg.id
@@ -1,6 +1,13 @@
-use crate::storage::{AcknowledgementResult, IntegrationTestsSudoFailureMock};
-use cosmwasm_std::Uint128;
+pub use crate::storage::VoteOption;
+use crate::storage::{
+    AcknowledgementResult, IntegrationTestsSudoFailureMock, LedgerEntry, LedgerRetention,
+    LedgerStatus, RetryPolicy,
+};
+pub use contract_failures::{FailureInfo, FailureKind};
+use cosmwasm_std::{AnyMsg, Timestamp, Uint128};
+use ibc_timeout::TimeoutSpec;
 use neutron_std::types::ibc::core::channel::v1::Order;
+pub use ownership::OwnershipResponse;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -23,15 +30,64 @@ pub enum QueryMsg {
     },
     // this query returns all acknowledgements stored in the contract's state
     AcknowledgementResults {},
+    // this query returns the operation record with decoded results of a redelegation,
+    // reward withdrawal, withdraw address change or vote after interchain transaction
+    OperationResult {
+        interchain_account_id: String,
+        sequence_id: u64,
+    },
+    // this query returns a single entry of the operations ledger
+    LedgerEntry {
+        id: u64,
+    },
+    // this query returns a page of the operations ledger, optionally filtered by ICA id,
+    // status and submission time range
+    Ledger {
+        interchain_account_id: Option<String>,
+        status: Option<LedgerStatus>,
+        from_time: Option<Timestamp>,
+        to_time: Option<Timestamp>,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // this query returns the current retry policy
+    RetryPolicy {},
+    // this query returns the retry history of a tx by the ledger id of any of its submissions
+    RetryHistory {
+        ledger_id: u64,
+    },
+    // this query returns the owner and the pending owner of the contract
+    Ownership {},
+    // this query returns the addresses allowed to execute an action besides the owner
+    Operators {
+        action: String,
+    },
     // this query returns non-critical errors list
     ErrorsQueue {},
+    // this query returns a page of the contract's contractmanager failures with their sudo
+    // payloads decoded
+    Failures {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // this query returns the outcomes of the failures resubmitted with ResubmitFailures
+    Resubmissions {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
-pub struct MigrateMsg {}
+pub struct MigrateMsg {
+    /// sets the owner of a contract instantiated before ownership has been introduced
+    pub owner: Option<String>,
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
-pub struct InstantiateMsg {}
+pub struct InstantiateMsg {
+    /// defaults to the instantiator
+    pub owner: Option<String>,
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
@@ -49,26 +105,94 @@ pub enum ExecuteMsg {
         validator: String,
         amount: Uint128,
         denom: String,
-        timeout: Option<u64>,
+        timeout: Option<TxTimeout>,
     },
     DelegateDoubleAck {
         interchain_account_id: String,
         validator: String,
         amount: Uint128,
         denom: String,
-        timeout: Option<u64>,
+        timeout: Option<TxTimeout>,
     },
     Undelegate {
         interchain_account_id: String,
         validator: String,
         amount: Uint128,
         denom: String,
-        timeout: Option<u64>,
+        timeout: Option<TxTimeout>,
+    },
+    BeginRedelegate {
+        interchain_account_id: String,
+        validator_src: String,
+        validator_dst: String,
+        amount: Uint128,
+        denom: String,
+        timeout: Option<TxTimeout>,
+    },
+    WithdrawDelegatorReward {
+        interchain_account_id: String,
+        validator: String,
+        timeout: Option<TxTimeout>,
+    },
+    SetWithdrawAddress {
+        interchain_account_id: String,
+        withdraw_address: String,
+        timeout: Option<TxTimeout>,
+    },
+    Vote {
+        interchain_account_id: String,
+        proposal_id: u64,
+        option: VoteOption,
+        timeout: Option<TxTimeout>,
+    },
+    /// Wraps arbitrary protobuf messages into a single interchain transaction.
+    /// A decoded result for every message is stored on acknowledgement.
+    SubmitTx {
+        interchain_account_id: String,
+        msgs: Vec<AnyMsg>,
+        memo: Option<String>,
+        timeout: Option<TxTimeout>,
     },
     CleanAckResults {},
+    SetLedgerRetention {
+        retention: LedgerRetention,
+    },
+    /// Removes up to `limit` finished ledger entries which fall out of the retention policy
+    /// along with their acknowledgement results.
+    PruneLedger {
+        limit: Option<u32>,
+    },
+    SetRetryPolicy {
+        policy: RetryPolicy,
+    },
+    /// Resubmits the earliest failed tx whose retry is due according to the retry policy.
+    /// Can be executed by anyone.
+    ProcessRetry {},
     ResubmitFailure {
         failure_id: u64,
     },
+    /// Resubmits up to `max` contractmanager failures after `start_after` of acknowledgements
+    /// and timeouts on `channel_id` whose error contains `error_contains`. Every failure is
+    /// resubmitted only once, a resubmission failing again doesn't fail the others and its error
+    /// is recorded. A few pages of failures are checked at once, the last checked failure id is
+    /// returned in the `last_failure_id` attribute to continue from.
+    ResubmitFailures {
+        channel_id: Option<String>,
+        error_contains: Option<String>,
+        start_after: Option<u64>,
+        max: Option<u32>,
+    },
+    /// Starts a two-step ownership transfer, completed by `AcceptOwnership` from the new owner.
+    TransferOwnership {
+        new_owner: String,
+    },
+    AcceptOwnership {},
+    /// Replaces the addresses allowed to execute `action` besides the owner. The action is
+    /// the snake_case name of an execute message, e.g. `delegate`.
+    SetOperators {
+        action: String,
+        operators: Vec<String>,
+    },
     /// Used only in integration tests framework to simulate failures.
     /// After executing this message, any sudo call to the contract will result in an error.
     IntegrationTestsSetSudoFailureMock {
@@ -90,6 +214,65 @@ pub enum ExecuteMsg {
     IntegrationTestsSudoSubmsg {},
 }
 
+impl ExecuteMsg {
+    /// Name of the action the operators allow-list is kept for
+    pub fn action(&self) -> &'static str {
+        match self {
+            ExecuteMsg::Register { .. } => "register",
+            ExecuteMsg::SetFees { .. } => "set_fees",
+            ExecuteMsg::Delegate { .. } => "delegate",
+            ExecuteMsg::DelegateDoubleAck { .. } => "delegate_double_ack",
+            ExecuteMsg::Undelegate { .. } => "undelegate",
+            ExecuteMsg::BeginRedelegate { .. } => "begin_redelegate",
+            ExecuteMsg::WithdrawDelegatorReward { .. } => "withdraw_delegator_reward",
+            ExecuteMsg::SetWithdrawAddress { .. } => "set_withdraw_address",
+            ExecuteMsg::Vote { .. } => "vote",
+            ExecuteMsg::SubmitTx { .. } => "submit_tx",
+            ExecuteMsg::CleanAckResults {} => "clean_ack_results",
+            ExecuteMsg::SetLedgerRetention { .. } => "set_ledger_retention",
+            ExecuteMsg::PruneLedger { .. } => "prune_ledger",
+            ExecuteMsg::SetRetryPolicy { .. } => "set_retry_policy",
+            ExecuteMsg::ProcessRetry {} => "process_retry",
+            ExecuteMsg::ResubmitFailure { .. } => "resubmit_failure",
+            ExecuteMsg::ResubmitFailures { .. } => "resubmit_failures",
+            ExecuteMsg::TransferOwnership { .. } => "transfer_ownership",
+            ExecuteMsg::AcceptOwnership {} => "accept_ownership",
+            ExecuteMsg::SetOperators { .. } => "set_operators",
+            ExecuteMsg::IntegrationTestsSetSudoFailureMock { .. } => {
+                "integration_tests_set_sudo_failure_mock"
+            }
+            ExecuteMsg::IntegrationTestsSetSudoSubmsgFailureMock {} => {
+                "integration_tests_set_sudo_submsg_failure_mock"
+            }
+            ExecuteMsg::IntegrationTestsSetSudoSubmsgReplyFailureMock {} => {
+                "integration_tests_set_sudo_submsg_reply_failure_mock"
+            }
+            ExecuteMsg::IntegrationTestsUnsetSudoFailureMock {} => {
+                "integration_tests_unset_sudo_failure_mock"
+            }
+            ExecuteMsg::IntegrationTestsSudoSubmsg {} => "integration_tests_sudo_submsg",
+        }
+    }
+}
+
+/// Timeout of an interchain tx. A number is a number of seconds, the same as `after_seconds`,
+/// as the `timeout` used to be.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(untagged)]
+pub enum TxTimeout {
+    Seconds(u64),
+    Spec(TimeoutSpec),
+}
+
+impl From<TxTimeout> for TimeoutSpec {
+    fn from(timeout: TxTimeout) -> Self {
+        match timeout {
+            TxTimeout::Seconds(seconds) => TimeoutSpec::AfterSeconds { seconds },
+            TxTimeout::Spec(spec) => spec,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct AcknowledgementResultsResponse {
     pub ack_result: AcknowledgementResult,
@@ -97,6 +280,14 @@ pub struct AcknowledgementResultsResponse {
     pub sequence_id: u64,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct LedgerResponse {
+    pub entries: Vec<LedgerEntry>,
+    /// id to pass as `start_after` to continue the query, none once the ledger has been
+    /// scanned to the end
+    pub next_start_after: Option<u64>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct Fees {
     pub denom: String,
@@ -1,7 +1,9 @@
-use cosmwasm_std::Uint128;
-use cosmwasm_std::{from_json, to_json_vec, Binary, Order, StdResult, Storage};
+pub use contract_failures::Resubmission;
+use cosmwasm_std::{from_json, to_json_vec, Binary, BlockInfo, Order, StdResult, Storage};
+use cosmwasm_std::{Addr, AnyMsg, Coin, Timestamp, Uint128};
 use cw_storage_plus::{Item, Map};
 use neutron_std::types::cosmos::base::v1beta1::Coin as StdCoin;
+use neutron_std::types::ibc::core::channel::v1::Order as ChannelOrder;
 use neutron_std::types::neutron::feerefunder::Fee;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -12,6 +14,50 @@ pub struct SudoPayload {
     pub message: String,
     pub port_id: String,
     pub info: Option<DoubleDelegateInfo>,
+    /// type urls of the messages sent within the tx, set only for generic `SubmitTx` calls
+    pub msgs: Option<Vec<String>>,
+    /// operation requested by one of the dedicated execute messages, e.g. `BeginRedelegate`
+    pub operation: Option<IcaOperation>,
+    /// id of the entry in the operations ledger
+    pub ledger_id: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IcaOperation {
+    BeginRedelegate {
+        validator_src: String,
+        validator_dst: String,
+        amount: Coin,
+    },
+    WithdrawDelegatorReward {
+        validator: String,
+    },
+    SetWithdrawAddress {
+        withdraw_address: String,
+    },
+    Vote {
+        proposal_id: u64,
+        option: VoteOption,
+    },
+}
+
+/// Mirrors `cosmos.gov.v1beta1.VoteOption` values
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum VoteOption {
+    Yes = 1,
+    Abstain = 2,
+    No = 3,
+    NoWithVeto = 4,
+}
+
+/// Operation sent over ICA paired with the decoded results from its acknowledgement
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct IcaOperationResult {
+    pub operation: IcaOperation,
+    pub results: Vec<MsgResult>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
@@ -27,34 +73,260 @@ pub const SUDO_PAYLOAD_REPLY_ID: u64 = 1;
 pub const SUDO_FAILING_SUBMSG_REPLY_ID: u64 = 2;
 // only used to make sure `sudo()` handler gets OpenAck message with correct port_id and channel_id
 pub const REGISTER_ICA_REPLY_ID: u64 = 3;
+pub const RESUBMIT_FAILURE_REPLY_ID: u64 = 4;
 
 pub const IBC_FEE: Item<Option<Fee>> = Item::new("ibc_fee");
 pub const REGISTER_FEE: Item<Vec<StdCoin>> = Item::new("register_fee");
 pub const REPLY_ID_STORAGE: Item<Vec<u8>> = Item::new("reply_queue_id");
 pub const SUDO_PAYLOAD: Map<(String, u64), Vec<u8>> = Map::new("sudo_payload");
+
 pub const INTERCHAIN_ACCOUNTS: Map<String, Option<(String, String)>> =
     Map::new("interchain_accounts");
 // only used to make sure `sudo()` handler gets OpenAck message with correct port_id and channel_id
 pub const ICA_CHANNELS: Map<String, String> = Map::new("ica_channels");
+// registration parameters of the contract's ICAs, keyed by port_id
+pub const ICA_REGISTRATIONS: Map<String, IcaRegistration> = Map::new("ica_registrations");
 
 // interchain transaction responses - ack/err/timeout state to query later
 pub const ACKNOWLEDGEMENT_RESULTS: Map<(String, u64), AcknowledgementResult> =
     Map::new("acknowledgement_results");
 
+// successfully acknowledged operations with their decoded results, keyed by (port_id, seq_id)
+pub const ICA_OPERATION_RESULTS: Map<(String, u64), IcaOperationResult> =
+    Map::new("ica_operation_results");
+
 pub const ERRORS_QUEUE: Map<u32, String> = Map::new("errors_queue");
 
+// every interchain tx sent by the contract along with its final outcome
+pub const LEDGER: Map<u64, LedgerEntry> = Map::new("ledger");
+pub const LEDGER_NEXT_ID: Item<u64> = Item::new("ledger_next_id");
+// number of entries in the ledger
+pub const LEDGER_COUNT: Item<u64> = Item::new("ledger_count");
+// ledger ids indexed by (interchain_account_id, id) and by (status, id)
+pub const LEDGER_BY_ICA: Map<(&str, u64), ()> = Map::new("ledger_by_ica");
+pub const LEDGER_BY_STATUS: Map<(&str, u64), ()> = Map::new("ledger_by_status");
+pub const LEDGER_RETENTION: Item<LedgerRetention> = Item::new("ledger_retention");
+
+pub const RETRY_POLICY: Item<RetryPolicy> = Item::new("retry_policy");
+// retry history of failed txs, keyed by the ledger id of the first submission
+pub const RETRIES: Map<u64, RetryHistory> = Map::new("retries");
+// scheduled resubmissions, keyed by (height since which the retry is allowed, ledger id of the first submission)
+pub const RETRY_QUEUE: Map<(u64, u64), ()> = Map::new("retry_queue");
+
+/// Parameters the ICA has been registered with, used to reopen its channel after a timeout
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct IcaRegistration {
+    pub interchain_account_id: String,
+    pub connection_id: String,
+    pub ordering: Option<ChannelOrder>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct LedgerEntry {
+    pub id: u64,
+    /// address which has requested the tx
+    pub sender: Addr,
+    pub interchain_account_id: String,
+    pub msgs: Vec<AnyMsg>,
+    pub memo: String,
+    pub operation: Option<IcaOperation>,
+    /// IBC fees locked for the tx at the moment of sending
+    pub fee: Option<IcaFee>,
+    /// tx timeout in seconds
+    pub timeout: u64,
+    /// id of the entry of the first submission if the tx is a retry of a failed one
+    pub retry_of: Option<u64>,
+    pub submitted_height: u64,
+    pub submitted_time: Timestamp,
+    /// outgoing packet identifiers, known after the tx has been sent
+    pub channel_id: Option<String>,
+    pub sequence_id: Option<u64>,
+    pub status: LedgerStatus,
+    pub outcome: Option<AcknowledgementResult>,
+    pub finished_height: Option<u64>,
+    pub finished_time: Option<Timestamp>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct IcaFee {
+    pub recv_fee: Vec<Coin>,
+    pub ack_fee: Vec<Coin>,
+    pub timeout_fee: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LedgerStatus {
+    Pending,
+    Success,
+    Error,
+    Timeout,
+}
+
+impl LedgerStatus {
+    /// Key of the status in the `LEDGER_BY_STATUS` index
+    pub fn key(&self) -> &'static str {
+        match self {
+            LedgerStatus::Pending => "pending",
+            LedgerStatus::Success => "success",
+            LedgerStatus::Error => "error",
+            LedgerStatus::Timeout => "timeout",
+        }
+    }
+}
+
+/// Defines which finished ledger entries are removed by `PruneLedger`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub struct LedgerRetention {
+    /// entries finished more than `max_age` seconds ago are removed
+    pub max_age: Option<u64>,
+    /// the oldest finished entries are removed while there are more than `max_entries` entries
+    pub max_entries: Option<u64>,
+}
+
+/// Defines whether and when failed interchain txs are resubmitted by `ProcessRetry`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub struct RetryPolicy {
+    /// maximum number of resubmissions of a single tx, zero disables retries
+    pub max_attempts: u32,
+    /// number of blocks to wait after a failure before the tx can be resubmitted
+    pub backoff_blocks: u64,
+    /// whether timed out txs are retried. The ICA is registered again on timeout in this case,
+    /// since a timeout closes an ordered channel
+    pub retry_on_timeout: bool,
+    /// an errored tx is retried if its error details contain any of these substrings
+    pub retryable_errors: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct RetryHistory {
+    /// ledger id of the first submission
+    pub original_id: u64,
+    /// ledger ids of the resubmissions, in order
+    pub attempts: Vec<u64>,
+    /// height since which the next resubmission is allowed, if one is scheduled
+    pub next_attempt_height: Option<u64>,
+}
+
+pub fn add_ledger_entry(store: &mut dyn Storage, mut entry: LedgerEntry) -> StdResult<u64> {
+    let id = LEDGER_NEXT_ID.may_load(store)?.unwrap_or_default();
+    entry.id = id;
+    LEDGER.save(store, id, &entry)?;
+    LEDGER_BY_ICA.save(store, (&entry.interchain_account_id, id), &())?;
+    LEDGER_BY_STATUS.save(store, (entry.status.key(), id), &())?;
+    LEDGER_NEXT_ID.save(store, &(id + 1))?;
+    let count = LEDGER_COUNT.may_load(store)?.unwrap_or_default();
+    LEDGER_COUNT.save(store, &(count + 1))?;
+    Ok(id)
+}
+
+pub fn remove_ledger_entry(store: &mut dyn Storage, entry: &LedgerEntry) -> StdResult<()> {
+    LEDGER.remove(store, entry.id);
+    LEDGER_BY_ICA.remove(store, (&entry.interchain_account_id, entry.id));
+    LEDGER_BY_STATUS.remove(store, (entry.status.key(), entry.id));
+    let count = LEDGER_COUNT.may_load(store)?.unwrap_or_default();
+    LEDGER_COUNT.save(store, &count.saturating_sub(1))
+}
+
+pub fn add_retry_attempt(
+    store: &mut dyn Storage,
+    original_id: u64,
+    ledger_id: u64,
+) -> StdResult<()> {
+    let mut history = RETRIES.load(store, original_id)?;
+    history.attempts.push(ledger_id);
+    history.next_attempt_height = None;
+    RETRIES.save(store, original_id, &history)
+}
+
+pub fn set_ledger_entry_packet(
+    store: &mut dyn Storage,
+    ledger_id: Option<u64>,
+    channel_id: String,
+    seq_id: u64,
+) -> StdResult<()> {
+    if let Some(entry) = ledger_id.and_then(|id| LEDGER.may_load(store, id).transpose()) {
+        let mut entry = entry?;
+        entry.channel_id = Some(channel_id);
+        entry.sequence_id = Some(seq_id);
+        LEDGER.save(store, entry.id, &entry)?;
+    }
+    Ok(())
+}
+
+pub fn finish_ledger_entry(
+    store: &mut dyn Storage,
+    block: &BlockInfo,
+    ledger_id: Option<u64>,
+    outcome: AcknowledgementResult,
+) -> StdResult<()> {
+    if let Some(entry) = ledger_id.and_then(|id| LEDGER.may_load(store, id).transpose()) {
+        let mut entry = entry?;
+        LEDGER_BY_STATUS.remove(store, (entry.status.key(), entry.id));
+        entry.status = match outcome {
+            AcknowledgementResult::Success(_) | AcknowledgementResult::SuccessResults(_) => {
+                LedgerStatus::Success
+            }
+            AcknowledgementResult::Error(_) => LedgerStatus::Error,
+            AcknowledgementResult::Timeout(_) => LedgerStatus::Timeout,
+        };
+        entry.outcome = Some(outcome);
+        entry.finished_height = Some(block.height);
+        entry.finished_time = Some(block.time);
+        LEDGER.save(store, entry.id, &entry)?;
+        LEDGER_BY_STATUS.save(store, (entry.status.key(), entry.id), &())?;
+    }
+    Ok(())
+}
+
 /// Serves for storing acknowledgement calls for interchain transactions
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum AcknowledgementResult {
     /// Success - Got success acknowledgement in sudo with array of message item types in it
     Success(Vec<String>),
+    /// SuccessResults - Got success acknowledgement in sudo for a generic tx with a decoded result
+    /// for every message in it
+    SuccessResults(Vec<MsgResult>),
     /// Error - Got error acknowledgement in sudo with payload message in it and error details
     Error((String, String)),
     /// Timeout - Got timeout acknowledgement in sudo with payload message in it
     Timeout(String),
 }
 
+/// Decoded response of a single message executed on the host chain
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum MsgResult {
+    Delegate {},
+    Undelegate {
+        completion_time: Option<Timestamp>,
+    },
+    BankSend {},
+    BeginRedelegate {
+        completion_time: Option<Timestamp>,
+    },
+    WithdrawDelegatorReward {
+        amount: Vec<Coin>,
+    },
+    SetWithdrawAddress {},
+    Vote {},
+    IbcTransfer {
+        sequence: u64,
+    },
+    /// Unknown - response type the contract doesn't know how to decode, stored as is
+    Unknown {
+        type_url: String,
+        data: Binary,
+    },
+}
+
 pub fn save_reply_payload(store: &mut dyn Storage, payload: SudoPayload) -> StdResult<()> {
     REPLY_ID_STORAGE.save(store, &to_json_vec(&payload)?)
 }
@@ -10,19 +10,47 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::contract::sudo;
-use crate::msg::ExecuteMsg;
-use crate::storage::IntegrationTestsSudoFailureMock;
+use crate::contract::{instantiate, query, reply, sudo};
+use crate::msg::{
+    ExecuteMsg, FailureInfo, FailureKind, Fees, InstantiateMsg, LedgerResponse, OwnershipResponse,
+    QueryMsg, TxTimeout,
+};
+use crate::storage::{
+    save_sudo_payload, AcknowledgementResult, IcaOperation, IcaOperationResult,
+    IntegrationTestsSudoFailureMock, LedgerRetention, LedgerStatus, MsgResult, Resubmission,
+    RetryHistory, RetryPolicy, SudoPayload, ACKNOWLEDGEMENT_RESULTS, IBC_FEE, INTERCHAIN_ACCOUNTS,
+    LEDGER_COUNT, REGISTER_ICA_REPLY_ID, SUDO_PAYLOAD_REPLY_ID,
+};
 use crate::{
     contract::{execute, query_errors_queue},
     storage::{add_error_to_queue, read_errors_from_queue, ERRORS_QUEUE},
 };
+use cosmos_sdk_proto::cosmos::base::abci::v1beta1::TxMsgData;
+use cosmos_sdk_proto::cosmos::base::v1beta1::Coin as ProtoCoin;
+use cosmos_sdk_proto::cosmos::distribution::v1beta1::MsgWithdrawDelegatorRewardResponse;
+use cosmos_sdk_proto::cosmos::staking::v1beta1::{MsgBeginRedelegateResponse, MsgDelegateResponse};
+use cosmos_sdk_proto::Any;
 use cosmwasm_std::testing::{
     message_info, mock_dependencies as cw_mock_dependencies, mock_env, MockApi, MockQuerier,
     MockStorage,
 };
-use cosmwasm_std::{from_json, Addr, OwnedDeps, StdError};
+use cosmwasm_std::{
+    from_json, Addr, Binary, Coin, Deps, Env, MsgResponse, OwnedDeps, Reply, StdError, Storage,
+    SubMsgResponse, SubMsgResult, Timestamp, Uint128,
+};
+use ibc_timeout::TimeoutSpec;
+use neutron_sdk::interchain_txs::helpers::get_port_id;
 use neutron_sdk::sudo::msg::{RequestPacket, SudoMsg};
+use neutron_std::types::cosmos::base::v1beta1::Coin as StdCoin;
+use neutron_std::types::neutron::feerefunder::Fee;
+use neutron_std::types::neutron::interchaintxs::v1::{
+    MsgSubmitTxResponse, QueryInterchainAccountAddressResponse,
+};
+use neutron_test_support::{
+    proto, ContractWrapper, Delivery, MockIca, NeutronApp, NeutronMockQuerier, RELAYER_ADDR,
+};
+use ownership::OWNER;
+use prost::Message;
 use std::marker::PhantomData;
 
 pub fn mock_dependencies() -> OwnedDeps<MockStorage, MockApi, MockQuerier> {
@@ -94,6 +122,13 @@ fn test_errors_queue() {
 #[test]
 fn test_failure_mocks() {
     let mut deps = cw_mock_dependencies();
+    instantiate(
+        deps.as_mut(),
+        mock_env(),
+        message_info(&Addr::unchecked(""), &[]),
+        InstantiateMsg { owner: None },
+    )
+    .unwrap();
     execute(
         deps.as_mut(),
         mock_env(),
@@ -124,3 +159,1181 @@ fn test_failure_mocks() {
     let err = sudo(deps.as_mut(), mock_env(), sudo_resp).unwrap_err();
     assert_eq!(err, StdError::generic_err("Integrations test mock error"));
 }
+
+fn build_request_packet(sequence: u64, source_channel: &str) -> RequestPacket {
+    RequestPacket {
+        sequence: Some(sequence),
+        source_port: Some("src_port".to_string()),
+        source_channel: Some(source_channel.to_string()),
+        destination_port: Some("dst_port".to_string()),
+        destination_channel: Some("dst_channel".to_string()),
+        data: None,
+        timeout_height: None,
+        timeout_timestamp: None,
+    }
+}
+
+#[test]
+fn test_submit_tx_ack_results() {
+    let mut deps = cw_mock_dependencies();
+    let port_id = "icacontroller-cosmos2contract.test".to_string();
+
+    save_sudo_payload(
+        &mut deps.storage,
+        "channel-0".to_string(),
+        1u64,
+        SudoPayload {
+            message: "submit_tx".to_string(),
+            port_id: port_id.clone(),
+            info: None,
+            msgs: Some(vec![
+                "/cosmos.staking.v1beta1.MsgBeginRedelegate".to_string(),
+                "/cosmos.bank.v1beta1.MsgSend".to_string(),
+                "/some.custom.MsgDoSomething".to_string(),
+            ]),
+            operation: None,
+            ledger_id: None,
+        },
+    )
+    .unwrap();
+
+    let ack = TxMsgData {
+        msg_responses: vec![
+            Any {
+                type_url: "/cosmos.staking.v1beta1.MsgBeginRedelegateResponse".to_string(),
+                value: MsgBeginRedelegateResponse {
+                    completion_time: Some(prost_types::Timestamp {
+                        seconds: 1_700_000_000,
+                        nanos: 0,
+                    }),
+                }
+                .encode_to_vec(),
+            },
+            Any {
+                type_url: "/cosmos.bank.v1beta1.MsgSendResponse".to_string(),
+                value: vec![],
+            },
+            Any {
+                type_url: "/some.custom.MsgDoSomethingResponse".to_string(),
+                value: vec![1, 2, 3],
+            },
+        ],
+        ..Default::default()
+    };
+
+    sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::Response {
+            request: build_request_packet(1u64, "channel-0"),
+            data: Binary::from(ack.encode_to_vec()),
+        },
+    )
+    .unwrap();
+
+    let ack_result = ACKNOWLEDGEMENT_RESULTS
+        .load(&deps.storage, (port_id, 1u64))
+        .unwrap();
+    assert_eq!(
+        ack_result,
+        AcknowledgementResult::SuccessResults(vec![
+            MsgResult::BeginRedelegate {
+                completion_time: Some(Timestamp::from_seconds(1_700_000_000)),
+            },
+            MsgResult::BankSend {},
+            MsgResult::Unknown {
+                type_url: "/some.custom.MsgDoSomethingResponse".to_string(),
+                data: Binary::from(vec![1, 2, 3]),
+            },
+        ])
+    );
+}
+
+#[test]
+fn test_operation_result() {
+    let mut deps = cw_mock_dependencies();
+    let env = mock_env();
+    let port_id = get_port_id(env.contract.address.as_str(), "test");
+    let operation = IcaOperation::WithdrawDelegatorReward {
+        validator: "cosmosvaloper1".to_string(),
+    };
+
+    save_sudo_payload(
+        &mut deps.storage,
+        "channel-0".to_string(),
+        2u64,
+        SudoPayload {
+            message: "submit_tx".to_string(),
+            port_id,
+            info: None,
+            msgs: Some(vec![
+                "/cosmos.distribution.v1beta1.MsgWithdrawDelegatorReward".to_string(),
+            ]),
+            operation: Some(operation.clone()),
+            ledger_id: None,
+        },
+    )
+    .unwrap();
+
+    let ack = TxMsgData {
+        msg_responses: vec![Any {
+            type_url: "/cosmos.distribution.v1beta1.MsgWithdrawDelegatorRewardResponse".to_string(),
+            value: MsgWithdrawDelegatorRewardResponse {
+                amount: vec![ProtoCoin {
+                    denom: "uatom".to_string(),
+                    amount: "1500".to_string(),
+                }],
+            }
+            .encode_to_vec(),
+        }],
+        ..Default::default()
+    };
+
+    sudo(
+        deps.as_mut(),
+        env.clone(),
+        SudoMsg::Response {
+            request: build_request_packet(2u64, "channel-0"),
+            data: Binary::from(ack.encode_to_vec()),
+        },
+    )
+    .unwrap();
+
+    let res: Option<IcaOperationResult> = from_json(
+        query(
+            deps.as_ref(),
+            env,
+            QueryMsg::OperationResult {
+                interchain_account_id: "test".to_string(),
+                sequence_id: 2u64,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        res,
+        Some(IcaOperationResult {
+            operation,
+            results: vec![MsgResult::WithdrawDelegatorReward {
+                amount: vec![Coin::new(1500u128, "uatom")],
+            }],
+        })
+    );
+}
+
+#[allow(deprecated)]
+fn build_submit_tx_reply(channel: &str, sequence_id: u64) -> Reply {
+    Reply {
+        id: SUDO_PAYLOAD_REPLY_ID,
+        payload: Binary::default(),
+        gas_used: 0,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: None,
+            msg_responses: vec![MsgResponse {
+                type_url: "/neutron.interchaintxs.v1.MsgSubmitTxResponse".to_string(),
+                value: Binary::from(
+                    MsgSubmitTxResponse {
+                        sequence_id,
+                        channel: channel.to_string(),
+                    }
+                    .encode_to_vec(),
+                ),
+            }],
+        }),
+    }
+}
+
+fn setup_ica(storage: &mut dyn Storage, env: &Env) {
+    OWNER.save(storage, &Addr::unchecked("sender")).unwrap();
+    let fee_coin = vec![StdCoin {
+        denom: "untrn".to_string(),
+        amount: "1000".to_string(),
+    }];
+    IBC_FEE
+        .save(
+            storage,
+            &Some(Fee {
+                recv_fee: vec![],
+                ack_fee: fee_coin.clone(),
+                timeout_fee: fee_coin,
+            }),
+        )
+        .unwrap();
+    open_ica(storage, env);
+}
+
+fn open_ica(storage: &mut dyn Storage, env: &Env) {
+    INTERCHAIN_ACCOUNTS
+        .save(
+            storage,
+            get_port_id(env.contract.address.as_str(), "test"),
+            &Some(("cosmos1ica".to_string(), "connection-0".to_string())),
+        )
+        .unwrap();
+}
+
+#[test]
+fn test_ledger() {
+    let mut deps = cw_mock_dependencies();
+    let env = mock_env();
+    setup_ica(&mut deps.storage, &env);
+
+    for _ in 0..2 {
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&Addr::unchecked("sender"), &[]),
+            ExecuteMsg::Delegate {
+                interchain_account_id: "test".to_string(),
+                validator: "cosmosvaloper1".to_string(),
+                amount: Uint128::new(100),
+                denom: "uatom".to_string(),
+                timeout: None,
+            },
+        )
+        .unwrap();
+    }
+    reply(
+        deps.as_mut(),
+        env.clone(),
+        build_submit_tx_reply("channel-0", 7),
+    )
+    .unwrap();
+
+    sudo(
+        deps.as_mut(),
+        env.clone(),
+        SudoMsg::Timeout {
+            request: build_request_packet(7u64, "channel-0"),
+        },
+    )
+    .unwrap();
+
+    let res: LedgerResponse = from_json(
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::Ledger {
+                interchain_account_id: Some("test".to_string()),
+                status: None,
+                from_time: None,
+                to_time: None,
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.entries.len(), 2);
+    assert_eq!(res.entries[0].sender, Addr::unchecked("sender"));
+    assert_eq!(res.entries[0].status, LedgerStatus::Pending);
+    assert_eq!(res.entries[0].sequence_id, None);
+    assert_eq!(res.entries[1].status, LedgerStatus::Timeout);
+    assert_eq!(res.entries[1].sequence_id, Some(7));
+    assert_eq!(
+        res.entries[1].fee.as_ref().unwrap().ack_fee,
+        vec![Coin::new(1000u128, "untrn")]
+    );
+
+    let res: LedgerResponse = from_json(
+        query(
+            deps.as_ref(),
+            env,
+            QueryMsg::Ledger {
+                interchain_account_id: None,
+                status: Some(LedgerStatus::Pending),
+                from_time: None,
+                to_time: None,
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.entries.len(), 1);
+    assert_eq!(res.entries[0].id, 0);
+}
+
+fn query_ledger_ids(deps: Deps, env: Env) -> Vec<u64> {
+    let res: LedgerResponse = from_json(
+        query(
+            deps,
+            env,
+            QueryMsg::Ledger {
+                interchain_account_id: None,
+                status: None,
+                from_time: None,
+                to_time: None,
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    res.entries.iter().map(|entry| entry.id).collect()
+}
+
+#[test]
+fn test_prune_ledger() {
+    let mut deps = cw_mock_dependencies();
+    let env = mock_env();
+    setup_ica(&mut deps.storage, &env);
+    let port_id = get_port_id(env.contract.address.as_str(), "test");
+    let delegate = ExecuteMsg::Delegate {
+        interchain_account_id: "test".to_string(),
+        validator: "cosmosvaloper1".to_string(),
+        amount: Uint128::new(100),
+        denom: "uatom".to_string(),
+        timeout: None,
+    };
+
+    // three finished txs followed by a pending one
+    for sequence_id in 0..3u64 {
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&Addr::unchecked("sender"), &[]),
+            delegate.clone(),
+        )
+        .unwrap();
+        reply(
+            deps.as_mut(),
+            env.clone(),
+            build_submit_tx_reply("channel-0", sequence_id),
+        )
+        .unwrap();
+        sudo(
+            deps.as_mut(),
+            env.clone(),
+            SudoMsg::Timeout {
+                request: build_request_packet(sequence_id, "channel-0"),
+            },
+        )
+        .unwrap();
+    }
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&Addr::unchecked("sender"), &[]),
+        delegate,
+    )
+    .unwrap();
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&Addr::unchecked("stranger"), &[]),
+        ExecuteMsg::SetLedgerRetention {
+            retention: LedgerRetention {
+                max_age: None,
+                max_entries: Some(2),
+            },
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, StdError::generic_err("Unauthorized"));
+
+    // the oldest finished entries are removed down to the maximum number of entries
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&Addr::unchecked("sender"), &[]),
+        ExecuteMsg::SetLedgerRetention {
+            retention: LedgerRetention {
+                max_age: None,
+                max_entries: Some(2),
+            },
+        },
+    )
+    .unwrap();
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&Addr::unchecked("sender"), &[]),
+        ExecuteMsg::PruneLedger { limit: None },
+    )
+    .unwrap();
+    assert_eq!(res.attributes[0].value, "2");
+    assert_eq!(query_ledger_ids(deps.as_ref(), env.clone()), vec![2, 3]);
+    assert_eq!(LEDGER_COUNT.load(&deps.storage).unwrap(), 2);
+    assert!(!ACKNOWLEDGEMENT_RESULTS.has(&deps.storage, (port_id.clone(), 0)));
+    assert!(ACKNOWLEDGEMENT_RESULTS.has(&deps.storage, (port_id.clone(), 2)));
+
+    // finished entries expire after the maximum age, the pending one is kept
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&Addr::unchecked("sender"), &[]),
+        ExecuteMsg::SetLedgerRetention {
+            retention: LedgerRetention {
+                max_age: Some(60),
+                max_entries: None,
+            },
+        },
+    )
+    .unwrap();
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&Addr::unchecked("sender"), &[]),
+        ExecuteMsg::PruneLedger { limit: None },
+    )
+    .unwrap();
+    assert_eq!(res.attributes[0].value, "0");
+
+    let mut later = env.clone();
+    later.block.time = env.block.time.plus_seconds(61);
+    let res = execute(
+        deps.as_mut(),
+        later.clone(),
+        message_info(&Addr::unchecked("sender"), &[]),
+        ExecuteMsg::PruneLedger { limit: None },
+    )
+    .unwrap();
+    assert_eq!(res.attributes[0].value, "1");
+    assert_eq!(query_ledger_ids(deps.as_ref(), later), vec![3]);
+    assert!(!ACKNOWLEDGEMENT_RESULTS.has(&deps.storage, (port_id, 2)));
+    assert_eq!(LEDGER_COUNT.load(&deps.storage).unwrap(), 1);
+}
+
+#[test]
+fn test_retry_on_timeout() {
+    let mut deps = cw_mock_dependencies();
+    let mut env = mock_env();
+    let info = message_info(&Addr::unchecked("sender"), &[]);
+    instantiate(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        InstantiateMsg { owner: None },
+    )
+    .unwrap();
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        ExecuteMsg::Register {
+            connection_id: "connection-0".to_string(),
+            interchain_account_id: "test".to_string(),
+            ordering: None,
+        },
+    )
+    .unwrap();
+    setup_ica(&mut deps.storage, &env);
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        ExecuteMsg::SetRetryPolicy {
+            policy: RetryPolicy {
+                max_attempts: 1,
+                backoff_blocks: 5,
+                retry_on_timeout: true,
+                retryable_errors: vec![],
+            },
+        },
+    )
+    .unwrap();
+
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        ExecuteMsg::Delegate {
+            interchain_account_id: "test".to_string(),
+            validator: "cosmosvaloper1".to_string(),
+            amount: Uint128::new(100),
+            denom: "uatom".to_string(),
+            timeout: None,
+        },
+    )
+    .unwrap();
+    reply(
+        deps.as_mut(),
+        env.clone(),
+        build_submit_tx_reply("channel-0", 1),
+    )
+    .unwrap();
+
+    // timeout closes the ordered channel, so the ICA is registered again
+    let res = sudo(
+        deps.as_mut(),
+        env.clone(),
+        SudoMsg::Timeout {
+            request: build_request_packet(1u64, "channel-0"),
+        },
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(res.messages[0].id, REGISTER_ICA_REPLY_ID);
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        ExecuteMsg::ProcessRetry {},
+    )
+    .unwrap_err();
+    assert_eq!(err, StdError::generic_err("no retries are due"));
+
+    // the channel is not reopened yet
+    env.block.height += 5;
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        ExecuteMsg::ProcessRetry {},
+    )
+    .unwrap();
+    assert!(res.messages.is_empty());
+
+    open_ica(&mut deps.storage, &env);
+    env.block.height += 5;
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        ExecuteMsg::ProcessRetry {},
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(res.messages[0].id, SUDO_PAYLOAD_REPLY_ID);
+    reply(
+        deps.as_mut(),
+        env.clone(),
+        build_submit_tx_reply("channel-1", 2),
+    )
+    .unwrap();
+
+    // the second timeout exhausts the attempts
+    sudo(
+        deps.as_mut(),
+        env.clone(),
+        SudoMsg::Timeout {
+            request: build_request_packet(2u64, "channel-1"),
+        },
+    )
+    .unwrap();
+
+    let history: Option<RetryHistory> = from_json(
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::RetryHistory { ledger_id: 1 },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        history,
+        Some(RetryHistory {
+            original_id: 0,
+            attempts: vec![1],
+            next_attempt_height: None,
+        })
+    );
+
+    env.block.height += 100;
+    let err = execute(deps.as_mut(), env, info, ExecuteMsg::ProcessRetry {}).unwrap_err();
+    assert_eq!(err, StdError::generic_err("no retries are due"));
+}
+
+#[test]
+fn test_ownership() {
+    let mut deps = cw_mock_dependencies();
+    let env = mock_env();
+    let owner = deps.api.addr_make("owner");
+    let operator = deps.api.addr_make("operator");
+    let new_owner = deps.api.addr_make("new_owner");
+    instantiate(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&Addr::unchecked("instantiator"), &[]),
+        InstantiateMsg {
+            owner: Some(owner.to_string()),
+        },
+    )
+    .unwrap();
+
+    let set_fees = ExecuteMsg::SetFees {
+        fees: Some(Fees {
+            denom: "untrn".to_string(),
+            recv_fee: Uint128::zero(),
+            ack_fee: Uint128::new(1000),
+            timeout_fee: Uint128::new(1000),
+        }),
+    };
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&operator, &[]),
+        set_fees.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, StdError::generic_err("Unauthorized"));
+
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&owner, &[]),
+        ExecuteMsg::SetOperators {
+            action: "set_fees".to_string(),
+            operators: vec![operator.to_string()],
+        },
+    )
+    .unwrap();
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&operator, &[]),
+        set_fees,
+    )
+    .unwrap();
+    // operators are allowed to execute only the actions they are listed for
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&operator, &[]),
+        ExecuteMsg::CleanAckResults {},
+    )
+    .unwrap_err();
+    assert_eq!(err, StdError::generic_err("Unauthorized"));
+
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&owner, &[]),
+        ExecuteMsg::TransferOwnership {
+            new_owner: new_owner.to_string(),
+        },
+    )
+    .unwrap();
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&operator, &[]),
+        ExecuteMsg::AcceptOwnership {},
+    )
+    .unwrap_err();
+    assert_eq!(err, StdError::generic_err("Unauthorized"));
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&new_owner, &[]),
+        ExecuteMsg::AcceptOwnership {},
+    )
+    .unwrap();
+
+    let res: OwnershipResponse =
+        from_json(query(deps.as_ref(), env.clone(), QueryMsg::Ownership {}).unwrap()).unwrap();
+    assert_eq!(
+        res,
+        OwnershipResponse {
+            owner: new_owner,
+            pending_owner: None,
+        }
+    );
+
+    let err = execute(
+        deps.as_mut(),
+        env,
+        message_info(&owner, &[]),
+        ExecuteMsg::IntegrationTestsUnsetSudoFailureMock {},
+    )
+    .unwrap_err();
+    assert_eq!(err, StdError::generic_err("Unauthorized"));
+}
+
+fn deliver(
+    deps: &mut OwnedDeps<MockStorage, MockApi, NeutronMockQuerier>,
+    env: &Env,
+    deliveries: Vec<Delivery>,
+) {
+    for delivery in deliveries {
+        match delivery {
+            Delivery::Reply(msg) => {
+                reply(deps.as_mut(), env.clone(), msg).unwrap();
+            }
+            Delivery::Sudo(msg) => {
+                sudo(deps.as_mut(), env.clone(), msg).unwrap();
+            }
+        }
+    }
+}
+
+fn delegate(
+    deps: &mut OwnedDeps<MockStorage, MockApi, NeutronMockQuerier>,
+    env: &Env,
+    ica: &mut MockIca,
+    owner: &Addr,
+) {
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(owner, &[]),
+        ExecuteMsg::Delegate {
+            interchain_account_id: "test".to_string(),
+            validator: "cosmosvaloper1qnk2n4nlkpw9xfqntladh74w6ujtulwnmxnh3k".to_string(),
+            amount: Uint128::new(100),
+            denom: "stake".to_string(),
+            timeout: None,
+        },
+    )
+    .unwrap();
+    let deliveries = ica.handle_response(&mut deps.querier, env, &res);
+    deliver(deps, env, deliveries);
+}
+
+#[test]
+fn test_ica_through_mock_ica_module() {
+    let mut deps = neutron_test_support::mock_dependencies(&[]);
+    let env = mock_env();
+    let mut ica = MockIca::new();
+    let owner = deps.api.addr_make("owner");
+    instantiate(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&owner, &[]),
+        InstantiateMsg { owner: None },
+    )
+    .unwrap();
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&owner, &[]),
+        ExecuteMsg::SetFees {
+            fees: Some(Fees {
+                denom: "untrn".to_string(),
+                recv_fee: Uint128::zero(),
+                ack_fee: Uint128::new(1000),
+                timeout_fee: Uint128::new(1000),
+            }),
+        },
+    )
+    .unwrap();
+
+    // the module replies with the channel and the relayer completes the handshake
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&owner, &[]),
+        ExecuteMsg::Register {
+            connection_id: "connection-0".to_string(),
+            interchain_account_id: "test".to_string(),
+            ordering: None,
+        },
+    )
+    .unwrap();
+    let deliveries = ica.handle_response(&mut deps.querier, &env, &res);
+    assert_eq!(deliveries.len(), 2);
+    deliver(&mut deps, &env, deliveries);
+
+    let from_module: QueryInterchainAccountAddressResponse = from_json(
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::InterchainAccountAddress {
+                interchain_account_id: "test".to_string(),
+                connection_id: "connection-0".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    let from_contract: (String, String) = from_json(
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::InterchainAccountAddressFromContract {
+                interchain_account_id: "test".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        from_contract,
+        (
+            from_module.interchain_account_address,
+            "connection-0".to_string()
+        )
+    );
+
+    let ack_result = |deps: &OwnedDeps<MockStorage, MockApi, NeutronMockQuerier>, sequence_id| {
+        let res: Option<AcknowledgementResult> = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::AcknowledgementResult {
+                    interchain_account_id: "test".to_string(),
+                    sequence_id,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        res
+    };
+
+    // acknowledged delegation
+    delegate(&mut deps, &env, &mut ica, &owner);
+    let data = proto::ack_data(vec![proto::any(
+        "/cosmos.staking.v1beta1.MsgDelegateResponse",
+        &MsgDelegateResponse {},
+    )]);
+    let ack = ica.ack("channel-0", 1, data);
+    deliver(&mut deps, &env, vec![Delivery::Sudo(ack)]);
+    assert_eq!(
+        ack_result(&deps, 1),
+        Some(AcknowledgementResult::Success(vec![
+            "/cosmos.staking.v1beta1.MsgDelegateResponse".to_string()
+        ]))
+    );
+
+    // failed delegation
+    delegate(&mut deps, &env, &mut ica, &owner);
+    let error = ica.error("channel-0", 2, "insufficient funds");
+    deliver(&mut deps, &env, vec![Delivery::Sudo(error)]);
+    assert_eq!(
+        ack_result(&deps, 2),
+        Some(AcknowledgementResult::Error((
+            "message".to_string(),
+            "insufficient funds".to_string()
+        )))
+    );
+
+    // timed out delegation
+    delegate(&mut deps, &env, &mut ica, &owner);
+    let timeout = ica.timeout("channel-0", 3);
+    deliver(&mut deps, &env, vec![Delivery::Sudo(timeout)]);
+    assert_eq!(
+        ack_result(&deps, 3),
+        Some(AcknowledgementResult::Timeout("message".to_string()))
+    );
+    assert!(ica.in_flight().is_empty());
+}
+
+/// App with the contract instantiated, its fees set and the "test" account registered
+fn setup_app() -> (NeutronApp, Addr) {
+    let mut app = NeutronApp::new(
+        ContractWrapper::new(execute, reply, sudo),
+        &[Coin::new(100_000u128, "untrn")],
+    );
+    let owner = app.deps.api.addr_make("owner");
+    instantiate(
+        app.deps.as_mut(),
+        app.env.clone(),
+        message_info(&owner, &[]),
+        InstantiateMsg { owner: None },
+    )
+    .unwrap();
+    app.execute(
+        message_info(&owner, &[]),
+        &ExecuteMsg::SetFees {
+            fees: Some(Fees {
+                denom: "untrn".to_string(),
+                recv_fee: Uint128::zero(),
+                ack_fee: Uint128::new(1000),
+                timeout_fee: Uint128::new(1000),
+            }),
+        },
+    )
+    .unwrap();
+    app.execute(
+        message_info(&owner, &[]),
+        &ExecuteMsg::Register {
+            connection_id: "connection-0".to_string(),
+            interchain_account_id: "test".to_string(),
+            ordering: None,
+        },
+    )
+    .unwrap();
+    (app, owner)
+}
+
+#[test]
+fn test_sudo_failures_through_neutron_app() {
+    let (mut app, owner) = setup_app();
+    let contract = app.contract_address();
+
+    let delegate = ExecuteMsg::Delegate {
+        interchain_account_id: "test".to_string(),
+        validator: "cosmosvaloper1qnk2n4nlkpw9xfqntladh74w6ujtulwnmxnh3k".to_string(),
+        amount: Uint128::new(5000),
+        denom: "stake".to_string(),
+        timeout: None,
+    };
+    let data = proto::ack_data(vec![proto::any(
+        "/cosmos.staking.v1beta1.MsgDelegateResponse",
+        &MsgDelegateResponse {},
+    )]);
+    let ack_result = |app: &NeutronApp, sequence_id| {
+        let res: Option<AcknowledgementResult> = from_json(
+            query(
+                app.deps.as_ref(),
+                app.env.clone(),
+                QueryMsg::AcknowledgementResult {
+                    interchain_account_id: "test".to_string(),
+                    sequence_id,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        res
+    };
+
+    // the fees are locked until the ack, then the timeout fee is refunded
+    app.execute(message_info(&owner, &[]), &delegate).unwrap();
+    assert_eq!(
+        app.deps.querier.balance_of(contract.as_str(), "untrn"),
+        Uint128::new(98_000)
+    );
+    app.ica_ack("channel-0", 1, data.clone()).unwrap();
+    assert_eq!(
+        app.deps.querier.balance_of(contract.as_str(), "untrn"),
+        Uint128::new(99_000)
+    );
+    assert_eq!(
+        app.deps.querier.balance_of(RELAYER_ADDR, "untrn"),
+        Uint128::new(1000)
+    );
+    assert!(matches!(
+        ack_result(&app, 1),
+        Some(AcknowledgementResult::Success(_))
+    ));
+
+    // a failed handler is reverted and recorded as a failure
+    app.execute(
+        message_info(&owner, &[]),
+        &ExecuteMsg::IntegrationTestsSetSudoFailureMock {
+            state: IntegrationTestsSudoFailureMock::Enabled,
+        },
+    )
+    .unwrap();
+    app.execute(message_info(&owner, &[]), &delegate).unwrap();
+    let err = app.ica_ack("channel-0", 2, data).unwrap_err();
+    assert!(err.contains("Integrations test mock error"));
+    assert_eq!(ack_result(&app, 2), None);
+    let failures = app.deps.querier.failures(contract.as_str());
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].id, 0);
+
+    // resubmitting fails the whole tx while the handler is still broken
+    app.execute(
+        message_info(&owner, &[]),
+        &ExecuteMsg::ResubmitFailure { failure_id: 0 },
+    )
+    .unwrap_err();
+    assert_eq!(app.deps.querier.failures(contract.as_str()).len(), 1);
+
+    app.execute(
+        message_info(&owner, &[]),
+        &ExecuteMsg::IntegrationTestsUnsetSudoFailureMock {},
+    )
+    .unwrap();
+    app.execute(
+        message_info(&owner, &[]),
+        &ExecuteMsg::ResubmitFailure { failure_id: 0 },
+    )
+    .unwrap();
+    assert!(app.deps.querier.failures(contract.as_str()).is_empty());
+    assert!(matches!(
+        ack_result(&app, 2),
+        Some(AcknowledgementResult::Success(_))
+    ));
+}
+
+#[test]
+fn test_resubmit_failures() {
+    let (mut app, owner) = setup_app();
+    let delegate = ExecuteMsg::Delegate {
+        interchain_account_id: "test".to_string(),
+        validator: "cosmosvaloper1qnk2n4nlkpw9xfqntladh74w6ujtulwnmxnh3k".to_string(),
+        amount: Uint128::new(5000),
+        denom: "stake".to_string(),
+        timeout: None,
+    };
+    let failures = |app: &NeutronApp| {
+        let res: Vec<FailureInfo> = from_json(
+            query(
+                app.deps.as_ref(),
+                app.env.clone(),
+                QueryMsg::Failures {
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        res
+    };
+
+    app.execute(
+        message_info(&owner, &[]),
+        &ExecuteMsg::IntegrationTestsSetSudoFailureMock {
+            state: IntegrationTestsSudoFailureMock::Enabled,
+        },
+    )
+    .unwrap();
+    for _ in 0..3 {
+        app.execute(message_info(&owner, &[]), &delegate).unwrap();
+    }
+    let data = proto::ack_data(vec![proto::any(
+        "/cosmos.staking.v1beta1.MsgDelegateResponse",
+        &MsgDelegateResponse {},
+    )]);
+    app.ica_ack("channel-0", 1, data).unwrap_err();
+    app.ica_error("channel-0", 2, "insufficient funds")
+        .unwrap_err();
+    app.ica_timeout("channel-0", 3).unwrap_err();
+
+    // the failures are listed with their sudo payloads decoded
+    let listed = failures(&app);
+    assert_eq!(
+        listed
+            .iter()
+            .map(|f| (f.id, f.kind.clone(), f.sequence_id))
+            .collect::<Vec<_>>(),
+        vec![
+            (0, FailureKind::Response, Some(1)),
+            (1, FailureKind::Error, Some(2)),
+            (2, FailureKind::Timeout, Some(3)),
+        ]
+    );
+    assert!(listed
+        .iter()
+        .all(|f| f.channel_id.as_deref() == Some("channel-0")));
+    assert_eq!(listed[1].details, Some("insufficient funds".to_string()));
+    assert!(listed[0].error.contains("Integrations test mock error"));
+
+    // resubmissions failing again don't fail the batch and are recorded
+    let res = app
+        .execute(
+            message_info(&owner, &[]),
+            &ExecuteMsg::ResubmitFailures {
+                channel_id: Some("channel-0".to_string()),
+                error_contains: Some("mock error".to_string()),
+                start_after: None,
+
+                max: Some(2),
+            },
+        )
+        .unwrap();
+    assert_eq!(res.messages.len(), 2);
+    let listed = failures(&app);
+    assert_eq!(listed.len(), 3);
+    assert!(listed[0].resubmission.as_ref().unwrap().error.is_some());
+    assert!(listed[1].resubmission.as_ref().unwrap().error.is_some());
+    assert_eq!(listed[2].resubmission, None);
+
+    // only the failure not resubmitted yet is picked up again
+    app.execute(
+        message_info(&owner, &[]),
+        &ExecuteMsg::IntegrationTestsUnsetSudoFailureMock {},
+    )
+    .unwrap();
+    let res = app
+        .execute(
+            message_info(&owner, &[]),
+            &ExecuteMsg::ResubmitFailures {
+                channel_id: None,
+                error_contains: None,
+                start_after: None,
+
+                max: None,
+            },
+        )
+        .unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(
+        failures(&app).iter().map(|f| f.id).collect::<Vec<_>>(),
+        vec![0, 1]
+    );
+    let resubmissions: Vec<Resubmission> = from_json(
+        query(
+            app.deps.as_ref(),
+            app.env.clone(),
+            QueryMsg::Resubmissions {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(resubmissions.len(), 3);
+    assert_eq!(resubmissions[2].failure_id, 2);
+    assert_eq!(resubmissions[2].error, None);
+}
+
+#[test]
+fn test_submit_tx_timeouts() {
+    let (mut app, owner) = setup_app();
+    let delegate = |timeout: Option<TimeoutSpec>| ExecuteMsg::Delegate {
+        interchain_account_id: "test".to_string(),
+        validator: "cosmosvaloper1".to_string(),
+        amount: Uint128::new(100),
+        denom: "uatom".to_string(),
+        timeout: timeout.map(TxTimeout::Spec),
+    };
+
+    // the interchain txs only time out by time, and it has to be in the future
+    app.execute(
+        message_info(&owner, &[]),
+        &delegate(Some(TimeoutSpec::AfterBlocks { blocks: 100 })),
+    )
+    .unwrap_err();
+    app.execute(
+        message_info(&owner, &[]),
+        &delegate(Some(TimeoutSpec::AtTime {
+            time: app.env.block.time.minus_seconds(1),
+        })),
+    )
+    .unwrap_err();
+
+    app.execute(
+        message_info(&owner, &[]),
+        &delegate(Some(TimeoutSpec::AtTime {
+            time: app.env.block.time.plus_seconds(3600),
+        })),
+    )
+    .unwrap();
+    app.execute(
+        message_info(&owner, &[]),
+        &delegate(Some(TimeoutSpec::AfterSeconds { seconds: 600 })),
+    )
+    .unwrap();
+    // a number is a number of seconds
+    let msg: ExecuteMsg = from_json(
+        br#"{"delegate":{"interchain_account_id":"test","validator":"cosmosvaloper1","amount":"100","denom":"uatom","timeout":1200}}"#,
+    )
+    .unwrap();
+    app.execute(message_info(&owner, &[]), &msg).unwrap();
+    let res: LedgerResponse = from_json(
+        query(
+            app.deps.as_ref(),
+            app.env.clone(),
+            QueryMsg::Ledger {
+                interchain_account_id: Some("test".to_string()),
+                status: None,
+                from_time: None,
+                to_time: None,
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        res.entries.iter().map(|e| e.timeout).collect::<Vec<_>>(),
+        vec![3600, 600, 1200]
+    );
+}
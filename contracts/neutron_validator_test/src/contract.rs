@@ -15,25 +15,30 @@
 use crate::msg::{ExecuteMsg, Fees, InstantiateMsg, QueryMsg};
 use crate::storage::{
     read_reply_payload, read_sudo_payload, save_reply_payload, save_sudo_payload,
-    AcknowledgementResult, GetRecipientTxsResponse, SudoPayload, Transfer, ACKNOWLEDGEMENT_RESULTS,
-    IBC_FEE, INTERCHAIN_ACCOUNTS, LAST_SEQ_ID, RECIPIENT_TXS, SUDO_PAYLOAD_REPLY_ID,
+    AcknowledgementResult, BalanceQuery, IcaPosition, ReconciliationReport, StakingOperation,
+    SudoPayload, Transfer, Unbonding, ACKNOWLEDGEMENT_RESULTS, BALANCE_QUERIES,
+    BALANCE_QUERY_REPLY_ID, IBC_FEE, INTERCHAIN_ACCOUNTS, LAST_SEQ_ID, PENDING_BALANCE_QUERY,
+    POSITIONS, RECIPIENT_TOTALS, RECONCILIATION_REPORTS, RECONCILIATION_REPORT_NEXT_ID,
+    REGISTER_QUERY_REPLY_ID, STALE_QUERY_BLOCKS, SUDO_PAYLOAD_REPLY_ID, TRACKED_QUERIES,
 };
 use cosmos_sdk_proto::{
     cosmos::{
         bank::v1beta1::MsgSend,
         base::v1beta1::Coin,
-        staking::v1beta1::{MsgDelegate, MsgUndelegate},
+        staking::v1beta1::{MsgDelegate, MsgUndelegate, MsgUndelegateResponse},
         tx::v1beta1::{TxBody, TxRaw},
     },
     traits::Message,
+    Any as ProtoAny,
 };
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_json_binary, Addr, Binary, CosmosMsg, CustomQuery, Deps, DepsMut, Env, MessageInfo, Reply,
-    Response, StdError, StdResult, SubMsg, Uint128,
+    to_json_binary, Addr, Binary, CosmosMsg, CustomQuery, Deps, DepsMut, Env, Int128, MessageInfo,
+    Reply, Response, StdError, StdResult, Storage, SubMsg, Timestamp, Uint128, Uint256,
 };
 use cw2::set_contract_version;
+use cw_storage_plus::Bound;
 use neutron_sdk::interchain_queries::helpers::remove_interchain_query as helpers_remove_interchain_query;
 use neutron_sdk::interchain_queries::queries::get_registered_query;
 use neutron_sdk::interchain_queries::types::{
@@ -55,14 +60,35 @@ use neutron_std::shim::Any;
 use neutron_std::types::cosmos::base::v1beta1::Coin as CosmosCoin;
 use neutron_std::types::ibc::core::channel::v1::Order;
 use neutron_std::types::neutron::feerefunder::Fee;
+use neutron_std::types::neutron::interchainqueries::MsgRegisterInterchainQueryResponse;
 use neutron_std::types::neutron::interchainqueries::RegisteredQuery;
 use neutron_std::types::neutron::interchaintxs::v1::{InterchaintxsQuerier, MsgSubmitTxResponse};
+use ownership::{
+    accept_ownership, assert_owner_or_operator, initialize_owner, query_operators, query_ownership,
+    set_operators, transfer_ownership,
+};
+use recipient_transfers::{
+    clear_transfers, query_pending_deposits, query_recipient_totals, query_recipient_txs,
+    queue_pending, save_transfers, tx_hash, TransferSource, PENDING_DEPOSITS,
+};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use tracked_queries::{
+    find_stale_queries, query_deposits_summary, touch_tracked_query, track_query,
+};
 
 // Default timeout for SubmitTX is two weeks
 const DEFAULT_TIMEOUT_SECONDS: u64 = 60 * 60 * 24 * 7 * 2;
 
+// Default and maximum page sizes for the paginated queries
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 100;
+
+/// defines how many messages of a tx are processed at once to prevent 'out of gas' conditions,
+/// the rest of them are processed later with ProcessPending.
+const MAX_ALLOWED_MESSAGES: usize = 20;
+
 const CONTRACT_NAME: &str = concat!("crates.io:neutron-contracts__", env!("CARGO_PKG_NAME"));
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -81,11 +107,12 @@ struct OpenAckVersion {
 pub fn instantiate(
     deps: DepsMut,
     _env: Env,
-    _info: MessageInfo,
-    _msg: InstantiateMsg,
+    info: MessageInfo,
+    msg: InstantiateMsg,
 ) -> NeutronResult<Response> {
     deps.api.debug("WASMDEBUG: instantiate");
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    initialize_owner(deps, &info, msg.owner)?;
     Ok(Response::default())
 }
 
@@ -93,11 +120,20 @@ pub fn instantiate(
 pub fn execute(
     deps: DepsMut,
     env: Env,
-    _: MessageInfo,
+    info: MessageInfo,
     msg: ExecuteMsg,
 ) -> NeutronResult<Response> {
     deps.api
         .debug(format!("WASMDEBUG: execute: received msg: {:?}", msg).as_str());
+    match &msg {
+        // ownership messages are checked by their handlers
+        ExecuteMsg::TransferOwnership { .. }
+        | ExecuteMsg::AcceptOwnership {}
+        | ExecuteMsg::SetOperators { .. } => {}
+        // anyone can credit the pending deposits and remove the stale queries
+        ExecuteMsg::ProcessPending { .. } | ExecuteMsg::RemoveStaleQueries { .. } => {}
+        _ => assert_owner_or_operator(deps.as_ref(), &info.sender, msg.action())?,
+    }
     match msg {
         ExecuteMsg::Register {
             connection_id,
@@ -142,6 +178,7 @@ pub fn execute(
             denom,
             update_period,
         } => register_balance_query(
+            deps,
             env.contract.address,
             connection_id,
             addr,
@@ -161,7 +198,19 @@ pub fn execute(
             min_height,
         ),
         ExecuteMsg::RemoveInterchainQuery { query_id } => {
-            remove_interchain_query(env.contract.address, query_id)
+            remove_interchain_query(deps, env.contract.address, query_id)
+        }
+        ExecuteMsg::SetStaleQueryBlocks { blocks } => set_stale_query_blocks(deps, blocks),
+        ExecuteMsg::RemoveStaleQueries { start_after, limit } => {
+            remove_stale_queries(deps, env, start_after, limit)
+        }
+        ExecuteMsg::ProcessPending { limit } => process_pending(deps, limit),
+        ExecuteMsg::TransferOwnership { new_owner } => {
+            Ok(transfer_ownership(deps, info, new_owner)?)
+        }
+        ExecuteMsg::AcceptOwnership {} => Ok(accept_ownership(deps, info)?),
+        ExecuteMsg::SetOperators { action, operators } => {
+            Ok(set_operators(deps, info, action, operators)?)
         }
     }
 }
@@ -182,7 +231,41 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> NeutronResult<Binary> {
         } => query_acknowledgement_result(deps, env, interchain_account_id, sequence_id),
         QueryMsg::LastAckSeqId {} => query_last_ack_seq_id(deps),
         QueryMsg::Balance { query_id } => Ok(to_json_binary(&query_balance(deps, env, query_id)?)?),
-        QueryMsg::GetRecipientTxs { recipient } => query_recipient_txs(deps, recipient),
+        QueryMsg::GetRecipientTxs {
+            recipient,
+            start_after,
+            limit,
+            filter,
+        } => Ok(to_json_binary(&query_recipient_txs(
+            deps.storage,
+            &recipient,
+            start_after,
+            limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize,
+            filter.unwrap_or_default(),
+        )?)?),
+        QueryMsg::GetRecipientTotals { recipient } => Ok(to_json_binary(&query_recipient_totals(
+            deps.storage,
+            &recipient,
+        )?)?),
+        QueryMsg::GetPendingDeposits { limit } => Ok(to_json_binary(&query_pending_deposits(
+            deps.storage,
+            limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize,
+        )?)?),
+        QueryMsg::Position {
+            interchain_account_id,
+            denom,
+        } => query_position(deps, env, interchain_account_id, denom),
+        QueryMsg::ReconciliationReports {
+            interchain_account_id,
+            start_after,
+            limit,
+        } => query_reconciliation_reports(deps, env, interchain_account_id, start_after, limit),
+        QueryMsg::TrackedQuery { query_id } => Ok(to_json_binary(
+            &TRACKED_QUERIES.may_load(deps.storage, query_id)?,
+        )?),
+        QueryMsg::DepositsSummary {} => Ok(to_json_binary(&query_deposits_summary(deps.storage)?)?),
+        QueryMsg::Ownership {} => Ok(to_json_binary(&query_ownership(deps)?)?),
+        QueryMsg::Operators { action } => Ok(to_json_binary(&query_operators(deps, action)?)?),
     }
 }
 
@@ -229,11 +312,40 @@ pub fn query_last_ack_seq_id(deps: Deps) -> NeutronResult<Binary> {
     Ok(to_json_binary(&res)?)
 }
 
-fn query_recipient_txs(deps: Deps, recipient: String) -> NeutronResult<Binary> {
-    let txs = RECIPIENT_TXS
-        .load(deps.storage, &recipient)
+fn query_position(
+    deps: Deps,
+    env: Env,
+    interchain_account_id: String,
+    denom: String,
+) -> NeutronResult<Binary> {
+    let port_id = get_port_id(env.contract.address.as_str(), &interchain_account_id);
+    let res: IcaPosition = POSITIONS
+        .may_load(deps.storage, (&port_id, &denom))?
         .unwrap_or_default();
-    Ok(to_json_binary(&GetRecipientTxsResponse { transfers: txs })?)
+    Ok(to_json_binary(&res)?)
+}
+
+fn query_reconciliation_reports(
+    deps: Deps,
+    env: Env,
+    interchain_account_id: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> NeutronResult<Binary> {
+    let port_id = get_port_id(env.contract.address.as_str(), &interchain_account_id);
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let res: Vec<ReconciliationReport> = RECONCILIATION_REPORTS
+        .prefix(&port_id)
+        .range(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            cosmwasm_std::Order::Ascending,
+        )
+        .take(limit)
+        .map(|item| item.map(|(_, report)| report))
+        .collect::<StdResult<_>>()?;
+    Ok(to_json_binary(&res)?)
 }
 
 fn msg_with_sudo_callback<C: Into<CosmosMsg<T>>, T>(
@@ -301,7 +413,7 @@ fn execute_delegate(
         delegator_address: delegator,
         validator_address: validator,
         amount: Some(Coin {
-            denom,
+            denom: denom.clone(),
             amount: amount.to_string(),
         }),
     };
@@ -338,6 +450,7 @@ fn execute_delegate(
             port_id: get_port_id(env.contract.address.as_str(), &interchain_account_id),
             message: "message".to_string(),
             amount,
+            operation: Some(StakingOperation::Delegate { denom }),
         },
     )?;
 
@@ -359,7 +472,7 @@ fn execute_undelegate(
         delegator_address: delegator,
         validator_address: validator,
         amount: Some(Coin {
-            denom,
+            denom: denom.clone(),
             amount: amount.to_string(),
         }),
     };
@@ -395,6 +508,7 @@ fn execute_undelegate(
             port_id: get_port_id(env.contract.address.as_str(), &interchain_account_id),
             message: "message".to_string(),
             amount,
+            operation: Some(StakingOperation::Undelegate { denom }),
         },
     )?;
 
@@ -413,26 +527,29 @@ fn execute_clean_ack_results(deps: DepsMut) -> NeutronResult<Response> {
 }
 
 fn execute_clean_recipient_txs(deps: DepsMut) -> NeutronResult<Response> {
-    let keys: Vec<StdResult<String>> = RECIPIENT_TXS
-        .keys(deps.storage, None, None, cosmwasm_std::Order::Descending)
-        .collect();
-    for key in keys {
-        RECIPIENT_TXS.remove(deps.storage, &key?);
-    }
+    clear_transfers(deps.storage);
     Ok(Response::default())
 }
 
 pub fn register_balance_query(
+    deps: DepsMut,
     contract: Addr,
     connection_id: String,
     addr: String,
     denom: String,
     update_period: u64,
 ) -> NeutronResult<Response> {
-    let msg =
-        new_register_balances_query_msg(contract, connection_id, addr, vec![denom], update_period)?;
+    let msg = new_register_balances_query_msg(
+        contract,
+        connection_id,
+        addr.clone(),
+        vec![denom.clone()],
+        update_period,
+    )?;
+    // the query id is known only in the reply, it's needed to reconcile the ICA balances
+    PENDING_BALANCE_QUERY.save(deps.storage, &BalanceQuery { addr, denom })?;
 
-    Ok(Response::new().add_message(msg))
+    Ok(Response::new().add_submessage(SubMsg::reply_on_success(msg, BALANCE_QUERY_REPLY_ID)))
 }
 
 pub fn register_transfers_query(
@@ -450,14 +567,60 @@ pub fn register_transfers_query(
         min_height,
     )?;
 
-    Ok(Response::new().add_message(msg))
+    // the reply starts tracking the query deposit
+    Ok(Response::new().add_submessage(SubMsg::reply_on_success(msg, REGISTER_QUERY_REPLY_ID)))
 }
 
-pub fn remove_interchain_query(contract: Addr, query_id: u64) -> NeutronResult<Response> {
+/// Removes the query and stops tracking it. The deposit of the query is refunded to the
+/// contract by the removal.
+pub fn remove_interchain_query(
+    deps: DepsMut,
+    contract: Addr,
+    query_id: u64,
+) -> NeutronResult<Response> {
+    TRACKED_QUERIES.remove(deps.storage, query_id);
     let remove_msg = helpers_remove_interchain_query(contract, query_id)?;
     Ok(Response::new().add_message(remove_msg))
 }
 
+fn set_stale_query_blocks(deps: DepsMut, blocks: Option<u64>) -> NeutronResult<Response> {
+    match blocks {
+        Some(blocks) => STALE_QUERY_BLOCKS.save(deps.storage, &blocks)?,
+        None => STALE_QUERY_BLOCKS.remove(deps.storage),
+    }
+    Ok(Response::new().add_attribute("action", "set_stale_query_blocks"))
+}
+
+/// Checks up to `limit` tracked queries after `start_after`, the oldest query ids first, and
+/// removes the ones which haven't received results for the stale query blocks.
+fn remove_stale_queries(
+    mut deps: DepsMut,
+    env: Env,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> NeutronResult<Response> {
+    let (stale, last_query_id) = find_stale_queries(
+        deps.as_ref(),
+        env.block.height,
+        start_after,
+        limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize,
+    )?;
+
+    let mut response = Response::new().add_attribute("action", "remove_stale_queries");
+    if let Some(query_id) = last_query_id {
+        response = response.add_attribute("last_query_id", query_id.to_string());
+    }
+    for query_id in stale {
+        response = response
+            .add_submessages(
+                remove_interchain_query(deps.branch(), env.contract.address.clone(), query_id)?
+                    .messages,
+            )
+            .add_attribute("removed_query_id", query_id.to_string());
+    }
+    Ok(response)
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn sudo(deps: DepsMut, env: Env, msg: SudoMsg) -> NeutronResult<Response> {
     deps.api
@@ -484,6 +647,7 @@ pub fn sudo(deps: DepsMut, env: Env, msg: SudoMsg) -> NeutronResult<Response> {
             height,
             data,
         } => sudo_tx_query_result(deps, env, query_id, height, data),
+        SudoMsg::KVQueryResult { query_id } => sudo_kv_query_result(deps, env, query_id),
         _ => Ok(Response::default()),
     }
 }
@@ -516,12 +680,15 @@ fn sudo_open_ack(
 
 pub fn sudo_tx_query_result(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     query_id: u64,
-    _height: Height,
+    height: Height,
     data: Binary,
 ) -> NeutronResult<Response> {
+    touch_tracked_query(deps.storage, query_id, env.block.height)?;
+
     // Decode the transaction data
+    let tx_hash = tx_hash(&data);
     let tx: TxRaw = TxRaw::decode(data.as_slice())?;
     let body: TxBody = TxBody::decode(tx.body_bytes.as_slice())?;
 
@@ -552,35 +719,226 @@ pub fn sudo_tx_query_result(
                 })
                 .unwrap_or("");
 
-            let deposits = recipient_deposits_from_tx_body(body, recipient)?;
+            let height = height.revision_height;
+            // Only handle up to MAX_ALLOWED_MESSAGES messages now, the rest of them are saved
+            // raw and processed later with ProcessPending to prevent 'out of gas' conditions.
+            let (messages, overflow) = body
+                .messages
+                .split_at(body.messages.len().min(MAX_ALLOWED_MESSAGES));
+            let deposits =
+                recipient_deposits_from_messages(messages, recipient, height, &tx_hash, 0)?;
+            let found = !deposits.is_empty();
+            let stored = save_transfers(deps.storage, deposits, |_, _| Ok(false))?;
+            // A resubmitted tx must not be counted twice
+            if (found && stored == 0) || PENDING_DEPOSITS.has(deps.storage, (height, &tx_hash)) {
+                return Err(NeutronError::Std(StdError::generic_err(
+                    "transaction has already been processed",
+                )));
+            }
             // If we didn't find a Send message with the correct recipient, return an error, and
             // this query result will be rejected by Neutron: no data will be saved to state.
-            if deposits.is_empty() {
+            if !found && overflow.is_empty() {
                 return Err(NeutronError::Std(StdError::generic_err(
                     "failed to find a matching transaction message",
                 )));
             }
 
-            let mut stored_deposits: Vec<Transfer> = RECIPIENT_TXS
-                .load(deps.storage, recipient)
-                .unwrap_or_default();
-            stored_deposits.extend(deposits);
-            RECIPIENT_TXS.save(deps.storage, recipient, &stored_deposits)?;
-            Ok(Response::new())
+            let mut response = Response::new();
+            if !overflow.is_empty() {
+                queue_pending(
+                    deps.storage,
+                    height,
+                    &tx_hash,
+                    &[recipient.to_string()],
+                    messages.len() as u32,
+                    overflow
+                        .iter()
+                        .map(|msg| Binary::from(msg.encode_to_vec()))
+                        .collect(),
+                )?;
+                response = response.add_attribute("pending_messages", overflow.len().to_string());
+            }
+            Ok(response)
+        }
+    }
+}
+
+/// Reconciles the balance of an ICA received by a balance query against the balance expected
+/// from its position and the incoming transfers, and stores a report if they don't match.
+pub fn sudo_kv_query_result(deps: DepsMut, env: Env, query_id: u64) -> NeutronResult<Response> {
+    deps.api.debug(
+        format!(
+            "WASMDEBUG: sudo_kv_query_result received; query_id: {:?}",
+            query_id,
+        )
+        .as_str(),
+    );
+    touch_tracked_query(deps.storage, query_id, env.block.height)?;
+
+    let balance_query = match BALANCE_QUERIES.may_load(deps.storage, query_id)? {
+        Some(balance_query) => balance_query,
+        None => return Ok(Response::default()),
+    };
+    // only the balances of the contract's ICAs are reconciled
+    let port_id = match find_ica_port(deps.storage, &balance_query.addr)? {
+        Some(port_id) => port_id,
+        None => return Ok(Response::default()),
+    };
+
+    let remote_balance = query_balance(deps.as_ref(), env.clone(), query_id)?
+        .balances
+        .coins
+        .into_iter()
+        .find(|coin| coin.denom == balance_query.denom)
+        .map_or(Uint128::zero(), |coin| coin.amount);
+    reconcile_balance(deps, &env, query_id, balance_query, port_id, remote_balance)?;
+
+    Ok(Response::default())
+}
+
+fn reconcile_balance(
+    deps: DepsMut,
+    env: &Env,
+    query_id: u64,
+    balance_query: BalanceQuery,
+    port_id: String,
+    remote_balance: Uint128,
+) -> StdResult<()> {
+    let received = Uint128::try_from(
+        RECIPIENT_TOTALS
+            .may_load(deps.storage, (&balance_query.addr, &balance_query.denom))?
+            .unwrap_or_default(),
+    )?;
+
+    let mut position = POSITIONS
+        .may_load(deps.storage, (&port_id, &balance_query.denom))?
+        .unwrap_or_default();
+    position.complete_unbondings(env.block.time)?;
+    // an undelegation only lowers `delegated`, its funds stay locked until the unbonding completes
+    let locked = position
+        .delegated
+        .checked_add(position.unbonding(env.block.time)?)?;
+
+    // everything not explained by the tracked operations at the first result is the baseline
+    let baseline = match position.baseline {
+        Some(baseline) => baseline,
+        None => {
+            let baseline = remote_balance.checked_add(locked)?.saturating_sub(received);
+            position.baseline = Some(baseline);
+            baseline
+        }
+    };
+    POSITIONS.save(deps.storage, (&port_id, &balance_query.denom), &position)?;
+    let expected_balance = baseline.checked_add(received)?.saturating_sub(locked);
+
+    if remote_balance != expected_balance {
+        let discrepancy =
+            Int128::try_from(remote_balance)?.checked_sub(Int128::try_from(expected_balance)?)?;
+        deps.api.debug(
+            format!(
+                "WASMDEBUG: reconciliation: {} balance of {} differs by {}",
+                balance_query.denom, port_id, discrepancy
+            )
+            .as_str(),
+        );
+        let id = RECONCILIATION_REPORT_NEXT_ID
+            .may_load(deps.storage)?
+            .unwrap_or_default();
+        RECONCILIATION_REPORT_NEXT_ID.save(deps.storage, &(id + 1))?;
+        RECONCILIATION_REPORTS.save(
+            deps.storage,
+            (&port_id, id),
+            &ReconciliationReport {
+                id,
+                query_id,
+                denom: balance_query.denom,
+                remote_balance,
+                expected_balance,
+                discrepancy,
+                delegated: position.delegated,
+                received,
+                unbonded: position.unbonded,
+                height: env.block.height,
+                time: env.block.time,
+            },
+        )?;
+    }
+    Ok(())
+}
+
+fn find_ica_port(storage: &dyn Storage, addr: &str) -> StdResult<Option<String>> {
+    for item in INTERCHAIN_ACCOUNTS.range(storage, None, None, cosmwasm_std::Order::Ascending) {
+        if let (port_id, Some((ica_addr, _))) = item? {
+            if ica_addr == addr {
+                return Ok(Some(port_id));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Applies an acknowledged staking operation to the position of the ICA
+fn update_position(
+    storage: &mut dyn Storage,
+    payload: &SudoPayload,
+    completion_time: Option<Timestamp>,
+) -> StdResult<()> {
+    let denom = match &payload.operation {
+        Some(StakingOperation::Delegate { denom })
+        | Some(StakingOperation::Undelegate { denom }) => denom,
+        None => return Ok(()),
+    };
+    let mut position = POSITIONS
+        .may_load(storage, (&payload.port_id, denom))?
+        .unwrap_or_default();
+    match payload.operation {
+        Some(StakingOperation::Delegate { .. }) => {
+            position.delegated = position.delegated.checked_add(payload.amount)?;
+        }
+        _ => {
+            position.delegated = position.delegated.saturating_sub(payload.amount);
+            position.unbondings.push(Unbonding {
+                amount: payload.amount,
+                completion_time,
+            });
         }
     }
+    POSITIONS.save(storage, (&payload.port_id, denom), &position)
+}
+
+/// Credits deposits from up to `limit` pending messages, the oldest transactions first.
+fn process_pending(deps: DepsMut, limit: Option<u32>) -> NeutronResult<Response> {
+    let limit = limit.map_or(MAX_ALLOWED_MESSAGES, |l| {
+        (l as usize).min(MAX_ALLOWED_MESSAGES)
+    });
+    let (processed, stored) = recipient_transfers::process_pending(
+        deps.storage,
+        limit,
+        |messages, recipient, height, tx_hash, first_index| -> NeutronResult<Vec<Transfer>> {
+            let messages = messages
+                .iter()
+                .map(|msg| ProtoAny::decode(msg.as_slice()))
+                .collect::<Result<Vec<ProtoAny>, _>>()?;
+            recipient_deposits_from_messages(&messages, recipient, height, tx_hash, first_index)
+        },
+        |_, _| Ok(false),
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("action", "process_pending")
+        .add_attribute("processed_messages", processed.to_string())
+        .add_attribute("stored_transfers", stored.to_string()))
 }
 
-fn recipient_deposits_from_tx_body(
-    tx_body: TxBody,
+fn recipient_deposits_from_messages(
+    messages: &[ProtoAny],
     recipient: &str,
+    height: u64,
+    tx_hash: &str,
+    first_index: u32,
 ) -> NeutronResult<Vec<Transfer>> {
     let mut deposits: Vec<Transfer> = vec![];
-    // Only handle up to MAX_ALLOWED_MESSAGES messages, everything else
-    // will be ignored to prevent 'out of gas' conditions.
-    // Note: in real contracts you will have to somehow save ignored
-    // data in order to handle it later.
-    for msg in tx_body.messages.iter().take(20) {
+    for (i, msg) in messages.iter().enumerate() {
         // Skip all messages in this transaction that are not Send messages.
         if msg.type_url != *COSMOS_SDK_TRANSFER_MSG_URL.to_string() {
             continue;
@@ -589,12 +947,17 @@ fn recipient_deposits_from_tx_body(
         // Parse a Send message and check that it has the required recipient.
         let transfer_msg: MsgSend = MsgSend::decode(msg.value.as_slice())?;
         if transfer_msg.to_address == recipient {
-            for coin in transfer_msg.amount {
+            for (coin_index, coin) in transfer_msg.amount.into_iter().enumerate() {
                 deposits.push(Transfer {
                     sender: transfer_msg.from_address.clone(),
-                    amount: coin.amount.clone(),
+                    amount: Uint256::from_str(&coin.amount)?,
                     denom: coin.denom,
                     recipient: recipient.to_string(),
+                    height,
+                    tx_hash: tx_hash.to_string(),
+                    index: first_index + i as u32,
+                    coin_index: coin_index as u32,
+                    source: TransferSource::Send,
                 });
             }
         }
@@ -631,10 +994,17 @@ fn sudo_response(deps: DepsMut, request: RequestPacket, data: Binary) -> Neutron
     let parsed_data = decode_acknowledgement_response(data)?;
 
     let mut item_types = vec![];
+    let mut completion_time = None;
     for item in parsed_data {
         let item_type = item.type_url.as_str();
         item_types.push(item_type.to_string());
+        if item_type == "/cosmos.staking.v1beta1.MsgUndelegateResponse" {
+            completion_time = MsgUndelegateResponse::decode(item.value.as_slice())?
+                .completion_time
+                .map(|t| Timestamp::from_seconds(t.seconds as u64).plus_nanos(t.nanos as u64));
+        }
     }
+    update_position(deps.storage, &payload, completion_time)?;
 
     // update but also check that we don't update same seq_id twice
     ACKNOWLEDGEMENT_RESULTS.update(
@@ -729,6 +1099,35 @@ fn prepare_sudo_payload(mut deps: DepsMut, _env: Env, msg: Reply) -> StdResult<R
     Ok(Response::new())
 }
 
+fn prepare_balance_query(mut deps: DepsMut, env: Env, msg: Reply) -> StdResult<Response> {
+    let query_id = registered_query_id(msg)?;
+    track_query(deps.branch(), query_id, env.block.height)?;
+    let balance_query = PENDING_BALANCE_QUERY.load(deps.storage)?;
+    PENDING_BALANCE_QUERY.remove(deps.storage);
+    BALANCE_QUERIES.save(deps.storage, query_id, &balance_query)?;
+    Ok(Response::new())
+}
+
+fn prepare_registered_query(deps: DepsMut, env: Env, msg: Reply) -> StdResult<Response> {
+    let query_id = registered_query_id(msg)?;
+    track_query(deps, query_id, env.block.height)?;
+    Ok(Response::new().add_attribute("query_id", query_id.to_string()))
+}
+
+fn registered_query_id(msg: Reply) -> StdResult<u64> {
+    let resp: MsgRegisterInterchainQueryResponse = decode_message_response(
+        &msg.result
+            .into_result()
+            .map_err(StdError::generic_err)?
+            .msg_responses[0]
+            .clone()
+            .value
+            .to_vec(),
+    )
+    .map_err(|e| StdError::generic_err(format!("failed to parse response: {:?}", e)))?;
+    Ok(resp.id)
+}
+
 fn get_ica(
     deps: Deps<impl CustomQuery>,
     env: &Env,
@@ -747,6 +1146,8 @@ pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> StdResult<Response> {
         .debug(format!("WASMDEBUG: reply msg: {:?}", msg).as_str());
     match msg.id {
         SUDO_PAYLOAD_REPLY_ID => prepare_sudo_payload(deps, env, msg),
+        BALANCE_QUERY_REPLY_ID => prepare_balance_query(deps, env, msg),
+        REGISTER_QUERY_REPLY_ID => prepare_registered_query(deps, env, msg),
         _ => Err(StdError::generic_err(format!(
             "unsupported reply message id {}",
             msg.id
@@ -1,6 +1,9 @@
 use cosmwasm_std::Uint128;
+pub use ownership::OwnershipResponse;
+pub use recipient_transfers::{TransferCursor, TransferFilter};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+pub use tracked_queries::DepositsSummaryResponse;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
@@ -24,13 +27,51 @@ pub enum QueryMsg {
     Balance {
         query_id: u64,
     },
+    // this query returns the transfers to the recipient ordered by remote height
     GetRecipientTxs {
         recipient: String,
+        start_after: Option<TransferCursor>,
+        limit: Option<u32>,
+        filter: Option<TransferFilter>,
+    },
+    // this query returns the sums of the transfers to the recipient per denom
+    GetRecipientTotals {
+        recipient: String,
+    },
+    // this query returns the transactions with messages waiting for ProcessPending, the oldest first
+    GetPendingDeposits {
+        limit: Option<u32>,
+    },
+    // this query returns the position of the ICA built from acknowledged staking operations
+    Position {
+        interchain_account_id: String,
+        denom: String,
+    },
+    // this query returns the balance discrepancies found by reconciliation for the ICA
+    ReconciliationReports {
+        interchain_account_id: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // this query returns the deposit and the result heights of the query registered by the contract
+    TrackedQuery {
+        query_id: u64,
+    },
+    // this query returns the sum of the deposits locked by the queries registered by the contract
+    DepositsSummary {},
+    // this query returns the owner and the pending owner of the contract
+    Ownership {},
+    // this query returns the addresses allowed to execute an action besides the owner
+    Operators {
+        action: String,
     },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
-pub struct InstantiateMsg {}
+pub struct InstantiateMsg {
+    /// defaults to the instantiator
+    pub owner: Option<String>,
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
@@ -73,6 +114,57 @@ pub enum ExecuteMsg {
     RemoveInterchainQuery {
         query_id: u64,
     },
+    /// Sets the number of blocks without results after which a query can be removed with
+    /// RemoveStaleQueries. Omitting it disables the removal.
+    SetStaleQueryBlocks {
+        blocks: Option<u64>,
+    },
+    /// Checks up to `limit` tracked queries after `start_after` and removes the ones that haven't
+    /// received results for the stale query blocks, reclaiming their deposits. The last checked
+    /// query id is returned in the `last_query_id` attribute. Anyone can call it.
+    RemoveStaleQueries {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Credits deposits from up to `limit` (at most 20) messages of the transfers query
+    /// results that had more messages than could be processed in the callback. Anyone can call it.
+    ProcessPending {
+        limit: Option<u32>,
+    },
+    /// Starts a two-step ownership transfer, completed by `AcceptOwnership` from the new owner.
+    TransferOwnership {
+        new_owner: String,
+    },
+    AcceptOwnership {},
+    /// Replaces the addresses allowed to execute `action` besides the owner. The action is
+    /// the snake_case name of an execute message, e.g. `delegate`.
+    SetOperators {
+        action: String,
+        operators: Vec<String>,
+    },
+}
+
+impl ExecuteMsg {
+    /// Name of the action the operators allow-list is kept for
+    pub fn action(&self) -> &'static str {
+        match self {
+            ExecuteMsg::Register { .. } => "register",
+            ExecuteMsg::Delegate { .. } => "delegate",
+            ExecuteMsg::Undelegate { .. } => "undelegate",
+            ExecuteMsg::CleanAckResults {} => "clean_ack_results",
+            ExecuteMsg::CleanRecipientTxs {} => "clean_recipient_txs",
+            ExecuteMsg::SetFees { .. } => "set_fees",
+            ExecuteMsg::RegisterBalanceQuery { .. } => "register_balance_query",
+            ExecuteMsg::RegisterTransfersQuery { .. } => "register_transfers_query",
+            ExecuteMsg::RemoveInterchainQuery { .. } => "remove_interchain_query",
+            ExecuteMsg::SetStaleQueryBlocks { .. } => "set_stale_query_blocks",
+            ExecuteMsg::RemoveStaleQueries { .. } => "remove_stale_queries",
+            ExecuteMsg::ProcessPending { .. } => "process_pending",
+            ExecuteMsg::TransferOwnership { .. } => "transfer_ownership",
+            ExecuteMsg::AcceptOwnership {} => "accept_ownership",
+            ExecuteMsg::SetOperators { .. } => "set_operators",
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
@@ -1,4 +1,6 @@
-use cosmwasm_std::{from_json, to_json_vec, Binary, StdResult, Storage, Uint128};
+use cosmwasm_std::{
+    from_json, to_json_vec, Binary, Int128, StdResult, Storage, Timestamp, Uint128,
+};
 use cw_storage_plus::{Item, Map};
 use neutron_std::types::neutron::feerefunder::Fee;
 use schemars::JsonSchema;
@@ -10,11 +12,23 @@ pub struct SudoPayload {
     pub message: String,
     pub port_id: String,
     pub amount: Uint128,
+    /// staking operation sent within the tx, used to track the position of the ICA
+    pub operation: Option<StakingOperation>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StakingOperation {
+    Delegate { denom: String },
+    Undelegate { denom: String },
 }
 
 pub const SUDO_PAYLOAD_REPLY_ID: u64 = 1;
+pub const BALANCE_QUERY_REPLY_ID: u64 = 2;
+pub const REGISTER_QUERY_REPLY_ID: u64 = 3;
 
 pub const IBC_FEE: Item<Option<Fee>> = Item::new("ibc_fee");
+
 pub const REPLY_ID_STORAGE: Item<Vec<u8>> = Item::new("reply_queue_id");
 pub const SUDO_PAYLOAD: Map<(String, u64), Vec<u8>> = Map::new("sudo_payload");
 pub const LAST_SEQ_ID: Item<u64> = Item::new("last_seq_id");
@@ -25,23 +39,98 @@ pub const INTERCHAIN_ACCOUNTS: Map<String, Option<(String, String)>> =
 pub const ACKNOWLEDGEMENT_RESULTS: Map<(String, u64), AcknowledgementResult> =
     Map::new("acknowledgement_results");
 
-pub type Recipient = str;
+pub use recipient_transfers::{
+    GetPendingDepositsResponse, GetRecipientTotalsResponse, GetRecipientTxsResponse, Recipient,
+    Transfer, RECIPIENT_TOTALS, RECIPIENT_TRANSFERS,
+};
+pub use tracked_queries::{TrackedQuery, STALE_QUERY_BLOCKS, TRACKED_QUERIES};
+
+// address and denom of the balance queries registered by the contract, keyed by query id
+pub const BALANCE_QUERIES: Map<u64, BalanceQuery> = Map::new("balance_queries");
+// balance query waiting for its id in the reply
+pub const PENDING_BALANCE_QUERY: Item<BalanceQuery> = Item::new("pending_balance_query");
+
+// positions of the ICAs built from acknowledged staking operations, keyed by (port_id, denom)
+pub const POSITIONS: Map<(&str, &str), IcaPosition> = Map::new("positions");
+
+// balance discrepancies found by reconciliation, keyed by (port_id, report id)
+pub const RECONCILIATION_REPORTS: Map<(&str, u64), ReconciliationReport> =
+    Map::new("reconciliation_reports");
+pub const RECONCILIATION_REPORT_NEXT_ID: Item<u64> = Item::new("reconciliation_report_next_id");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct BalanceQuery {
+    pub addr: String,
+    pub denom: String,
+}
 
-/// contains all transfers mapped by a recipient address observed by the contract.
-pub const RECIPIENT_TXS: Map<&Recipient, Vec<Transfer>> = Map::new("recipient_txs");
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub struct IcaPosition {
+    /// part of the liquid balance not explained by the tracked operations,
+    /// set by the first balance result
+    pub baseline: Option<Uint128>,
+    /// total amount of acknowledged delegations
+    pub delegated: Uint128,
+    /// total amount of completed undelegations
+    pub unbonded: Uint128,
+    /// acknowledged undelegations which haven't been folded into `unbonded` yet
+    pub unbondings: Vec<Unbonding>,
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
-pub struct GetRecipientTxsResponse {
-    pub transfers: Vec<Transfer>,
+pub struct Unbonding {
+    pub amount: Uint128,
+    /// the funds are treated as returned once this time has passed or if it's unknown
+    pub completion_time: Option<Timestamp>,
 }
 
+impl Unbonding {
+    fn completed(&self, now: Timestamp) -> bool {
+        self.completion_time.map_or(true, |t| t <= now)
+    }
+}
+
+impl IcaPosition {
+    /// Adds the completed unbondings to `unbonded` and keeps only the pending ones
+    pub fn complete_unbondings(&mut self, now: Timestamp) -> StdResult<()> {
+        for unbonding in self.unbondings.iter().filter(|u| u.completed(now)) {
+            self.unbonded = self.unbonded.checked_add(unbonding.amount)?;
+        }
+        self.unbondings.retain(|u| !u.completed(now));
+        Ok(())
+    }
+
+    /// undelegated funds which haven't returned to the liquid balance yet
+    pub fn unbonding(&self, now: Timestamp) -> StdResult<Uint128> {
+        self.unbondings
+            .iter()
+            .filter(|u| !u.completed(now))
+            .try_fold(Uint128::zero(), |acc, u| -> StdResult<Uint128> {
+                Ok(acc.checked_add(u.amount)?)
+            })
+    }
+}
+
+/// Mismatch between the remote balance of an ICA and the balance expected from its position
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
-pub struct Transfer {
-    pub recipient: String,
-    pub sender: String,
+#[serde(rename_all = "snake_case")]
+pub struct ReconciliationReport {
+    pub id: u64,
+    pub query_id: u64,
     pub denom: String,
-    pub amount: String,
+    pub remote_balance: Uint128,
+    pub expected_balance: Uint128,
+    /// remote balance minus the expected one
+    pub discrepancy: Int128,
+    pub delegated: Uint128,
+    /// sum of incoming transfers observed by the transfers query
+    pub received: Uint128,
+    pub unbonded: Uint128,
+    pub height: u64,
+    pub time: Timestamp,
 }
 
 /// Serves for storing acknowledgement calls for interchain transactions
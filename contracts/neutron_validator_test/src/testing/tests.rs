@@ -0,0 +1,488 @@
+// Copyright 2022 Neutron
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::contract::{execute, instantiate, query, reply, sudo};
+use crate::msg::DepositsSummaryResponse;
+use crate::msg::{ExecuteMsg, Fees, InstantiateMsg, QueryMsg};
+use crate::storage::{
+    GetPendingDepositsResponse, GetRecipientTotalsResponse, IcaPosition, ReconciliationReport,
+    TrackedQuery,
+};
+use cosmos_sdk_proto::cosmos::bank::v1beta1::MsgSend;
+use cosmos_sdk_proto::cosmos::base::v1beta1::Coin as CosmosCoin;
+use cosmos_sdk_proto::cosmos::staking::v1beta1::{MsgDelegateResponse, MsgUndelegateResponse};
+use cosmwasm_std::testing::{message_info, mock_env, MockApi, MockStorage};
+use cosmwasm_std::{
+    coins, from_json, Addr, Env, Int128, OwnedDeps, Response, StdError, Timestamp, Uint128, Uint256,
+};
+use neutron_sdk::sudo::msg::SudoMsg;
+use neutron_sdk::NeutronError;
+use neutron_test_support::{
+    mock_dependencies, proto, Delivery, MockIca, MockIcq, NeutronMockQuerier,
+};
+
+type TestDeps = OwnedDeps<MockStorage, MockApi, NeutronMockQuerier>;
+
+const VALIDATOR: &str = "cosmosvaloper1qnk2n4nlkpw9xfqntladh74w6ujtulwnmxnh3k";
+
+/// Simulated chain side: the modules handling the messages the contract returns
+struct Modules {
+    ica: MockIca,
+    icq: MockIcq,
+}
+
+impl Modules {
+    fn new() -> Self {
+        Modules {
+            ica: MockIca::new(),
+            icq: MockIcq::new(),
+        }
+    }
+
+    fn handle(&mut self, deps: &mut TestDeps, env: &Env, res: Response) {
+        let mut deliveries = self.icq.handle_response(&mut deps.querier, env, &res);
+        deliveries.extend(self.ica.handle_response(&mut deps.querier, env, &res));
+        deliver(deps, env, deliveries);
+    }
+}
+
+fn deliver(deps: &mut TestDeps, env: &Env, deliveries: Vec<Delivery>) {
+    for delivery in deliveries {
+        match delivery {
+            Delivery::Reply(msg) => {
+                reply(deps.as_mut(), env.clone(), msg).unwrap();
+            }
+            Delivery::Sudo(msg) => {
+                sudo(deps.as_mut(), env.clone(), msg).unwrap();
+            }
+        }
+    }
+}
+
+fn run(deps: &mut TestDeps, env: &Env, modules: &mut Modules, sender: &Addr, msg: ExecuteMsg) {
+    let res = execute(deps.as_mut(), env.clone(), message_info(sender, &[]), msg).unwrap();
+    modules.handle(deps, env, res);
+}
+
+fn position(deps: &TestDeps) -> IcaPosition {
+    from_json(
+        query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Position {
+                interchain_account_id: "test".to_string(),
+                denom: "stake".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap()
+}
+
+fn reconciliation_reports(deps: &TestDeps) -> Vec<ReconciliationReport> {
+    from_json(
+        query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::ReconciliationReports {
+                interchain_account_id: "test".to_string(),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap()
+}
+
+fn ack_undelegation(modules: &mut Modules, sequence: u64, completion_time: Timestamp) -> SudoMsg {
+    let data = proto::ack_data(vec![proto::any(
+        "/cosmos.staking.v1beta1.MsgUndelegateResponse",
+        &MsgUndelegateResponse {
+            completion_time: Some(prost_types::Timestamp {
+                seconds: completion_time.seconds() as i64,
+                nanos: completion_time.subsec_nanos() as i32,
+            }),
+        },
+    )]);
+    modules.ica.ack("channel-0", sequence, data)
+}
+
+fn submit_balance(deps: &mut TestDeps, env: &Env, modules: &Modules, amount: u128, height: u64) {
+    let ica_addr = ica_address(deps);
+    let callback = modules.icq.submit_kv_result(
+        &mut deps.querier,
+        env,
+        1,
+        vec![proto::balance_value(&ica_addr, "stake", amount)],
+        height,
+    );
+    deliver(deps, env, vec![Delivery::Sudo(callback)]);
+}
+
+fn ica_address(deps: &TestDeps) -> String {
+    let (ica_addr, _): (String, String) = from_json(
+        query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::InterchainAccountAddressFromContract {
+                interchain_account_id: "test".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    ica_addr
+}
+
+/// Registers the "test" ICA with an acknowledged delegation of 100stake and a balance query
+/// of it (query 1) which hasn't received any result yet
+fn setup() -> (TestDeps, Env, Modules, Addr) {
+    let mut deps = mock_dependencies(&[]);
+    let env = mock_env();
+    let mut modules = Modules::new();
+    let owner = deps.api.addr_make("owner");
+    instantiate(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&owner, &[]),
+        InstantiateMsg { owner: None },
+    )
+    .unwrap();
+    run(
+        &mut deps,
+        &env,
+        &mut modules,
+        &owner,
+        ExecuteMsg::SetFees {
+            fees: Some(Fees {
+                denom: "untrn".to_string(),
+                recv_fee: Uint128::zero(),
+                ack_fee: Uint128::new(1000),
+                timeout_fee: Uint128::new(1000),
+            }),
+        },
+    );
+    run(
+        &mut deps,
+        &env,
+        &mut modules,
+        &owner,
+        ExecuteMsg::Register {
+            connection_id: "connection-0".to_string(),
+            interchain_account_id: "test".to_string(),
+        },
+    );
+    let ica_addr = ica_address(&deps);
+
+    // an acknowledged delegation moves the funds into the position
+    run(
+        &mut deps,
+        &env,
+        &mut modules,
+        &owner,
+        ExecuteMsg::Delegate {
+            interchain_account_id: "test".to_string(),
+            validator: VALIDATOR.to_string(),
+            amount: Uint128::new(100),
+            denom: "stake".to_string(),
+            timeout: None,
+        },
+    );
+    let data = proto::ack_data(vec![proto::any(
+        "/cosmos.staking.v1beta1.MsgDelegateResponse",
+        &MsgDelegateResponse {},
+    )]);
+    let ack = modules.ica.ack("channel-0", 1, data);
+    deliver(&mut deps, &env, vec![Delivery::Sudo(ack)]);
+    assert_eq!(position(&deps).delegated, Uint128::new(100));
+
+    run(
+        &mut deps,
+        &env,
+        &mut modules,
+        &owner,
+        ExecuteMsg::RegisterBalanceQuery {
+            connection_id: "connection-0".to_string(),
+            update_period: 10,
+            addr: ica_addr,
+            denom: "stake".to_string(),
+        },
+    );
+    (deps, env, modules, owner)
+}
+
+#[test]
+fn test_balance_reconciliation() {
+    let (mut deps, env, modules, _) = setup();
+
+    // the first balance sets the baseline
+    submit_balance(&mut deps, &env, &modules, 900, 100);
+    assert_eq!(position(&deps).baseline, Some(Uint128::new(1000)));
+    assert!(reconciliation_reports(&deps).is_empty());
+
+    // funds appearing without a tracked transfer are reported
+    submit_balance(&mut deps, &env, &modules, 950, 101);
+    let reports = reconciliation_reports(&deps);
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].remote_balance, Uint128::new(950));
+    assert_eq!(reports[0].expected_balance, Uint128::new(900));
+    assert_eq!(reports[0].discrepancy, Int128::new(50));
+}
+
+#[test]
+fn test_reconciliation_acked_undelegation() {
+    let (mut deps, env, mut modules, owner) = setup();
+    submit_balance(&mut deps, &env, &modules, 900, 100);
+
+    run(
+        &mut deps,
+        &env,
+        &mut modules,
+        &owner,
+        ExecuteMsg::Undelegate {
+            interchain_account_id: "test".to_string(),
+            validator: VALIDATOR.to_string(),
+            amount: Uint128::new(40),
+            denom: "stake".to_string(),
+            timeout: None,
+        },
+    );
+    let completion_time = env.block.time.plus_seconds(100);
+    let ack = ack_undelegation(&mut modules, 2, completion_time);
+    deliver(&mut deps, &env, vec![Delivery::Sudo(ack)]);
+    let pos = position(&deps);
+    assert_eq!(pos.delegated, Uint128::new(60));
+    assert_eq!(pos.unbondings.len(), 1);
+    assert_eq!(pos.unbondings[0].completion_time, Some(completion_time));
+
+    // the unbonding funds are neither delegated nor liquid yet
+    submit_balance(&mut deps, &env, &modules, 900, 101);
+    assert!(reconciliation_reports(&deps).is_empty());
+
+    // and are back in the liquid balance once the unbonding completes
+    let mut later = env.clone();
+    later.block.time = completion_time.plus_seconds(1);
+    submit_balance(&mut deps, &later, &modules, 940, 102);
+    assert!(reconciliation_reports(&deps).is_empty());
+    // the completed unbonding is folded into the total
+    let pos = position(&deps);
+    assert_eq!(pos.unbonded, Uint128::new(40));
+    assert!(pos.unbondings.is_empty());
+
+    submit_balance(&mut deps, &later, &modules, 900, 103);
+    let reports = reconciliation_reports(&deps);
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].unbonded, Uint128::new(40));
+    assert_eq!(reports[0].expected_balance, Uint128::new(940));
+    assert_eq!(reports[0].discrepancy, Int128::new(-40));
+}
+
+#[test]
+fn test_reconciliation_incoming_transfer() {
+    let (mut deps, env, mut modules, owner) = setup();
+    submit_balance(&mut deps, &env, &modules, 900, 100);
+    let ica_addr = ica_address(&deps);
+
+    run(
+        &mut deps,
+        &env,
+        &mut modules,
+        &owner,
+        ExecuteMsg::RegisterTransfersQuery {
+            connection_id: "connection-0".to_string(),
+            update_period: 10,
+            recipient: ica_addr.clone(),
+            min_height: None,
+        },
+    );
+    let data = proto::send_tx("cosmos1sender", &ica_addr, &[("stake", 50)]);
+    let callback = modules
+        .icq
+        .submit_tx_result(&mut deps.querier, 2, 101, data);
+    deliver(&mut deps, &env, vec![Delivery::Sudo(callback)]);
+
+    // the tracked transfer explains the new funds
+    submit_balance(&mut deps, &env, &modules, 950, 102);
+    assert!(reconciliation_reports(&deps).is_empty());
+
+    submit_balance(&mut deps, &env, &modules, 1000, 103);
+    let reports = reconciliation_reports(&deps);
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].received, Uint128::new(50));
+    assert_eq!(reports[0].expected_balance, Uint128::new(950));
+    assert_eq!(reports[0].discrepancy, Int128::new(50));
+}
+
+#[test]
+fn test_process_pending_deposits() {
+    let (mut deps, env, mut modules, owner) = setup();
+    let ica_addr = ica_address(&deps);
+    run(
+        &mut deps,
+        &env,
+        &mut modules,
+        &owner,
+        ExecuteMsg::RegisterTransfersQuery {
+            connection_id: "connection-0".to_string(),
+            update_period: 10,
+            recipient: ica_addr.clone(),
+            min_height: None,
+        },
+    );
+
+    // 25 MsgSend of 1 stake each, 5 of them are left for ProcessPending
+    let messages = (0..25)
+        .map(|_| {
+            proto::any(
+                "/cosmos.bank.v1beta1.MsgSend",
+                &MsgSend {
+                    from_address: "cosmos1sender".to_string(),
+                    to_address: ica_addr.clone(),
+                    amount: vec![CosmosCoin {
+                        denom: "stake".to_string(),
+                        amount: "1".to_string(),
+                    }],
+                },
+            )
+        })
+        .collect();
+    let data = proto::tx(messages);
+    let callback = modules
+        .icq
+        .submit_tx_result(&mut deps.querier, 2, 101, data.clone());
+    deliver(&mut deps, &env, vec![Delivery::Sudo(callback)]);
+    // the tx with pending messages can't be submitted again
+    let callback = modules
+        .icq
+        .submit_tx_result(&mut deps.querier, 2, 101, data);
+    let err = sudo(deps.as_mut(), env.clone(), callback).unwrap_err();
+    assert_eq!(
+        err,
+        NeutronError::Std(StdError::generic_err(
+            "transaction has already been processed"
+        ))
+    );
+
+    let pending_deposits = |deps: &TestDeps| -> GetPendingDepositsResponse {
+        from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::GetPendingDeposits { limit: None },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+    };
+    let received = |deps: &TestDeps| -> GetRecipientTotalsResponse {
+        from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::GetRecipientTotals {
+                    recipient: ica_addr.clone(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+    };
+    let pending = pending_deposits(&deps).txs;
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].messages, 5);
+    assert_eq!(received(&deps).totals[0].amount, Uint256::from(20u128));
+
+    // anyone can process the pending messages
+    let anyone = deps.api.addr_make("anyone");
+    run(
+        &mut deps,
+        &env,
+        &mut modules,
+        &anyone,
+        ExecuteMsg::ProcessPending { limit: None },
+    );
+    assert!(pending_deposits(&deps).txs.is_empty());
+    assert_eq!(received(&deps).totals[0].amount, Uint256::from(25u128));
+}
+
+#[test]
+fn test_remove_stale_queries() {
+    let (mut deps, mut env, mut modules, owner) = setup();
+    let ica_addr = ica_address(&deps);
+    run(
+        &mut deps,
+        &env,
+        &mut modules,
+        &owner,
+        ExecuteMsg::RegisterTransfersQuery {
+            connection_id: "connection-0".to_string(),
+            update_period: 10,
+            recipient: ica_addr,
+            min_height: None,
+        },
+    );
+    let summary: DepositsSummaryResponse =
+        from_json(query(deps.as_ref(), mock_env(), QueryMsg::DepositsSummary {}).unwrap()).unwrap();
+    // the balance query and the transfers query
+    assert_eq!(summary.queries, 2);
+    assert_eq!(summary.locked, coins(2_000_000, "untrn"));
+
+    run(
+        &mut deps,
+        &env,
+        &mut modules,
+        &owner,
+        ExecuteMsg::SetStaleQueryBlocks { blocks: Some(10) },
+    );
+    env.block.height += 15;
+    // the balance query receives a result, the transfers query doesn't
+    submit_balance(&mut deps, &env, &modules, 900, 100);
+
+    let anyone = deps.api.addr_make("anyone");
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        message_info(&anyone, &[]),
+        ExecuteMsg::RemoveStaleQueries {
+            start_after: None,
+            limit: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert!(res
+        .attributes
+        .iter()
+        .any(|attr| attr.key == "removed_query_id" && attr.value == "2"));
+    modules.handle(&mut deps, &env, res);
+
+    let tracked = |deps: &TestDeps, query_id: u64| -> Option<TrackedQuery> {
+        from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::TrackedQuery { query_id },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+    };
+    assert_eq!(
+        tracked(&deps, 1).unwrap().last_result_height,
+        env.block.height
+    );
+    assert_eq!(tracked(&deps, 2), None);
+    assert!(deps.querier.registered_query(2).is_none());
+}
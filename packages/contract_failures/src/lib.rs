@@ -0,0 +1,278 @@
+// Copyright 2022 Neutron
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Failures the contractmanager recorded for the sudo calls of a contract. They are listed with
+//! their sudo payloads decoded and resubmitted in bulk, every failure only once, with the
+//! outcome of each resubmission recorded by the contract.
+
+#![warn(clippy::unwrap_used, clippy::expect_used)]
+
+use cosmwasm_std::{
+    from_json, to_json_binary, CosmosMsg, Deps, DepsMut, Env, Order, Reply, Response, StdResult,
+    SubMsg,
+};
+use cw_storage_plus::{Bound, Map};
+use neutron_sdk::sudo::msg::{RequestPacket, SudoMsg, TransferSudoMsg};
+use neutron_std::types::cosmos::base::query::v1beta1::PageRequest;
+use neutron_std::types::neutron::contractmanager::{
+    ContractmanagerQuerier, Failure, MsgResubmitFailure, QueryFailuresResponse,
+};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+// Number of failures read from the contractmanager at once when looking for ones to resubmit
+const FAILURES_PAGE_LIMIT: u64 = 50;
+// Maximum number of pages read by a single resubmission, the rest is left for the next one
+const MAX_FAILURES_PAGES: usize = 4;
+// Default and maximum number of failures resubmitted at once
+const DEFAULT_RESUBMIT_MAX: u32 = 10;
+const MAX_RESUBMIT_MAX: u32 = 50;
+
+/// Outcome of the bulk resubmission of a contractmanager failure, by failure id
+pub const RESUBMISSIONS: Map<u64, Resubmission> = Map::new("resubmissions");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Resubmission {
+    pub failure_id: u64,
+    pub height: u64,
+    /// error the resubmitted handler failed with again, `None` if it succeeded
+    pub error: Option<String>,
+}
+
+/// Sudo call a contractmanager failure has been recorded for
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    Response,
+    Error,
+    Timeout,
+    /// a payload which isn't an acknowledgement or a timeout
+    Unknown,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct FailureInfo {
+    pub id: u64,
+    pub kind: FailureKind,
+    pub channel_id: Option<String>,
+    pub sequence_id: Option<u64>,
+    /// details of the error acknowledgement
+    pub details: Option<String>,
+    /// error of the sudo handler as recorded by the contractmanager
+    pub error: String,
+    pub resubmission: Option<Resubmission>,
+}
+
+/// Sudo message of a contract whose failed acknowledgements and timeouts can be told apart
+pub trait FailedSudo: DeserializeOwned {
+    /// Returns the kind of the call, its packet and the details of an error acknowledgement
+    fn into_failure(self) -> (FailureKind, Option<RequestPacket>, Option<String>);
+}
+
+impl FailedSudo for SudoMsg {
+    fn into_failure(self) -> (FailureKind, Option<RequestPacket>, Option<String>) {
+        match self {
+            SudoMsg::Response { request, .. } => (FailureKind::Response, Some(request), None),
+            SudoMsg::Error { request, details } => {
+                (FailureKind::Error, Some(request), Some(details))
+            }
+            SudoMsg::Timeout { request } => (FailureKind::Timeout, Some(request), None),
+            _ => (FailureKind::Unknown, None, None),
+        }
+    }
+}
+
+impl FailedSudo for TransferSudoMsg {
+    fn into_failure(self) -> (FailureKind, Option<RequestPacket>, Option<String>) {
+        match self {
+            TransferSudoMsg::Response { request, .. } => {
+                (FailureKind::Response, Some(request), None)
+            }
+            TransferSudoMsg::Error { request, details } => {
+                (FailureKind::Error, Some(request), Some(details))
+            }
+            TransferSudoMsg::Timeout { request } => (FailureKind::Timeout, Some(request), None),
+        }
+    }
+}
+
+/// Filter of the failures to resubmit
+#[derive(Default)]
+pub struct ResubmitFilter {
+    pub channel_id: Option<String>,
+    pub error_contains: Option<String>,
+}
+
+impl ResubmitFilter {
+    fn matches(&self, failure: &FailureInfo) -> bool {
+        failure.resubmission.is_none()
+            && self
+                .channel_id
+                .as_ref()
+                .map_or(true, |c| failure.channel_id.as_ref() == Some(c))
+            && self
+                .error_contains
+                .as_ref()
+                .map_or(true, |e| failure.error.contains(e.as_str()))
+    }
+}
+
+// the failures are keyed by their big endian id within the contract's prefix
+fn failures_key(start_after: Option<u64>) -> Vec<u8> {
+    start_after
+        .map(|id| id.saturating_add(1).to_be_bytes().to_vec())
+        .unwrap_or_default()
+}
+
+fn failures_page(
+    deps: Deps,
+    env: &Env,
+    key: Vec<u8>,
+    limit: u64,
+) -> StdResult<QueryFailuresResponse> {
+    ContractmanagerQuerier::new(&deps.querier).failures(
+        env.contract.address.to_string(),
+        Some(PageRequest {
+            key,
+            offset: 0,
+            limit,
+            count_total: false,
+            reverse: false,
+        }),
+    )
+}
+
+/// Decodes the sudo call the failure has been recorded for
+fn decode_failure<M: FailedSudo>(deps: Deps, failure: Failure) -> StdResult<FailureInfo> {
+    let (kind, request, details) = match from_json::<M>(&failure.sudo_payload) {
+        Ok(msg) => msg.into_failure(),
+        Err(_) => (FailureKind::Unknown, None, None),
+    };
+    Ok(FailureInfo {
+        id: failure.id,
+        kind,
+        channel_id: request.as_ref().and_then(|r| r.source_channel.clone()),
+        sequence_id: request.and_then(|r| r.sequence),
+        details,
+        error: failure.error,
+        resubmission: RESUBMISSIONS.may_load(deps.storage, failure.id)?,
+    })
+}
+
+/// Failures of the contract ordered by id, read from the contractmanager
+pub fn query_failures<M: FailedSudo>(
+    deps: Deps,
+    env: Env,
+    start_after: Option<u64>,
+    limit: u32,
+) -> StdResult<Vec<FailureInfo>> {
+    failures_page(deps, &env, failures_key(start_after), limit as u64)?
+        .failures
+        .into_iter()
+        .map(|failure| decode_failure::<M>(deps, failure))
+        .collect()
+}
+
+pub fn query_resubmissions(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: u32,
+) -> StdResult<Vec<Resubmission>> {
+    RESUBMISSIONS
+        .range(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit as usize)
+        .map(|item| item.map(|(_, resubmission)| resubmission))
+        .collect()
+}
+
+/// Resubmits up to `max` matching failures after `start_after` which haven't been resubmitted
+/// yet, each in a submessage replied to with `reply_id` whatever the outcome is, so one failing
+/// again doesn't revert the others. At most a few pages of failures are read, the last checked
+/// failure id is returned in the `last_failure_id` attribute to continue from.
+pub fn execute_resubmit_failures<M: FailedSudo>(
+    deps: DepsMut,
+    env: Env,
+    filter: ResubmitFilter,
+    start_after: Option<u64>,
+    max: Option<u32>,
+    reply_id: u64,
+) -> StdResult<Response> {
+    let max = max.unwrap_or(DEFAULT_RESUBMIT_MAX).min(MAX_RESUBMIT_MAX) as usize;
+
+    let mut failure_ids = vec![];
+    let mut last_failure_id = None;
+    let mut key = failures_key(start_after);
+    'pages: for _ in 0..MAX_FAILURES_PAGES {
+        let page = failures_page(deps.as_ref(), &env, key, FAILURES_PAGE_LIMIT)?;
+        for failure in page.failures {
+            let failure = decode_failure::<M>(deps.as_ref(), failure)?;
+            last_failure_id = Some(failure.id);
+            if filter.matches(&failure) {
+                failure_ids.push(failure.id);
+                if failure_ids.len() == max {
+                    break 'pages;
+                }
+            }
+        }
+        match page.pagination {
+            Some(pagination) if !pagination.next_key.is_empty() => key = pagination.next_key,
+            _ => break,
+        }
+    }
+
+    let submsgs = failure_ids
+        .iter()
+        .map(|failure_id| {
+            let msg: CosmosMsg = MsgResubmitFailure {
+                sender: env.contract.address.to_string(),
+                failure_id: *failure_id,
+            }
+            .into();
+            Ok(SubMsg::reply_always(msg, reply_id).with_payload(to_json_binary(failure_id)?))
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    let mut response = Response::default()
+        .add_attribute("action", "resubmit_failures")
+        .add_attribute("count", submsgs.len().to_string());
+    if let Some(failure_id) = last_failure_id {
+        response = response.add_attribute("last_failure_id", failure_id.to_string());
+    }
+    Ok(response.add_submessages(submsgs))
+}
+
+/// Records the outcome of a resubmission, the failure id is passed in the payload
+pub fn resubmit_failure_reply(deps: DepsMut, env: Env, msg: Reply) -> StdResult<Response> {
+    let failure_id: u64 = from_json(&msg.payload)?;
+    RESUBMISSIONS.save(
+        deps.storage,
+        failure_id,
+        &Resubmission {
+            failure_id,
+            height: env.block.height,
+            error: msg.result.into_result().err(),
+        },
+    )?;
+    Ok(Response::default())
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod testing;
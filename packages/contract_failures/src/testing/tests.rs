@@ -0,0 +1,120 @@
+// Copyright 2022 Neutron
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    execute_resubmit_failures, query_failures, query_resubmissions, resubmit_failure_reply,
+    FailureKind, Resubmission, ResubmitFilter,
+};
+use cosmwasm_std::testing::mock_env;
+use cosmwasm_std::{Binary, DepsMut, Reply, Response, SubMsgResult};
+use neutron_sdk::sudo::msg::TransferSudoMsg;
+use neutron_test_support::{mock_dependencies, MockContractManager};
+
+fn timeout_payload(channel: &str, sequence: u64) -> Binary {
+    Binary::from(
+        format!(
+            r#"{{"timeout":{{"request":{{"sequence":{},"source_channel":"{}"}}}}}}"#,
+            sequence, channel
+        )
+        .into_bytes(),
+    )
+}
+
+#[test]
+fn test_resubmit_failures_pages() {
+    let mut deps = mock_dependencies(&[]);
+    let env = mock_env();
+    let contract = env.contract.address.to_string();
+    let mut contractmanager = MockContractManager::new();
+    // 205 failures on channel-1 followed by 5 on channel-0
+    for sequence in 0..210 {
+        let channel = if sequence < 205 {
+            "channel-1"
+        } else {
+            "channel-0"
+        };
+        contractmanager.add_failure(
+            &mut deps.querier,
+            &contract,
+            timeout_payload(channel, sequence),
+            "handler failed",
+        );
+    }
+    contractmanager.add_failure(
+        &mut deps.querier,
+        &contract,
+        Binary::from(b"{}".as_slice()),
+        "handler failed",
+    );
+
+    let failures =
+        query_failures::<TransferSudoMsg>(deps.as_ref(), env.clone(), Some(204), 10).unwrap();
+    assert_eq!(failures.len(), 6);
+    assert_eq!(failures[0].id, 205);
+    assert_eq!(failures[0].kind, FailureKind::Timeout);
+    assert_eq!(failures[0].channel_id, Some("channel-0".to_string()));
+    assert_eq!(failures[0].sequence_id, Some(205));
+    assert_eq!(failures[5].kind, FailureKind::Unknown);
+
+    let resubmit = |deps: DepsMut, start_after: Option<u64>| {
+        execute_resubmit_failures::<TransferSudoMsg>(
+            deps,
+            env.clone(),
+            ResubmitFilter {
+                channel_id: Some("channel-0".to_string()),
+                error_contains: None,
+            },
+            start_after,
+            None,
+            1,
+        )
+        .unwrap()
+    };
+    let last_failure_id = |res: &Response| {
+        res.attributes
+            .iter()
+            .find(|attr| attr.key == "last_failure_id")
+            .map(|attr| attr.value.clone())
+    };
+    // the scan stops after a few pages without a match
+    let res = resubmit(deps.as_mut(), None);
+    assert!(res.messages.is_empty());
+    assert_eq!(last_failure_id(&res), Some("199".to_string()));
+    // and continues from the last checked failure
+    let res = resubmit(deps.as_mut(), Some(199));
+    assert_eq!(res.messages.len(), 5);
+    assert_eq!(last_failure_id(&res), Some("210".to_string()));
+
+    resubmit_failure_reply(
+        deps.as_mut(),
+        env.clone(),
+        Reply {
+            id: 1,
+            payload: res.messages[0].payload.clone(),
+            gas_used: 0,
+            result: SubMsgResult::Err("handler failed again".to_string()),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        query_resubmissions(deps.as_ref(), None, 10).unwrap(),
+        vec![Resubmission {
+            failure_id: 205,
+            height: env.block.height,
+            error: Some("handler failed again".to_string()),
+        }]
+    );
+    // a resubmitted failure isn't picked up again
+    assert_eq!(resubmit(deps.as_mut(), Some(199)).messages.len(), 4);
+}
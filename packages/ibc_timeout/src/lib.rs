@@ -0,0 +1,206 @@
+// Copyright 2022 Neutron
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Timeouts of the IBC transfers and the interchain txs sent by the contracts. A
+//! [`TimeoutSpec`] is validated and resolved to the timeout of the message before it is
+//! dispatched, the relative ones against the current block time or the latest height of the
+//! counterparty chain known to the channel's light client. The latter is also exposed for the
+//! contracts measuring how far behind the counterparty chain their data is.
+
+#![warn(clippy::unwrap_used, clippy::expect_used)]
+
+use cosmwasm_std::{CustomQuery, Env, QuerierWrapper, StdError, StdResult, Timestamp};
+use neutron_std::types::ibc::core::channel::v1::ChannelQuerier;
+use neutron_std::types::ibc::core::client::v1::{Height, IdentifiedClientState};
+use neutron_std::types::ibc::core::connection::v1::ConnectionQuerier;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub const TENDERMINT_CLIENT_STATE_TYPE_URL: &str = "/ibc.lightclients.tendermint.v1.ClientState";
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeoutSpec {
+    /// height of the counterparty chain in its current revision
+    AtHeight { height: u64 },
+    /// time of the counterparty chain
+    AtTime { time: Timestamp },
+    /// seconds after the current block time
+    AfterSeconds { seconds: u64 },
+    /// blocks after the latest height of the counterparty chain known to the light client
+    AfterBlocks { blocks: u64 },
+}
+
+/// Timeout of an IBC packet, as set in `MsgTransfer`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PacketTimeout {
+    pub height: Option<Height>,
+    /// nanoseconds since the epoch, 0 if the packet only times out by height
+    pub timestamp: u64,
+}
+
+/// Fields of the tendermint light client state the timeouts are checked against
+#[derive(Clone, PartialEq, prost::Message)]
+struct TendermintClientState {
+    #[prost(message, optional, tag = "6")]
+    frozen_height: Option<Height>,
+    #[prost(message, optional, tag = "7")]
+    latest_height: Option<Height>,
+}
+
+/// Latest height of the counterparty chain known to the light client of the channel
+pub fn client_latest_height<Q: CustomQuery>(
+    querier: &QuerierWrapper<Q>,
+    port_id: &str,
+    channel_id: &str,
+) -> StdResult<Height> {
+    let client = ChannelQuerier::new(querier)
+        .channel_client_state(port_id.to_string(), channel_id.to_string())?
+        .identified_client_state
+        .ok_or_else(|| {
+            StdError::generic_err(format!("no client state of channel {}", channel_id))
+        })?;
+    tendermint_latest_height(client)
+}
+
+/// Latest height of the counterparty chain known to the light client of the connection
+pub fn connection_client_latest_height<Q: CustomQuery>(
+    querier: &QuerierWrapper<Q>,
+    connection_id: &str,
+) -> StdResult<Height> {
+    let client = ConnectionQuerier::new(querier)
+        .connection_client_state(connection_id.to_string())?
+        .identified_client_state
+        .ok_or_else(|| {
+            StdError::generic_err(format!("no client state of connection {}", connection_id))
+        })?;
+    tendermint_latest_height(client)
+}
+
+fn tendermint_latest_height(client: IdentifiedClientState) -> StdResult<Height> {
+    let state = client
+        .client_state
+        .filter(|state| state.type_url == TENDERMINT_CLIENT_STATE_TYPE_URL)
+        .ok_or_else(|| {
+            StdError::generic_err(format!(
+                "client {} is not a tendermint light client",
+                client.client_id
+            ))
+        })?;
+    let state: TendermintClientState = prost::Message::decode(state.value.as_slice())
+        .map_err(|e| StdError::generic_err(format!("failed to decode client state: {}", e)))?;
+    if state
+        .frozen_height
+        .is_some_and(|h| h.revision_number != 0 || h.revision_height != 0)
+    {
+        return Err(StdError::generic_err(format!(
+            "client {} is frozen",
+            client.client_id
+        )));
+    }
+    state.latest_height.ok_or_else(|| {
+        StdError::generic_err(format!("client {} has no latest height", client.client_id))
+    })
+}
+
+fn after_seconds(env: &Env, seconds: u64) -> StdResult<Timestamp> {
+    seconds
+        .checked_mul(NANOS_PER_SECOND)
+        .and_then(|nanos| env.block.time.nanos().checked_add(nanos))
+        .map(Timestamp::from_nanos)
+        .ok_or_else(|| StdError::generic_err(format!("timeout of {} seconds overflows", seconds)))
+}
+
+impl TimeoutSpec {
+    /// Timeout of a packet sent over the channel
+    pub fn packet_timeout<Q: CustomQuery>(
+        &self,
+        querier: &QuerierWrapper<Q>,
+        env: &Env,
+        port_id: &str,
+        channel_id: &str,
+    ) -> StdResult<PacketTimeout> {
+        match self {
+            TimeoutSpec::AtHeight { height } => {
+                let latest = client_latest_height(querier, port_id, channel_id)?;
+                if *height <= latest.revision_height {
+                    return Err(StdError::generic_err(format!(
+                        "timeout height {} has been reached, the counterparty chain is at {}",
+                        height, latest.revision_height
+                    )));
+                }
+                Ok(PacketTimeout {
+                    height: Some(Height {
+                        revision_number: latest.revision_number,
+                        revision_height: *height,
+                    }),
+                    timestamp: 0,
+                })
+            }
+            TimeoutSpec::AfterBlocks { blocks } => {
+                if *blocks == 0 {
+                    return Err(StdError::generic_err("timeout blocks must be positive"));
+                }
+                let latest = client_latest_height(querier, port_id, channel_id)?;
+                let height = latest.revision_height.checked_add(*blocks).ok_or_else(|| {
+                    StdError::generic_err(format!("timeout of {} blocks overflows", blocks))
+                })?;
+                Ok(PacketTimeout {
+                    height: Some(Height {
+                        revision_number: latest.revision_number,
+                        revision_height: height,
+                    }),
+                    timestamp: 0,
+                })
+            }
+            TimeoutSpec::AtTime { .. } | TimeoutSpec::AfterSeconds { .. } => Ok(PacketTimeout {
+                height: None,
+                timestamp: self.timeout_time(env)?.nanos(),
+            }),
+        }
+    }
+
+    /// Timeout in seconds after the current block time, as set in the interchaintxs
+    /// `MsgSubmitTx`. The module only times the txs out by time.
+    pub fn relative_seconds(&self, env: &Env) -> StdResult<u64> {
+        let time = self.timeout_time(env)?;
+        Ok((time.nanos() - env.block.time.nanos()).div_ceil(NANOS_PER_SECOND))
+    }
+
+    /// Time the spec times out at, it has to be after the current block time
+    fn timeout_time(&self, env: &Env) -> StdResult<Timestamp> {
+        let time = match self {
+            TimeoutSpec::AtTime { time } => *time,
+            TimeoutSpec::AfterSeconds { seconds } => after_seconds(env, *seconds)?,
+            TimeoutSpec::AtHeight { .. } | TimeoutSpec::AfterBlocks { .. } => {
+                return Err(StdError::generic_err(
+                    "a timeout by height is only supported for the IBC transfers",
+                ));
+            }
+        };
+        if time <= env.block.time {
+            return Err(StdError::generic_err(format!(
+                "timeout time {} has passed, the current block time is {}",
+                time, env.block.time
+            )));
+        }
+        Ok(time)
+    }
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod testing;
@@ -0,0 +1,104 @@
+// Copyright 2022 Neutron
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{connection_client_latest_height, PacketTimeout, TimeoutSpec};
+use cosmwasm_std::testing::mock_env;
+use neutron_std::types::ibc::core::client::v1::Height;
+use neutron_test_support::mock_dependencies;
+
+fn height(revision_number: u64, revision_height: u64) -> Height {
+    Height {
+        revision_number,
+        revision_height,
+    }
+}
+
+#[test]
+fn test_packet_timeout_by_height() {
+    let mut deps = mock_dependencies(&[]);
+    deps.querier
+        .set_channel_client("transfer", "channel-0", "07-tendermint-0", height(4, 1000));
+    let env = mock_env();
+    let timeout = |spec: TimeoutSpec, channel: &str| {
+        spec.packet_timeout(&deps.as_ref().querier, &env, "transfer", channel)
+    };
+
+    assert_eq!(
+        timeout(TimeoutSpec::AfterBlocks { blocks: 50 }, "channel-0").unwrap(),
+        PacketTimeout {
+            height: Some(height(4, 1050)),
+            timestamp: 0,
+        }
+    );
+    assert_eq!(
+        timeout(TimeoutSpec::AtHeight { height: 1001 }, "channel-0").unwrap(),
+        PacketTimeout {
+            height: Some(height(4, 1001)),
+            timestamp: 0,
+        }
+    );
+    // the height has to be ahead of the counterparty chain, and the client known
+    timeout(TimeoutSpec::AtHeight { height: 1000 }, "channel-0").unwrap_err();
+    timeout(TimeoutSpec::AfterBlocks { blocks: 0 }, "channel-0").unwrap_err();
+    timeout(TimeoutSpec::AfterBlocks { blocks: 50 }, "channel-1").unwrap_err();
+}
+
+#[test]
+fn test_timeout_by_time() {
+    let deps = mock_dependencies(&[]);
+    let env = mock_env();
+
+    let spec = TimeoutSpec::AfterSeconds { seconds: 60 };
+    assert_eq!(
+        spec.packet_timeout(&deps.as_ref().querier, &env, "transfer", "channel-0")
+            .unwrap(),
+        PacketTimeout {
+            height: None,
+            timestamp: env.block.time.plus_seconds(60).nanos(),
+        }
+    );
+    assert_eq!(spec.relative_seconds(&env).unwrap(), 60);
+
+    // an absolute time is rounded up to whole seconds
+    let spec = TimeoutSpec::AtTime {
+        time: env.block.time.plus_nanos(1_500_000_000),
+    };
+    assert_eq!(spec.relative_seconds(&env).unwrap(), 2);
+
+    for spec in [
+        TimeoutSpec::AtTime {
+            time: env.block.time,
+        },
+        TimeoutSpec::AfterSeconds { seconds: 0 },
+        TimeoutSpec::AfterSeconds { seconds: u64::MAX },
+        // interchain txs don't time out by height
+        TimeoutSpec::AfterBlocks { blocks: 50 },
+    ] {
+        spec.relative_seconds(&env).unwrap_err();
+    }
+}
+
+#[test]
+fn test_connection_client_latest_height() {
+    let mut deps = mock_dependencies(&[]);
+    deps.querier
+        .set_connection_client("connection-0", "07-tendermint-0", height(4, 1000));
+    let querier = &deps.as_ref().querier;
+
+    assert_eq!(
+        connection_client_latest_height(querier, "connection-0").unwrap(),
+        height(4, 1000)
+    );
+    connection_client_latest_height(querier, "connection-1").unwrap_err();
+}
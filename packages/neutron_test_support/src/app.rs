@@ -0,0 +1,446 @@
+// Copyright 2022 Neutron
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt::Display;
+
+use cosmwasm_std::testing::{mock_env, MockApi, MockStorage};
+use cosmwasm_std::{
+    from_json, to_json_vec, Addr, BankMsg, Binary, Coin, CosmosMsg, DepsMut, Env, MessageInfo,
+    Order, OwnedDeps, Record, Reply, ReplyOn, Response, Storage, WasmMsg,
+};
+use neutron_sdk::sudo::msg::SudoMsg;
+use neutron_std::types::neutron::interchainqueries::StorageValue;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::contractmanager::{RESUBMIT_FAILURE_RESPONSE_TYPE_URL, RESUBMIT_FAILURE_TYPE_URL};
+use crate::querier::{mock_dependencies, ChainState, NeutronMockQuerier};
+use crate::{
+    any_msg, error_reply, message_reply, msg_response, Handled, MockContractManager,
+    MockFeeRefunder, MockIca, MockIcq, MockTransfer,
+};
+
+const BANK_SEND_RESPONSE_TYPE_URL: &str = "/cosmos.bank.v1beta1.MsgSendResponse";
+const EXECUTE_CONTRACT_RESPONSE_TYPE_URL: &str = "/cosmwasm.wasm.v1.MsgExecuteContractResponse";
+
+type ExecuteFn = Box<dyn Fn(DepsMut, Env, MessageInfo, &[u8]) -> Result<Response, String>>;
+type ReplyFn = Box<dyn Fn(DepsMut, Env, Reply) -> Result<Response, String>>;
+type SudoFn = Box<dyn Fn(DepsMut, Env, &[u8]) -> Result<Response, String>>;
+
+/// Entry points of the contract [`NeutronApp`] runs. The messages are passed as JSON, so the
+/// contract may take any type the chain messages deserialize into, e.g. `TransferSudoMsg`.
+pub struct ContractWrapper {
+    execute: ExecuteFn,
+    reply: ReplyFn,
+    sudo: SudoFn,
+}
+
+impl ContractWrapper {
+    pub fn new<M, S, E1, E2, E3>(
+        execute: impl Fn(DepsMut, Env, MessageInfo, M) -> Result<Response, E1> + 'static,
+        reply: impl Fn(DepsMut, Env, Reply) -> Result<Response, E2> + 'static,
+        sudo: impl Fn(DepsMut, Env, S) -> Result<Response, E3> + 'static,
+    ) -> Self
+    where
+        M: DeserializeOwned + 'static,
+        S: DeserializeOwned + 'static,
+        E1: Display + 'static,
+        E2: Display + 'static,
+        E3: Display + 'static,
+    {
+        ContractWrapper {
+            execute: Box::new(
+                move |deps: DepsMut, env: Env, info: MessageInfo, msg: &[u8]| {
+                    let msg = from_json(msg).map_err(|e| e.to_string())?;
+                    execute(deps, env, info, msg).map_err(|e| e.to_string())
+                },
+            ),
+            reply: Box::new(move |deps: DepsMut, env: Env, msg: Reply| {
+                reply(deps, env, msg).map_err(|e| e.to_string())
+            }),
+            sudo: Box::new(move |deps: DepsMut, env: Env, msg: &[u8]| {
+                let msg = from_json(msg).map_err(|e| e.to_string())?;
+                sudo(deps, env, msg).map_err(|e| e.to_string())
+            }),
+        }
+    }
+}
+
+/// State of the whole app taken before a transaction, to revert it if it fails
+struct Snapshot {
+    storage: Vec<Record>,
+    chain: ChainState,
+    icq: MockIcq,
+    ica: MockIca,
+    transfer: MockTransfer,
+    feerefunder: MockFeeRefunder,
+    contractmanager: MockContractManager,
+}
+
+/// A single contract running on a simulated Neutron chain.
+///
+/// Executing the contract dispatches the messages it returns to the simulated modules and
+/// delivers the replies to its submessages, depth first, the way wasmd does. A transaction
+/// is reverted as a whole if any of its messages fail. The callbacks the modules send later,
+/// like `OpenAck`, are delivered right after the transaction, while acknowledgements,
+/// timeouts and query results are delivered when the test relays them. Sudo calls follow the
+/// contractmanager rules: a failed acknowledgement or timeout handler is reverted and
+/// recorded as a failure the contract can resubmit.
+pub struct NeutronApp {
+    pub deps: OwnedDeps<MockStorage, MockApi, NeutronMockQuerier>,
+    pub env: Env,
+    pub icq: MockIcq,
+    pub ica: MockIca,
+    pub transfer: MockTransfer,
+    pub feerefunder: MockFeeRefunder,
+    pub contractmanager: MockContractManager,
+    contract: ContractWrapper,
+}
+
+impl NeutronApp {
+    pub fn new(contract: ContractWrapper, contract_balance: &[Coin]) -> Self {
+        NeutronApp {
+            deps: mock_dependencies(contract_balance),
+            env: mock_env(),
+            icq: MockIcq::new(),
+            ica: MockIca::new(),
+            transfer: MockTransfer::new(),
+            feerefunder: MockFeeRefunder::new(),
+            contractmanager: MockContractManager::new(),
+            contract,
+        }
+    }
+
+    pub fn contract_address(&self) -> Addr {
+        self.env.contract.address.clone()
+    }
+
+    /// Moves the chain to the next block
+    pub fn next_block(&mut self) {
+        self.env.block.height += 1;
+        self.env.block.time = self.env.block.time.plus_seconds(5);
+    }
+
+    /// Executes the contract in a transaction, returns the response of the contract
+    pub fn execute<M: Serialize>(
+        &mut self,
+        info: MessageInfo,
+        msg: &M,
+    ) -> Result<Response, String> {
+        let msg = to_json_vec(msg).map_err(|e| e.to_string())?;
+        let (res, callbacks) = self.transaction(|app| {
+            if !info.funds.is_empty() {
+                let contract = app.contract_address();
+                app.deps
+                    .querier
+                    .send(info.sender.as_str(), contract.as_str(), &info.funds)?;
+            }
+            let res = (app.contract.execute)(app.deps.as_mut(), app.env.clone(), info, &msg)?;
+            let callbacks = app.dispatch(&res)?;
+            Ok((res, callbacks))
+        })?;
+        self.deliver_callbacks(callbacks);
+        Ok(res)
+    }
+
+    /// Delivers the sudo call to the contract in a transaction. A failed acknowledgement or
+    /// timeout handler is recorded as a contractmanager failure.
+    pub fn sudo(&mut self, msg: &SudoMsg) -> Result<(), String> {
+        let payload = to_json_vec(msg).map_err(|e| e.to_string())?;
+        let result = self.transaction(|app| {
+            let res = (app.contract.sudo)(app.deps.as_mut(), app.env.clone(), &payload)?;
+            app.dispatch(&res)
+        });
+        match result {
+            Ok(callbacks) => {
+                self.deliver_callbacks(callbacks);
+                Ok(())
+            }
+            Err(e) => {
+                if matches!(
+                    msg,
+                    SudoMsg::Response { .. } | SudoMsg::Error { .. } | SudoMsg::Timeout { .. }
+                ) {
+                    let contract = self.contract_address();
+                    self.contractmanager.add_failure(
+                        &mut self.deps.querier,
+                        contract.as_str(),
+                        Binary::from(payload),
+                        &e,
+                    );
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Acknowledges the interchain tx with the `data` the host returned, see
+    /// [`crate::proto::ack_data`]
+    pub fn ica_ack(&mut self, channel: &str, sequence: u64, data: Binary) -> Result<(), String> {
+        let msg = self.ica.ack(channel, sequence, data);
+        self.relay(msg)
+    }
+
+    pub fn ica_error(&mut self, channel: &str, sequence: u64, details: &str) -> Result<(), String> {
+        let msg = self.ica.error(channel, sequence, details);
+        self.relay(msg)
+    }
+
+    pub fn ica_timeout(&mut self, channel: &str, sequence: u64) -> Result<(), String> {
+        let msg = self.ica.timeout(channel, sequence);
+        self.relay(msg)
+    }
+
+    pub fn transfer_ack(&mut self, channel: &str, sequence: u64) -> Result<(), String> {
+        let msg = self.transfer.ack(channel, sequence);
+        self.relay(msg)
+    }
+
+    pub fn transfer_ack_with(
+        &mut self,
+        channel: &str,
+        sequence: u64,
+        data: Binary,
+    ) -> Result<(), String> {
+        let msg = self.transfer.ack_with(channel, sequence, data);
+        self.relay(msg)
+    }
+
+    pub fn transfer_error(
+        &mut self,
+        channel: &str,
+        sequence: u64,
+        details: &str,
+    ) -> Result<(), String> {
+        let msg = self
+            .transfer
+            .error(&mut self.deps.querier, channel, sequence, details);
+        self.relay(msg)
+    }
+
+    pub fn transfer_timeout(&mut self, channel: &str, sequence: u64) -> Result<(), String> {
+        let msg = self
+            .transfer
+            .timeout(&mut self.deps.querier, channel, sequence);
+        self.relay(msg)
+    }
+
+    /// Submits the KV result the relayer read at `remote_height` and delivers the callback
+    pub fn submit_kv_result(
+        &mut self,
+        query_id: u64,
+        kv_results: Vec<StorageValue>,
+        remote_height: u64,
+    ) -> Result<(), String> {
+        let msg = self.icq.submit_kv_result(
+            &mut self.deps.querier,
+            &self.env,
+            query_id,
+            kv_results,
+            remote_height,
+        );
+        self.sudo(&msg)
+    }
+
+    /// Submits a transaction matching the TX query and delivers the callback
+    pub fn submit_tx_result(
+        &mut self,
+        query_id: u64,
+        remote_height: u64,
+        data: Binary,
+    ) -> Result<(), String> {
+        let msg = self
+            .icq
+            .submit_tx_result(&mut self.deps.querier, query_id, remote_height, data);
+        self.sudo(&msg)
+    }
+
+    /// Distributes the fee of the packet the callback is about and delivers the callback
+    fn relay(&mut self, msg: SudoMsg) -> Result<(), String> {
+        let (request, timed_out) = match &msg {
+            SudoMsg::Response { request, .. } | SudoMsg::Error { request, .. } => (request, false),
+            SudoMsg::Timeout { request } => (request, true),
+            _ => return self.sudo(&msg),
+        };
+        let port_id = request.source_port.clone().unwrap_or_default();
+        let channel_id = request.source_channel.clone().unwrap_or_default();
+        let sequence = request.sequence.unwrap_or_default();
+        let querier = &mut self.deps.querier;
+        if timed_out {
+            self.feerefunder
+                .distribute_timeout(querier, &port_id, &channel_id, sequence)?;
+        } else {
+            self.feerefunder
+                .distribute_ack(querier, &port_id, &channel_id, sequence)?;
+        }
+        self.sudo(&msg)
+    }
+
+    fn deliver_callbacks(&mut self, callbacks: Vec<SudoMsg>) {
+        for msg in callbacks {
+            // a failed callback is reverted on its own and doesn't affect the others
+            let _ = self.sudo(&msg);
+        }
+    }
+
+    /// Dispatches the messages of the response in order, returns the callbacks to deliver
+    /// after the transaction
+    fn dispatch(&mut self, res: &Response) -> Result<Vec<SudoMsg>, String> {
+        let mut callbacks = vec![];
+        for sub_msg in &res.messages {
+            match self.transaction(|app| app.handle_msg(&sub_msg.msg)) {
+                Ok(handled) => {
+                    callbacks.extend(handled.callbacks);
+                    if matches!(sub_msg.reply_on, ReplyOn::Success | ReplyOn::Always) {
+                        let reply =
+                            message_reply(sub_msg.id, sub_msg.payload.clone(), handled.response);
+                        callbacks.extend(self.reply(reply)?);
+                    }
+                }
+                Err(e) if matches!(sub_msg.reply_on, ReplyOn::Error | ReplyOn::Always) => {
+                    let reply = error_reply(sub_msg.id, sub_msg.payload.clone(), e);
+                    callbacks.extend(self.reply(reply)?);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(callbacks)
+    }
+
+    fn reply(&mut self, reply: Reply) -> Result<Vec<SudoMsg>, String> {
+        let res = (self.contract.reply)(self.deps.as_mut(), self.env.clone(), reply)?;
+        self.dispatch(&res)
+    }
+
+    fn handle_msg(&mut self, msg: &CosmosMsg) -> Result<Handled, String> {
+        let contract = self.contract_address();
+        if let Some((type_url, value)) = any_msg(msg) {
+            if type_url == RESUBMIT_FAILURE_TYPE_URL {
+                return self.resubmit_failure(value);
+            }
+            let querier = &mut self.deps.querier;
+            let mut handled = self.icq.handle_msg(querier, &self.env, type_url, value)?;
+            if handled.is_none() {
+                handled = self.ica.handle_msg(querier, &self.env, type_url, value)?;
+            }
+            if handled.is_none() {
+                handled = self.transfer.handle_msg(querier, type_url, value)?;
+            }
+            let handled = handled.ok_or_else(|| format!("unsupported message {}", type_url))?;
+            if let Some(fee) = &handled.fee {
+                self.feerefunder.lock(querier, fee.clone())?;
+            }
+            return Ok(handled);
+        }
+        match msg {
+            CosmosMsg::Bank(BankMsg::Send { to_address, amount }) => {
+                self.deps
+                    .querier
+                    .send(contract.as_str(), to_address, amount)?;
+                Ok(Handled {
+                    response: msg_response(BANK_SEND_RESPONSE_TYPE_URL, ()),
+                    callbacks: vec![],
+                    fee: None,
+                })
+            }
+            // the contract calling itself, e.g. to run a part of the handler as a submessage
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr,
+                msg,
+                funds,
+            }) if contract.as_str() == contract_addr => {
+                let info = MessageInfo {
+                    sender: contract,
+                    funds: funds.clone(),
+                };
+                let res = (self.contract.execute)(
+                    self.deps.as_mut(),
+                    self.env.clone(),
+                    info,
+                    msg.as_slice(),
+                )?;
+                Ok(Handled {
+                    response: msg_response(EXECUTE_CONTRACT_RESPONSE_TYPE_URL, ()),
+                    callbacks: self.dispatch(&res)?,
+                    fee: None,
+                })
+            }
+            _ => Err(format!("unsupported message {:?}", msg)),
+        }
+    }
+
+    /// Runs the failed sudo call again within the current transaction, so its failure fails
+    /// the resubmission
+    fn resubmit_failure(&mut self, value: &[u8]) -> Result<Handled, String> {
+        let failure = self
+            .contractmanager
+            .resubmit(&mut self.deps.querier, value)?;
+        let res =
+            (self.contract.sudo)(self.deps.as_mut(), self.env.clone(), &failure.sudo_payload)?;
+        Ok(Handled {
+            response: msg_response(RESUBMIT_FAILURE_RESPONSE_TYPE_URL, ()),
+            callbacks: self.dispatch(&res)?,
+            fee: None,
+        })
+    }
+
+    fn transaction<R>(
+        &mut self,
+        run: impl FnOnce(&mut Self) -> Result<R, String>,
+    ) -> Result<R, String> {
+        let snapshot = self.snapshot();
+        let result = run(self);
+        if result.is_err() {
+            self.restore(snapshot);
+        }
+        result
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            storage: self
+                .deps
+                .storage
+                .range(None, None, Order::Ascending)
+                .collect(),
+            chain: self.deps.querier.snapshot(),
+            icq: self.icq.clone(),
+            ica: self.ica.clone(),
+            transfer: self.transfer.clone(),
+            feerefunder: self.feerefunder.clone(),
+            contractmanager: self.contractmanager.clone(),
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        let keys: Vec<Vec<u8>> = self
+            .deps
+            .storage
+            .range(None, None, Order::Ascending)
+            .map(|(key, _)| key)
+            .collect();
+        for key in keys {
+            self.deps.storage.remove(&key);
+        }
+        for (key, value) in snapshot.storage {
+            self.deps.storage.set(&key, &value);
+        }
+        self.deps.querier.restore(snapshot.chain);
+        self.icq = snapshot.icq;
+        self.ica = snapshot.ica;
+        self.transfer = snapshot.transfer;
+        self.feerefunder = snapshot.feerefunder;
+        self.contractmanager = snapshot.contractmanager;
+    }
+}
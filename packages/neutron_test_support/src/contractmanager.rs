@@ -0,0 +1,81 @@
+// Copyright 2022 Neutron
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use cosmwasm_std::Binary;
+use neutron_std::types::neutron::contractmanager::{Failure, MsgResubmitFailure};
+use prost::Message;
+
+use crate::querier::NeutronMockQuerier;
+
+pub(crate) const RESUBMIT_FAILURE_TYPE_URL: &str = "/neutron.contractmanager.MsgResubmitFailure";
+pub(crate) const RESUBMIT_FAILURE_RESPONSE_TYPE_URL: &str =
+    "/neutron.contractmanager.MsgResubmitFailureResponse";
+
+/// Simulated contractmanager module. The failures are kept in the [`NeutronMockQuerier`], so
+/// the contract reads them back through the usual grpc queries. Unlike the chain, which
+/// redacts the error to its code, the mock keeps the whole error text.
+#[derive(Clone, Default)]
+pub struct MockContractManager {
+    // contract address -> id of its next failure
+    next_failure_ids: HashMap<String, u64>,
+}
+
+impl MockContractManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the failed sudo call and returns the id of the failure
+    pub fn add_failure(
+        &mut self,
+        querier: &mut NeutronMockQuerier,
+        address: &str,
+        sudo_payload: Binary,
+        error: &str,
+    ) -> u64 {
+        let next_id = self
+            .next_failure_ids
+            .entry(address.to_string())
+            .or_default();
+        let id = *next_id;
+        *next_id += 1;
+        querier.add_failure(Failure {
+            address: address.to_string(),
+            id,
+            sudo_payload: sudo_payload.to_vec(),
+            error: error.to_string(),
+        });
+        id
+    }
+
+    /// Takes the failure a `MsgResubmitFailure` resubmits, only the contract which failed is
+    /// allowed to
+    pub fn resubmit(
+        &mut self,
+        querier: &mut NeutronMockQuerier,
+        value: &[u8],
+    ) -> Result<Failure, String> {
+        let msg = MsgResubmitFailure::decode(value).map_err(|e| e.to_string())?;
+        querier
+            .remove_failure(&msg.sender, msg.failure_id)
+            .ok_or_else(|| {
+                format!(
+                    "no failure with given FailureId found to resubmit: {}",
+                    msg.failure_id
+                )
+            })
+    }
+}
@@ -0,0 +1,121 @@
+// Copyright 2022 Neutron
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use neutron_std::types::cosmos::base::v1beta1::Coin as StdCoin;
+use neutron_std::types::neutron::feerefunder::Fee;
+
+use crate::querier::NeutronMockQuerier;
+use crate::to_coins;
+
+/// Address the relayer gets paid the fees to
+pub const RELAYER_ADDR: &str = "cosmos1relayer";
+
+/// Fee the sender of a packet pays to the relayers, see [`MockFeeRefunder::lock`]
+#[derive(Clone, Debug)]
+pub struct PacketFee {
+    pub port_id: String,
+    pub channel_id: String,
+    pub sequence: u64,
+    pub payer: String,
+    pub fee: Fee,
+}
+
+/// Simulated feerefunder module: locks the fees of the outgoing packets and distributes them
+/// once the packet is acknowledged or timed out.
+#[derive(Clone, Default)]
+pub struct MockFeeRefunder {
+    // (port id, channel id, sequence) -> locked fee
+    locked: BTreeMap<(String, String, u64), PacketFee>,
+}
+
+impl MockFeeRefunder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes all the fees of the packet from the payer
+    pub fn lock(&mut self, querier: &mut NeutronMockQuerier, fee: PacketFee) -> Result<(), String> {
+        let Fee {
+            recv_fee,
+            ack_fee,
+            timeout_fee,
+        } = &fee.fee;
+        for coins in [recv_fee, ack_fee, timeout_fee] {
+            querier.debit(&fee.payer, &to_coins(coins)?)?;
+        }
+        self.locked.insert(
+            (fee.port_id.clone(), fee.channel_id.clone(), fee.sequence),
+            fee,
+        );
+        Ok(())
+    }
+
+    /// Fee locked for the packet
+    pub fn locked(&self, port_id: &str, channel_id: &str, sequence: u64) -> Option<&PacketFee> {
+        self.locked
+            .get(&(port_id.to_string(), channel_id.to_string(), sequence))
+    }
+
+    /// Pays the ack fee to the relayer and refunds the rest to the payer. An error
+    /// acknowledgement is an acknowledgement too.
+    pub fn distribute_ack(
+        &mut self,
+        querier: &mut NeutronMockQuerier,
+        port_id: &str,
+        channel_id: &str,
+        sequence: u64,
+    ) -> Result<(), String> {
+        self.distribute(querier, port_id, channel_id, sequence, |fee| {
+            (&fee.ack_fee, [&fee.recv_fee, &fee.timeout_fee])
+        })
+    }
+
+    /// Pays the timeout fee to the relayer and refunds the rest to the payer
+    pub fn distribute_timeout(
+        &mut self,
+        querier: &mut NeutronMockQuerier,
+        port_id: &str,
+        channel_id: &str,
+        sequence: u64,
+    ) -> Result<(), String> {
+        self.distribute(querier, port_id, channel_id, sequence, |fee| {
+            (&fee.timeout_fee, [&fee.recv_fee, &fee.ack_fee])
+        })
+    }
+
+    fn distribute(
+        &mut self,
+        querier: &mut NeutronMockQuerier,
+        port_id: &str,
+        channel_id: &str,
+        sequence: u64,
+        split: impl Fn(&Fee) -> (&Vec<StdCoin>, [&Vec<StdCoin>; 2]),
+    ) -> Result<(), String> {
+        // packets sent without a fee have nothing to distribute
+        let Some(locked) =
+            self.locked
+                .remove(&(port_id.to_string(), channel_id.to_string(), sequence))
+        else {
+            return Ok(());
+        };
+        let (paid, refunded) = split(&locked.fee);
+        querier.credit(RELAYER_ADDR, &to_coins(paid)?);
+        for coins in refunded {
+            querier.credit(&locked.payer, &to_coins(coins)?);
+        }
+        Ok(())
+    }
+}
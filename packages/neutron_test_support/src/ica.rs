@@ -0,0 +1,250 @@
+// Copyright 2022 Neutron
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use cosmwasm_std::testing::MockApi;
+use cosmwasm_std::{Binary, Env, Response};
+use neutron_sdk::interchain_txs::helpers::get_port_id;
+use neutron_sdk::sudo::msg::{RequestPacket, SudoMsg};
+use neutron_std::types::ibc::core::channel::v1::Order;
+use neutron_std::types::neutron::interchaintxs::v1::{
+    MsgRegisterInterchainAccount, MsgRegisterInterchainAccountResponse, MsgSubmitTx,
+    MsgSubmitTxResponse,
+};
+use prost::Message;
+
+use crate::feerefunder::PacketFee;
+use crate::querier::NeutronMockQuerier;
+use crate::{handle_response_with, msg_response, Delivery, Handled};
+
+const REGISTER_ICA_TYPE_URL: &str = "/neutron.interchaintxs.v1.MsgRegisterInterchainAccount";
+const REGISTER_ICA_RESPONSE_TYPE_URL: &str =
+    "/neutron.interchaintxs.v1.MsgRegisterInterchainAccountResponse";
+const SUBMIT_TX_TYPE_URL: &str = "/neutron.interchaintxs.v1.MsgSubmitTx";
+const SUBMIT_TX_RESPONSE_TYPE_URL: &str = "/neutron.interchaintxs.v1.MsgSubmitTxResponse";
+
+const ICA_HOST_PORT: &str = "icahost";
+
+/// Simulated interchaintxs module together with the relayer and the ICA host on the remote
+/// chain. Submitted transactions stay in flight until the test acknowledges, times out or
+/// fails them.
+#[derive(Clone)]
+pub struct MockIca {
+    address_prefix: &'static str,
+    next_channel: u64,
+    // port id -> (channel id, ordered) of the open ICA channels
+    channels: HashMap<String, (String, bool)>,
+    // channel id -> sequence of the last sent packet
+    sequences: HashMap<String, u64>,
+    in_flight: Vec<RequestPacket>,
+}
+
+impl Default for MockIca {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockIca {
+    pub fn new() -> Self {
+        MockIca {
+            address_prefix: "cosmos",
+            next_channel: 0,
+            channels: HashMap::new(),
+            sequences: HashMap::new(),
+            in_flight: vec![],
+        }
+    }
+
+    /// Sets the bech32 prefix of the interchain account addresses
+    pub fn with_address_prefix(mut self, prefix: &'static str) -> Self {
+        self.address_prefix = prefix;
+        self
+    }
+
+    /// Handles the interchaintxs messages of a contract response the way the module and the
+    /// relayer would: returns the replies to the submessages which asked for one, followed by
+    /// an `OpenAck` for every registered interchain account.
+    pub fn handle_response<T>(
+        &mut self,
+        querier: &mut NeutronMockQuerier,
+        env: &Env,
+        res: &Response<T>,
+    ) -> Vec<Delivery> {
+        handle_response_with(res, |type_url, value| {
+            self.handle_msg(querier, env, type_url, value)
+        })
+    }
+
+    /// Handles a single message, returns `None` if it isn't an interchaintxs one
+    pub fn handle_msg(
+        &mut self,
+        querier: &mut NeutronMockQuerier,
+        env: &Env,
+        type_url: &str,
+        value: &[u8],
+    ) -> Result<Option<Handled>, String> {
+        let handled = match type_url {
+            REGISTER_ICA_TYPE_URL => {
+                let msg = MsgRegisterInterchainAccount::decode(value).map_err(|e| e.to_string())?;
+                let (open_ack, response) = self.register(querier, msg);
+                Handled {
+                    response: msg_response(REGISTER_ICA_RESPONSE_TYPE_URL, response),
+                    callbacks: vec![open_ack],
+                    fee: None,
+                }
+            }
+            SUBMIT_TX_TYPE_URL => {
+                let msg = MsgSubmitTx::decode(value).map_err(|e| e.to_string())?;
+                let payer = msg.from_address.clone();
+                let port_id = get_port_id(&msg.from_address, &msg.interchain_account_id);
+                let fee = msg.fee.clone();
+                let response = self.submit_tx(env, msg)?;
+                let fee = fee.map(|fee| PacketFee {
+                    port_id,
+                    channel_id: response.channel.clone(),
+                    sequence: response.sequence_id,
+                    payer,
+                    fee,
+                });
+                Handled {
+                    response: msg_response(SUBMIT_TX_RESPONSE_TYPE_URL, response),
+                    callbacks: vec![],
+                    fee,
+                }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(handled))
+    }
+
+    /// Opens a new channel for the interchain account and returns the `OpenAck` callback
+    /// together with the module response
+    pub fn register(
+        &mut self,
+        querier: &mut NeutronMockQuerier,
+        msg: MsgRegisterInterchainAccount,
+    ) -> (SudoMsg, MsgRegisterInterchainAccountResponse) {
+        let port_id = get_port_id(&msg.from_address, &msg.interchain_account_id);
+        let channel_id = format!("channel-{}", self.next_channel);
+        self.next_channel += 1;
+        // the module opens an ordered channel unless asked otherwise
+        let ordered = msg.ordering != Order::Unordered as i32;
+        self.channels
+            .insert(port_id.clone(), (channel_id.clone(), ordered));
+
+        let address = MockApi::default()
+            .with_prefix(self.address_prefix)
+            .addr_make(&port_id)
+            .to_string();
+        querier.add_interchain_account(
+            &msg.from_address,
+            &msg.connection_id,
+            &msg.interchain_account_id,
+            &address,
+        );
+
+        let counterparty_version = format!(
+            r#"{{"version":"ics27-1","controller_connection_id":"{0}","host_connection_id":"{0}","address":"{1}","encoding":"proto3","tx_type":"sdk_multi_msg"}}"#,
+            msg.connection_id, address
+        );
+        let open_ack = SudoMsg::OpenAck {
+            port_id: port_id.clone(),
+            channel_id: channel_id.clone(),
+            counterparty_channel_id: channel_id.clone(),
+            counterparty_version,
+        };
+        (
+            open_ack,
+            MsgRegisterInterchainAccountResponse {
+                channel_id,
+                port_id,
+            },
+        )
+    }
+
+    /// Sends the transaction over the channel of the interchain account
+    pub fn submit_tx(
+        &mut self,
+        env: &Env,
+        msg: MsgSubmitTx,
+    ) -> Result<MsgSubmitTxResponse, String> {
+        let port_id = get_port_id(&msg.from_address, &msg.interchain_account_id);
+        let (channel, _) = self
+            .channels
+            .get(&port_id)
+            .ok_or_else(|| format!("no open channel for {}", port_id))?
+            .clone();
+        let sequence = self.sequences.entry(channel.clone()).or_default();
+        *sequence += 1;
+
+        self.in_flight.push(RequestPacket {
+            sequence: Some(*sequence),
+            source_port: Some(port_id),
+            source_channel: Some(channel.clone()),
+            destination_port: Some(ICA_HOST_PORT.to_string()),
+            destination_channel: Some(channel.clone()),
+            data: None,
+            timeout_height: None,
+            timeout_timestamp: Some(env.block.time.plus_seconds(msg.timeout).nanos()),
+        });
+        Ok(MsgSubmitTxResponse {
+            sequence_id: *sequence,
+            channel,
+        })
+    }
+
+    /// Packets sent and not yet acknowledged, timed out or failed
+    pub fn in_flight(&self) -> &[RequestPacket] {
+        &self.in_flight
+    }
+
+    /// Acknowledges the packet with the `data` the host returned, see
+    /// [`crate::proto::ack_data`]
+    pub fn ack(&mut self, channel: &str, sequence: u64, data: Binary) -> SudoMsg {
+        SudoMsg::Response {
+            request: self.take_packet(channel, sequence),
+            data,
+        }
+    }
+
+    /// Fails the packet with an error acknowledgement
+    pub fn error(&mut self, channel: &str, sequence: u64, details: &str) -> SudoMsg {
+        SudoMsg::Error {
+            request: self.take_packet(channel, sequence),
+            details: details.to_string(),
+        }
+    }
+
+    /// Times the packet out. An ordered channel gets closed by the timeout, so the account
+    /// has to be registered again.
+    pub fn timeout(&mut self, channel: &str, sequence: u64) -> SudoMsg {
+        let request = self.take_packet(channel, sequence);
+        self.channels
+            .retain(|_, (open, ordered)| !*ordered || open.as_str() != channel);
+        SudoMsg::Timeout { request }
+    }
+
+    fn take_packet(&mut self, channel: &str, sequence: u64) -> RequestPacket {
+        let index = self
+            .in_flight
+            .iter()
+            .position(|p| {
+                p.source_channel.as_deref() == Some(channel) && p.sequence == Some(sequence)
+            })
+            .unwrap_or_else(|| panic!("no packet {} in flight on {}", sequence, channel));
+        self.in_flight.remove(index)
+    }
+}
@@ -0,0 +1,233 @@
+// Copyright 2022 Neutron
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use cosmwasm_std::{Binary, Env, Response};
+use neutron_sdk::sudo::msg::{Height, SudoMsg};
+use neutron_std::types::cosmos::base::v1beta1::Coin;
+use neutron_std::types::ibc::core::client::v1::Height as IbcHeight;
+use neutron_std::types::neutron::interchainqueries::{
+    MsgRegisterInterchainQuery, MsgRegisterInterchainQueryResponse,
+    MsgRemoveInterchainQueryRequest, QueryResult, RegisteredQuery, StorageValue,
+};
+use prost::Message;
+
+use crate::querier::NeutronMockQuerier;
+use crate::{handle_response_with, msg_response, Delivery, Handled};
+
+const REGISTER_QUERY_TYPE_URL: &str = "/neutron.interchainqueries.MsgRegisterInterchainQuery";
+const REGISTER_QUERY_RESPONSE_TYPE_URL: &str =
+    "/neutron.interchainqueries.MsgRegisterInterchainQueryResponse";
+const REMOVE_QUERY_TYPE_URL: &str = "/neutron.interchainqueries.MsgRemoveInterchainQueryRequest";
+const REMOVE_QUERY_RESPONSE_TYPE_URL: &str =
+    "/neutron.interchainqueries.MsgRemoveInterchainQueryResponse";
+
+// Module defaults on a fresh neutron chain
+const DEFAULT_QUERY_SUBMIT_TIMEOUT: u64 = 1036800;
+const DEFAULT_QUERY_DEPOSIT: u128 = 1_000_000;
+
+/// Simulated interchainqueries module. Registered queries and their results are kept in the
+/// [`NeutronMockQuerier`], so the contract reads them back through the usual grpc queries.
+#[derive(Clone)]
+pub struct MockIcq {
+    next_query_id: u64,
+    deposit: Vec<Coin>,
+    revision: u64,
+}
+
+impl Default for MockIcq {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockIcq {
+    pub fn new() -> Self {
+        MockIcq {
+            next_query_id: 1,
+            deposit: vec![Coin {
+                denom: "untrn".to_string(),
+                amount: DEFAULT_QUERY_DEPOSIT.to_string(),
+            }],
+            revision: 1,
+        }
+    }
+
+    /// Sets the deposit every registered query locks
+    pub fn with_deposit(mut self, deposit: Vec<Coin>) -> Self {
+        self.deposit = deposit;
+        self
+    }
+
+    /// Sets the revision number of the remote chain the results come from
+    pub fn with_revision(mut self, revision: u64) -> Self {
+        self.revision = revision;
+        self
+    }
+
+    /// Handles the interchainqueries messages of a contract response the way the module would:
+    /// registers and removes the queries and returns the replies to the submessages which
+    /// asked for one, in the order of the messages.
+    pub fn handle_response<T>(
+        &mut self,
+        querier: &mut NeutronMockQuerier,
+        env: &Env,
+        res: &Response<T>,
+    ) -> Vec<Delivery> {
+        handle_response_with(res, |type_url, value| {
+            self.handle_msg(querier, env, type_url, value)
+        })
+    }
+
+    /// Handles a single message, returns `None` if it isn't an interchainqueries one
+    pub fn handle_msg(
+        &mut self,
+        querier: &mut NeutronMockQuerier,
+        env: &Env,
+        type_url: &str,
+        value: &[u8],
+    ) -> Result<Option<Handled>, String> {
+        let response = match type_url {
+            REGISTER_QUERY_TYPE_URL => {
+                let msg = MsgRegisterInterchainQuery::decode(value).map_err(|e| e.to_string())?;
+                let id = self.register(querier, env, msg);
+                msg_response(
+                    REGISTER_QUERY_RESPONSE_TYPE_URL,
+                    MsgRegisterInterchainQueryResponse { id },
+                )
+            }
+            REMOVE_QUERY_TYPE_URL => {
+                let msg =
+                    MsgRemoveInterchainQueryRequest::decode(value).map_err(|e| e.to_string())?;
+                self.remove(querier, &msg.sender, msg.query_id)?;
+                msg_response(REMOVE_QUERY_RESPONSE_TYPE_URL, ())
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(Handled {
+            response,
+            callbacks: vec![],
+            fee: None,
+        }))
+    }
+
+    /// Registers the query and returns its id
+    pub fn register(
+        &mut self,
+        querier: &mut NeutronMockQuerier,
+        env: &Env,
+        msg: MsgRegisterInterchainQuery,
+    ) -> u64 {
+        let id = self.next_query_id;
+        self.next_query_id += 1;
+        querier.add_registered_query(RegisteredQuery {
+            id,
+            owner: msg.sender,
+            query_type: msg.query_type,
+            keys: msg.keys,
+            transactions_filter: msg.transactions_filter,
+            connection_id: msg.connection_id,
+            update_period: msg.update_period,
+            last_submitted_result_local_height: 0,
+            last_submitted_result_remote_height: None,
+            deposit: self.deposit.clone(),
+            submit_timeout: DEFAULT_QUERY_SUBMIT_TIMEOUT,
+            registered_at_height: env.block.height,
+        });
+        id
+    }
+
+    /// Removes the query, only its owner is allowed to
+    pub fn remove(
+        &mut self,
+        querier: &mut NeutronMockQuerier,
+        sender: &str,
+        query_id: u64,
+    ) -> Result<(), String> {
+        let query = querier
+            .registered_query(query_id)
+            .ok_or_else(|| format!("query {} is not registered", query_id))?;
+        if query.owner != sender {
+            return Err(format!("only the owner can remove query {}", query_id));
+        }
+        querier.remove_registered_query(query_id);
+        Ok(())
+    }
+
+    /// Submits the KV result the relayer read at `remote_height` and returns the callback the
+    /// module sends to the query owner
+    pub fn submit_kv_result(
+        &self,
+        querier: &mut NeutronMockQuerier,
+        env: &Env,
+        query_id: u64,
+        kv_results: Vec<StorageValue>,
+        remote_height: u64,
+    ) -> SudoMsg {
+        let revision = self.revision;
+        let query = self.registered_query_of_type(querier, query_id, "kv");
+        query.last_submitted_result_local_height = env.block.height;
+        query.last_submitted_result_remote_height = Some(IbcHeight {
+            revision_number: revision,
+            revision_height: remote_height,
+        });
+        querier.set_query_result(
+            query_id,
+            QueryResult {
+                kv_results,
+                block: None,
+                height: remote_height,
+                revision,
+                allow_kv_callbacks: true,
+            },
+        );
+        SudoMsg::KVQueryResult { query_id }
+    }
+
+    /// Submits a transaction matching the TX query and returns the callback the module sends
+    /// to the query owner
+    pub fn submit_tx_result(
+        &self,
+        querier: &mut NeutronMockQuerier,
+        query_id: u64,
+        remote_height: u64,
+        data: Binary,
+    ) -> SudoMsg {
+        self.registered_query_of_type(querier, query_id, "tx");
+        SudoMsg::TxQueryResult {
+            query_id,
+            height: Height {
+                revision_number: self.revision,
+                revision_height: remote_height,
+            },
+            data,
+        }
+    }
+
+    fn registered_query_of_type<'a>(
+        &self,
+        querier: &'a mut NeutronMockQuerier,
+        query_id: u64,
+        query_type: &str,
+    ) -> &'a mut RegisteredQuery {
+        let query = querier
+            .registered_query_mut(query_id)
+            .unwrap_or_else(|| panic!("query {} is not registered", query_id));
+        assert_eq!(
+            query.query_type, query_type,
+            "query {} has a different type",
+            query_id
+        );
+        query
+    }
+}
@@ -0,0 +1,148 @@
+// Copyright 2022 Neutron
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test support for the contracts: a mock querier answering the Neutron module queries,
+//! simulated interchainqueries, interchaintxs, transfer, feerefunder and contractmanager
+//! modules that turn the messages a contract returns into the replies and sudo calls the chain
+//! would deliver, and [`NeutronApp`] running a contract against all of them.
+
+use std::str::FromStr;
+
+use cosmwasm_std::{
+    AnyMsg, Binary, Coin, CosmosMsg, MsgResponse, Reply, ReplyOn, Response, SubMsgResponse,
+    SubMsgResult, Uint128,
+};
+use neutron_sdk::sudo::msg::SudoMsg;
+use neutron_std::types::cosmos::base::v1beta1::Coin as StdCoin;
+use prost::Message;
+
+pub mod app;
+pub mod contractmanager;
+pub mod feerefunder;
+pub mod ica;
+pub mod icq;
+pub mod proto;
+pub mod querier;
+pub mod transfer;
+
+pub use app::{ContractWrapper, NeutronApp};
+pub use contractmanager::MockContractManager;
+pub use feerefunder::{MockFeeRefunder, PacketFee, RELAYER_ADDR};
+pub use ica::MockIca;
+pub use icq::MockIcq;
+pub use querier::{mock_dependencies, NeutronMockQuerier, MOCK_CONTRACT_ADDR};
+pub use transfer::MockTransfer;
+
+/// A call the simulated modules deliver back to the contract
+#[derive(Clone, Debug)]
+pub enum Delivery {
+    /// A reply to a submessage, to be passed to the `reply` entry point
+    Reply(Reply),
+    /// A callback to be passed to the `sudo` entry point
+    Sudo(SudoMsg),
+}
+
+/// Returns the type url and the encoded body of a protobuf message, if `msg` is one
+#[allow(deprecated)]
+pub fn any_msg<T>(msg: &CosmosMsg<T>) -> Option<(&str, &[u8])> {
+    match msg {
+        CosmosMsg::Any(AnyMsg { type_url, value }) => Some((type_url.as_str(), value.as_slice())),
+        CosmosMsg::Stargate { type_url, value } => Some((type_url.as_str(), value.as_slice())),
+        _ => None,
+    }
+}
+
+/// Outcome of a module handling a message: the response returned to the sender, the
+/// callbacks the chain delivers to it later and the fee of the packet it sent, if any
+#[derive(Clone, Debug)]
+pub struct Handled {
+    pub response: MsgResponse,
+    pub callbacks: Vec<SudoMsg>,
+    pub fee: Option<PacketFee>,
+}
+
+/// Message response of the `type_url` message
+pub fn msg_response(type_url: &str, response: impl Message) -> MsgResponse {
+    MsgResponse {
+        type_url: type_url.to_string(),
+        value: Binary::from(response.encode_to_vec()),
+    }
+}
+
+/// Successful reply to submessage `id` carrying a single message response
+#[allow(deprecated)]
+pub fn message_reply(id: u64, payload: Binary, response: MsgResponse) -> Reply {
+    Reply {
+        id,
+        payload,
+        gas_used: 0,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: None,
+            msg_responses: vec![response],
+        }),
+    }
+}
+
+/// Failed reply to submessage `id`
+pub fn error_reply(id: u64, payload: Binary, error: String) -> Reply {
+    Reply {
+        id,
+        payload,
+        gas_used: 0,
+        result: SubMsgResult::Err(error),
+    }
+}
+
+pub(crate) fn to_coins(coins: &[StdCoin]) -> Result<Vec<Coin>, String> {
+    coins
+        .iter()
+        .map(|coin| {
+            Ok(Coin {
+                denom: coin.denom.clone(),
+                amount: Uint128::from_str(&coin.amount).map_err(|e| e.to_string())?,
+            })
+        })
+        .collect()
+}
+
+/// Runs `handle` over the protobuf messages of the response and returns the replies to the
+/// submessages which asked for one, followed by the callbacks of the handled messages
+pub(crate) fn handle_response_with<T>(
+    res: &Response<T>,
+    mut handle: impl FnMut(&str, &[u8]) -> Result<Option<Handled>, String>,
+) -> Vec<Delivery> {
+    let mut replies = vec![];
+    let mut callbacks = vec![];
+    for sub_msg in &res.messages {
+        let Some((type_url, value)) = any_msg(&sub_msg.msg) else {
+            continue;
+        };
+        let handled = match handle(type_url, value) {
+            Ok(Some(handled)) => handled,
+            Ok(None) => continue,
+            Err(e) => panic!("failed to handle {}: {}", type_url, e),
+        };
+        if matches!(sub_msg.reply_on, ReplyOn::Success | ReplyOn::Always) {
+            replies.push(Delivery::Reply(message_reply(
+                sub_msg.id,
+                sub_msg.payload.clone(),
+                handled.response,
+            )));
+        }
+        callbacks.extend(handled.callbacks.into_iter().map(Delivery::Sudo));
+    }
+    replies.extend(callbacks);
+    replies
+}
@@ -0,0 +1,91 @@
+// Copyright 2022 Neutron
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Builders for the protobuf values the remote chain hands out: KV storage values, raw
+//! transactions and ICA acknowledgements.
+
+use cosmos_sdk_proto::cosmos::bank::v1beta1::MsgSend;
+use cosmos_sdk_proto::cosmos::base::abci::v1beta1::TxMsgData;
+use cosmos_sdk_proto::cosmos::base::v1beta1::Coin as CosmosCoin;
+use cosmos_sdk_proto::cosmos::tx::v1beta1::{TxBody, TxRaw};
+use cosmos_sdk_proto::Any;
+use cosmwasm_std::Binary;
+use neutron_sdk::interchain_queries::helpers::decode_and_convert;
+use neutron_sdk::interchain_queries::v047::helpers::create_account_denom_balance_key;
+use neutron_std::types::neutron::interchainqueries::StorageValue;
+use prost::Message;
+
+/// Storage value under `key` in the `storage_prefix` store of the remote chain
+pub fn storage_value(storage_prefix: &str, key: Vec<u8>, value: Vec<u8>) -> StorageValue {
+    StorageValue {
+        storage_prefix: storage_prefix.to_string(),
+        key,
+        value,
+        proof: None,
+    }
+}
+
+/// Storage value of the `denom` balance of a bech32 address on the remote chain
+pub fn balance_value(addr: &str, denom: &str, amount: u128) -> StorageValue {
+    let addr = decode_and_convert(addr).expect("invalid bech32 address");
+    let key = create_account_denom_balance_key(addr, denom.to_string())
+        .expect("failed to build balance key");
+    storage_value("bank", key, amount.to_string().into_bytes())
+}
+
+/// Wraps a message into `Any` under its type url
+pub fn any(type_url: &str, msg: &impl Message) -> Any {
+    Any {
+        type_url: type_url.to_string(),
+        value: msg.encode_to_vec(),
+    }
+}
+
+/// Raw transaction carrying `messages`, as delivered to `SudoMsg::TxQueryResult`
+pub fn tx(messages: Vec<Any>) -> Binary {
+    let body = TxBody {
+        messages,
+        ..Default::default()
+    };
+    let tx = TxRaw {
+        body_bytes: body.encode_to_vec(),
+        ..Default::default()
+    };
+    Binary::from(tx.encode_to_vec())
+}
+
+/// Raw transaction with a single `MsgSend`
+pub fn send_tx(from: &str, to: &str, coins: &[(&str, u128)]) -> Binary {
+    let send = MsgSend {
+        from_address: from.to_string(),
+        to_address: to.to_string(),
+        amount: coins
+            .iter()
+            .map(|(denom, amount)| CosmosCoin {
+                denom: denom.to_string(),
+                amount: amount.to_string(),
+            })
+            .collect(),
+    };
+    tx(vec![any("/cosmos.bank.v1beta1.MsgSend", &send)])
+}
+
+/// Acknowledgement data of a successful ICA transaction, one response per submitted message
+pub fn ack_data(msg_responses: Vec<Any>) -> Binary {
+    let data = TxMsgData {
+        msg_responses,
+        ..Default::default()
+    };
+    Binary::from(data.encode_to_vec())
+}
@@ -0,0 +1,516 @@
+// Copyright 2022 Neutron
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, HashMap};
+use std::marker::PhantomData;
+
+use cosmwasm_std::testing::{MockApi, MockQuerier, MockStorage};
+use cosmwasm_std::{
+    from_json, Binary, Coin, ContractResult, GrpcQuery, OwnedDeps, Querier, QuerierResult,
+    QueryRequest, SystemError, SystemResult, Uint128, WasmQuery,
+};
+use neutron_std::shim::Any;
+use neutron_std::types::cosmos::base::query::v1beta1::PageResponse;
+use neutron_std::types::ibc::applications::transfer::v1::{
+    DenomTrace, QueryDenomTraceRequest, QueryDenomTraceResponse,
+};
+use neutron_std::types::ibc::core::channel::v1::{
+    QueryChannelClientStateRequest, QueryChannelClientStateResponse,
+};
+use neutron_std::types::ibc::core::client::v1::{Height, IdentifiedClientState};
+use neutron_std::types::ibc::core::connection::v1::{
+    QueryConnectionClientStateRequest, QueryConnectionClientStateResponse,
+};
+use neutron_std::types::neutron::contractmanager::{
+    Failure, QueryAddressFailureRequest, QueryAddressFailureResponse, QueryFailuresRequest,
+    QueryFailuresResponse,
+};
+use neutron_std::types::neutron::interchainqueries::{
+    QueryRegisteredQueryRequest, QueryRegisteredQueryResponse, QueryRegisteredQueryResultRequest,
+    QueryRegisteredQueryResultResponse, QueryResult, RegisteredQuery,
+};
+use neutron_std::types::neutron::interchaintxs::v1::{
+    QueryInterchainAccountAddressRequest, QueryInterchainAccountAddressResponse,
+};
+use prost::Message;
+use sha2::{Digest, Sha256};
+
+pub const MOCK_CONTRACT_ADDR: &str = "cosmos2contract";
+
+const REGISTERED_QUERY_PATH: &str = "/neutron.interchainqueries.Query/RegisteredQuery";
+const QUERY_RESULT_PATH: &str = "/neutron.interchainqueries.Query/QueryResult";
+const INTERCHAIN_ACCOUNT_ADDRESS_PATH: &str =
+    "/neutron.interchaintxs.v1.Query/InterchainAccountAddress";
+const FAILURES_PATH: &str = "/neutron.contractmanager.Query/Failures";
+const ADDRESS_FAILURES_PATH: &str = "/neutron.contractmanager.Query/AddressFailures";
+const ADDRESS_FAILURE_PATH: &str = "/neutron.contractmanager.Query/AddressFailure";
+const DENOM_TRACE_PATH: &str = "/ibc.applications.transfer.v1.Query/DenomTrace";
+const CHANNEL_CLIENT_STATE_PATH: &str = "/ibc.core.channel.v1.Query/ChannelClientState";
+const CONNECTION_CLIENT_STATE_PATH: &str = "/ibc.core.connection.v1.Query/ConnectionClientState";
+const TENDERMINT_CLIENT_STATE_TYPE_URL: &str = "/ibc.lightclients.tendermint.v1.ClientState";
+
+// Page size the cosmos-sdk uses if the request doesn't set one
+const DEFAULT_PAGE_LIMIT: u64 = 100;
+
+/// The tendermint light client state with only the latest height of the counterparty chain set
+#[derive(Clone, PartialEq, prost::Message)]
+struct TendermintClientState {
+    #[prost(message, optional, tag = "7")]
+    latest_height: Option<Height>,
+}
+
+pub fn mock_dependencies(
+    contract_balance: &[Coin],
+) -> OwnedDeps<MockStorage, MockApi, NeutronMockQuerier> {
+    OwnedDeps {
+        storage: MockStorage::default(),
+        api: MockApi::default(),
+        querier: {
+            let mut querier = NeutronMockQuerier::new(MockQuerier::new(&[]));
+            querier.update_balance(MOCK_CONTRACT_ADDR, contract_balance.to_vec());
+            querier
+        },
+        custom_query_type: PhantomData,
+    }
+}
+
+/// Mock querier answering the interchainqueries and interchaintxs grpc queries from the state
+/// kept by [`crate::MockIcq`] and [`crate::MockIca`], and everything else with `MockQuerier`.
+pub struct NeutronMockQuerier {
+    base: MockQuerier,
+    state: ChainState,
+}
+
+/// Chain state the simulated modules keep in the querier, cloned by [`crate::NeutronApp`] to
+/// revert a failed transaction
+#[derive(Clone, Default)]
+pub(crate) struct ChainState {
+    registered_queries: HashMap<u64, RegisteredQuery>,
+    query_results: HashMap<u64, QueryResult>,
+    // (owner, connection_id, interchain_account_id) -> interchain account address
+    interchain_accounts: HashMap<(String, String, String), String>,
+    balances: BTreeMap<String, Vec<Coin>>,
+    // (contract address, failure id) -> failure
+    failures: BTreeMap<(String, u64), Failure>,
+    // uppercase hex hash -> trace of the voucher
+    denom_traces: HashMap<String, DenomTrace>,
+    // (port_id, channel_id) -> (client_id, latest height of the counterparty chain)
+    channel_clients: HashMap<(String, String), (String, Height)>,
+    // connection_id -> (client_id, latest height of the counterparty chain)
+    connection_clients: HashMap<String, (String, Height)>,
+}
+
+impl Querier for NeutronMockQuerier {
+    fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+        let request: QueryRequest = match from_json(bin_request) {
+            Ok(v) => v,
+            Err(e) => {
+                return QuerierResult::Err(SystemError::InvalidRequest {
+                    error: format!("Parsing query request: {}", e),
+                    request: bin_request.into(),
+                });
+            }
+        };
+        self.handle_query(&request)
+    }
+}
+
+impl NeutronMockQuerier {
+    pub fn new(base: MockQuerier) -> Self {
+        NeutronMockQuerier {
+            base,
+            state: ChainState::default(),
+        }
+    }
+
+    pub fn handle_query(&self, request: &QueryRequest) -> QuerierResult {
+        match request {
+            QueryRequest::Grpc(GrpcQuery { path, data }) => match path.as_str() {
+                REGISTERED_QUERY_PATH => self.registered_query_response(data),
+                QUERY_RESULT_PATH => self.query_result_response(data),
+                INTERCHAIN_ACCOUNT_ADDRESS_PATH => self.interchain_account_address_response(data),
+                FAILURES_PATH | ADDRESS_FAILURES_PATH => self.failures_response(data),
+                ADDRESS_FAILURE_PATH => self.failure_response(data),
+                DENOM_TRACE_PATH => self.denom_trace_response(data),
+                CHANNEL_CLIENT_STATE_PATH => self.channel_client_state_response(data),
+                CONNECTION_CLIENT_STATE_PATH => self.connection_client_state_response(data),
+                _ => self.base.handle_query(request),
+            },
+            _ => self.base.handle_query(request),
+        }
+    }
+
+    fn registered_query_response(&self, data: &Binary) -> QuerierResult {
+        let request = match QueryRegisteredQueryRequest::decode(data.as_slice()) {
+            Ok(request) => request,
+            Err(e) => return invalid_request(e, data),
+        };
+        match self.state.registered_queries.get(&request.query_id) {
+            Some(query) => encode_response(QueryRegisteredQueryResponse {
+                registered_query: Some(query.clone()),
+            }),
+            None => not_found(format!(
+                "failed to get registered query by query id {}",
+                request.query_id
+            )),
+        }
+    }
+
+    fn query_result_response(&self, data: &Binary) -> QuerierResult {
+        let request = match QueryRegisteredQueryResultRequest::decode(data.as_slice()) {
+            Ok(request) => request,
+            Err(e) => return invalid_request(e, data),
+        };
+        match self.state.query_results.get(&request.query_id) {
+            Some(result) => encode_response(QueryRegisteredQueryResultResponse {
+                result: Some(result.clone()),
+            }),
+            None => not_found(format!(
+                "failed to get query result by query id {}",
+                request.query_id
+            )),
+        }
+    }
+
+    fn interchain_account_address_response(&self, data: &Binary) -> QuerierResult {
+        let request = match QueryInterchainAccountAddressRequest::decode(data.as_slice()) {
+            Ok(request) => request,
+            Err(e) => return invalid_request(e, data),
+        };
+        let key = (
+            request.owner_address,
+            request.connection_id,
+            request.interchain_account_id,
+        );
+        match self.state.interchain_accounts.get(&key) {
+            Some(address) => encode_response(QueryInterchainAccountAddressResponse {
+                interchain_account_address: address.clone(),
+            }),
+            None => not_found("failed to get interchain account address".to_string()),
+        }
+    }
+
+    /// Failures of the address, paginated by the big endian failure id like the module does
+    fn failures_response(&self, data: &Binary) -> QuerierResult {
+        let request = match QueryFailuresRequest::decode(data.as_slice()) {
+            Ok(request) => request,
+            Err(e) => return invalid_request(e, data),
+        };
+        let page = request.pagination.unwrap_or_default();
+        let start = match <[u8; 8]>::try_from(page.key.as_slice()) {
+            Ok(key) => u64::from_be_bytes(key),
+            Err(_) if page.key.is_empty() => 0,
+            Err(_) => return not_found("invalid pagination key".to_string()),
+        };
+        let limit = match page.limit {
+            0 => DEFAULT_PAGE_LIMIT,
+            limit => limit,
+        } as usize;
+        let mut failures = self
+            .failures(&request.address)
+            .into_iter()
+            .filter(|failure| failure.id >= start)
+            .skip(page.offset as usize);
+        let page_failures: Vec<Failure> = failures.by_ref().take(limit).collect();
+        let next_key = failures
+            .next()
+            .map(|failure| failure.id.to_be_bytes().to_vec())
+            .unwrap_or_default();
+        encode_response(QueryFailuresResponse {
+            failures: page_failures,
+            pagination: Some(PageResponse { next_key, total: 0 }),
+        })
+    }
+
+    fn failure_response(&self, data: &Binary) -> QuerierResult {
+        let request = match QueryAddressFailureRequest::decode(data.as_slice()) {
+            Ok(request) => request,
+            Err(e) => return invalid_request(e, data),
+        };
+        match self.failure(&request.address, request.failure_id) {
+            Some(failure) => encode_response(QueryAddressFailureResponse {
+                failure: Some(failure.clone()),
+            }),
+            None => not_found(format!(
+                "no failure found for contractAddress = {} and failureId = {}",
+                request.address, request.failure_id
+            )),
+        }
+    }
+
+    fn denom_trace_response(&self, data: &Binary) -> QuerierResult {
+        let request = match QueryDenomTraceRequest::decode(data.as_slice()) {
+            Ok(request) => request,
+            Err(e) => return invalid_request(e, data),
+        };
+        // the module accepts the hash with and without the ibc/ prefix
+        let hash = request.hash.trim_start_matches("ibc/").to_uppercase();
+        match self.state.denom_traces.get(&hash) {
+            Some(trace) => encode_response(QueryDenomTraceResponse {
+                denom_trace: Some(trace.clone()),
+            }),
+            None => not_found(format!("denomination trace not found: {}", hash)),
+        }
+    }
+
+    fn channel_client_state_response(&self, data: &Binary) -> QuerierResult {
+        let request = match QueryChannelClientStateRequest::decode(data.as_slice()) {
+            Ok(request) => request,
+            Err(e) => return invalid_request(e, data),
+        };
+        match self
+            .state
+            .channel_clients
+            .get(&(request.port_id, request.channel_id.clone()))
+        {
+            Some((client_id, latest_height)) => encode_response(QueryChannelClientStateResponse {
+                identified_client_state: Some(tendermint_client(client_id, latest_height)),
+                proof: vec![],
+                proof_height: None,
+            }),
+            None => not_found(format!("channel not found: {}", request.channel_id)),
+        }
+    }
+
+    fn connection_client_state_response(&self, data: &Binary) -> QuerierResult {
+        let request = match QueryConnectionClientStateRequest::decode(data.as_slice()) {
+            Ok(request) => request,
+            Err(e) => return invalid_request(e, data),
+        };
+        match self.state.connection_clients.get(&request.connection_id) {
+            Some((client_id, latest_height)) => {
+                encode_response(QueryConnectionClientStateResponse {
+                    identified_client_state: Some(tendermint_client(client_id, latest_height)),
+                    proof: vec![],
+                    proof_height: None,
+                })
+            }
+            None => not_found(format!("connection not found: {}", request.connection_id)),
+        }
+    }
+
+    pub fn add_registered_query(&mut self, query: RegisteredQuery) {
+        self.state.registered_queries.insert(query.id, query);
+    }
+
+    pub fn registered_query(&self, query_id: u64) -> Option<&RegisteredQuery> {
+        self.state.registered_queries.get(&query_id)
+    }
+
+    pub fn registered_query_mut(&mut self, query_id: u64) -> Option<&mut RegisteredQuery> {
+        self.state.registered_queries.get_mut(&query_id)
+    }
+
+    /// Removes the registered query together with its result
+    pub fn remove_registered_query(&mut self, query_id: u64) -> Option<RegisteredQuery> {
+        self.state.query_results.remove(&query_id);
+        self.state.registered_queries.remove(&query_id)
+    }
+
+    pub fn set_query_result(&mut self, query_id: u64, result: QueryResult) {
+        self.state.query_results.insert(query_id, result);
+    }
+
+    pub fn add_interchain_account(
+        &mut self,
+        owner: &str,
+        connection_id: &str,
+        interchain_account_id: &str,
+        address: &str,
+    ) {
+        self.state.interchain_accounts.insert(
+            (
+                owner.to_string(),
+                connection_id.to_string(),
+                interchain_account_id.to_string(),
+            ),
+            address.to_string(),
+        );
+    }
+
+    /// Sets the bank balance of the address
+    pub fn update_balance(&mut self, addr: impl Into<String>, balance: Vec<Coin>) {
+        let addr = addr.into();
+        self.base.update_balance(addr.clone(), balance.clone());
+        self.state.balances.insert(addr, balance);
+    }
+
+    /// Bank balance of the address. Only the balances set through [`Self::update_balance`]
+    /// and [`mock_dependencies`] are known.
+    pub fn balance(&self, addr: &str) -> Vec<Coin> {
+        self.state.balances.get(addr).cloned().unwrap_or_default()
+    }
+
+    pub fn balance_of(&self, addr: &str, denom: &str) -> Uint128 {
+        self.state
+            .balances
+            .get(addr)
+            .and_then(|coins| coins.iter().find(|c| c.denom == denom))
+            .map(|c| c.amount)
+            .unwrap_or_default()
+    }
+
+    /// Adds the coins to the balance of the address
+    pub fn credit(&mut self, addr: &str, coins: &[Coin]) {
+        let mut balance = self.balance(addr);
+        for coin in coins.iter().filter(|c| !c.amount.is_zero()) {
+            match balance.iter_mut().find(|c| c.denom == coin.denom) {
+                Some(c) => c.amount += coin.amount,
+                None => balance.push(coin.clone()),
+            }
+        }
+        self.update_balance(addr, balance);
+    }
+
+    /// Takes the coins from the balance of the address, fails if it is insufficient
+    pub fn debit(&mut self, addr: &str, coins: &[Coin]) -> Result<(), String> {
+        let mut balance = self.balance(addr);
+        for coin in coins.iter().filter(|c| !c.amount.is_zero()) {
+            let available = balance
+                .iter_mut()
+                .find(|c| c.denom == coin.denom)
+                .filter(|c| c.amount >= coin.amount)
+                .ok_or_else(|| format!("{} has insufficient funds to pay {}", addr, coin))?;
+            available.amount -= coin.amount;
+        }
+        balance.retain(|c| !c.amount.is_zero());
+        self.update_balance(addr, balance);
+        Ok(())
+    }
+
+    /// Moves the coins between the balances
+    pub fn send(&mut self, from: &str, to: &str, coins: &[Coin]) -> Result<(), String> {
+        self.debit(from, coins)?;
+        self.credit(to, coins);
+        Ok(())
+    }
+
+    pub fn add_failure(&mut self, failure: Failure) {
+        self.state
+            .failures
+            .insert((failure.address.clone(), failure.id), failure);
+    }
+
+    pub fn failure(&self, address: &str, failure_id: u64) -> Option<&Failure> {
+        self.state.failures.get(&(address.to_string(), failure_id))
+    }
+
+    /// Failures of the contract ordered by id
+    pub fn failures(&self, address: &str) -> Vec<Failure> {
+        self.state
+            .failures
+            .iter()
+            .filter(|((addr, _), _)| addr == address)
+            .map(|(_, failure)| failure.clone())
+            .collect()
+    }
+
+    pub fn remove_failure(&mut self, address: &str, failure_id: u64) -> Option<Failure> {
+        self.state
+            .failures
+            .remove(&(address.to_string(), failure_id))
+    }
+
+    /// Makes the trace of the voucher known to the transfer module, returns its `ibc/` denom
+    pub fn add_denom_trace(&mut self, path: &str, base_denom: &str) -> String {
+        let hash: String = Sha256::digest(format!("{}/{}", path, base_denom))
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect();
+        self.state.denom_traces.insert(
+            hash.clone(),
+            DenomTrace {
+                path: path.to_string(),
+                base_denom: base_denom.to_string(),
+            },
+        );
+        format!("ibc/{}", hash)
+    }
+
+    /// Sets the light client of the channel and the latest height of the counterparty chain
+    /// known to it
+    pub fn set_channel_client(
+        &mut self,
+        port_id: &str,
+        channel_id: &str,
+        client_id: &str,
+        latest_height: Height,
+    ) {
+        self.state.channel_clients.insert(
+            (port_id.to_string(), channel_id.to_string()),
+            (client_id.to_string(), latest_height),
+        );
+    }
+
+    /// Sets the light client of the connection and the latest height of the counterparty chain
+    /// known to it
+    pub fn set_connection_client(
+        &mut self,
+        connection_id: &str,
+        client_id: &str,
+        latest_height: Height,
+    ) {
+        self.state.connection_clients.insert(
+            connection_id.to_string(),
+            (client_id.to_string(), latest_height),
+        );
+    }
+
+    pub(crate) fn snapshot(&self) -> ChainState {
+        self.state.clone()
+    }
+
+    pub(crate) fn restore(&mut self, state: ChainState) {
+        for addr in self.state.balances.keys() {
+            let balance = state.balances.get(addr).cloned().unwrap_or_default();
+            self.base.update_balance(addr.clone(), balance);
+        }
+        self.state = state;
+    }
+
+    pub fn update_wasm<WH>(&mut self, handler: WH)
+    where
+        WH: Fn(&WasmQuery) -> QuerierResult + 'static,
+    {
+        self.base.update_wasm(handler);
+    }
+}
+
+fn tendermint_client(client_id: &str, latest_height: &Height) -> IdentifiedClientState {
+    IdentifiedClientState {
+        client_id: client_id.to_string(),
+        client_state: Some(Any {
+            type_url: TENDERMINT_CLIENT_STATE_TYPE_URL.to_string(),
+            value: TendermintClientState {
+                latest_height: Some(latest_height.clone()),
+            }
+            .encode_to_vec(),
+        }),
+    }
+}
+
+fn encode_response(response: impl Message) -> QuerierResult {
+    SystemResult::Ok(ContractResult::Ok(Binary::from(response.encode_to_vec())))
+}
+
+fn not_found(error: String) -> QuerierResult {
+    SystemResult::Ok(ContractResult::Err(error))
+}
+
+fn invalid_request(error: prost::DecodeError, data: &Binary) -> QuerierResult {
+    SystemResult::Err(SystemError::InvalidRequest {
+        error: format!("Parsing grpc request: {}", error),
+        request: data.clone(),
+    })
+}
@@ -0,0 +1,250 @@
+// Copyright 2022 Neutron
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use cosmwasm_std::{to_json_binary, Binary, Coin};
+use neutron_sdk::sudo::msg::{RequestPacket, RequestPacketTimeoutHeight, SudoMsg};
+use neutron_std::types::ibc::applications::transfer::v1::MsgTransfer as NativeMsgTransfer;
+use neutron_std::types::ibc::core::client::v1::Height;
+use neutron_std::types::neutron::transfer::{MsgTransfer, MsgTransferResponse};
+use prost::Message;
+use serde::Serialize;
+
+use crate::feerefunder::PacketFee;
+use crate::querier::NeutronMockQuerier;
+use crate::{msg_response, to_coins, Handled};
+
+const TRANSFER_TYPE_URL: &str = "/neutron.transfer.MsgTransfer";
+const TRANSFER_RESPONSE_TYPE_URL: &str = "/neutron.transfer.MsgTransferResponse";
+const NATIVE_TRANSFER_TYPE_URL: &str = "/ibc.applications.transfer.v1.MsgTransfer";
+const NATIVE_TRANSFER_RESPONSE_TYPE_URL: &str = "/ibc.applications.transfer.v1.MsgTransferResponse";
+
+const TRANSFER_PORT: &str = "transfer";
+
+/// ICS-20 packet data
+#[derive(Serialize)]
+struct FungibleTokenPacketData<'a> {
+    denom: &'a str,
+    amount: String,
+    sender: &'a str,
+    receiver: &'a str,
+    memo: &'a str,
+}
+
+/// A transfer sent and not yet acknowledged, timed out or failed
+#[derive(Clone, Debug)]
+pub struct InFlightTransfer {
+    pub packet: RequestPacket,
+    pub sender: String,
+    pub receiver: String,
+    pub token: Coin,
+    pub memo: String,
+}
+
+/// Simulated transfer module together with the relayer. The sent tokens are escrowed until
+/// the test acknowledges the transfer, and refunded if it fails or times out.
+#[derive(Clone, Default)]
+pub struct MockTransfer {
+    // channel id -> sequence of the last sent packet
+    sequences: HashMap<String, u64>,
+    in_flight: Vec<InFlightTransfer>,
+}
+
+impl MockTransfer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handles a single message, returns `None` if it isn't a transfer one. Neutron answers
+    /// the native `MsgTransfer` with its own response carrying the channel too.
+    pub fn handle_msg(
+        &mut self,
+        querier: &mut NeutronMockQuerier,
+        type_url: &str,
+        value: &[u8],
+    ) -> Result<Option<Handled>, String> {
+        let (response_type_url, msg, fee) = match type_url {
+            TRANSFER_TYPE_URL => {
+                let msg = MsgTransfer::decode(value).map_err(|e| e.to_string())?;
+                let fee = msg.fee.clone();
+                (
+                    TRANSFER_RESPONSE_TYPE_URL,
+                    NativeMsgTransfer {
+                        source_port: msg.source_port,
+                        source_channel: msg.source_channel,
+                        token: msg.token,
+                        sender: msg.sender,
+                        receiver: msg.receiver,
+                        timeout_height: msg.timeout_height,
+                        timeout_timestamp: msg.timeout_timestamp,
+                        memo: msg.memo,
+                    },
+                    fee,
+                )
+            }
+            NATIVE_TRANSFER_TYPE_URL => {
+                let msg = NativeMsgTransfer::decode(value).map_err(|e| e.to_string())?;
+                (NATIVE_TRANSFER_RESPONSE_TYPE_URL, msg, None)
+            }
+            _ => return Ok(None),
+        };
+        let payer = msg.sender.clone();
+        let response = self.send(querier, msg)?;
+        let fee = fee.map(|fee| PacketFee {
+            port_id: TRANSFER_PORT.to_string(),
+            channel_id: response.channel.clone(),
+            sequence: response.sequence_id,
+            payer,
+            fee,
+        });
+        Ok(Some(Handled {
+            response: msg_response(response_type_url, response),
+            callbacks: vec![],
+            fee,
+        }))
+    }
+
+    /// Escrows the token and sends the transfer packet
+    pub fn send(
+        &mut self,
+        querier: &mut NeutronMockQuerier,
+        msg: NativeMsgTransfer,
+    ) -> Result<MsgTransferResponse, String> {
+        if msg.source_port != TRANSFER_PORT {
+            return Err(format!("invalid source port {}", msg.source_port));
+        }
+        if msg.timeout_height.is_none() && msg.timeout_timestamp == 0 {
+            return Err("packet timeout height and timeout timestamp cannot both be 0".to_string());
+        }
+        let token = msg
+            .token
+            .as_ref()
+            .map(|token| to_coins(std::slice::from_ref(token)))
+            .transpose()?
+            .and_then(|coins| coins.into_iter().next())
+            .ok_or_else(|| "invalid token amount".to_string())?;
+        querier.debit(&msg.sender, std::slice::from_ref(&token))?;
+
+        let sequence = self
+            .sequences
+            .entry(msg.source_channel.clone())
+            .or_default();
+        *sequence += 1;
+        let data = to_json_binary(&FungibleTokenPacketData {
+            denom: &token.denom,
+            amount: token.amount.to_string(),
+            sender: &msg.sender,
+            receiver: &msg.receiver,
+            memo: &msg.memo,
+        })
+        .map_err(|e| e.to_string())?;
+        self.in_flight.push(InFlightTransfer {
+            packet: RequestPacket {
+                sequence: Some(*sequence),
+                source_port: Some(msg.source_port.clone()),
+                source_channel: Some(msg.source_channel.clone()),
+                destination_port: Some(TRANSFER_PORT.to_string()),
+                destination_channel: Some(msg.source_channel.clone()),
+                data: Some(data),
+                timeout_height: msg.timeout_height.map(
+                    |Height {
+                         revision_number,
+                         revision_height,
+                     }| RequestPacketTimeoutHeight {
+                        revision_number: Some(revision_number),
+                        revision_height: Some(revision_height),
+                    },
+                ),
+                timeout_timestamp: Some(msg.timeout_timestamp),
+            },
+            sender: msg.sender,
+            receiver: msg.receiver,
+            token,
+            memo: msg.memo,
+        });
+        Ok(MsgTransferResponse {
+            sequence_id: *sequence,
+            channel: msg.source_channel,
+        })
+    }
+
+    pub fn in_flight(&self) -> &[InFlightTransfer] {
+        &self.in_flight
+    }
+
+    /// Acknowledges the transfer, the tokens stay on the remote chain
+    pub fn ack(&mut self, channel: &str, sequence: u64) -> SudoMsg {
+        // the result of a successful ICS-20 acknowledgement
+        self.ack_with(channel, sequence, Binary::from(vec![1]))
+    }
+
+    /// Acknowledges the transfer with the result a middleware of the remote chain returned,
+    /// e.g. ibc-hooks
+    pub fn ack_with(&mut self, channel: &str, sequence: u64, data: Binary) -> SudoMsg {
+        SudoMsg::Response {
+            request: self.take_transfer(channel, sequence).packet,
+            data,
+        }
+    }
+
+    /// Fails the transfer with an error acknowledgement and refunds the tokens
+    pub fn error(
+        &mut self,
+        querier: &mut NeutronMockQuerier,
+        channel: &str,
+        sequence: u64,
+        details: &str,
+    ) -> SudoMsg {
+        SudoMsg::Error {
+            request: self.refund(querier, channel, sequence),
+            details: details.to_string(),
+        }
+    }
+
+    /// Times the transfer out and refunds the tokens
+    pub fn timeout(
+        &mut self,
+        querier: &mut NeutronMockQuerier,
+        channel: &str,
+        sequence: u64,
+    ) -> SudoMsg {
+        SudoMsg::Timeout {
+            request: self.refund(querier, channel, sequence),
+        }
+    }
+
+    fn refund(
+        &mut self,
+        querier: &mut NeutronMockQuerier,
+        channel: &str,
+        sequence: u64,
+    ) -> RequestPacket {
+        let transfer = self.take_transfer(channel, sequence);
+        querier.credit(&transfer.sender, &[transfer.token]);
+        transfer.packet
+    }
+
+    fn take_transfer(&mut self, channel: &str, sequence: u64) -> InFlightTransfer {
+        let index = self
+            .in_flight
+            .iter()
+            .position(|t| {
+                t.packet.source_channel.as_deref() == Some(channel)
+                    && t.packet.sequence == Some(sequence)
+            })
+            .unwrap_or_else(|| panic!("no transfer {} in flight on {}", sequence, channel));
+        self.in_flight.remove(index)
+    }
+}
@@ -0,0 +1,133 @@
+// Copyright 2022 Neutron
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Access control of the contracts: an owner set at instantiation, a two-step ownership
+//! transfer and an optional allow-list of operators per action. The actions are named by the
+//! contracts, usually after their execute messages.
+
+#![warn(clippy::unwrap_used, clippy::expect_used)]
+
+use cosmwasm_std::{Addr, Deps, DepsMut, MessageInfo, Order, Response, StdError, StdResult};
+use cw_storage_plus::{Item, Map};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub const OWNER: Item<Addr> = Item::new("owner");
+// set by the owner to transfer the ownership, becomes the owner once accepted
+pub const PENDING_OWNER: Item<Addr> = Item::new("pending_owner");
+// addresses allowed to execute an action besides the owner, keyed by (action, operator)
+pub const OPERATORS: Map<(&str, &Addr), ()> = Map::new("operators");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct OwnershipResponse {
+    pub owner: Addr,
+    pub pending_owner: Option<Addr>,
+}
+
+/// Sets the owner, the given address or the sender if none is given
+pub fn initialize_owner(
+    deps: DepsMut,
+    info: &MessageInfo,
+    owner: Option<String>,
+) -> StdResult<Addr> {
+    let owner = match owner {
+        Some(owner) => deps.api.addr_validate(&owner)?,
+        None => info.sender.clone(),
+    };
+    OWNER.save(deps.storage, &owner)?;
+    Ok(owner)
+}
+
+/// Passes if the sender is the owner
+pub fn assert_owner(deps: Deps, sender: &Addr) -> StdResult<()> {
+    if *sender != OWNER.load(deps.storage)? {
+        return Err(StdError::generic_err("Unauthorized"));
+    }
+    Ok(())
+}
+
+/// Passes if the sender is the owner or is in the allow-list of operators of the action
+pub fn assert_owner_or_operator(deps: Deps, sender: &Addr, action: &str) -> StdResult<()> {
+    if assert_owner(deps, sender).is_ok() || OPERATORS.has(deps.storage, (action, sender)) {
+        return Ok(());
+    }
+    Err(StdError::generic_err("Unauthorized"))
+}
+
+/// Starts a two-step ownership transfer, the new owner has to accept it
+pub fn transfer_ownership(
+    deps: DepsMut,
+    info: MessageInfo,
+    new_owner: String,
+) -> StdResult<Response> {
+    assert_owner(deps.as_ref(), &info.sender)?;
+    let new_owner = deps.api.addr_validate(&new_owner)?;
+    PENDING_OWNER.save(deps.storage, &new_owner)?;
+    Ok(Response::default()
+        .add_attribute("action", "transfer_ownership")
+        .add_attribute("pending_owner", new_owner))
+}
+
+pub fn accept_ownership(deps: DepsMut, info: MessageInfo) -> StdResult<Response> {
+    if PENDING_OWNER.may_load(deps.storage)? != Some(info.sender.clone()) {
+        return Err(StdError::generic_err("Unauthorized"));
+    }
+    OWNER.save(deps.storage, &info.sender)?;
+    PENDING_OWNER.remove(deps.storage);
+    Ok(Response::default()
+        .add_attribute("action", "accept_ownership")
+        .add_attribute("owner", info.sender))
+}
+
+/// Replaces the allow-list of operators of the action
+pub fn set_operators(
+    deps: DepsMut,
+    info: MessageInfo,
+    action: String,
+    operators: Vec<String>,
+) -> StdResult<Response> {
+    assert_owner(deps.as_ref(), &info.sender)?;
+    let current: Vec<Addr> = OPERATORS
+        .prefix(&action)
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()?;
+    for operator in current {
+        OPERATORS.remove(deps.storage, (&action, &operator));
+    }
+    for operator in operators {
+        let operator = deps.api.addr_validate(&operator)?;
+        OPERATORS.save(deps.storage, (&action, &operator), &())?;
+    }
+    Ok(Response::default()
+        .add_attribute("action", "set_operators")
+        .add_attribute("operators_action", action))
+}
+
+pub fn query_ownership(deps: Deps) -> StdResult<OwnershipResponse> {
+    Ok(OwnershipResponse {
+        owner: OWNER.load(deps.storage)?,
+        pending_owner: PENDING_OWNER.may_load(deps.storage)?,
+    })
+}
+
+pub fn query_operators(deps: Deps, action: String) -> StdResult<Vec<Addr>> {
+    OPERATORS
+        .prefix(&action)
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect()
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod testing;
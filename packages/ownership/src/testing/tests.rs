@@ -0,0 +1,107 @@
+// Copyright 2022 Neutron
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    accept_ownership, assert_owner, assert_owner_or_operator, initialize_owner, query_operators,
+    query_ownership, set_operators, transfer_ownership, OwnershipResponse,
+};
+use cosmwasm_std::testing::{message_info, mock_dependencies, mock_env};
+use cosmwasm_std::StdError;
+
+#[test]
+fn test_two_step_ownership_transfer() {
+    let mut deps = mock_dependencies();
+    let owner = deps.api.addr_make("owner");
+    let new_owner = deps.api.addr_make("new_owner");
+    initialize_owner(deps.as_mut(), &message_info(&owner, &[]), None).unwrap();
+
+    // the contract itself isn't the owner
+    let err = assert_owner(deps.as_ref(), &mock_env().contract.address).unwrap_err();
+    assert_eq!(err, StdError::generic_err("Unauthorized"));
+
+    let err = transfer_ownership(
+        deps.as_mut(),
+        message_info(&new_owner, &[]),
+        new_owner.to_string(),
+    )
+    .unwrap_err();
+    assert_eq!(err, StdError::generic_err("Unauthorized"));
+
+    transfer_ownership(
+        deps.as_mut(),
+        message_info(&owner, &[]),
+        new_owner.to_string(),
+    )
+    .unwrap();
+    assert_eq!(
+        query_ownership(deps.as_ref()).unwrap(),
+        OwnershipResponse {
+            owner: owner.clone(),
+            pending_owner: Some(new_owner.clone()),
+        }
+    );
+
+    let err = accept_ownership(deps.as_mut(), message_info(&owner, &[])).unwrap_err();
+    assert_eq!(err, StdError::generic_err("Unauthorized"));
+    accept_ownership(deps.as_mut(), message_info(&new_owner, &[])).unwrap();
+    assert_eq!(
+        query_ownership(deps.as_ref()).unwrap(),
+        OwnershipResponse {
+            owner: new_owner.clone(),
+            pending_owner: None,
+        }
+    );
+    assert_owner(deps.as_ref(), &new_owner).unwrap();
+    assert_owner(deps.as_ref(), &owner).unwrap_err();
+}
+
+#[test]
+fn test_operators() {
+    let mut deps = mock_dependencies();
+    let owner = deps.api.addr_make("owner");
+    let operator = deps.api.addr_make("operator");
+    let other = deps.api.addr_make("other");
+    initialize_owner(
+        deps.as_mut(),
+        &message_info(&other, &[]),
+        Some(owner.to_string()),
+    )
+    .unwrap();
+
+    assert_owner_or_operator(deps.as_ref(), &operator, "send").unwrap_err();
+    set_operators(
+        deps.as_mut(),
+        message_info(&owner, &[]),
+        "send".to_string(),
+        vec![operator.to_string(), other.to_string()],
+    )
+    .unwrap();
+    assert_owner_or_operator(deps.as_ref(), &operator, "send").unwrap();
+    assert_owner_or_operator(deps.as_ref(), &operator, "set_fees").unwrap_err();
+    assert_owner_or_operator(deps.as_ref(), &owner, "set_fees").unwrap();
+
+    // the allow-list is replaced as a whole
+    set_operators(
+        deps.as_mut(),
+        message_info(&owner, &[]),
+        "send".to_string(),
+        vec![other.to_string()],
+    )
+    .unwrap();
+    assert_eq!(
+        query_operators(deps.as_ref(), "send".to_string()).unwrap(),
+        vec![other]
+    );
+    assert_owner_or_operator(deps.as_ref(), &operator, "send").unwrap_err();
+}
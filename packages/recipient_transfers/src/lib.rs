@@ -0,0 +1,345 @@
+// Copyright 2022 Neutron
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Transfers to the recipients watched by the TX queries of the contracts. A transfer is keyed by
+//! (recipient, remote height, (tx hash, msg index, coin index)), so a resubmitted tx is recognized
+//! and isn't counted twice. A transfer made by an IBC packet is also recognized by the packet,
+//! which can be relayed again in another tx. The transfers are also indexed by sender and by denom
+//! for the filtered queries, and summed per denom. The messages of a tx over what a callback can
+//! process are queued and credited later with [`process_pending`].
+
+#![warn(clippy::unwrap_used, clippy::expect_used)]
+
+use cosmwasm_std::{Binary, Order, StdResult, Storage, Uint256};
+use cw_storage_plus::{Bound, Map};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+
+mod pending;
+
+pub use pending::{
+    process_pending, query_pending_deposits, queue_pending, GetPendingDepositsResponse,
+    PendingDeposits, PendingTx, PENDING_DEPOSITS,
+};
+
+pub type Recipient = str;
+
+/// contains all transfers observed by the contract mapped by
+/// (recipient, remote height, (tx hash, msg index, coin index)).
+pub const RECIPIENT_TRANSFERS: Map<(&Recipient, u64, (&str, u32, u32)), Transfer> =
+    Map::new("recipient_transfers");
+/// contains transfers flagged when saved mapped the same way as RECIPIENT_TRANSFERS,
+/// they aren't indexed nor added to the recipient totals.
+pub const FLAGGED_TRANSFERS: Map<(&Recipient, u64, (&str, u32, u32)), Transfer> =
+    Map::new("flagged_transfers");
+/// index of RECIPIENT_TRANSFERS by ((recipient, sender), remote height, (tx hash, msg index,
+/// coin index)).
+pub const SENDER_TRANSFERS: Map<((&Recipient, &str), u64, (&str, u32, u32)), ()> =
+    Map::new("recipient_sender_transfers");
+/// index of RECIPIENT_TRANSFERS by ((recipient, denom), remote height, (tx hash, msg index,
+/// coin index)).
+pub const DENOM_TRANSFERS: Map<((&Recipient, &str), u64, (&str, u32, u32)), ()> =
+    Map::new("recipient_denom_transfers");
+/// contains the IBC packets the transfers were received with mapped by
+/// (destination channel, sequence).
+pub const RECEIVED_PACKETS: Map<(&str, u64), ()> = Map::new("recipient_received_packets");
+/// contains sums of the transfers observed by the contract mapped by (recipient, denom).
+pub const RECIPIENT_TOTALS: Map<(&Recipient, &str), Uint256> = Map::new("recipient_totals");
+/// transfers stored as a list per recipient before they were indexed, see migrate_recipient_txs.
+const LEGACY_RECIPIENT_TXS: Map<&Recipient, Vec<LegacyTransfer>> = Map::new("recipient_txs");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Transfer {
+    pub recipient: String,
+    pub sender: String,
+    pub denom: String,
+    pub amount: Uint256,
+    /// remote height of the tx
+    pub height: u64,
+    pub tx_hash: String,
+    /// index of the message in the tx
+    pub index: u32,
+    /// index of the coin among the coins the message transfers to the recipient,
+    /// a MsgSend of several coins makes a transfer per coin
+    #[serde(default)]
+    pub coin_index: u32,
+    #[serde(default)]
+    pub source: TransferSource,
+}
+
+/// Kind of the remote chain message the transfer was made with.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferSource {
+    /// bank MsgSend
+    #[default]
+    Send,
+    /// an output of bank MsgMultiSend, the sender is the address of the first input
+    MultiSend,
+    /// ICS-20 packet received with MsgRecvPacket, the sender is an address on the counterparty
+    /// chain and the denom is the one the tokens have on the remote chain
+    IbcRecv {
+        destination_channel: String,
+        sequence: u64,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+struct LegacyTransfer {
+    recipient: String,
+    sender: String,
+    denom: String,
+    amount: String,
+}
+
+/// Position of a transfer in the transfers of an address, taken from the last transfer of a page
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct TransferCursor {
+    pub height: u64,
+    pub tx_hash: String,
+    pub index: u32,
+    /// only the recipient transfers have it, it's 0 for the other pages
+    #[serde(default)]
+    pub coin_index: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct TransferFilter {
+    pub sender: Option<String>,
+    pub denom: Option<String>,
+    pub min_height: Option<u64>,
+    pub max_height: Option<u64>,
+}
+
+impl TransferFilter {
+    pub fn matches(&self, transfer: &Transfer) -> bool {
+        self.sender.as_ref().map_or(true, |s| *s == transfer.sender)
+            && self.denom.as_ref().map_or(true, |d| *d == transfer.denom)
+            && self.min_height.map_or(true, |h| transfer.height >= h)
+            && self.max_height.map_or(true, |h| transfer.height <= h)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct GetRecipientTxsResponse {
+    pub transfers: Vec<Transfer>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct GetRecipientTotalsResponse {
+    pub totals: Vec<TransferTotal>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct TransferTotal {
+    pub denom: String,
+    pub amount: Uint256,
+}
+
+/// returns the hash of the tx the way the remote chain identifies it.
+pub fn tx_hash(data: &Binary) -> String {
+    sha256_upper_hex(data.as_slice())
+}
+
+pub fn sha256_upper_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect()
+}
+
+/// Saves the transfers which haven't been saved yet, indexes them and adds them to the recipient
+/// totals. IBC transfers are skipped if their packet has been received already. Transfers for
+/// which `is_flagged` returns true are saved as flagged instead.
+/// Returns the number of saved transfers.
+pub fn save_transfers(
+    storage: &mut dyn Storage,
+    transfers: Vec<Transfer>,
+    is_flagged: impl Fn(&dyn Storage, &Transfer) -> StdResult<bool>,
+) -> StdResult<u64> {
+    let mut saved = 0u64;
+    for transfer in transfers {
+        let id = (
+            transfer.tx_hash.as_str(),
+            transfer.index,
+            transfer.coin_index,
+        );
+        let key = (transfer.recipient.as_str(), transfer.height, id);
+        if RECIPIENT_TRANSFERS.has(storage, key) || FLAGGED_TRANSFERS.has(storage, key) {
+            continue;
+        }
+        // A packet relayed again in a later tx doesn't transfer anything again
+        if let TransferSource::IbcRecv {
+            destination_channel,
+            sequence,
+        } = &transfer.source
+        {
+            let packet = (destination_channel.as_str(), *sequence);
+            if RECEIVED_PACKETS.has(storage, packet) {
+                continue;
+            }
+            RECEIVED_PACKETS.save(storage, packet, &())?;
+        }
+        saved += 1;
+
+        if is_flagged(storage, &transfer)? {
+            FLAGGED_TRANSFERS.save(storage, key, &transfer)?;
+            continue;
+        }
+        RECIPIENT_TRANSFERS.save(storage, key, &transfer)?;
+        SENDER_TRANSFERS.save(
+            storage,
+            ((&transfer.recipient, &transfer.sender), transfer.height, id),
+            &(),
+        )?;
+        DENOM_TRANSFERS.save(
+            storage,
+            ((&transfer.recipient, &transfer.denom), transfer.height, id),
+            &(),
+        )?;
+        RECIPIENT_TOTALS.update(
+            storage,
+            (&transfer.recipient, &transfer.denom),
+            |total| -> StdResult<Uint256> {
+                Ok(total.unwrap_or_default().checked_add(transfer.amount)?)
+            },
+        )?;
+    }
+    Ok(saved)
+}
+
+/// Returns up to `limit` transfers to the recipient matching the filter ordered by remote height.
+/// A sender or a denom in the filter narrows the scan down to the matching index.
+pub fn query_recipient_txs(
+    storage: &dyn Storage,
+    recipient: &str,
+    start_after: Option<TransferCursor>,
+    limit: usize,
+    filter: TransferFilter,
+) -> StdResult<GetRecipientTxsResponse> {
+    let min = match &start_after {
+        Some(c) => Some(Bound::exclusive((
+            c.height,
+            (c.tx_hash.as_str(), c.index, c.coin_index),
+        ))),
+        // an empty hash goes before any other one at the same height
+        None => filter.min_height.map(|h| Bound::inclusive((h, ("", 0, 0)))),
+    };
+    let max = filter
+        .max_height
+        .and_then(|h| h.checked_add(1))
+        .map(|h| Bound::exclusive((h, ("", 0, 0))));
+
+    let transfers: Box<dyn Iterator<Item = StdResult<Transfer>> + '_> =
+        match (&filter.sender, &filter.denom) {
+            (Some(sender), _) => Box::new(
+                SENDER_TRANSFERS
+                    .sub_prefix((recipient, sender.as_str()))
+                    .keys(storage, min, max, Order::Ascending)
+                    .map(|key| load_transfer(storage, recipient, key?)),
+            ),
+            (None, Some(denom)) => Box::new(
+                DENOM_TRANSFERS
+                    .sub_prefix((recipient, denom.as_str()))
+                    .keys(storage, min, max, Order::Ascending)
+                    .map(|key| load_transfer(storage, recipient, key?)),
+            ),
+            (None, None) => Box::new(
+                RECIPIENT_TRANSFERS
+                    .sub_prefix(recipient)
+                    .range(storage, min, max, Order::Ascending)
+                    .map(|item| item.map(|(_, t)| t)),
+            ),
+        };
+    let transfers = transfers
+        .filter(|item| item.as_ref().map_or(true, |t| filter.matches(t)))
+        .take(limit)
+        .collect::<StdResult<Vec<Transfer>>>()?;
+    Ok(GetRecipientTxsResponse { transfers })
+}
+
+fn load_transfer(
+    storage: &dyn Storage,
+    recipient: &str,
+    (height, (tx_hash, index, coin_index)): (u64, (String, u32, u32)),
+) -> StdResult<Transfer> {
+    RECIPIENT_TRANSFERS.load(storage, (recipient, height, (&tx_hash, index, coin_index)))
+}
+
+pub fn query_recipient_totals(
+    storage: &dyn Storage,
+    recipient: &str,
+) -> StdResult<GetRecipientTotalsResponse> {
+    let totals = RECIPIENT_TOTALS
+        .prefix(recipient)
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(denom, amount)| TransferTotal { denom, amount }))
+        .collect::<StdResult<Vec<TransferTotal>>>()?;
+    Ok(GetRecipientTotalsResponse { totals })
+}
+
+/// Removes all the transfers, their indexes, the recipient totals and the pending messages
+pub fn clear_transfers(storage: &mut dyn Storage) {
+    PENDING_DEPOSITS.clear(storage);
+    RECIPIENT_TRANSFERS.clear(storage);
+    FLAGGED_TRANSFERS.clear(storage);
+    SENDER_TRANSFERS.clear(storage);
+    DENOM_TRANSFERS.clear(storage);
+    RECEIVED_PACKETS.clear(storage);
+    RECIPIENT_TOTALS.clear(storage);
+    LEGACY_RECIPIENT_TXS.clear(storage);
+}
+
+/// Moves the transfers stored as a list per recipient to RECIPIENT_TRANSFERS. Their height and
+/// tx hash weren't stored, so they are keyed at height 0 with an empty tx hash by their position
+/// in the list, before any other transfer to the recipient. Returns the number of moved transfers.
+pub fn migrate_recipient_txs(storage: &mut dyn Storage) -> StdResult<u64> {
+    let legacy = LEGACY_RECIPIENT_TXS
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<(String, Vec<LegacyTransfer>)>>>()?;
+    let mut moved = 0u64;
+    for (recipient, transfers) in legacy {
+        let transfers = transfers
+            .into_iter()
+            .enumerate()
+            .map(|(index, t)| {
+                Ok(Transfer {
+                    recipient: t.recipient,
+                    sender: t.sender,
+                    denom: t.denom,
+                    amount: Uint256::from_str(&t.amount)?,
+                    height: 0,
+                    tx_hash: String::new(),
+                    index: index as u32,
+                    coin_index: 0,
+                    source: TransferSource::Send,
+                })
+            })
+            .collect::<StdResult<Vec<Transfer>>>()?;
+        moved += save_transfers(storage, transfers, |_, _| Ok(false))?;
+        LEGACY_RECIPIENT_TXS.remove(storage, &recipient);
+    }
+    Ok(moved)
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod testing;
@@ -0,0 +1,128 @@
+// Copyright 2022 Neutron
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Messages of the TX query results which couldn't be processed in the callback without
+//! running out of gas. They are stored raw and processed later, the oldest txs first.
+
+use crate::{save_transfers, Transfer};
+use cosmwasm_std::{Binary, Order, StdError, StdResult, Storage};
+use cw_storage_plus::Map;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// contains the messages of TX query results left to process mapped by (remote height, tx hash).
+pub const PENDING_DEPOSITS: Map<(u64, &str), PendingDeposits> = Map::new("pending_deposits");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PendingDeposits {
+    pub recipients: Vec<String>,
+    /// index of the first of the messages in the tx
+    pub first_index: u32,
+    /// raw protobuf-encoded messages of the tx which haven't been processed yet
+    pub messages: Vec<Binary>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct GetPendingDepositsResponse {
+    pub txs: Vec<PendingTx>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PendingTx {
+    pub height: u64,
+    pub tx_hash: String,
+    /// number of messages left to process
+    pub messages: u32,
+}
+
+/// Stores the messages of the tx to the recipients starting from the `first_index` message
+pub fn queue_pending(
+    storage: &mut dyn Storage,
+    height: u64,
+    tx_hash: &str,
+    recipients: &[String],
+    first_index: u32,
+    messages: Vec<Binary>,
+) -> StdResult<()> {
+    PENDING_DEPOSITS.save(
+        storage,
+        (height, tx_hash),
+        &PendingDeposits {
+            recipients: recipients.to_vec(),
+            first_index,
+            messages,
+        },
+    )
+}
+
+/// Processes up to `limit` pending messages. `deposits` returns the transfers made to a recipient
+/// by the messages given the index of the first of them, they are saved with `save_transfers`.
+/// Returns the numbers of processed messages and of saved transfers.
+pub fn process_pending<E: From<StdError>>(
+    storage: &mut dyn Storage,
+    limit: usize,
+    deposits: impl Fn(&[Binary], &str, u64, &str, u32) -> Result<Vec<Transfer>, E>,
+    is_flagged: impl Fn(&dyn Storage, &Transfer) -> StdResult<bool>,
+) -> Result<(usize, u64), E> {
+    let mut left = limit;
+    let mut processed = 0usize;
+    let mut stored = 0u64;
+    while left > 0 {
+        let Some(((height, tx_hash), mut pending)) = PENDING_DEPOSITS
+            .range(storage, None, None, Order::Ascending)
+            .next()
+            .transpose()?
+        else {
+            break;
+        };
+
+        let take = left.min(pending.messages.len());
+        let messages: Vec<Binary> = pending.messages.drain(..take).collect();
+        for recipient in &pending.recipients {
+            let transfers = deposits(&messages, recipient, height, &tx_hash, pending.first_index)?;
+            stored += save_transfers(storage, transfers, &is_flagged)?;
+        }
+        pending.first_index += take as u32;
+
+        if pending.messages.is_empty() {
+            PENDING_DEPOSITS.remove(storage, (height, &tx_hash));
+        } else {
+            PENDING_DEPOSITS.save(storage, (height, &tx_hash), &pending)?;
+        }
+        left -= take;
+        processed += take;
+    }
+    Ok((processed, stored))
+}
+
+/// Returns up to `limit` txs with pending messages, the oldest first
+pub fn query_pending_deposits(
+    storage: &dyn Storage,
+    limit: usize,
+) -> StdResult<GetPendingDepositsResponse> {
+    let txs = PENDING_DEPOSITS
+        .range(storage, None, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            item.map(|((height, tx_hash), pending)| PendingTx {
+                height,
+                tx_hash,
+                messages: pending.messages.len() as u32,
+            })
+        })
+        .collect::<StdResult<Vec<PendingTx>>>()?;
+    Ok(GetPendingDepositsResponse { txs })
+}
@@ -0,0 +1,276 @@
+// Copyright 2022 Neutron
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    migrate_recipient_txs, query_recipient_totals, query_recipient_txs, save_transfers,
+    LegacyTransfer, Transfer, TransferCursor, TransferFilter, TransferSource, TransferTotal,
+    FLAGGED_TRANSFERS, LEGACY_RECIPIENT_TXS, RECEIVED_PACKETS,
+};
+use cosmwasm_std::testing::MockStorage;
+use cosmwasm_std::{Order, StdResult, Storage, Uint256};
+
+const RECIPIENT: &str = "cosmos1recipient";
+
+fn transfer(sender: &str, denom: &str, amount: u128, height: u64, index: u32) -> Transfer {
+    Transfer {
+        recipient: RECIPIENT.to_string(),
+        sender: sender.to_string(),
+        denom: denom.to_string(),
+        amount: Uint256::from(amount),
+        height,
+        tx_hash: format!("TX{}", height),
+        index,
+        coin_index: 0,
+        source: TransferSource::Send,
+    }
+}
+
+fn not_flagged(_: &dyn Storage, _: &Transfer) -> StdResult<bool> {
+    Ok(false)
+}
+
+#[test]
+fn test_save_transfers_dedup() {
+    let mut storage = MockStorage::new();
+    let mut second_coin = transfer("alice", "uatom", 5, 1, 0);
+    second_coin.coin_index = 1;
+    let transfers = vec![transfer("alice", "untrn", 10, 1, 0), second_coin];
+
+    assert_eq!(
+        save_transfers(&mut storage, transfers.clone(), not_flagged).unwrap(),
+        2
+    );
+    // a resubmitted tx is ignored
+    assert_eq!(
+        save_transfers(&mut storage, transfers, not_flagged).unwrap(),
+        0
+    );
+    assert_eq!(
+        query_recipient_totals(&storage, RECIPIENT).unwrap().totals,
+        vec![
+            TransferTotal {
+                denom: "uatom".to_string(),
+                amount: Uint256::from(5u128),
+            },
+            TransferTotal {
+                denom: "untrn".to_string(),
+                amount: Uint256::from(10u128),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_save_transfers_packet_dedup() {
+    let mut storage = MockStorage::new();
+    let recv = |height: u64, sequence: u64| Transfer {
+        source: TransferSource::IbcRecv {
+            destination_channel: "channel-0".to_string(),
+            sequence,
+        },
+        ..transfer("osmo1sender", "uosmo", 10, height, 0)
+    };
+
+    assert_eq!(
+        save_transfers(&mut storage, vec![recv(1, 1)], not_flagged).unwrap(),
+        1
+    );
+    // the same packet relayed again in another tx is ignored
+    assert_eq!(
+        save_transfers(&mut storage, vec![recv(2, 1), recv(2, 2)], not_flagged).unwrap(),
+        1
+    );
+    assert!(RECEIVED_PACKETS.has(&storage, ("channel-0", 2)));
+    assert_eq!(
+        query_recipient_totals(&storage, RECIPIENT).unwrap().totals,
+        vec![TransferTotal {
+            denom: "uosmo".to_string(),
+            amount: Uint256::from(20u128),
+        }]
+    );
+}
+
+#[test]
+fn test_query_recipient_txs_indexes() {
+    let mut storage = MockStorage::new();
+    let transfers = vec![
+        transfer("alice", "untrn", 10, 1, 0),
+        transfer("bob", "untrn", 20, 2, 0),
+        transfer("alice", "uatom", 30, 3, 1),
+        transfer("alice", "untrn", 40, 4, 2),
+    ];
+    save_transfers(&mut storage, transfers.clone(), not_flagged).unwrap();
+
+    let by_sender = |start_after: Option<TransferCursor>, limit: usize| {
+        query_recipient_txs(
+            &storage,
+            RECIPIENT,
+            start_after,
+            limit,
+            TransferFilter {
+                sender: Some("alice".to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .transfers
+    };
+    assert_eq!(
+        by_sender(None, 10),
+        vec![
+            transfers[0].clone(),
+            transfers[2].clone(),
+            transfers[3].clone(),
+        ]
+    );
+    let page = by_sender(None, 2);
+    let last = page.last().unwrap();
+    assert_eq!(
+        by_sender(
+            Some(TransferCursor {
+                height: last.height,
+                tx_hash: last.tx_hash.clone(),
+                index: last.index,
+                coin_index: last.coin_index,
+            }),
+            2,
+        ),
+        vec![transfers[3].clone()]
+    );
+
+    let filtered = query_recipient_txs(
+        &storage,
+        RECIPIENT,
+        None,
+        10,
+        TransferFilter {
+            sender: Some("alice".to_string()),
+            denom: Some("untrn".to_string()),
+            min_height: Some(2),
+            max_height: None,
+        },
+    )
+    .unwrap()
+    .transfers;
+    assert_eq!(filtered, vec![transfers[3].clone()]);
+
+    let by_denom = query_recipient_txs(
+        &storage,
+        RECIPIENT,
+        None,
+        10,
+        TransferFilter {
+            denom: Some("untrn".to_string()),
+            max_height: Some(2),
+            ..Default::default()
+        },
+    )
+    .unwrap()
+    .transfers;
+    assert_eq!(by_denom, vec![transfers[0].clone(), transfers[1].clone()]);
+}
+
+#[test]
+fn test_flagged_transfers() {
+    let mut storage = MockStorage::new();
+    let over_ten =
+        |_: &dyn Storage, t: &Transfer| -> StdResult<bool> { Ok(t.amount > Uint256::from(10u128)) };
+    let transfers = vec![
+        transfer("alice", "untrn", 10, 1, 0),
+        transfer("alice", "untrn", 11, 2, 0),
+    ];
+    assert_eq!(
+        save_transfers(&mut storage, transfers.clone(), over_ten).unwrap(),
+        2
+    );
+    // a flagged transfer isn't saved again either
+    assert_eq!(
+        save_transfers(&mut storage, transfers.clone(), not_flagged).unwrap(),
+        0
+    );
+
+    let flagged = FLAGGED_TRANSFERS
+        .range(&storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, t)| t))
+        .collect::<StdResult<Vec<Transfer>>>()
+        .unwrap();
+    assert_eq!(flagged, vec![transfers[1].clone()]);
+    // flagged transfers are neither indexed nor added to the totals
+    let by_sender = query_recipient_txs(
+        &storage,
+        RECIPIENT,
+        None,
+        10,
+        TransferFilter {
+            sender: Some("alice".to_string()),
+            ..Default::default()
+        },
+    )
+    .unwrap()
+    .transfers;
+    assert_eq!(by_sender, vec![transfers[0].clone()]);
+    assert_eq!(
+        query_recipient_totals(&storage, RECIPIENT).unwrap().totals,
+        vec![TransferTotal {
+            denom: "untrn".to_string(),
+            amount: Uint256::from(10u128),
+        }]
+    );
+}
+
+#[test]
+fn test_migrate_recipient_txs() {
+    let mut storage = MockStorage::new();
+    let legacy = |amount: &str| LegacyTransfer {
+        recipient: RECIPIENT.to_string(),
+        sender: "alice".to_string(),
+        denom: "untrn".to_string(),
+        amount: amount.to_string(),
+    };
+    LEGACY_RECIPIENT_TXS
+        .save(&mut storage, RECIPIENT, &vec![legacy("10"), legacy("20")])
+        .unwrap();
+    save_transfers(
+        &mut storage,
+        vec![transfer("bob", "untrn", 30, 5, 0)],
+        not_flagged,
+    )
+    .unwrap();
+
+    assert_eq!(migrate_recipient_txs(&mut storage).unwrap(), 2);
+    assert!(!LEGACY_RECIPIENT_TXS.has(&storage, RECIPIENT));
+
+    let transfers = query_recipient_txs(&storage, RECIPIENT, None, 10, TransferFilter::default())
+        .unwrap()
+        .transfers;
+    // the migrated transfers go first
+    assert_eq!(
+        transfers
+            .iter()
+            .map(|t| (t.height, t.index, t.amount))
+            .collect::<Vec<_>>(),
+        vec![
+            (0, 0, Uint256::from(10u128)),
+            (0, 1, Uint256::from(20u128)),
+            (5, 0, Uint256::from(30u128)),
+        ]
+    );
+    assert_eq!(
+        query_recipient_totals(&storage, RECIPIENT).unwrap().totals,
+        vec![TransferTotal {
+            denom: "untrn".to_string(),
+            amount: Uint256::from(60u128),
+        }]
+    );
+}
@@ -0,0 +1,141 @@
+// Copyright 2022 Neutron
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Deposits locked by the interchain queries the contracts register. A query is tracked from its
+//! registration reply until it's removed, together with the local height of its last result, so
+//! the queries which stopped receiving results can be found and removed to reclaim their
+//! deposits.
+
+#![warn(clippy::unwrap_used, clippy::expect_used)]
+
+use cosmwasm_std::{Coin, Coins, Deps, DepsMut, Order, StdError, StdResult, Storage, Uint128};
+use cw_storage_plus::{Bound, Item, Map};
+use neutron_sdk::interchain_queries::queries::get_registered_query;
+use neutron_sdk::NeutronResult;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// contains queries registered by the contract and not removed yet mapped by query id.
+pub const TRACKED_QUERIES: Map<u64, TrackedQuery> = Map::new("tracked_queries");
+/// number of blocks without results after which a tracked query can be removed
+/// with RemoveStaleQueries.
+pub const STALE_QUERY_BLOCKS: Item<u64> = Item::new("stale_query_blocks");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct TrackedQuery {
+    /// deposit locked by the interchainqueries module until the query is removed
+    pub deposit: Vec<Coin>,
+    /// local height of the query registration
+    pub registered_height: u64,
+    /// local height of the last result callback, the registration height if there were none
+    pub last_result_height: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct DepositsSummaryResponse {
+    /// number of the tracked queries
+    pub queries: u32,
+    pub locked: Vec<Coin>,
+}
+
+/// Starts tracking the query registered at the local height with the deposit it locked
+pub fn track_query(deps: DepsMut, query_id: u64, height: u64) -> StdResult<()> {
+    let registered_query = get_registered_query(deps.as_ref(), query_id)
+        .map_err(|e| StdError::generic_err(e.to_string()))?;
+    let deposit = registered_query
+        .deposit
+        .into_iter()
+        .map(|coin| Ok(Coin::new(Uint128::from_str(&coin.amount)?, coin.denom)))
+        .collect::<StdResult<Vec<Coin>>>()?;
+    TRACKED_QUERIES.save(
+        deps.storage,
+        query_id,
+        &TrackedQuery {
+            deposit,
+            registered_height: height,
+            last_result_height: height,
+        },
+    )
+}
+
+/// Records the local height of the query result, the query isn't stale until later.
+pub fn touch_tracked_query(storage: &mut dyn Storage, query_id: u64, height: u64) -> StdResult<()> {
+    if let Some(mut tracked) = TRACKED_QUERIES.may_load(storage, query_id)? {
+        tracked.last_result_height = height;
+        TRACKED_QUERIES.save(storage, query_id, &tracked)?;
+    }
+    Ok(())
+}
+
+/// Checks up to `limit` tracked queries after `start_after`, the oldest query ids first, and
+/// returns the ones which haven't received results for the stale query blocks at the local
+/// height, together with the last checked query id.
+pub fn find_stale_queries(
+    deps: Deps,
+    height: u64,
+    start_after: Option<u64>,
+    limit: usize,
+) -> NeutronResult<(Vec<u64>, Option<u64>)> {
+    let blocks = STALE_QUERY_BLOCKS
+        .may_load(deps.storage)?
+        .ok_or_else(|| StdError::generic_err("stale query blocks are not set"))?;
+
+    let mut stale: Vec<u64> = vec![];
+    let mut last_query_id = None;
+    for item in TRACKED_QUERIES
+        .range(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+    {
+        let (query_id, tracked) = item?;
+        last_query_id = Some(query_id);
+        if tracked.last_result_height + blocks > height {
+            continue;
+        }
+        // KV results may be submitted without the callback, the submission height counts too
+        let registered_query = get_registered_query(deps, query_id)?;
+        if registered_query.last_submitted_result_local_height + blocks > height {
+            continue;
+        }
+        stale.push(query_id);
+    }
+    Ok((stale, last_query_id))
+}
+
+/// Returns the number of the tracked queries and the sum of their deposits
+pub fn query_deposits_summary(storage: &dyn Storage) -> StdResult<DepositsSummaryResponse> {
+    let mut locked = Coins::default();
+    let mut queries = 0u32;
+    for item in TRACKED_QUERIES.range(storage, None, None, Order::Ascending) {
+        let (_, tracked) = item?;
+        for coin in tracked.deposit {
+            locked.add(coin)?;
+        }
+        queries += 1;
+    }
+    Ok(DepositsSummaryResponse {
+        queries,
+        locked: locked.into_vec(),
+    })
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod testing;
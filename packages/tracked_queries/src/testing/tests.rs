@@ -0,0 +1,76 @@
+// Copyright 2022 Neutron
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    find_stale_queries, query_deposits_summary, touch_tracked_query, track_query,
+    DepositsSummaryResponse, TrackedQuery, STALE_QUERY_BLOCKS, TRACKED_QUERIES,
+};
+use cosmwasm_std::Coin;
+use neutron_std::types::cosmos::base::v1beta1::Coin as ProtoCoin;
+use neutron_std::types::neutron::interchainqueries::RegisteredQuery;
+use neutron_test_support::mock_dependencies;
+
+fn registered_query(id: u64) -> RegisteredQuery {
+    RegisteredQuery {
+        id,
+        deposit: vec![ProtoCoin {
+            denom: "untrn".to_string(),
+            amount: "100".to_string(),
+        }],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_stale_queries() {
+    let mut deps = mock_dependencies(&[]);
+    for query_id in 1..=3 {
+        deps.querier
+            .add_registered_query(registered_query(query_id));
+        track_query(deps.as_mut(), query_id, 100).unwrap();
+    }
+    assert_eq!(
+        TRACKED_QUERIES.load(&deps.storage, 1).unwrap(),
+        TrackedQuery {
+            deposit: vec![Coin::new(100u128, "untrn")],
+            registered_height: 100,
+            last_result_height: 100,
+        }
+    );
+    assert_eq!(
+        query_deposits_summary(&deps.storage).unwrap(),
+        DepositsSummaryResponse {
+            queries: 3,
+            locked: vec![Coin::new(300u128, "untrn")],
+        }
+    );
+
+    find_stale_queries(deps.as_ref(), 120, None, 10).unwrap_err();
+    STALE_QUERY_BLOCKS.save(&mut deps.storage, &10).unwrap();
+
+    touch_tracked_query(&mut deps.storage, 2, 115).unwrap();
+    // the KV results submitted without the callback count too
+    deps.querier
+        .registered_query_mut(3)
+        .unwrap()
+        .last_submitted_result_local_height = 112;
+    assert_eq!(
+        find_stale_queries(deps.as_ref(), 120, None, 10).unwrap(),
+        (vec![1], Some(3))
+    );
+    assert_eq!(
+        find_stale_queries(deps.as_ref(), 125, Some(1), 1).unwrap(),
+        (vec![2], Some(2))
+    );
+}